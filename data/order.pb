@@ -0,0 +1,11 @@
+
+
+order.protoshop"`
+Order
+order_id (RorderId
+customer (	Rcustomer 
+items (2
+.shop.ItemRitems"4
+Item
+sku (	Rsku
+quantity (Rquantitybproto3
\ No newline at end of file
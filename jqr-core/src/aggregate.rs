@@ -0,0 +1,203 @@
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+use crate::transform::to_number;
+use crate::{json_type, CompiledQuery, JqrError};
+
+/// An aggregation over the values of a query result, selected with `--agg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The sum of the numbers (`sum`).
+    Sum,
+    /// The smallest number (`min`).
+    Min,
+    /// The largest number (`max`).
+    Max,
+    /// The arithmetic mean of the numbers (`avg`).
+    Avg,
+    /// The number of values, of any type (`count`).
+    Count,
+}
+
+impl FromStr for Aggregate {
+    type Err = JqrError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "sum" => Ok(Aggregate::Sum),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            "avg" => Ok(Aggregate::Avg),
+            "count" => Ok(Aggregate::Count),
+            other => Err(JqrError::InvalidArgument(format!(
+                "Unknown aggregation '{}' (expected one of: sum, min, max, avg, count)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Aggregates the values of a query result into a single value.
+///
+/// An array is aggregated over its elements, and any other value as an array
+/// of one. `count` counts values of any type, while the other aggregations
+/// take numbers only. Over no values, `sum` is `0` and `min`, `max` and
+/// `avg` are `null`.
+///
+/// A sum of integers is exact as long as it fits in 64 bits, and `min` and
+/// `max` return the number as written in the input.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `sum`, `min`, `max` or `avg`
+///   meets a value that is not a number.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{aggregate, Aggregate};
+/// use serde_json::json;
+///
+/// let latencies = json!([120, 80, 95.5]);
+///
+/// assert_eq!(aggregate(&latencies, Aggregate::Sum).unwrap(), json!(295.5));
+/// assert_eq!(aggregate(&latencies, Aggregate::Max).unwrap(), json!(120));
+/// assert_eq!(aggregate(&json!([1, 2, 3]), Aggregate::Avg).unwrap(), json!(2));
+/// assert_eq!(aggregate(&json!(["a", null]), Aggregate::Count).unwrap(), json!(2));
+/// assert_eq!(aggregate(&json!([]), Aggregate::Min).unwrap(), json!(null));
+/// assert!(aggregate(&json!([1, "2"]), Aggregate::Sum).is_err());
+/// ```
+pub fn aggregate(value: &Value, op: Aggregate) -> Result<Value, JqrError> {
+    let values = match value {
+        Value::Array(items) => items.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+    if op == Aggregate::Count {
+        return Ok(Value::from(values.len()));
+    }
+
+    let numbers = values
+        .iter()
+        .map(|value| match value {
+            Value::Number(n) => Ok((value, n.as_f64().unwrap_or(f64::NAN))),
+            other => Err(JqrError::UnsupportedValue(format!(
+                "Cannot aggregate a value of type {}: {}",
+                json_type(other),
+                other
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(match op {
+        Aggregate::Sum => {
+            let exact = values.iter().try_fold(0i64, |sum, value| {
+                value.as_i64().and_then(|n| sum.checked_add(n))
+            });
+            match exact {
+                Some(sum) => Value::from(sum),
+                None => to_number(numbers.iter().map(|(_, n)| n).sum())?,
+            }
+        }
+        Aggregate::Min | Aggregate::Max => {
+            let pick = numbers.iter().copied().reduce(|best, candidate| {
+                let better = if op == Aggregate::Min {
+                    candidate.1 < best.1
+                } else {
+                    candidate.1 > best.1
+                };
+                if better {
+                    candidate
+                } else {
+                    best
+                }
+            });
+            pick.map_or(Value::Null, |(value, _)| value.clone())
+        }
+        Aggregate::Avg if numbers.is_empty() => Value::Null,
+        Aggregate::Avg => {
+            to_number(numbers.iter().map(|(_, n)| n).sum::<f64>() / numbers.len() as f64)?
+        }
+        Aggregate::Count => unreachable!("counted above"),
+    })
+}
+
+/// Groups values by the value of a key query into an object of arrays.
+///
+/// The key query runs on each value. A string key names its group as is,
+/// and any other key by its JSON text (`1`, `true`). Values the key query
+/// matches nothing in go to the `null` group. Groups appear in the order
+/// their first value does.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{group_by, CompiledQuery};
+/// use serde_json::json;
+///
+/// let items = [
+///     json!({"name": "apple", "category": "fruit"}),
+///     json!({"name": "leek", "category": "vegetable"}),
+///     json!({"name": "pear", "category": "fruit"}),
+/// ];
+/// let key = CompiledQuery::new("$.category").unwrap();
+///
+/// assert_eq!(
+///     serde_json::Value::Object(group_by(&items, &key)),
+///     json!({
+///         "fruit": [
+///             {"name": "apple", "category": "fruit"},
+///             {"name": "pear", "category": "fruit"}
+///         ],
+///         "vegetable": [{"name": "leek", "category": "vegetable"}]
+///     })
+/// );
+/// ```
+pub fn group_by(values: &[Value], key: &CompiledQuery) -> Map<String, Value> {
+    let mut groups = Map::new();
+    for value in values {
+        let group = groups
+            .entry(group_name(value, key))
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(members) = group {
+            members.push(value.clone());
+        }
+    }
+    groups
+}
+
+/// Counts values by the value of a key query, naming the groups as
+/// [`group_by`] does.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{count_by, CompiledQuery};
+/// use serde_json::json;
+///
+/// let events = [json!({"level": "warn"}), json!({"level": "error"}), json!({"level": "warn"})];
+/// let key = CompiledQuery::new("$.level").unwrap();
+///
+/// assert_eq!(
+///     serde_json::Value::Object(count_by(&events, &key)),
+///     json!({"warn": 2, "error": 1})
+/// );
+/// ```
+pub fn count_by(values: &[Value], key: &CompiledQuery) -> Map<String, Value> {
+    let mut counts: Map<String, Value> = Map::new();
+    for value in values {
+        let count = counts
+            .entry(group_name(value, key))
+            .or_insert(Value::from(0));
+        *count = Value::from(count.as_u64().unwrap_or(0) + 1);
+    }
+    counts
+}
+
+/// Names the group of a value: its key as is if a string, else as JSON text.
+fn group_name(value: &Value, key: &CompiledQuery) -> String {
+    match key.apply(value) {
+        Some(Value::String(name)) => name,
+        Some(other) => other.to_string(),
+        None => "null".to_string(),
+    }
+}
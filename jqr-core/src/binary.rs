@@ -0,0 +1,603 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{Map, Number, Value};
+
+use crate::compare::child_path;
+use crate::JqrError;
+
+/// Decodes MessagePack input into JSON values, one per encoded value, so a
+/// stream of concatenated messages reads like NDJSON.
+///
+/// Binary data and extension values have no JSON counterpart: binary becomes
+/// a base64 string, and an extension an object with its `type` and base64
+/// `data`. Map keys that are not strings are written as their JSON text, so
+/// the key `1` becomes `"1"`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidMsgpack`] if the input is truncated or not
+///   MessagePack, or a float is NaN or infinite.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::msgpack_to_json;
+/// use serde_json::json;
+///
+/// // {"id": 1, "raw": bin8 [0xff]} followed by the string "next"
+/// let bytes = b"\x82\xa2id\x01\xa3raw\xc4\x01\xff\xa4next";
+///
+/// assert_eq!(
+///     msgpack_to_json(bytes).unwrap(),
+///     vec![json!({"id": 1, "raw": "/w=="}), json!("next")]
+/// );
+/// assert!(msgpack_to_json(b"\x82\xa2id").is_err());
+/// ```
+pub fn msgpack_to_json(bytes: &[u8]) -> Result<Vec<Value>, JqrError> {
+    let mut reader = bytes;
+    let mut values = Vec::new();
+    while !reader.is_empty() {
+        let offset = bytes.len() - reader.len();
+        let value = rmpv::decode::read_value(&mut reader).map_err(|e| {
+            JqrError::InvalidMsgpack(format!("{} (in the value at byte {})", e, offset))
+        })?;
+        values.push(from_msgpack_value(value, "$")?);
+    }
+    Ok(values)
+}
+
+fn from_msgpack_value(value: rmpv::Value, path: &str) -> Result<Value, JqrError> {
+    Ok(match value {
+        rmpv::Value::Nil => Value::Null,
+        rmpv::Value::Boolean(b) => Value::Bool(b),
+        rmpv::Value::Integer(i) => match i.as_u64() {
+            Some(u) => Value::from(u),
+            None => Value::from(i.as_i64().unwrap_or_default()),
+        },
+        rmpv::Value::F32(f) => float(f64::from(f), path)?,
+        rmpv::Value::F64(f) => float(f, path)?,
+        rmpv::Value::String(s) => Value::String(match s.into_str() {
+            Some(s) => s,
+            None => {
+                return Err(JqrError::InvalidMsgpack(format!(
+                    "string is not valid UTF-8 (at {})",
+                    path
+                )))
+            }
+        }),
+        rmpv::Value::Binary(bytes) => Value::String(STANDARD.encode(bytes)),
+        rmpv::Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| from_msgpack_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        rmpv::Value::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    rmpv::Value::String(s) if s.is_str() => s.into_str().unwrap(),
+                    other => from_msgpack_value(other, path)?.to_string(),
+                };
+                let value = from_msgpack_value(value, &child_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+        rmpv::Value::Ext(kind, data) => {
+            let mut map = Map::new();
+            map.insert("type".to_string(), Value::from(kind));
+            map.insert("data".to_string(), Value::String(STANDARD.encode(data)));
+            Value::Object(map)
+        }
+    })
+}
+
+fn float(f: f64, path: &str) -> Result<Value, JqrError> {
+    Number::from_f64(f)
+        .map(Value::Number)
+        .ok_or_else(|| JqrError::InvalidMsgpack(format!("JSON has no {} value (at {})", f, path)))
+}
+
+/// Encodes a JSON value as a single MessagePack value.
+///
+/// Integers use the smallest integer encoding that holds them and other
+/// numbers a 64-bit float; object keys keep the order the value holds them in.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] for an integer outside the 64-bit
+///   range MessagePack can encode.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{json_to_msgpack, msgpack_to_json};
+/// use serde_json::json;
+///
+/// let value = json!({"id": 1, "tags": ["a"], "score": 0.5});
+/// let bytes = json_to_msgpack(&value).unwrap();
+///
+/// assert_eq!(&bytes[..5], b"\x83\xa2id\x01");
+/// assert_eq!(msgpack_to_json(&bytes).unwrap(), vec![value]);
+/// ```
+pub fn json_to_msgpack(value: &Value) -> Result<Vec<u8>, JqrError> {
+    let mut bytes = Vec::new();
+    rmpv::encode::write_value(&mut bytes, &to_msgpack_value(value, "$")?)
+        .map_err(|e| JqrError::Serialization(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn to_msgpack_value(value: &Value, path: &str) -> Result<rmpv::Value, JqrError> {
+    Ok(match value {
+        Value::Null => rmpv::Value::Nil,
+        Value::Bool(b) => rmpv::Value::Boolean(*b),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                rmpv::Value::from(u)
+            } else if let Some(i) = n.as_i64() {
+                rmpv::Value::from(i)
+            } else if !n.to_string().contains(['.', 'e', 'E']) {
+                // An integer literal that did not fit would silently become a float
+                return Err(JqrError::UnsupportedValue(format!(
+                    "Integer {} is out of MessagePack's 64-bit range (at {})",
+                    n, path
+                )));
+            } else {
+                rmpv::Value::F64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        Value::String(s) => rmpv::Value::from(s.as_str()),
+        Value::Array(items) => rmpv::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_msgpack_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => rmpv::Value::Map(
+            map.iter()
+                .map(|(key, value)| {
+                    Ok((
+                        rmpv::Value::from(key.as_str()),
+                        to_msgpack_value(value, &child_path(path, key))?,
+                    ))
+                })
+                .collect::<Result<_, JqrError>>()?,
+        ),
+    })
+}
+
+/// Decodes CBOR input into JSON values, one per encoded data item, so a CBOR
+/// sequence (RFC 8742) reads like NDJSON.
+///
+/// Byte strings become base64 strings and tagged items are read as the item
+/// they wrap, so a COSE payload or a date keeps its content but not its tag.
+/// Map keys that are not strings are written as their JSON text, so the key
+/// `1` becomes `"1"`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidCbor`] if the input is truncated or not CBOR,
+///   or a float is NaN or infinite.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::cbor_to_json;
+/// use serde_json::json;
+///
+/// // {"id": 1, "raw": h'ff'} followed by tag 1 (epoch time) wrapping 0
+/// let bytes = b"\xa2\x62id\x01\x63raw\x41\xff\xc1\x00";
+///
+/// assert_eq!(
+///     cbor_to_json(bytes).unwrap(),
+///     vec![json!({"id": 1, "raw": "/w=="}), json!(0)]
+/// );
+/// assert!(cbor_to_json(b"\xa2\x62id").is_err());
+/// ```
+pub fn cbor_to_json(bytes: &[u8]) -> Result<Vec<Value>, JqrError> {
+    let mut reader = bytes;
+    let mut values = Vec::new();
+    while !reader.is_empty() {
+        let offset = bytes.len() - reader.len();
+        let value: ciborium::Value = ciborium::from_reader(&mut reader).map_err(|e| {
+            JqrError::InvalidCbor(format!("{} (in the item at byte {})", e, offset))
+        })?;
+        values.push(from_cbor_value(value, "$")?);
+    }
+    Ok(values)
+}
+
+fn from_cbor_value(value: ciborium::Value, path: &str) -> Result<Value, JqrError> {
+    Ok(match value {
+        ciborium::Value::Null => Value::Null,
+        ciborium::Value::Bool(b) => Value::Bool(b),
+        // Beyond 64 bits, the number is kept exactly as its decimal digits
+        ciborium::Value::Integer(i) => match (u64::try_from(i), i64::try_from(i)) {
+            (Ok(u), _) => Value::from(u),
+            (_, Ok(i)) => Value::from(i),
+            _ => Value::Number(i128::from(i).to_string().parse().unwrap()),
+        },
+        ciborium::Value::Float(f) => Number::from_f64(f).map(Value::Number).ok_or_else(|| {
+            JqrError::InvalidCbor(format!("JSON has no {} value (at {})", f, path))
+        })?,
+        ciborium::Value::Text(s) => Value::String(s),
+        ciborium::Value::Bytes(bytes) => Value::String(STANDARD.encode(bytes)),
+        ciborium::Value::Tag(_, value) => from_cbor_value(*value, path)?,
+        ciborium::Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| from_cbor_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        ciborium::Value::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    ciborium::Value::Text(s) => s,
+                    other => from_cbor_value(other, path)?.to_string(),
+                };
+                let value = from_cbor_value(value, &child_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+        other => {
+            return Err(JqrError::InvalidCbor(format!(
+                "unsupported CBOR value {:?} (at {})",
+                other, path
+            )))
+        }
+    })
+}
+
+/// Encodes a JSON value as a single CBOR data item.
+///
+/// Integers use the smallest encoding that holds them and other numbers a
+/// 64-bit float; object keys keep the order the value holds them in.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] for an integer outside the range
+///   CBOR can encode without a bignum tag, from -2^64 to 2^64 - 1.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{cbor_to_json, json_to_cbor};
+/// use serde_json::json;
+///
+/// let value = json!({"id": 1, "tags": ["a"], "score": 0.5});
+/// let bytes = json_to_cbor(&value).unwrap();
+///
+/// assert_eq!(&bytes[..5], b"\xa3\x62id\x01");
+/// assert_eq!(cbor_to_json(&bytes).unwrap(), vec![value]);
+/// ```
+pub fn json_to_cbor(value: &Value) -> Result<Vec<u8>, JqrError> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&to_cbor_value(value, "$")?, &mut bytes)
+        .map_err(|e| JqrError::Serialization(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn to_cbor_value(value: &Value, path: &str) -> Result<ciborium::Value, JqrError> {
+    Ok(match value {
+        Value::Null => ciborium::Value::Null,
+        Value::Bool(b) => ciborium::Value::Bool(*b),
+        Value::Number(n) => {
+            let text = n.to_string();
+            if text.contains(['.', 'e', 'E']) {
+                ciborium::Value::Float(n.as_f64().unwrap_or(f64::NAN))
+            } else {
+                let integer = text
+                    .parse::<i128>()
+                    .ok()
+                    .and_then(|i| ciborium::value::Integer::try_from(i).ok())
+                    .ok_or_else(|| {
+                        JqrError::UnsupportedValue(format!(
+                            "Integer {} is out of CBOR's 64-bit range (at {})",
+                            n, path
+                        ))
+                    })?;
+                ciborium::Value::Integer(integer)
+            }
+        }
+        Value::String(s) => ciborium::Value::Text(s.clone()),
+        Value::Array(items) => ciborium::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_cbor_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => ciborium::Value::Map(
+            map.iter()
+                .map(|(key, value)| {
+                    Ok((
+                        ciborium::Value::Text(key.clone()),
+                        to_cbor_value(value, &child_path(path, key))?,
+                    ))
+                })
+                .collect::<Result<_, JqrError>>()?,
+        ),
+    })
+}
+
+/// Decodes BSON input, such as a `mongodump` collection file, into JSON
+/// values, one per document.
+///
+/// Values without a plain JSON counterpart follow MongoDB's relaxed Extended
+/// JSON: an ObjectId becomes `{"$oid": ...}`, a date `{"$date": ...}` and
+/// binary data `{"$binary": {"base64": ..., "subType": ...}}`, while numbers
+/// are written as plain JSON numbers.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidBson`] if a document is truncated or not
+///   valid BSON.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::bson_to_json;
+/// use serde_json::json;
+///
+/// // {"_id": ObjectId("65a1b2c3d4e5f60718293a4b"), "n": 1}
+/// let bytes = b"\x1d\x00\x00\x00\
+///     \x07_id\x00\x65\xa1\xb2\xc3\xd4\xe5\xf6\x07\x18\x29\x3a\x4b\
+///     \x10n\x00\x01\x00\x00\x00\
+///     \x00";
+///
+/// assert_eq!(
+///     bson_to_json(bytes).unwrap(),
+///     vec![json!({"_id": {"$oid": "65a1b2c3d4e5f60718293a4b"}, "n": 1})]
+/// );
+/// assert!(bson_to_json(&bytes[..10]).is_err());
+/// ```
+pub fn bson_to_json(bytes: &[u8]) -> Result<Vec<Value>, JqrError> {
+    let mut reader = bytes;
+    let mut values = Vec::new();
+    while !reader.is_empty() {
+        let offset = bytes.len() - reader.len();
+        let document = bson::Document::from_reader(&mut reader).map_err(|e| {
+            JqrError::InvalidBson(format!("{} (in the document at byte {})", e, offset))
+        })?;
+        values.push(bson::Bson::Document(document).into_relaxed_extjson());
+    }
+    Ok(values)
+}
+
+/// Decodes Avro input into JSON values, one per record.
+///
+/// Without a schema, the input must be an object container file, whose
+/// header carries the schema. With `schema`, the JSON text of an `.avsc`
+/// schema, the input is instead a sequence of raw datums written with it, as
+/// found in message queues.
+///
+/// Bytes and fixed values become base64 strings, enum symbols and UUIDs
+/// strings, and unions the value of their branch. Dates, times and
+/// timestamps stay numbers in the units of their logical type, and a decimal
+/// is its unscaled integer, since the scale is only in the schema.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidAvro`] if the schema is invalid, or the
+///   input is not an Avro container file or does not match the schema.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::avro_to_json;
+/// use serde_json::json;
+///
+/// let schema = r#"{"type": "record", "name": "User", "fields": [
+///     {"name": "id", "type": "long"},
+///     {"name": "name", "type": "string"}
+/// ]}"#;
+///
+/// // Two datums: {id: 1, name: "Ann"} and {id: 2, name: "Bo"}
+/// assert_eq!(
+///     avro_to_json(b"\x02\x06Ann\x04\x04Bo", Some(schema)).unwrap(),
+///     vec![json!({"id": 1, "name": "Ann"}), json!({"id": 2, "name": "Bo"})]
+/// );
+/// assert!(avro_to_json(b"\x02\x06Ann", None).is_err());
+/// ```
+pub fn avro_to_json(bytes: &[u8], schema: Option<&str>) -> Result<Vec<Value>, JqrError> {
+    let invalid = |e: apache_avro::Error| JqrError::InvalidAvro(e.to_string());
+    let mut reader = bytes;
+    let mut values = Vec::new();
+
+    match schema {
+        Some(schema) => {
+            let schema = apache_avro::Schema::parse_str(schema).map_err(invalid)?;
+            let datums = apache_avro::reader::datum::GenericDatumReader::builder(&schema)
+                .build()
+                .map_err(invalid)?;
+            while !reader.is_empty() {
+                let offset = bytes.len() - reader.len();
+                let value = datums.read_value(&mut reader).map_err(|e| {
+                    JqrError::InvalidAvro(format!("{} (in the datum at byte {})", e, offset))
+                })?;
+                values.push(from_avro_value(value, "$")?);
+            }
+        }
+        None => {
+            for value in apache_avro::Reader::new(reader).map_err(invalid)? {
+                values.push(from_avro_value(value.map_err(invalid)?, "$")?);
+            }
+        }
+    }
+    Ok(values)
+}
+
+fn from_avro_value(value: apache_avro::types::Value, path: &str) -> Result<Value, JqrError> {
+    use apache_avro::types::Value as Avro;
+
+    let float = |f: f64| {
+        Number::from_f64(f)
+            .map(Value::Number)
+            .ok_or_else(|| JqrError::InvalidAvro(format!("JSON has no {} value (at {})", f, path)))
+    };
+    Ok(match value {
+        Avro::Null => Value::Null,
+        Avro::Boolean(b) => Value::Bool(b),
+        Avro::Int(i) | Avro::Date(i) | Avro::TimeMillis(i) => Value::from(i),
+        Avro::Long(i)
+        | Avro::TimeMicros(i)
+        | Avro::TimestampMillis(i)
+        | Avro::TimestampMicros(i)
+        | Avro::TimestampNanos(i)
+        | Avro::LocalTimestampMillis(i)
+        | Avro::LocalTimestampMicros(i)
+        | Avro::LocalTimestampNanos(i) => Value::from(i),
+        Avro::Float(f) => float(f64::from(f))?,
+        Avro::Double(f) => float(f)?,
+        Avro::String(s) | Avro::Enum(_, s) => Value::String(s),
+        Avro::Bytes(bytes) | Avro::Fixed(_, bytes) => Value::String(STANDARD.encode(bytes)),
+        Avro::Uuid(uuid) => Value::String(uuid.to_string()),
+        Avro::Decimal(decimal) => {
+            let unscaled = Vec::<u8>::try_from(decimal)
+                .ok()
+                .and_then(|bytes| unscaled_integer(&bytes))
+                .ok_or_else(|| {
+                    JqrError::InvalidAvro(format!("decimal is wider than 128 bits (at {})", path))
+                })?;
+            Value::Number(unscaled.to_string().parse().unwrap())
+        }
+        Avro::BigDecimal(decimal) => Value::Number(decimal.to_string().parse().map_err(|_| {
+            JqrError::InvalidAvro(format!(
+                "decimal {} is not a JSON number (at {})",
+                decimal, path
+            ))
+        })?),
+        Avro::Duration(duration) => {
+            let mut map = Map::new();
+            map.insert(
+                "months".to_string(),
+                Value::from(u32::from(duration.months())),
+            );
+            map.insert("days".to_string(), Value::from(u32::from(duration.days())));
+            map.insert(
+                "millis".to_string(),
+                Value::from(u32::from(duration.millis())),
+            );
+            Value::Object(map)
+        }
+        Avro::Union(_, value) => from_avro_value(*value, path)?,
+        Avro::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| from_avro_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        // Avro maps are unordered, so their keys are sorted
+        Avro::Map(entries) => {
+            let mut entries: Vec<_> = entries.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let value = from_avro_value(value, &child_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+        Avro::Record(fields) => {
+            let mut map = Map::new();
+            for (key, value) in fields {
+                let value = from_avro_value(value, &child_path(path, &key))?;
+                map.insert(key, value);
+            }
+            Value::Object(map)
+        }
+    })
+}
+
+/// Reads big-endian two's complement bytes as an integer, if they fit in 128 bits.
+fn unscaled_integer(bytes: &[u8]) -> Option<i128> {
+    if bytes.len() > 16 {
+        return None;
+    }
+    let negative = bytes.first().is_some_and(|byte| byte & 0x80 != 0);
+    let mut extended = [if negative { 0xff } else { 0 }; 16];
+    extended[16 - bytes.len()..].copy_from_slice(bytes);
+    Some(i128::from_be_bytes(extended))
+}
+
+/// Decodes a binary protobuf message of type `message_type` into JSON, with
+/// the types from `descriptor_set`, a compiled `FileDescriptorSet` as written
+/// by `protoc --descriptor_set_out` (with `--include_imports`).
+///
+/// The result follows the canonical proto3 JSON mapping: fields use their
+/// lowerCamelCase JSON names, fields holding their default value are left
+/// out, 64-bit integers are strings and bytes are base64.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidArgument`] if the descriptor set cannot be
+///   read or has no message named `message_type`.
+/// * Returns [`JqrError::InvalidProtobuf`] if the payload is not a valid
+///   encoding of the message.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::proto_to_json;
+/// use prost_reflect::prost::Message;
+/// use prost_reflect::prost_types::{
+///     field_descriptor_proto::Type, DescriptorProto, FieldDescriptorProto,
+///     FileDescriptorProto, FileDescriptorSet,
+/// };
+/// use serde_json::json;
+///
+/// // message shop.Order { int32 order_id = 1; }
+/// let descriptors = FileDescriptorSet {
+///     file: vec![FileDescriptorProto {
+///         name: Some("order.proto".into()),
+///         package: Some("shop".into()),
+///         syntax: Some("proto3".into()),
+///         message_type: vec![DescriptorProto {
+///             name: Some("Order".into()),
+///             field: vec![FieldDescriptorProto {
+///                 name: Some("order_id".into()),
+///                 number: Some(1),
+///                 r#type: Some(Type::Int32 as i32),
+///                 json_name: Some("orderId".into()),
+///                 ..Default::default()
+///             }],
+///             ..Default::default()
+///         }],
+///         ..Default::default()
+///     }],
+/// }
+/// .encode_to_vec();
+///
+/// assert_eq!(
+///     proto_to_json(b"\x08\x2a", &descriptors, "shop.Order").unwrap(),
+///     json!({"orderId": 42})
+/// );
+/// assert!(proto_to_json(b"\x08", &descriptors, "shop.Order").is_err());
+/// assert!(proto_to_json(b"", &descriptors, "shop.Missing").is_err());
+/// ```
+pub fn proto_to_json(
+    bytes: &[u8],
+    descriptor_set: &[u8],
+    message_type: &str,
+) -> Result<Value, JqrError> {
+    let pool = prost_reflect::DescriptorPool::decode(descriptor_set).map_err(|e| {
+        JqrError::InvalidArgument(format!("Invalid protobuf descriptor set: {}", e))
+    })?;
+    let descriptor = pool.get_message_by_name(message_type).ok_or_else(|| {
+        JqrError::InvalidArgument(format!(
+            "The descriptor set has no message type '{}'",
+            message_type
+        ))
+    })?;
+
+    let message = prost_reflect::DynamicMessage::decode(descriptor, bytes)
+        .map_err(|e| JqrError::InvalidProtobuf(e.to_string()))?;
+    serde_json::to_value(&message).map_err(|e| JqrError::Serialization(e.to_string()))
+}
@@ -0,0 +1,128 @@
+use serde_json::{Number, Value};
+
+/// Compares two JSON values structurally.
+///
+/// Object key order is ignored and numbers are compared by value, so `1`
+/// and `1.0` are considered equal even though `serde_json` represents them
+/// differently.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_equal;
+/// use serde_json::json;
+///
+/// assert!(json_equal(&json!({"a": 1, "b": [true]}), &json!({"b": [true], "a": 1.0})));
+/// assert!(!json_equal(&json!("1"), &json!(1)));
+/// ```
+pub fn json_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => numbers_equal(x, y),
+        (Value::Array(xs), Value::Array(ys)) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| json_equal(x, y))
+        }
+        (Value::Object(xm), Value::Object(ym)) => {
+            xm.len() == ym.len()
+                && xm
+                    .iter()
+                    .all(|(k, x)| ym.get(k).is_some_and(|y| json_equal(x, y)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Lists the differences between an expected and an actual JSON value.
+///
+/// Each entry names the JSONPath location of a difference, using the same
+/// semantic equality as `json_equal`. An empty vector means the values are
+/// equal.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_diff;
+/// use serde_json::json;
+///
+/// let expected = json!({"status": "ok", "count": 2});
+/// let actual = json!({"status": "error", "count": 2, "extra": true});
+///
+/// assert_eq!(
+///     json_diff(&expected, &actual),
+///     vec![
+///         "$.extra: unexpected value true".to_string(),
+///         "$.status: expected \"ok\", found \"error\"".to_string(),
+///     ]
+/// );
+/// ```
+pub fn json_diff(expected: &Value, actual: &Value) -> Vec<String> {
+    let mut differences = Vec::new();
+    diff_at("$", expected, actual, &mut differences);
+    differences
+}
+
+/// Recursively collects the differences below `path`.
+fn diff_at(path: &str, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(em), Value::Object(am)) => {
+            // Walk the union of keys in sorted order for a stable report
+            let mut keys: Vec<&String> = em.keys().chain(am.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let child = child_path(path, key);
+                match (em.get(key), am.get(key)) {
+                    (Some(e), Some(a)) => diff_at(&child, e, a, out),
+                    (Some(e), None) => out.push(format!("{}: missing, expected {}", child, e)),
+                    (None, Some(a)) => out.push(format!("{}: unexpected value {}", child, a)),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (Value::Array(ea), Value::Array(aa)) => {
+            for i in 0..ea.len().max(aa.len()) {
+                let child = format!("{}[{}]", path, i);
+                match (ea.get(i), aa.get(i)) {
+                    (Some(e), Some(a)) => diff_at(&child, e, a, out),
+                    (Some(e), None) => out.push(format!("{}: missing, expected {}", child, e)),
+                    (None, Some(a)) => out.push(format!("{}: unexpected value {}", child, a)),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        _ if json_equal(expected, actual) => {}
+        _ => out.push(format!("{}: expected {}, found {}", path, expected, actual)),
+    }
+}
+
+/// Appends an object key to a JSONPath, using bracket notation when the key
+/// is not a plain identifier.
+pub(crate) fn child_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!(
+            "{}['{}']",
+            path,
+            key.replace('\\', "\\\\").replace('\'', "\\'")
+        )
+    }
+}
+
+/// Compares two numbers by value, exactly for integers and via `f64` otherwise.
+fn numbers_equal(x: &Number, y: &Number) -> bool {
+    if let (Some(a), Some(b)) = (x.as_i64(), y.as_i64()) {
+        return a == b;
+    }
+    if let (Some(a), Some(b)) = (x.as_u64(), y.as_u64()) {
+        return a == b;
+    }
+    match (x.as_f64(), y.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
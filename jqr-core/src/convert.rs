@@ -0,0 +1,730 @@
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::io::BufRead;
+
+use crate::compare::child_path;
+use crate::JqrError;
+
+/// Converts a JSON object into Java `.properties` format.
+///
+/// Nested objects are flattened into dotted keys and array elements into
+/// indexed keys (`servers[0].host`), in the order the object holds them. Scalars are written as plain text, `null`
+/// as an empty value, and empty arrays or objects (which have no leaves to
+/// flatten) as compact JSON. Keys and values are escaped per the
+/// `java.util.Properties` format, including `=`, `:`, line breaks and
+/// non-ASCII characters (as `\uXXXX`, since `Properties.load` reads ISO-8859-1).
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if the value is not a JSON object,
+///   since the top level of a properties file must be a set of named keys.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_to_properties;
+/// use serde_json::json;
+///
+/// let value = json!({"db": {"url": "jdbc:h2:mem", "pool": [5, 10]}, "debug": false});
+/// let properties = json_to_properties(&value).unwrap();
+///
+/// assert_eq!(
+///     properties,
+///     "db.url=jdbc\\:h2\\:mem\ndb.pool[0]=5\ndb.pool[1]=10\ndebug=false\n"
+/// );
+/// ```
+pub fn json_to_properties(value: &Value) -> Result<String, JqrError> {
+    let map = value.as_object().ok_or_else(|| {
+        JqrError::UnsupportedValue(
+            "Properties output requires a JSON object at the top level".to_string(),
+        )
+    })?;
+
+    let mut lines = Vec::new();
+    for (key, child) in map {
+        collect_properties(key.clone(), child, &mut lines);
+    }
+
+    Ok(lines.into_iter().map(|line| line + "\n").collect())
+}
+
+/// Flattens `value` below `key` into `key=value` lines.
+fn collect_properties(key: String, value: &Value, lines: &mut Vec<String>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (child_key, child) in map {
+                collect_properties(format!("{}.{}", key, child_key), child, lines);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                collect_properties(format!("{}[{}]", key, i), item, lines);
+            }
+        }
+        leaf => {
+            let text = match leaf {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            lines.push(format!(
+                "{}={}",
+                escape_properties(&key, true),
+                escape_properties(&text, false)
+            ));
+        }
+    }
+}
+
+/// Escapes a key or value for a `.properties` file.
+///
+/// Spaces are significant at the start of values and anywhere in keys, so they
+/// are escaped there only.
+fn escape_properties(text: &str, is_key: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '=' | ':' | '#' | '!' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' ' if is_key || i == 0 => escaped.push_str("\\ "),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{0c}' => escaped.push_str("\\f"),
+            c if (c as u32) < 0x20 || (c as u32) > 0x7e => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Converts a JSON object into a TOML document.
+///
+/// Nested objects become tables and arrays of objects arrays of tables, in
+/// the order the object holds them. Integers are written as TOML integers and
+/// other numbers as floats.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if the value is not a JSON object,
+///   or holds a `null` or an integer outside the 64-bit range, which TOML
+///   cannot represent. The message gives the path of the offending value.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_to_toml;
+/// use serde_json::json;
+///
+/// let value = json!({"title": "demo", "server": {"port": 8080, "hosts": ["a", "b"]}});
+/// assert_eq!(
+///     json_to_toml(&value).unwrap(),
+///     "title = \"demo\"\n\n[server]\nport = 8080\nhosts = [\"a\", \"b\"]\n"
+/// );
+///
+/// let err = json_to_toml(&json!({"server": {"host": null}})).unwrap_err();
+/// assert_eq!(err.to_string(), "TOML has no null value (at $.server.host)");
+/// ```
+pub fn json_to_toml(value: &Value) -> Result<String, JqrError> {
+    if !value.is_object() {
+        return Err(JqrError::UnsupportedValue(
+            "TOML output requires a JSON object at the top level".to_string(),
+        ));
+    }
+
+    toml::to_string(&to_toml_value(value, "$")?).map_err(|e| JqrError::Serialization(e.to_string()))
+}
+
+fn to_toml_value(value: &Value, path: &str) -> Result<toml::Value, JqrError> {
+    Ok(match value {
+        Value::Null => {
+            return Err(JqrError::UnsupportedValue(format!(
+                "TOML has no null value (at {})",
+                path
+            )))
+        }
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            // An integer literal that did not fit would silently become a float
+            None if !n.to_string().contains(['.', 'e', 'E']) => {
+                return Err(JqrError::UnsupportedValue(format!(
+                    "Integer {} is out of TOML's 64-bit range (at {})",
+                    n, path
+                )))
+            }
+            None => toml::Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(items) => toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_toml_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => toml::Value::Table(
+            map.iter()
+                .map(|(key, value)| {
+                    Ok((key.clone(), to_toml_value(value, &child_path(path, key))?))
+                })
+                .collect::<Result<_, JqrError>>()?,
+        ),
+    })
+}
+
+/// Parses a TOML document into a JSON object.
+///
+/// Tables become objects with their keys in document order. Dates and times
+/// have no JSON counterpart and are converted to strings in their TOML form.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidToml`] if the input is not valid TOML.
+/// * Returns [`JqrError::UnsupportedValue`] for the floats `nan` and `inf`,
+///   which JSON cannot represent.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::toml_to_json;
+/// use serde_json::json;
+///
+/// let toml = "title = \"demo\"\n\n[server]\nport = 8080\nstarted = 2024-05-01\n";
+/// assert_eq!(
+///     toml_to_json(toml).unwrap(),
+///     json!({"title": "demo", "server": {"port": 8080, "started": "2024-05-01"}})
+/// );
+///
+/// assert!(toml_to_json("title = ").unwrap_err().to_string().starts_with("Invalid TOML"));
+/// ```
+pub fn toml_to_json(content: &str) -> Result<Value, JqrError> {
+    let table: toml::Table =
+        toml::from_str(content).map_err(|e| JqrError::InvalidToml(e.to_string()))?;
+    from_toml_value(toml::Value::Table(table), "$")
+}
+
+/// Parses a JSON5 document into JSON.
+///
+/// JSON5 adds what hand-written configuration files tend to use: comments,
+/// unquoted and single-quoted keys, single-quoted strings, trailing commas,
+/// hexadecimal numbers and numbers with a leading `+` or a leading or
+/// trailing decimal point. Objects keep their keys in document order.
+/// Numbers are read as 64-bit floats or 128-bit integers, except in plain
+/// JSON, which is valid JSON5 and reads exactly as with [`parse_json`](crate::parse_json).
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson5`] if the input is not valid JSON5, or
+///   holds `Infinity` or `NaN`, which JSON cannot represent.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json5_to_json;
+/// use serde_json::json;
+///
+/// let json5 = "{\n  // the service\n  name: 'api',\n  port: 0x1F90,\n  ratio: .5,\n  tags: ['a', 'b',],\n}";
+/// assert_eq!(
+///     json5_to_json(json5).unwrap(),
+///     json!({"name": "api", "port": 8080, "ratio": 0.5, "tags": ["a", "b"]})
+/// );
+///
+/// let err = json5_to_json("{timeout: Infinity}").unwrap_err();
+/// assert!(err.to_string().starts_with("Invalid JSON5: JSON has no inf value"));
+/// ```
+pub fn json5_to_json(content: &str) -> Result<Value, JqrError> {
+    // Plain JSON keeps its numbers exact, which the JSON5 reader does not
+    if let Ok(value) = serde_json::from_str(content) {
+        return Ok(value);
+    }
+    json5::from_str::<Json5Value>(content)
+        .map(|value| value.0)
+        .map_err(|e| JqrError::InvalidJson5(e.to_string()))
+}
+
+/// Parses JSON with comments (JSONC), as in VS Code settings and
+/// `tsconfig.json`: `//` line comments, `/* */` block comments and trailing
+/// commas are allowed, and everything else must be standard JSON.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] if the input is not valid JSON once
+///   comments and trailing commas are removed. Lines and columns refer to
+///   the original input.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::jsonc_to_json;
+/// use serde_json::json;
+///
+/// let settings = "{\n  // editor\n  \"editor.tabSize\": 2, /* was 4 */\n  \"files.exclude\": [\"dist\",],\n}";
+/// assert_eq!(
+///     jsonc_to_json(settings).unwrap(),
+///     json!({"editor.tabSize": 2, "files.exclude": ["dist"]})
+/// );
+///
+/// let err = jsonc_to_json("{\n  // unquoted\n  tabSize: 2\n}").unwrap_err();
+/// assert_eq!(err.to_string(), "Invalid JSON: key must be a string at line 3 column 3");
+/// ```
+pub fn jsonc_to_json(content: &str) -> Result<Value, JqrError> {
+    crate::parse_json(&strip_jsonc(content))
+}
+
+/// Replaces the comments and trailing commas of JSONC input with spaces,
+/// leaving standard JSON in which every other byte is where it was.
+///
+/// Comment markers inside strings are left alone, and so is an unterminated
+/// block comment, so the JSON parser reports it where it starts.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::strip_jsonc;
+///
+/// assert_eq!(
+///     strip_jsonc("[1, // one\n \"//\", /* two */ 2,]"),
+///     "[1,       \n \"//\",           2 ]"
+/// );
+/// ```
+pub fn strip_jsonc(content: &str) -> String {
+    let mut bytes = content.as_bytes().to_vec();
+    let mut in_string = false;
+    // The last byte outside whitespace and comments, and its offset if it is
+    // a comma that follows a value
+    let mut previous = b' ';
+    let mut pending_comma = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if in_string {
+            match byte {
+                b'\\' => i += 1,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+
+        match (byte, bytes.get(i + 1)) {
+            (b'/', Some(b'/')) => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |length| i + length);
+                blank(&mut bytes[i..end]);
+                i = end;
+                continue;
+            }
+            (b'/', Some(b'*')) => {
+                if let Some(length) = bytes[i + 2..].windows(2).position(|w| w == b"*/") {
+                    let end = i + 2 + length + 2;
+                    blank(&mut bytes[i..end]);
+                    i = end;
+                    continue;
+                }
+            }
+            (b'}' | b']', _) => {
+                if let Some(comma) = pending_comma.take() {
+                    bytes[comma] = b' ';
+                }
+            }
+            _ => {}
+        }
+
+        if !byte.is_ascii_whitespace() {
+            let follows_value = !matches!(previous, b'{' | b'[' | b',');
+            pending_comma = (byte == b',' && follows_value).then_some(i);
+            in_string = byte == b'"';
+            previous = byte;
+        }
+        i += 1;
+    }
+
+    // Only ASCII bytes and whole UTF-8 sequences were replaced by spaces
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Overwrites `bytes` with spaces, keeping line breaks so lines stay numbered.
+fn blank(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut().filter(|b| **b != b'\n' && **b != b'\r') {
+        *byte = b' ';
+    }
+}
+
+/// A JSON value read from JSON5, refusing the floats JSON cannot represent
+/// instead of turning them into `null`.
+struct Json5Value(Value);
+
+impl<'de> Deserialize<'de> for Json5Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(Json5Visitor).map(Json5Value)
+    }
+}
+
+struct Json5Visitor;
+
+impl<'de> Visitor<'de> for Json5Visitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON5 value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::from(i))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Value, E> {
+        Ok(Value::from(u))
+    }
+
+    fn visit_i128<E: de::Error>(self, i: i128) -> Result<Value, E> {
+        serde_json::from_str(&i.to_string()).map_err(E::custom)
+    }
+
+    fn visit_u128<E: de::Error>(self, u: u128) -> Result<Value, E> {
+        serde_json::from_str(&u.to_string()).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Value, E> {
+        Number::from_f64(f)
+            .map(Value::Number)
+            .ok_or_else(|| E::custom(format!("JSON has no {} value", f)))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(Json5Value(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some((key, Json5Value(child))) = map.next_entry::<String, Json5Value>()? {
+            object.insert(key, child);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+fn from_toml_value(value: toml::Value, path: &str) -> Result<Value, JqrError> {
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => match serde_json::Number::from_f64(f) {
+            Some(n) => Value::Number(n),
+            None => {
+                return Err(JqrError::UnsupportedValue(format!(
+                    "JSON has no {} value (at {})",
+                    f, path
+                )))
+            }
+        },
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| from_toml_value(item, &format!("{}[{}]", path, i)))
+                .collect::<Result<_, _>>()?,
+        ),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| {
+                    let child = child_path(path, &key);
+                    Ok((key, from_toml_value(value, &child)?))
+                })
+                .collect::<Result<_, JqrError>>()?,
+        ),
+    })
+}
+
+/// The RFC 7464 record separator (ASCII RS) that starts every JSON text sequence record.
+pub const JSON_SEQ_RS: char = '\u{1e}';
+
+/// Parses an RFC 7464 JSON text sequence (`application/json-seq`).
+///
+/// The input is split on the record separator (0x1E) and each record is
+/// parsed as a JSON text. Whitespace-only records (such as the empty chunk
+/// before the first separator) are skipped.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJsonRecord`] with the zero-based index of the
+///   first malformed record.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::parse_json_seq;
+/// use serde_json::json;
+///
+/// let input = "\u{1e}{\"id\": 1}\n\u{1e}{\"id\": 2}\n";
+/// assert_eq!(parse_json_seq(input).unwrap(), vec![json!({"id": 1}), json!({"id": 2})]);
+///
+/// let err = parse_json_seq("\u{1e}1\n\u{1e}{oops}\n").unwrap_err();
+/// assert!(err.to_string().starts_with("Invalid JSON in record 1"));
+/// ```
+pub fn parse_json_seq(content: &str) -> Result<Vec<Value>, JqrError> {
+    content
+        .split(JSON_SEQ_RS)
+        .filter(|record| !record.trim().is_empty())
+        .enumerate()
+        .map(|(i, record)| {
+            serde_json::from_str(record).map_err(|e| JqrError::invalid_json_record(e, i))
+        })
+        .collect()
+}
+
+/// Parses newline-delimited JSON (NDJSON / JSON Lines): one JSON text per
+/// line. Blank lines are skipped.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] with the line number of the first
+///   malformed line.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::parse_ndjson;
+/// use serde_json::json;
+///
+/// let input = "{\"id\": 1}\n\n{\"id\": 2}\n";
+/// assert_eq!(parse_ndjson(input).unwrap(), vec![json!({"id": 1}), json!({"id": 2})]);
+///
+/// let err = parse_ndjson("1\n2\n{oops}\n").unwrap_err();
+/// assert!(err.to_string().ends_with("at line 3 column 2"));
+/// ```
+pub fn parse_ndjson(content: &str) -> Result<Vec<Value>, JqrError> {
+    ndjson_records(content.as_bytes()).collect()
+}
+
+/// Reads newline-delimited JSON from `reader` one line at a time, yielding
+/// each record as soon as its line is read, so input of any size, or input
+/// that is still being written, is never held in memory as a whole.
+///
+/// Blank lines are skipped. A malformed line yields an error and reading
+/// goes on with the next line, while an error reading `reader` itself, such
+/// as corrupt compressed input, ends the records.
+///
+/// # Errors
+///
+/// * Yields [`JqrError::InvalidJson`] with the line number of a malformed
+///   line.
+/// * Yields [`JqrError::Io`] if reading fails or a line is not UTF-8.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{ndjson_records, JqrError};
+/// use serde_json::json;
+///
+/// let mut records = ndjson_records("{\"id\": 1}\n{oops}\n\n[2]\n".as_bytes());
+///
+/// assert_eq!(records.next().unwrap().unwrap(), json!({"id": 1}));
+/// assert!(matches!(records.next(), Some(Err(JqrError::InvalidJson { line: 2, .. }))));
+/// assert_eq!(records.next().unwrap().unwrap(), json!([2]));
+/// assert!(records.next().is_none());
+/// ```
+pub fn ndjson_records<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Value, JqrError>> {
+    let mut lines = reader.lines().enumerate();
+    let mut failed = false;
+    std::iter::from_fn(move || loop {
+        if failed {
+            return None;
+        }
+        let (i, line) = lines.next()?;
+        match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => {
+                return Some(
+                    serde_json::from_str(&line).map_err(|e| JqrError::invalid_json_line(e, i + 1)),
+                )
+            }
+            Err(e) => {
+                failed = true;
+                return Some(Err(JqrError::Io(e)));
+            }
+        }
+    })
+}
+
+/// Parses every JSON text in `content`, whether they are on separate lines
+/// (NDJSON) or simply concatenated, as in `{"a":1}{"a":2} 3`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] at the position of the first malformed text.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::parse_json_values;
+/// use serde_json::json;
+///
+/// let values = parse_json_values("{\"a\": 1}{\"a\": 2}\n[3]").unwrap();
+/// assert_eq!(values, vec![json!({"a": 1}), json!({"a": 2}), json!([3])]);
+/// assert!(parse_json_values("").unwrap().is_empty());
+/// ```
+pub fn parse_json_values(content: &str) -> Result<Vec<Value>, JqrError> {
+    serde_json::Deserializer::from_str(content)
+        .into_iter()
+        .map(|value| value.map_err(|e| JqrError::invalid_json(e, false)))
+        .collect()
+}
+
+/// Returns `true` if `text` looks like newline-delimited JSON rather than a
+/// single document: its first non-blank line is a complete JSON text and
+/// another non-blank line follows.
+///
+/// Only the first line is parsed, so `text` may be just the start of the
+/// input.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::looks_like_ndjson;
+///
+/// assert!(looks_like_ndjson("{\"id\": 1}\n{\"id\": 2}\n"));
+/// assert!(!looks_like_ndjson("{\"id\": 1}\n"));
+/// assert!(!looks_like_ndjson("{\n  \"id\": 1\n}\n"));
+/// ```
+pub fn looks_like_ndjson(text: &str) -> bool {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    match (lines.next(), lines.next()) {
+        (Some(first), Some(_)) => serde_json::from_str::<IgnoredAny>(first).is_ok(),
+        _ => false,
+    }
+}
+
+/// Returns `true` if `text` is an RFC 7464 JSON text sequence, i.e. its
+/// first non-whitespace character is the record separator (0x1E).
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::looks_like_json_seq;
+///
+/// assert!(looks_like_json_seq("\u{1e}{\"id\": 1}\n"));
+/// assert!(!looks_like_json_seq("{\"id\": 1}\n"));
+/// ```
+pub fn looks_like_json_seq(text: &str) -> bool {
+    text.trim_start_matches(|c: char| c.is_ascii_whitespace())
+        .starts_with(JSON_SEQ_RS)
+}
+
+/// Serializes values as an RFC 7464 JSON text sequence.
+///
+/// Each value is written as compact JSON, prefixed with the record separator
+/// (0x1E) and terminated by a line feed.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Serialization`] if a value cannot be serialized.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::to_json_seq;
+/// use serde_json::json;
+///
+/// let output = to_json_seq(&[json!({"id": 1}), json!("two")]).unwrap();
+/// assert_eq!(output, "\u{1e}{\"id\":1}\n\u{1e}\"two\"\n");
+/// ```
+pub fn to_json_seq(values: &[Value]) -> Result<String, JqrError> {
+    let mut output = String::new();
+    for value in values {
+        let record =
+            serde_json::to_string(value).map_err(|e| JqrError::Serialization(e.to_string()))?;
+        output.push(JSON_SEQ_RS);
+        output.push_str(&record);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Rewrites the string tokens of serialized JSON to use single quotes.
+///
+/// The input must be JSON text as produced by `serde_json`. Each string's
+/// delimiters become `'`, escaped double quotes (`\"`) inside it are unescaped,
+/// and literal single quotes are escaped as `\'`. All other escapes and the
+/// structural characters are left untouched. The result is a JavaScript
+/// literal, **not** valid JSON.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::single_quote_strings;
+///
+/// let json = r#"{"name": "O'Brien", "quote": "say \"hi\"", "n": 1}"#;
+/// assert_eq!(
+///     single_quote_strings(json),
+///     r#"{'name': 'O\'Brien', 'quote': 'say "hi"', 'n': 1}"#
+/// );
+/// ```
+pub fn single_quote_strings(json: &str) -> String {
+    let mut output = String::with_capacity(json.len());
+    let mut chars = json.chars();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                output.push('\'');
+            }
+            '\\' if in_string => match chars.next() {
+                Some('"') => output.push('"'),
+                Some(escaped) => {
+                    output.push('\\');
+                    output.push(escaped);
+                }
+                None => output.push('\\'),
+            },
+            '\'' if in_string => output.push_str("\\'"),
+            c => output.push(c),
+        }
+    }
+
+    output
+}
@@ -0,0 +1,116 @@
+use serde::de::IgnoredAny;
+use std::path::Path;
+
+use crate::{looks_like_ndjson, looks_like_yaml};
+
+/// The input formats recognized by `detect_input_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// A single JSON document.
+    Json,
+    /// One or more YAML documents.
+    Yaml,
+    /// A TOML document.
+    Toml,
+    /// Newline-delimited JSON, one document per line.
+    Ndjson,
+    /// MessagePack, one document per encoded value.
+    Msgpack,
+}
+
+/// Detects the format of an input from its file name and content.
+///
+/// The rules are applied in order:
+///
+/// - The extension of `path` decides if it is a known one: `.json`
+///   (`.json5`, `.jsonc`), `.yaml` (`.yml`), `.toml`, `.ndjson` (`.jsonl`) or
+///   `.msgpack` (`.mpk`).
+/// - Content starting with the byte of a MessagePack map or array is
+///   MessagePack.
+/// - Text starting with `{` or `[` is JSON, unless its first line is a TOML
+///   table header such as `[package]`, or it looks like newline-delimited
+///   JSON (see `looks_like_ndjson`).
+/// - Text starting with `---` or a `%YAML` directive is YAML.
+/// - Other text is JSON if it parses as JSON, TOML if it parses as TOML and
+///   YAML if it parses as a YAML mapping or sequence.
+///
+/// Anything else, empty input included, is reported as JSON, so that it is
+/// handled the way JSON input is.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{detect_input_format, InputFormat};
+///
+/// assert_eq!(detect_input_format(Some("config.yml"), b"{}"), InputFormat::Yaml);
+/// assert_eq!(detect_input_format(None, b"{\"name\": \"api\"}"), InputFormat::Json);
+/// assert_eq!(detect_input_format(None, b"[package]\nname = \"api\"\n"), InputFormat::Toml);
+/// assert_eq!(detect_input_format(None, b"name: api\nport: 80\n"), InputFormat::Yaml);
+/// assert_eq!(detect_input_format(None, b"{\"id\": 1}\n{\"id\": 2}\n"), InputFormat::Ndjson);
+/// assert_eq!(detect_input_format(None, &[0x81, 0xa1, b'a', 0x01]), InputFormat::Msgpack);
+/// assert_eq!(detect_input_format(None, b"not json"), InputFormat::Json);
+/// ```
+pub fn detect_input_format(path: Option<&str>, content: &[u8]) -> InputFormat {
+    let extension = path
+        .and_then(|path| Path::new(path).extension())
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json" | "json5" | "jsonc") => return InputFormat::Json,
+        Some("yaml" | "yml") => return InputFormat::Yaml,
+        Some("toml") => return InputFormat::Toml,
+        Some("ndjson" | "jsonl") => return InputFormat::Ndjson,
+        Some("msgpack" | "mpk") => return InputFormat::Msgpack,
+        _ => {}
+    }
+
+    // Fixmap, fixarray, and the 16- and 32-bit arrays and maps; none of
+    // these bytes can start UTF-8 text
+    if matches!(content.first(), Some(0x80..=0x9f | 0xdc..=0xdf)) {
+        return InputFormat::Msgpack;
+    }
+
+    let text = String::from_utf8_lossy(content);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text).trim_start();
+    if text.is_empty() {
+        InputFormat::Json
+    } else if text.starts_with(['{', '[']) {
+        if is_toml_table_header(text.lines().next().unwrap_or_default()) {
+            InputFormat::Toml
+        } else if looks_like_ndjson(text) {
+            InputFormat::Ndjson
+        } else {
+            InputFormat::Json
+        }
+    } else if text.starts_with("---") || text.starts_with("%YAML") {
+        InputFormat::Yaml
+    } else if serde_json::from_str::<IgnoredAny>(text).is_ok() {
+        InputFormat::Json
+    } else if toml::from_str::<toml::Table>(text).is_ok() {
+        InputFormat::Toml
+    } else if looks_like_yaml(text) {
+        InputFormat::Yaml
+    } else {
+        InputFormat::Json
+    }
+}
+
+/// Returns `true` if `line` is a TOML `[table]` or `[[array.of.tables]]`
+/// header with bare keys, which no JSON array looks like.
+fn is_toml_table_header(line: &str) -> bool {
+    let line = line.trim_end();
+    let name = line
+        .strip_prefix("[[")
+        .and_then(|rest| rest.strip_suffix("]]"))
+        .or_else(|| {
+            line.strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+        });
+    name.map(str::trim).is_some_and(|name| {
+        name.chars().any(|c| c.is_ascii_alphabetic())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            && !matches!(name, "true" | "false" | "null")
+    })
+}
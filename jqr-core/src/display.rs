@@ -0,0 +1,255 @@
+use serde_json::Value;
+
+use crate::{format_yaml, FormatOptions, JqrError};
+
+/// Prefixes every line of `text` with a right-aligned line number, similar to `cat -n`.
+///
+/// The number column is as wide as the largest line number, so the `|`
+/// separators stay aligned regardless of how long the output is. This is a
+/// display convenience for terminal viewing: the result is no longer valid
+/// JSON or YAML and should not be piped into other tools.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::number_lines;
+///
+/// let text = "{\n  \"a\": 1\n}";
+/// assert_eq!(number_lines(text), "1 | {\n2 |   \"a\": 1\n3 | }");
+/// ```
+pub fn number_lines(text: &str) -> String {
+    let total = text.lines().count();
+    let width = total.to_string().len();
+
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>width$} | {}", i + 1, line, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collapses arrays and objects with more than `threshold` direct children into summaries.
+///
+/// Large containers are replaced by a placeholder string such as
+/// `"[... 500 items]"` or `"{... 30 keys}"`, while smaller ones are kept and
+/// folded recursively. Because the placeholders are plain JSON strings, the
+/// folded output is still valid JSON; it is however lossy, so a query should
+/// be used to drill into a folded section and see its contents.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::fold_large;
+/// use serde_json::json;
+///
+/// let value = json!({"ids": [1, 2, 3, 4], "name": "Alice"});
+/// let folded = fold_large(value, 3);
+///
+/// assert_eq!(folded, json!({"ids": "[... 4 items]", "name": "Alice"}));
+/// ```
+pub fn fold_large(value: Value, threshold: usize) -> Value {
+    match value {
+        Value::Array(items) if items.len() > threshold => {
+            Value::String(format!("[... {} items]", items.len()))
+        }
+        Value::Object(map) if map.len() > threshold => {
+            Value::String(format!("{{... {} keys}}", map.len()))
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| fold_large(item, threshold))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, fold_large(v, threshold)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Maximum width of a table cell before its content is truncated.
+const MAX_CELL_WIDTH: usize = 40;
+
+/// Renders an array of objects as a box-drawn table for terminal viewing.
+///
+/// The columns are the union of all object keys, in order of first
+/// appearance. Strings are shown without quotes, `null` and missing keys as
+/// empty cells, numbers right-aligned, and nested arrays/objects as compact
+/// JSON. Cells longer than 40 characters are truncated with `…`. The output is
+/// meant for eyeballing, not for machine consumption.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if the value is not an array, or if any
+///   element is not an object.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_to_table;
+/// use serde_json::json;
+///
+/// let value = json!([{"name": "Alice", "age": 30}, {"name": "Bob"}]);
+/// let table = json_to_table(&value).unwrap();
+///
+/// assert_eq!(
+///     table,
+///     "┌───────┬─────┐\n\
+///      │ name  │ age │\n\
+///      ├───────┼─────┤\n\
+///      │ Alice │  30 │\n\
+///      │ Bob   │     │\n\
+///      └───────┴─────┘"
+/// );
+/// ```
+pub fn json_to_table(value: &Value) -> Result<String, JqrError> {
+    let not_a_table =
+        || JqrError::UnsupportedValue("Table output requires an array of objects".to_string());
+    let rows = value.as_array().ok_or_else(not_a_table)?;
+
+    // Collect the union of keys, keeping the order in which they first appear
+    let mut columns: Vec<&String> = Vec::new();
+    for row in rows {
+        let object = row.as_object().ok_or_else(not_a_table)?;
+        for key in object.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    // Render every cell up front so column widths can be measured
+    let header: Vec<Cell> = columns
+        .iter()
+        .map(|c| Cell::new(truncate_cell(c), false))
+        .collect();
+    let body: Vec<Vec<Cell>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|c| match row.get(c.as_str()) {
+                    None | Some(Value::Null) => Cell::new(String::new(), false),
+                    Some(Value::String(s)) => Cell::new(truncate_cell(s), false),
+                    Some(v @ Value::Number(_)) => Cell::new(v.to_string(), true),
+                    Some(v) => Cell::new(truncate_cell(&v.to_string()), false),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            body.iter()
+                .map(|row| row[i].width)
+                .chain(std::iter::once(header[i].width))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut lines = vec![border(&widths, '┌', '┬', '┐'), table_row(&header, &widths)];
+    if !body.is_empty() {
+        lines.push(border(&widths, '├', '┼', '┤'));
+        lines.extend(body.iter().map(|row| table_row(row, &widths)));
+    }
+    lines.push(border(&widths, '└', '┴', '┘'));
+
+    Ok(lines.join("\n"))
+}
+
+/// A rendered table cell with its display width and alignment.
+struct Cell {
+    text: String,
+    width: usize,
+    right_align: bool,
+}
+
+impl Cell {
+    fn new(text: String, right_align: bool) -> Self {
+        let width = text.chars().count();
+        Cell {
+            text,
+            width,
+            right_align,
+        }
+    }
+}
+
+/// Flattens control characters and truncates overly long cell content.
+fn truncate_cell(text: &str) -> String {
+    let flat: String = text
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+
+    if flat.chars().count() > MAX_CELL_WIDTH {
+        let mut truncated: String = flat.chars().take(MAX_CELL_WIDTH - 1).collect();
+        truncated.push('…');
+        truncated
+    } else {
+        flat
+    }
+}
+
+/// Draws a horizontal table border using the given corner and junction characters.
+fn border(widths: &[usize], left: char, middle: char, right: char) -> String {
+    let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+    format!("{}{}{}", left, segments.join(&middle.to_string()), right)
+}
+
+/// Draws one row of padded cells.
+fn table_row(cells: &[Cell], widths: &[usize]) -> String {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| {
+            let padding = " ".repeat(width - cell.width);
+            if cell.right_align {
+                format!(" {}{} ", padding, cell.text)
+            } else {
+                format!(" {}{} ", cell.text, padding)
+            }
+        })
+        .collect();
+    format!("│{}│", padded.join("│"))
+}
+
+/// The JSON and YAML renderings of the same value, for showing side by side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DualView {
+    /// The value as pretty-printed JSON.
+    pub json: String,
+    /// The value as YAML, without the trailing newline.
+    pub yaml: String,
+}
+
+/// Renders a value as both pretty JSON and YAML.
+///
+/// Laying out the two sections (headers, colors) is left to the caller.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Serialization`] if the value cannot be serialized to JSON or YAML.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::dual_view;
+/// use serde_json::json;
+///
+/// let view = dual_view(&json!({"name": "Alice"})).unwrap();
+///
+/// assert_eq!(view.json, "{\n  \"name\": \"Alice\"\n}");
+/// assert_eq!(view.yaml, "name: Alice");
+/// ```
+pub fn dual_view(value: &Value) -> Result<DualView, JqrError> {
+    let json =
+        serde_json::to_string_pretty(value).map_err(|e| JqrError::Serialization(e.to_string()))?;
+    let yaml = format_yaml(value, &FormatOptions::new().preserve_order(true))?;
+
+    Ok(DualView { json, yaml })
+}
@@ -0,0 +1,262 @@
+use serde_json::{Map, Value};
+
+use crate::compare::child_path;
+use crate::{find_pointer_paths, json_type, shorthand_to_jsonpath, JqrError};
+
+/// One step of a plain path: an object member or an array element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Sets every node a JSONPath query matches in a document to `new`.
+///
+/// If the query matches nothing, it must be a plain path of member names
+/// and array indexes, such as `$.server.port` or `$.hosts[0]['ip addr']`,
+/// and the node it names is added: a member is added to an object and an
+/// element appended to an array. With `create_missing`, missing or `null`
+/// values along the path are created too, as objects or, before an index,
+/// as arrays, and array elements skipped over are filled with `null`. The
+/// path may be written without the `$` prefix, as `server.port`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if `path` is not valid JSONPath.
+/// * Returns [`JqrError::UnsupportedValue`] if `path` matches nothing and
+///   cannot be added: it is not a plain path, a value along it is missing
+///   and `create_missing` is not set, or a value along it is not an object
+///   or array as the path needs.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::set_path;
+/// use serde_json::json;
+///
+/// let mut config = json!({"server": {"port": 80}, "hosts": [{"up": false}, {"up": false}]});
+/// set_path(&mut config, "$.server.port", json!(8080), false).unwrap();
+/// set_path(&mut config, "$.hosts[*].up", json!(true), false).unwrap();
+/// set_path(&mut config, "$.server.tls", json!({}), false).unwrap();
+/// set_path(&mut config, "$.log.level", json!("debug"), true).unwrap();
+///
+/// assert_eq!(
+///     config,
+///     json!({
+///         "server": {"port": 8080, "tls": {}},
+///         "hosts": [{"up": true}, {"up": true}],
+///         "log": {"level": "debug"}
+///     })
+/// );
+/// assert!(set_path(&mut config, "$.cache.size", json!(1), false).is_err());
+/// ```
+pub fn set_path(
+    value: &mut Value,
+    path: &str,
+    new: Value,
+    create_missing: bool,
+) -> Result<(), JqrError> {
+    let path = shorthand_to_jsonpath(path);
+    let pointers = find_pointer_paths(value, &path)?;
+    if !pointers.is_empty() {
+        for pointer in pointers {
+            if let Some(node) = value.pointer_mut(&pointer) {
+                *node = new.clone();
+            }
+        }
+        return Ok(());
+    }
+
+    let cannot_set =
+        |reason: String| JqrError::UnsupportedValue(format!("Cannot set '{}': {}", path, reason));
+    let steps = parse_plain_path(&path).ok_or_else(|| {
+        cannot_set(
+            "it matches nothing, and only a plain path of names and indexes can be added"
+                .to_string(),
+        )
+    })?;
+
+    let mut node = value;
+    let mut location = "$".to_string();
+    for (depth, step) in steps.iter().enumerate() {
+        let is_last = depth + 1 == steps.len();
+        if node.is_null() && create_missing {
+            *node = match step {
+                Step::Key(_) => Value::Object(Map::new()),
+                Step::Index(_) => Value::Array(Vec::new()),
+            };
+        }
+        node = match (step, node) {
+            (Step::Key(key), Value::Object(map)) => {
+                if !is_last && !create_missing && !map.contains_key(key) {
+                    return Err(cannot_set(format!("{} has no member '{}'", location, key)));
+                }
+                location = child_path(&location, key);
+                map.entry(key.clone()).or_insert(Value::Null)
+            }
+            (Step::Index(i), Value::Array(items)) => {
+                let appends = *i == items.len() && is_last;
+                if *i >= items.len() && !appends && !create_missing {
+                    return Err(cannot_set(format!(
+                        "{} has {} elements, so it has no index {}",
+                        location,
+                        items.len(),
+                        i
+                    )));
+                }
+                if items.len() <= *i {
+                    items.resize(i + 1, Value::Null);
+                }
+                location = format!("{}[{}]", location, i);
+                &mut items[*i]
+            }
+            (step, other) => {
+                let expected = match step {
+                    Step::Key(_) => "an object",
+                    Step::Index(_) => "an array",
+                };
+                return Err(cannot_set(format!(
+                    "{} is of type {}, not {}",
+                    location,
+                    json_type(other),
+                    expected
+                )));
+            }
+        };
+    }
+    *node = new;
+    Ok(())
+}
+
+/// Removes every node a JSONPath query matches from a document: object
+/// members are removed with their key, and array elements are removed so
+/// the elements after them move up. Matching nothing leaves the document
+/// as it is.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if `path` is not valid JSONPath.
+/// * Returns [`JqrError::UnsupportedValue`] if `path` matches the document
+///   itself, which cannot be removed.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::delete_paths;
+/// use serde_json::json;
+///
+/// let mut payload = json!({
+///     "user": {"name": "Ada", "password": "hunter2"},
+///     "tokens": ["a", "b", "c"],
+///     "db": {"password": "s3cret"}
+/// });
+/// delete_paths(&mut payload, "$..password").unwrap();
+/// delete_paths(&mut payload, "$.tokens[0,2]").unwrap();
+///
+/// assert_eq!(payload, json!({"user": {"name": "Ada"}, "tokens": ["b"], "db": {}}));
+/// assert!(delete_paths(&mut payload, "$").is_err());
+/// ```
+pub fn delete_paths(value: &mut Value, path: &str) -> Result<(), JqrError> {
+    let path = shorthand_to_jsonpath(path);
+    let mut pointers: Vec<Vec<String>> = find_pointer_paths(value, &path)?
+        .iter()
+        .map(|pointer| {
+            pointer
+                .split('/')
+                .skip(1)
+                .map(|token| token.replace("~1", "/").replace("~0", "~"))
+                .collect()
+        })
+        .collect();
+    if pointers.iter().any(Vec::is_empty) {
+        return Err(JqrError::UnsupportedValue(format!(
+            "Cannot delete '{}': it matches the whole document",
+            path
+        )));
+    }
+
+    // Remove the last array elements first, so the indexes of the others
+    // still hold, and descendants before their ancestors
+    pointers.sort_by_cached_key(|tokens| {
+        tokens
+            .iter()
+            .map(|token| match token.parse::<usize>() {
+                Ok(i) => (0, i, token.clone()),
+                Err(_) => (1, 0, token.clone()),
+            })
+            .collect::<Vec<_>>()
+    });
+    pointers.dedup();
+    for tokens in pointers.iter().rev() {
+        let (last, parent) = tokens.split_last().expect("the root is rejected above");
+        let mut node = &mut *value;
+        for token in parent {
+            node = match node {
+                Value::Object(map) => map.get_mut(token),
+                Value::Array(items) => token.parse().ok().and_then(|i: usize| items.get_mut(i)),
+                _ => None,
+            }
+            .expect("matched nodes exist until they are removed");
+        }
+        match node {
+            Value::Object(map) => {
+                map.shift_remove(last);
+            }
+            Value::Array(items) => {
+                if let Ok(i) = last.parse::<usize>() {
+                    if i < items.len() {
+                        items.remove(i);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Splits a plain path such as `$.a['b c'][0]` into its steps, or returns
+/// `None` if the path has wildcards, slices, filters or other selectors.
+fn parse_plain_path(path: &str) -> Option<Vec<Step>> {
+    let mut chars = path.strip_prefix('$')?.chars().peekable();
+    let mut steps = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|&c| c.is_alphanumeric() || c == '_' || c == '-')
+                {
+                    name.push(c);
+                }
+                if name.is_empty() {
+                    return None;
+                }
+                steps.push(Step::Key(name));
+            }
+            '[' => match chars.next()? {
+                quote @ ('\'' | '"') => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next()? {
+                            '\\' => name.push(chars.next()?),
+                            c if c == quote => break,
+                            c => name.push(c),
+                        }
+                    }
+                    chars.next_if_eq(&']')?;
+                    steps.push(Step::Key(name));
+                }
+                first => {
+                    let mut digits = first.to_string();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    chars.next_if_eq(&']')?;
+                    steps.push(Step::Index(digits.parse().ok()?));
+                }
+            },
+            _ => return None,
+        }
+    }
+    Some(steps)
+}
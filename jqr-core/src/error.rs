@@ -0,0 +1,178 @@
+use std::io;
+use thiserror::Error;
+
+/// The error type returned by the `jqr` library.
+///
+/// Messages are plain text; presentation such as color is left to the caller.
+#[derive(Debug, Error)]
+pub enum JqrError {
+    /// The input is not valid JSON.
+    ///
+    /// `looks_like_yaml` is set when the input parses as a block YAML mapping
+    /// or sequence instead, so a caller can suggest reading it as YAML.
+    #[error("Invalid JSON: {message} at line {line} column {column}")]
+    InvalidJson {
+        line: usize,
+        column: usize,
+        message: String,
+        looks_like_yaml: bool,
+    },
+
+    /// The input is not valid YAML.
+    #[error("Invalid YAML: {0}")]
+    InvalidYaml(String),
+
+    /// The input is not valid TOML.
+    #[error("Invalid TOML: {0}")]
+    InvalidToml(String),
+
+    /// The input is not well-formed XML.
+    #[error("Invalid XML: {0}")]
+    InvalidXml(String),
+
+    /// The input is not a valid CSV or TSV table.
+    #[error("Invalid CSV: {0}")]
+    InvalidCsv(String),
+
+    /// The input is not a valid JSON5 document.
+    #[error("Invalid JSON5: {0}")]
+    InvalidJson5(String),
+
+    /// The input is not valid MessagePack.
+    #[error("Invalid MessagePack: {0}")]
+    InvalidMsgpack(String),
+
+    /// The input is not valid CBOR.
+    #[error("Invalid CBOR: {0}")]
+    InvalidCbor(String),
+
+    /// The input is not valid BSON.
+    #[error("Invalid BSON: {0}")]
+    InvalidBson(String),
+
+    /// The input is not valid Avro, or does not match its schema.
+    #[error("Invalid Avro: {0}")]
+    InvalidAvro(String),
+
+    /// The input is not a valid encoding of the protobuf message type.
+    #[error("Invalid protobuf: {0}")]
+    InvalidProtobuf(String),
+
+    /// A record of a JSON text sequence is not valid JSON.
+    #[error("Invalid JSON in record {record}: {message} at line {line} column {column}")]
+    InvalidJsonRecord {
+        record: usize,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// An object repeats a key, and the parse options refuse duplicate keys.
+    #[error("Duplicate key '{key}' at line {line} column {column}")]
+    DuplicateKey {
+        key: String,
+        line: usize,
+        column: usize,
+    },
+
+    /// The input nests arrays or objects deeper than the configured limit.
+    #[error("Maximum nesting depth of {max_depth} exceeded at line {line}, column {column}")]
+    NestingTooDeep {
+        max_depth: usize,
+        line: usize,
+        column: usize,
+    },
+
+    /// A query is malformed or its syntax cannot be determined.
+    #[error("{0}")]
+    InvalidQuery(String),
+
+    /// A value could not be serialized to the output format.
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+
+    /// An option value, such as a transform list or rename mapping, is malformed.
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// The value does not have the shape an operation requires, such as
+    /// properties output on a non-object.
+    #[error("{0}")]
+    UnsupportedValue(String),
+
+    /// A transform could not be applied to the value.
+    #[error("{0}")]
+    Transform(String),
+
+    /// Reading input or writing output failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl JqrError {
+    /// Builds a [`JqrError::InvalidJson`] from a parse error.
+    pub(crate) fn invalid_json(error: serde_json::Error, looks_like_yaml: bool) -> Self {
+        let (line, column, message) = locate(&error);
+        JqrError::InvalidJson {
+            line,
+            column,
+            message,
+            looks_like_yaml,
+        }
+    }
+
+    /// Builds a [`JqrError::InvalidJson`] from a parse error in line `line` of
+    /// a multi-line input whose lines are parsed separately.
+    pub(crate) fn invalid_json_line(error: serde_json::Error, line: usize) -> Self {
+        let column = error.column();
+        JqrError::InvalidJson {
+            line,
+            column,
+            message: strip_position(&error, error.line(), column),
+            looks_like_yaml: false,
+        }
+    }
+
+    /// Builds a [`JqrError::InvalidJsonRecord`] from a parse error in record `record`.
+    pub(crate) fn invalid_json_record(error: serde_json::Error, record: usize) -> Self {
+        let (line, column) = (error.line(), error.column());
+        JqrError::InvalidJsonRecord {
+            record,
+            line,
+            column,
+            message: strip_position(&error, line, column),
+        }
+    }
+}
+
+/// Returns the message of a `serde_json` error without its trailing position,
+/// which the variants report in their own fields.
+fn strip_position(error: &serde_json::Error, line: usize, column: usize) -> String {
+    let message = error.to_string();
+    let suffix = format!(" at line {} column {}", line, column);
+    message
+        .strip_suffix(&suffix)
+        .unwrap_or(&message)
+        .to_string()
+}
+
+/// Returns the line, column and message of a parse error.
+///
+/// Errors raised while transcoding reach us re-wrapped by the serializer,
+/// without a position of their own; the original one is then recovered from
+/// the end of the message.
+fn locate(error: &serde_json::Error) -> (usize, usize, String) {
+    if error.line() > 0 {
+        let (line, column) = (error.line(), error.column());
+        return (line, column, strip_position(error, line, column));
+    }
+
+    let message = error.to_string();
+    let position = message
+        .rsplit_once(" at line ")
+        .and_then(|(text, position)| {
+            let (line, column) = position.split_once(" column ")?;
+            Some((line.parse().ok()?, column.parse().ok()?, text.to_string()))
+        });
+    position.unwrap_or((0, 0, message))
+}
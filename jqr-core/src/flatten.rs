@@ -0,0 +1,249 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::{json_type, JqrError};
+
+/// One step of a flat key: an object member or an array element.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Flattens a nested document into an object of its leaves, keyed by their
+/// path in dot notation (`a.b[0].c`).
+///
+/// Object keys are joined with `.` and array elements are written as
+/// `[index]`. A `.`, `[` or `\` inside an object key is escaped with a
+/// backslash, so every path can be read back by [`unflatten`]. Scalars are
+/// leaves, and so are empty arrays and objects, which have no leaves of
+/// their own.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array or
+///   object.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::flatten;
+/// use serde_json::json;
+///
+/// let value = json!({"a": {"b": [{"c": 1}, 2]}, "x.y": true, "tags": []});
+///
+/// assert_eq!(
+///     serde_json::Value::Object(flatten(&value).unwrap()),
+///     json!({"a.b[0].c": 1, "a.b[1]": 2, "x\\.y": true, "tags": []})
+/// );
+/// assert!(flatten(&json!(1)).is_err());
+/// ```
+pub fn flatten(value: &Value) -> Result<Map<String, Value>, JqrError> {
+    let mut flat = Map::new();
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                collect_leaves(escape_key(key), child, &mut flat);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_leaves(format!("[{}]", i), item, &mut flat);
+            }
+        }
+        other => {
+            return Err(JqrError::UnsupportedValue(format!(
+                "Flattening requires an array or object, but the value is of type {}",
+                json_type(other)
+            )))
+        }
+    }
+    Ok(flat)
+}
+
+/// Adds the leaves of `value`, found at the flat key `path`, to `flat`.
+fn collect_leaves(path: String, value: &Value, flat: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                collect_leaves(format!("{}.{}", path, escape_key(key)), child, flat);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                collect_leaves(format!("{}[{}]", path, i), item, flat);
+            }
+        }
+        leaf => {
+            flat.insert(path, leaf.clone());
+        }
+    }
+}
+
+/// Escapes the characters of an object key that have a meaning in flat keys.
+fn escape_key(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        if matches!(c, '.' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Rebuilds a nested document from an object of dot-notation keys, as
+/// written by [`flatten`].
+///
+/// Array elements missing between the indexes given are filled with `null`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an object, if
+///   a key is not a valid flat key, or if two keys conflict: one a leaf and
+///   the other below it (`a` and `a.b`), or one indexing an array where the
+///   other names an object member (`a[0]` and `a.b`).
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::unflatten;
+/// use serde_json::json;
+///
+/// let flat = json!({"a.b[0].c": 1, "a.b[1]": 2, "x\\.y": true});
+/// assert_eq!(
+///     unflatten(&flat).unwrap(),
+///     json!({"a": {"b": [{"c": 1}, 2]}, "x.y": true})
+/// );
+///
+/// assert_eq!(unflatten(&json!({"[1]": "b"})).unwrap(), json!([null, "b"]));
+/// assert!(unflatten(&json!({"a": 1, "a.b": 2})).is_err());
+/// ```
+pub fn unflatten(value: &Value) -> Result<Value, JqrError> {
+    let Value::Object(flat) = value else {
+        return Err(JqrError::UnsupportedValue(format!(
+            "Unflattening requires an object of flat keys, but the value is of type {}",
+            json_type(value)
+        )));
+    };
+
+    // The flat key that made each container and each leaf, to name conflicts
+    let mut containers: HashMap<&[Segment], (bool, &str)> = HashMap::new();
+    let mut leaves: HashMap<&[Segment], &str> = HashMap::new();
+    let paths = flat
+        .keys()
+        .map(|key| parse_flat_key(key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let conflict = |a: &str, b: &str| {
+        JqrError::UnsupportedValue(format!("The flat keys '{}' and '{}' conflict", a, b))
+    };
+
+    for (path, key) in paths.iter().zip(flat.keys()) {
+        for depth in 0..path.len() {
+            let parent = &path[..depth];
+            let is_array = matches!(path[depth], Segment::Index(_));
+            if let Some(leaf) = leaves.get(parent) {
+                return Err(conflict(leaf, key));
+            }
+            match containers.get(parent) {
+                Some(&(kind, other)) if kind != is_array => return Err(conflict(other, key)),
+                Some(_) => {}
+                None => {
+                    containers.insert(parent, (is_array, key.as_str()));
+                }
+            }
+        }
+        if let Some((_, other)) = containers.get(path.as_slice()) {
+            return Err(conflict(other, key));
+        }
+        leaves.insert(path, key);
+    }
+
+    let mut root = match paths.first().and_then(|path| path.first()) {
+        Some(Segment::Index(_)) => Value::Array(Vec::new()),
+        _ => Value::Object(Map::new()),
+    };
+    for (path, leaf) in paths.iter().zip(flat.values()) {
+        let mut node = &mut root;
+        for (depth, segment) in path.iter().enumerate() {
+            let child = match segment {
+                Segment::Key(key) => node
+                    .as_object_mut()
+                    .expect("container kinds are checked above")
+                    .entry(key.clone())
+                    .or_insert(Value::Null),
+                Segment::Index(i) => {
+                    let items = node
+                        .as_array_mut()
+                        .expect("container kinds are checked above");
+                    if items.len() <= *i {
+                        items.resize(i + 1, Value::Null);
+                    }
+                    &mut items[*i]
+                }
+            };
+            // Nulls are new members and array gaps, not leaves already set
+            if child.is_null() {
+                *child = match path.get(depth + 1) {
+                    Some(Segment::Index(_)) => Value::Array(Vec::new()),
+                    Some(Segment::Key(_)) => Value::Object(Map::new()),
+                    None => leaf.clone(),
+                };
+            }
+            node = child;
+        }
+    }
+    Ok(root)
+}
+
+/// Splits a flat key such as `a.b[0].c` into its segments.
+fn parse_flat_key(key: &str) -> Result<Vec<Segment>, JqrError> {
+    let invalid = |reason: &str| {
+        JqrError::UnsupportedValue(format!("Invalid flat key '{}': {}", key, reason))
+    };
+    let mut segments = Vec::new();
+    let mut chars = key.chars().peekable();
+
+    // A key starts with an object member unless it indexes an array
+    let mut expect_key = chars.peek() != Some(&'[');
+    loop {
+        if expect_key {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                match c {
+                    '.' | '[' => break,
+                    '\\' => {
+                        chars.next();
+                        name.push(chars.next().ok_or_else(|| invalid("trailing backslash"))?);
+                    }
+                    c => {
+                        chars.next();
+                        name.push(c);
+                    }
+                }
+            }
+            segments.push(Segment::Key(name));
+        }
+        match chars.next() {
+            None => return Ok(segments),
+            Some('.') => expect_key = true,
+            Some('[') => {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                let index = match (digits.parse(), chars.next()) {
+                    (Ok(index), Some(']')) => index,
+                    _ => return Err(invalid("expected an array index such as '[0]'")),
+                };
+                segments.push(Segment::Index(index));
+                expect_key = false;
+                match chars.peek() {
+                    None | Some('.') | Some('[') => {}
+                    Some(_) => return Err(invalid("expected '.' or '[' after an index")),
+                }
+            }
+            Some(c) => unreachable!("'{}' ends a key", c),
+        }
+    }
+}
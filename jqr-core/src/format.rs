@@ -0,0 +1,975 @@
+use serde::de::{self, Deserializer as _, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::{CharEscape, CompactFormatter, Formatter, PrettyFormatter, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::{DepthScanner, JqrError, DEFAULT_MAX_DEPTH};
+
+/// Controls how values are written out as JSON or YAML.
+///
+/// The defaults are two-space indentation, keys sorted alphabetically and no
+/// trailing newline. Options are set with chained builder methods.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{format_json, FormatOptions};
+/// use serde_json::json;
+///
+/// let options = FormatOptions::new().indent(4).trailing_newline(true);
+/// let output = format_json(&json!({"a": [1]}), &options).unwrap();
+///
+/// assert_eq!(output, "{\n    \"a\": [\n        1\n    ]\n}\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    indent: usize,
+    tabs: bool,
+    sort_keys: bool,
+    preserve_order: bool,
+    compact: bool,
+    line_width: Option<usize>,
+    ascii_output: bool,
+    pub(crate) trailing_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 2,
+            tabs: false,
+            sort_keys: false,
+            preserve_order: false,
+            compact: false,
+            line_width: None,
+            ascii_output: false,
+            trailing_newline: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of spaces per indentation level.
+    pub fn indent(mut self, width: usize) -> Self {
+        self.indent = width;
+        self
+    }
+
+    /// Indents with one tab per level instead of spaces (JSON only).
+    pub fn tabs(mut self, tabs: bool) -> Self {
+        self.tabs = tabs;
+        self
+    }
+
+    /// Sorts object keys alphabetically at every level.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Writes object keys in the order they appear in the input instead of
+    /// alphabetically. `sort_keys` takes precedence.
+    pub fn preserve_order(mut self, preserve_order: bool) -> Self {
+        self.preserve_order = preserve_order;
+        self
+    }
+
+    /// Writes JSON on a single line without any whitespace (JSON only).
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Keeps arrays and objects that contain only scalars on a single line
+    /// when they fit within `width` columns, and fills arrays of scalars that
+    /// don't fit with as many elements per line as fit. Nested containers are
+    /// still written one element per line (JSON only; ignored with `compact`).
+    pub fn line_width(mut self, width: usize) -> Self {
+        self.line_width = Some(width);
+        self
+    }
+
+    /// Escapes every non-ASCII character in JSON strings as `\uXXXX` (JSON only).
+    pub fn ascii_output(mut self, ascii_output: bool) -> Self {
+        self.ascii_output = ascii_output;
+        self
+    }
+
+    /// Ends the output with a newline.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Returns `true` if object keys are written alphabetically.
+    pub(crate) fn sorts_keys(&self) -> bool {
+        self.sort_keys || !self.preserve_order
+    }
+
+    /// The whitespace written for one level of JSON indentation.
+    pub(crate) fn indent_string(&self) -> String {
+        if self.tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.indent)
+        }
+    }
+}
+
+/// Serializes a value as JSON according to `options`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Serialization`] if the value cannot be serialized.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{format_json, FormatOptions};
+/// use serde_json::json;
+///
+/// let value = json!({"b": 1, "a": [true]});
+///
+/// let compact = FormatOptions::new().compact(true).sort_keys(true);
+/// assert_eq!(format_json(&value, &compact).unwrap(), r#"{"a":[true],"b":1}"#);
+///
+/// let ascii = FormatOptions::new().compact(true).ascii_output(true);
+/// assert_eq!(format_json(&json!(["é😀"]), &ascii).unwrap(), r#"["\u00e9\ud83d\ude00"]"#);
+///
+/// let tabs = FormatOptions::new().tabs(true);
+/// assert!(format_json(&value, &tabs).unwrap().contains("\n\t\"a\": [\n\t\ttrue"));
+///
+/// let ordered = FormatOptions::new().compact(true).preserve_order(true);
+/// assert_eq!(format_json(&value, &ordered).unwrap(), r#"{"b":1,"a":[true]}"#);
+/// ```
+pub fn format_json(value: &Value, options: &FormatOptions) -> Result<String, JqrError> {
+    let sorted;
+    let value = if options.sorts_keys() {
+        sorted = sort_object_keys(value.clone());
+        &sorted
+    } else {
+        value
+    };
+
+    if let (Some(width), false) = (options.line_width, options.compact) {
+        let mut output = String::new();
+        write_semi_compact(value, options, width, 0, 0, &mut output)?;
+        if options.trailing_newline {
+            output.push('\n');
+        }
+        return Ok(output);
+    }
+
+    let mut buffer = Vec::new();
+    let result = if options.compact {
+        let formatter = OutputFormatter::new(CompactFormatter, options.ascii_output);
+        value.serialize(&mut Serializer::with_formatter(&mut buffer, formatter))
+    } else {
+        let indent = options.indent_string();
+        let formatter = OutputFormatter::new(
+            PrettyFormatter::with_indent(indent.as_bytes()),
+            options.ascii_output,
+        );
+        value.serialize(&mut Serializer::with_formatter(&mut buffer, formatter))
+    };
+    result.map_err(|e| JqrError::Serialization(e.to_string()))?;
+
+    // serde_json only ever writes valid UTF-8
+    let mut output =
+        String::from_utf8(buffer).map_err(|e| JqrError::Serialization(e.to_string()))?;
+
+    if options.trailing_newline {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Formats a value for shell scripts: a string is written as its raw text
+/// without quotes or escapes, and a non-empty array of strings as one string
+/// per line. Anything else is formatted as JSON according to `options`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Serialization`] if the value cannot be serialized.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{format_raw, FormatOptions};
+/// use serde_json::json;
+///
+/// let options = FormatOptions::new();
+///
+/// assert_eq!(format_raw(&json!("Alice \"Al\""), &options).unwrap(), "Alice \"Al\"");
+/// assert_eq!(format_raw(&json!(["a", "b c"]), &options).unwrap(), "a\nb c");
+/// assert_eq!(format_raw(&json!(["a", 1]), &options.compact(true)).unwrap(), r#"["a",1]"#);
+/// ```
+pub fn format_raw(value: &Value, options: &FormatOptions) -> Result<String, JqrError> {
+    let mut output = match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_string) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other => return format_json(other, options),
+    };
+
+    if options.trailing_newline {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Writes `value` for [`FormatOptions::line_width`]: `depth` is its nesting
+/// level and `column` where on the current line it starts.
+fn write_semi_compact(
+    value: &Value,
+    options: &FormatOptions,
+    width: usize,
+    depth: usize,
+    column: usize,
+    output: &mut String,
+) -> Result<(), JqrError> {
+    let compact = options.clone().compact(true).trailing_newline(false);
+    let scalar = |value: &Value| format_json(value, &compact);
+    let (open, close) = match value {
+        Value::Array(items) if !items.is_empty() => ('[', ']'),
+        Value::Object(map) if !map.is_empty() => ('{', '}'),
+        other => {
+            output.push_str(&scalar(other)?);
+            return Ok(());
+        }
+    };
+
+    let indent = options.indent_string();
+    let inner_indent = indent.repeat(depth + 1);
+    let is_scalar = |value: &Value| !value.is_array() && !value.is_object();
+
+    // Entries as `(rendered key prefix, value)`; arrays have no keys
+    let entries: Vec<(String, &Value)> = match value {
+        Value::Array(items) => items.iter().map(|item| (String::new(), item)).collect(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, child)| Ok((format!("{}: ", scalar(&Value::String(key.clone()))?), child)))
+            .collect::<Result<_, JqrError>>()?,
+        _ => unreachable!(),
+    };
+
+    if entries.iter().all(|(_, child)| is_scalar(child)) {
+        let parts = entries
+            .iter()
+            .map(|(key, child)| Ok(format!("{}{}", key, scalar(child)?)))
+            .collect::<Result<Vec<_>, JqrError>>()?;
+
+        let one_line = format!("{}{}{}", open, parts.join(", "), close);
+        if column + one_line.chars().count() <= width {
+            output.push_str(&one_line);
+            return Ok(());
+        }
+
+        if open == '[' {
+            // Fill each line with as many elements as fit
+            output.push(open);
+            let mut line_length: Option<usize> = None;
+            for (i, part) in parts.iter().enumerate() {
+                let separator = if i + 1 < parts.len() { "," } else { "" };
+                let length = part.chars().count() + separator.len();
+                match line_length.filter(|used| used + 1 + length <= width) {
+                    Some(used) => {
+                        output.push(' ');
+                        line_length = Some(used + 1 + length);
+                    }
+                    None => {
+                        output.push('\n');
+                        output.push_str(&inner_indent);
+                        line_length = Some(inner_indent.chars().count() + length);
+                    }
+                }
+                output.push_str(part);
+                output.push_str(separator);
+            }
+            output.push('\n');
+            output.push_str(&indent.repeat(depth));
+            output.push(close);
+            return Ok(());
+        }
+    }
+
+    output.push(open);
+    for (i, (key, child)) in entries.iter().enumerate() {
+        output.push('\n');
+        output.push_str(&inner_indent);
+        output.push_str(key);
+        let column = inner_indent.chars().count() + key.chars().count();
+        write_semi_compact(child, options, width, depth + 1, column, output)?;
+        if i + 1 < entries.len() {
+            output.push(',');
+        }
+    }
+    output.push('\n');
+    output.push_str(&indent.repeat(depth));
+    output.push(close);
+    Ok(())
+}
+
+/// Reformats the JSON document read from `reader` into `writer` according to `options`.
+///
+/// With `preserve_order` the document is transcoded token by token, so
+/// neither the input nor the output is held in memory as a whole; this makes
+/// it suitable for formatting multi-gigabyte files. Sorting keys needs each
+/// object complete before writing it, so a top-level array is then written
+/// one element at a time, and any other document is parsed first, as is
+/// every document with `line_width`.
+///
+/// As with [`parse_json_with_max_depth`](crate::parse_json_with_max_depth),
+/// input nested deeper than [`DEFAULT_MAX_DEPTH`] is refused. Output written
+/// before an error is detected is not retracted.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] if the input is not a single valid JSON document.
+/// * Returns [`JqrError::NestingTooDeep`] if the input is nested too deeply.
+/// * Returns [`JqrError::Io`] if reading or writing fails.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{pretty_print_json_stream, FormatOptions};
+///
+/// let input = r#"{"name": "Alice", "tags": ["a"]}"#;
+/// let mut output = Vec::new();
+/// let options = FormatOptions::new().preserve_order(true);
+///
+/// pretty_print_json_stream(input.as_bytes(), &mut output, &options).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "{\n  \"name\": \"Alice\",\n  \"tags\": [\n    \"a\"\n  ]\n}"
+/// );
+///
+/// let records = r#"[{"b": 1, "a": [2]}, {"c": 3}]"#;
+/// let mut output = Vec::new();
+/// pretty_print_json_stream(records.as_bytes(), &mut output, &FormatOptions::new()).unwrap();
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "[\n  {\n    \"a\": [\n      2\n    ],\n    \"b\": 1\n  },\n  {\n    \"c\": 3\n  }\n]"
+/// );
+/// ```
+pub fn pretty_print_json_stream<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    options: &FormatOptions,
+) -> Result<(), JqrError> {
+    let mut guard = DepthGuard {
+        inner: reader,
+        scanner: DepthScanner::new(DEFAULT_MAX_DEPTH),
+        error: None,
+    };
+    let result = stream_document(&mut guard, writer, options);
+
+    // A read refused by the guard surfaces from the parser as an opaque error
+    match guard.error {
+        Some(error) => Err(error),
+        None => result,
+    }
+}
+
+/// Reformats one document; the depth limit is enforced by the reader.
+fn stream_document<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    options: &FormatOptions,
+) -> Result<(), JqrError> {
+    let mut reader = BufReader::new(reader);
+    let sorts_elements =
+        options.sorts_keys() && options.line_width.is_none() && starts_with_array(&mut reader)?;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer.disable_recursion_limit();
+
+    if sorts_elements {
+        let mut failed = None;
+        let elements = SortedElements {
+            writer: &mut writer,
+            options,
+            failed: &mut failed,
+        };
+        deserializer
+            .deserialize_seq(elements)
+            .and_then(|_| deserializer.end())
+            .map_err(|e| failed.take().unwrap_or_else(|| stream_error(e)))?;
+    } else if options.sorts_keys() || options.line_width.is_some() {
+        let value = Value::deserialize(&mut deserializer)
+            .and_then(|value| deserializer.end().map(|_| value))
+            .map_err(stream_error)?;
+        writer
+            .write_all(format_json(&value, &options.clone().trailing_newline(false))?.as_bytes())?;
+    } else if options.compact {
+        transcode(
+            &mut deserializer,
+            &mut writer,
+            OutputFormatter::new(CompactFormatter, options.ascii_output),
+        )?;
+    } else {
+        let indent = options.indent_string();
+        transcode(
+            &mut deserializer,
+            &mut writer,
+            OutputFormatter::new(
+                PrettyFormatter::with_indent(indent.as_bytes()),
+                options.ascii_output,
+            ),
+        )?;
+    }
+
+    if options.trailing_newline {
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Skips the whitespace at the start of `reader` and returns `true` if a
+/// JSON array comes next.
+pub(crate) fn starts_with_array(reader: &mut impl BufRead) -> io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(i) => {
+                let first = buf[i];
+                reader.consume(i);
+                return Ok(first == b'[');
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Writes a top-level array with sorted keys one element at a time, each
+/// element parsed and formatted on its own. An output error is kept in
+/// `failed`, since the parser can only report it as a message.
+struct SortedElements<'a, W> {
+    writer: &'a mut W,
+    options: &'a FormatOptions,
+    failed: &'a mut Option<JqrError>,
+}
+
+impl<'de, W: Write> Visitor<'de> for SortedElements<'_, W> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let SortedElements {
+            writer,
+            options,
+            failed,
+        } = self;
+        let mut fail = |e: JqrError| {
+            let message = e.to_string();
+            *failed = Some(e);
+            de::Error::custom(message)
+        };
+        let element_options = options.clone().trailing_newline(false);
+        let indent = options.indent_string();
+
+        writer.write_all(b"[").map_err(|e| fail(e.into()))?;
+        let mut empty = true;
+        while let Some(element) = seq.next_element::<Value>()? {
+            let formatted = format_json(&element, &element_options).map_err(&mut fail)?;
+            let separator = if empty { "" } else { "," };
+            let text = if options.compact {
+                format!("{}{}", separator, formatted)
+            } else {
+                // Strings never hold a raw line break, so every line is indented
+                let nested = formatted.replace('\n', &format!("\n{}", indent));
+                format!("{}\n{}{}", separator, indent, nested)
+            };
+            writer
+                .write_all(text.as_bytes())
+                .map_err(|e| fail(e.into()))?;
+            empty = false;
+        }
+        let close = if empty || options.compact { "]" } else { "\n]" };
+        writer
+            .write_all(close.as_bytes())
+            .map_err(|e| fail(e.into()))
+    }
+}
+
+/// Copies one JSON document from `deserializer` to `writer` with the given formatter.
+fn transcode<'de, R, W, F>(
+    deserializer: &mut serde_json::Deserializer<R>,
+    writer: W,
+    formatter: F,
+) -> Result<(), JqrError>
+where
+    R: serde_json::de::Read<'de>,
+    W: Write,
+    F: Formatter,
+{
+    let mut serializer = Serializer::with_formatter(writer, formatter);
+    serde_transcode::transcode(&mut *deserializer, &mut serializer)
+        .and_then(|_| deserializer.end())
+        .map_err(stream_error)
+}
+
+/// The map key under which `serde_json` passes arbitrary-precision numbers
+/// through generic serializers.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Where [`OutputFormatter`] is within the current object.
+enum NumberState {
+    Normal,
+    /// An object has begun; it is held back until its first key shows
+    /// whether it is a number in disguise.
+    Deferred {
+        key: String,
+        has_key: bool,
+    },
+    /// Inside a number object: only the literal is written.
+    Number,
+}
+
+/// The formatter behind all JSON output, wrapping a compact or pretty one.
+///
+/// It writes numbers transcoded from an arbitrary-precision parser as their
+/// literal instead of as `{"$serde_json::private::Number": ...}`, and with
+/// `ascii` escapes non-ASCII characters in strings.
+struct OutputFormatter<F> {
+    inner: F,
+    state: NumberState,
+    ascii: bool,
+}
+
+impl<F: Formatter> OutputFormatter<F> {
+    fn new(inner: F, ascii: bool) -> Self {
+        OutputFormatter {
+            inner,
+            state: NumberState::Normal,
+            ascii,
+        }
+    }
+
+    /// Writes part of a string, escaping non-ASCII characters if requested.
+    fn write_text<W: ?Sized + Write>(&mut self, writer: &mut W, text: &str) -> io::Result<()> {
+        if !self.ascii || text.is_ascii() {
+            return self.inner.write_string_fragment(writer, text);
+        }
+
+        let mut rest = text;
+        while let Some(i) = rest.find(|c: char| !c.is_ascii()) {
+            self.inner.write_string_fragment(writer, &rest[..i])?;
+            let c = rest[i..].chars().next().unwrap();
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                write!(writer, "\\u{:04x}", unit)?;
+            }
+            rest = &rest[i + c.len_utf8()..];
+        }
+        self.inner.write_string_fragment(writer, rest)
+    }
+
+    /// Writes the held-back start of an object that turned out not to be a number.
+    fn resume<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if let NumberState::Deferred { key, has_key } =
+            std::mem::replace(&mut self.state, NumberState::Normal)
+        {
+            self.inner.begin_object(writer)?;
+            if has_key {
+                self.inner.begin_object_key(writer, true)?;
+                self.inner.begin_string(writer)?;
+                self.write_text(writer, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_deferred(&self) -> bool {
+        matches!(self.state, NumberState::Deferred { .. })
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self.state, NumberState::Number)
+    }
+}
+
+impl<F: Formatter> Formatter for OutputFormatter<F> {
+    fn write_null<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.write_null(writer)
+    }
+
+    fn write_bool<W: ?Sized + Write>(&mut self, writer: &mut W, value: bool) -> io::Result<()> {
+        self.inner.write_bool(writer, value)
+    }
+
+    fn write_i64<W: ?Sized + Write>(&mut self, writer: &mut W, value: i64) -> io::Result<()> {
+        self.inner.write_i64(writer, value)
+    }
+
+    fn write_u64<W: ?Sized + Write>(&mut self, writer: &mut W, value: u64) -> io::Result<()> {
+        self.inner.write_u64(writer, value)
+    }
+
+    fn write_f64<W: ?Sized + Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        self.inner.write_f64(writer, value)
+    }
+
+    fn write_number_str<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        value: &str,
+    ) -> io::Result<()> {
+        self.inner.write_number_str(writer, value)
+    }
+
+    fn begin_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.is_deferred() || self.is_number() {
+            return Ok(());
+        }
+        self.inner.begin_string(writer)
+    }
+
+    fn end_string<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.is_deferred() || self.is_number() {
+            return Ok(());
+        }
+        self.inner.end_string(writer)
+    }
+
+    fn write_string_fragment<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        match &mut self.state {
+            NumberState::Deferred { key, .. } => {
+                key.push_str(fragment);
+                Ok(())
+            }
+            NumberState::Number => writer.write_all(fragment.as_bytes()),
+            NumberState::Normal => self.write_text(writer, fragment),
+        }
+    }
+
+    fn write_char_escape<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        char_escape: CharEscape,
+    ) -> io::Result<()> {
+        // The number token contains nothing that needs escaping
+        self.resume(writer)?;
+        self.inner.write_char_escape(writer, char_escape)
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, _writer: &mut W) -> io::Result<()> {
+        self.state = NumberState::Deferred {
+            key: String::new(),
+            has_key: false,
+        };
+        Ok(())
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.is_number() {
+            self.state = NumberState::Normal;
+            return Ok(());
+        }
+        self.resume(writer)?;
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        if let NumberState::Deferred { has_key, .. } = &mut self.state {
+            *has_key = true;
+            return Ok(());
+        }
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if let NumberState::Deferred { key, .. } = &self.state {
+            if key == NUMBER_TOKEN {
+                self.state = NumberState::Number;
+                return Ok(());
+            }
+            self.resume(writer)?;
+            self.inner.end_string(writer)?;
+        }
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.is_number() {
+            return Ok(());
+        }
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.is_number() {
+            return Ok(());
+        }
+        self.inner.end_object_value(writer)
+    }
+
+    fn write_raw_fragment<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        self.inner.write_raw_fragment(writer, fragment)
+    }
+}
+
+/// A reader that refuses to return input nested deeper than its scanner allows.
+struct DepthGuard<R> {
+    inner: R,
+    scanner: DepthScanner,
+    error: Option<JqrError>,
+}
+
+impl<R: Read> Read for DepthGuard<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The parser may read on while unwinding; keep reporting the first error
+        if let Some(e) = &self.error {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+        }
+
+        let n = self.inner.read(buf)?;
+        for &byte in &buf[..n] {
+            if let Err(e) = self.scanner.feed(byte) {
+                let message = e.to_string();
+                self.error = Some(e);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Maps a streaming error to an I/O or invalid-JSON error.
+fn stream_error(error: serde_json::Error) -> JqrError {
+    if error.is_io() {
+        JqrError::Io(error.into())
+    } else {
+        JqrError::invalid_json(error, false)
+    }
+}
+
+/// Serializes a value as YAML according to `options`.
+///
+/// YAML output is written in block style with `indent` spaces per level
+/// (at least one); sequences inside a mapping start at the mapping's own
+/// indentation, and the items of a sequence are indented to the same width.
+/// Key order and `trailing_newline` apply as for JSON, while `tabs`,
+/// `compact`, `line_width` and `ascii_output` do not. Numbers are written
+/// exactly as they appeared in the JSON input, however large or precise.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Serialization`] if a string cannot be serialized.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{format_yaml, FormatOptions};
+/// use serde_json::json;
+///
+/// let value = json!({"name": "Alice", "tags": ["a", "b"]});
+/// let output = format_yaml(&value, &FormatOptions::new()).unwrap();
+///
+/// assert_eq!(output, "name: Alice\ntags:\n- a\n- b");
+///
+/// let value: serde_json::Value = serde_json::from_str(r#"{"id": 123456789012345678901234567890, "price": 1.10}"#).unwrap();
+/// let output = format_yaml(&value, &FormatOptions::new()).unwrap();
+///
+/// assert_eq!(output, "id: 123456789012345678901234567890\nprice: 1.10");
+///
+/// let value = json!({"spec": {"ports": [{"name": "http", "port": 80}]}});
+/// let output = format_yaml(&value, &FormatOptions::new().indent(4)).unwrap();
+///
+/// assert_eq!(output, "spec:\n    ports:\n    -   name: http\n        port: 80");
+/// ```
+pub fn format_yaml(value: &Value, options: &FormatOptions) -> Result<String, JqrError> {
+    let sorted;
+    let value = if options.sorts_keys() {
+        sorted = sort_object_keys(value.clone());
+        &sorted
+    } else {
+        value
+    };
+
+    let mut output = String::new();
+    write_yaml_node(value, 0, 0, options.indent.max(1), &mut output)?;
+    if options.trailing_newline {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Writes `value` as a YAML node starting at `column` on the current line.
+///
+/// `parent` is the indentation of the enclosing node, which the lines of a
+/// block scalar are indented past.
+fn write_yaml_node(
+    value: &Value,
+    column: usize,
+    parent: usize,
+    width: usize,
+    output: &mut String,
+) -> Result<(), JqrError> {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (i, (key, child)) in map.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                    output.push_str(&" ".repeat(column));
+                }
+                output.push_str(&yaml_key(key)?);
+                output.push(':');
+                match child {
+                    Value::Object(map) if !map.is_empty() => {
+                        output.push('\n');
+                        output.push_str(&" ".repeat(column + width));
+                        write_yaml_node(child, column + width, column, width, output)?;
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        output.push('\n');
+                        output.push_str(&" ".repeat(column));
+                        write_yaml_node(child, column, column, width, output)?;
+                    }
+                    _ => {
+                        output.push(' ');
+                        write_yaml_node(child, column + width, column, width, output)?;
+                    }
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            // The dash needs a space after it, so items are at least two columns in
+            let item_column = column + width.max(2);
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                    output.push_str(&" ".repeat(column));
+                }
+                output.push('-');
+                output.push_str(&" ".repeat(item_column - column - 1));
+                write_yaml_node(item, item_column, column, width, output)?;
+            }
+        }
+        Value::Object(_) => output.push_str("{}"),
+        Value::Array(_) => output.push_str("[]"),
+        Value::Null => output.push_str("null"),
+        Value::Bool(b) => output.push_str(&b.to_string()),
+        Value::Number(n) => output.push_str(&n.to_string()),
+        Value::String(s) => output.push_str(&yaml_string(s, parent + width)?),
+    }
+    Ok(())
+}
+
+/// Returns `s` as a YAML scalar, quoted only where YAML needs it. Multi-line
+/// strings become literal block scalars with their lines at `indent`.
+fn yaml_string(s: &str, indent: usize) -> Result<String, JqrError> {
+    let yaml = serde_yaml::to_string(s).map_err(|e| JqrError::Serialization(e.to_string()))?;
+    let yaml = yaml.strip_suffix('\n').unwrap_or(&yaml);
+    let Some((header, lines)) = yaml.split_once('\n') else {
+        return Ok(yaml.to_string());
+    };
+
+    // An explicit indentation indicator would have to be recomputed, so such
+    // strings (those starting with a space) are written double-quoted instead
+    if header.contains(|c: char| c.is_ascii_digit()) {
+        return serde_json::to_string(s).map_err(|e| JqrError::Serialization(e.to_string()));
+    }
+
+    // The serializer indents block scalar lines by two spaces
+    let mut block = header.to_string();
+    for line in lines.split('\n') {
+        block.push('\n');
+        if let Some(line) = line.strip_prefix("  ") {
+            block.push_str(&" ".repeat(indent));
+            block.push_str(line);
+        }
+    }
+    Ok(block)
+}
+
+/// Returns `key` as a YAML mapping key, double-quoted if it spans lines.
+fn yaml_key(key: &str) -> Result<String, JqrError> {
+    if key.contains(['\n', '\r']) {
+        serde_json::to_string(key).map_err(|e| JqrError::Serialization(e.to_string()))
+    } else {
+        yaml_string(key, 0)
+    }
+}
+
+/// Recursively rebuilds every object with its keys in alphabetical order.
+///
+/// Parsed objects keep their input key order; output functions that take no
+/// [`FormatOptions`] write keys in whatever order the value holds them.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_json, sort_object_keys};
+///
+/// let value = parse_json(r#"{"b": {"d": 1, "c": 2}, "a": 3}"#).unwrap();
+/// assert_eq!(value.to_string(), r#"{"b":{"d":1,"c":2},"a":3}"#);
+/// assert_eq!(sort_object_keys(value).to_string(), r#"{"a":3,"b":{"c":2,"d":1}}"#);
+/// ```
+pub fn sort_object_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, child)| (key, sort_object_keys(child)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_object_keys).collect()),
+        other => other,
+    }
+}
@@ -0,0 +1,980 @@
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+use crate::transform::to_number;
+use crate::{json_equal, json_type, JqrError};
+
+/// A JMESPath expression, parsed once so it can be run against many
+/// documents.
+///
+/// The whole JMESPath specification is supported: sub-expressions, indexes
+/// and slices, list, object, flatten and filter projections, multi-select
+/// lists and hashes, pipes, `||`, `&&`, `!`, comparisons, literals, and the
+/// built-in functions (`length`, `sort_by`, `max_by`, `join`, `keys`, ...).
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::JmesPath;
+/// use serde_json::json;
+///
+/// let doc = json!({"Reservations": [
+///     {"Instances": [{"InstanceId": "i-1", "State": {"Name": "running"}}]},
+///     {"Instances": [{"InstanceId": "i-2", "State": {"Name": "stopped"}}]},
+/// ]});
+///
+/// let running = JmesPath::new("Reservations[].Instances[?State.Name == 'running'].InstanceId[]").unwrap();
+/// assert_eq!(running.apply(&doc).unwrap(), json!(["i-1"]));
+///
+/// let count = JmesPath::new("length(Reservations)").unwrap();
+/// assert_eq!(count.apply(&doc).unwrap(), json!(2));
+///
+/// assert!(JmesPath::new("Reservations[").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct JmesPath {
+    source: String,
+    node: Node,
+}
+
+impl JmesPath {
+    /// Parses a JMESPath expression.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the expression cannot be
+    ///   parsed or calls an unknown function or with the wrong number of
+    ///   arguments.
+    pub fn new(expression: &str) -> Result<Self, JqrError> {
+        let node = Parser::parse(expression).map_err(|message| {
+            JqrError::InvalidQuery(format!(
+                "Invalid JMESPath expression '{}': {}",
+                expression, message
+            ))
+        })?;
+        Ok(JmesPath {
+            source: expression.to_string(),
+            node,
+        })
+    }
+
+    /// Returns the expression text this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluates the expression. As JMESPath specifies, a path to nothing
+    /// evaluates to `null`.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::UnsupportedValue`] if a function is given an
+    ///   argument of the wrong type, such as `sum` of strings.
+    pub fn apply(&self, json: &Value) -> Result<Value, JqrError> {
+        eval(&self.node, json)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Current,
+    Field(String),
+    Literal(Value),
+    Subexpr(Box<Node>, Box<Node>),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    /// A list projection: the right side runs on every element of the left.
+    Projection(Box<Node>, Box<Node>),
+    /// An object projection (`*`): the right side runs on every value.
+    ValueProjection(Box<Node>, Box<Node>),
+    /// A filter projection: the left side, the right side and the condition.
+    FilterProjection(Box<Node>, Box<Node>, Box<Node>),
+    Flatten(Box<Node>),
+    MultiList(Vec<Node>),
+    MultiHash(Vec<(String, Node)>),
+    Pipe(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare(Comparator, Box<Node>, Box<Node>),
+    Function(&'static str, Vec<Node>),
+    ExprRef(Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// The built-in functions, with their least number of arguments and
+/// whether they take more.
+const FUNCTIONS: [(&str, usize, bool); 26] = [
+    ("abs", 1, false),
+    ("avg", 1, false),
+    ("ceil", 1, false),
+    ("contains", 2, false),
+    ("ends_with", 2, false),
+    ("floor", 1, false),
+    ("join", 2, false),
+    ("keys", 1, false),
+    ("length", 1, false),
+    ("map", 2, false),
+    ("max", 1, false),
+    ("max_by", 2, false),
+    ("merge", 1, true),
+    ("min", 1, false),
+    ("min_by", 2, false),
+    ("not_null", 1, true),
+    ("reverse", 1, false),
+    ("sort", 1, false),
+    ("sort_by", 2, false),
+    ("starts_with", 2, false),
+    ("sum", 1, false),
+    ("to_array", 1, false),
+    ("to_number", 1, false),
+    ("to_string", 1, false),
+    ("type", 1, false),
+    ("values", 1, false),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    QuotedIdentifier(String),
+    Number(i64),
+    Literal(Value),
+    Dot,
+    Star,
+    Flatten,
+    Filter,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+    Pipe,
+    Or,
+    And,
+    Not,
+    Ampersand,
+    At,
+    Compare(Comparator),
+    End,
+}
+
+impl Token {
+    /// How tightly the token binds the expression before it.
+    fn binding_power(&self) -> u8 {
+        match self {
+            Token::Pipe => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            Token::Compare(_) => 5,
+            Token::Flatten => 9,
+            Token::Star => 20,
+            Token::Filter => 21,
+            Token::Dot => 40,
+            Token::Not => 45,
+            Token::LBrace => 50,
+            Token::LBracket => 55,
+            Token::LParen => 60,
+            _ => 0,
+        }
+    }
+}
+
+/// Tokens with a lower binding power end a projection.
+const PROJECTION_STOP: u8 = 10;
+
+/// Splits an expression into tokens, each with the column it starts at,
+/// ending with [`Token::End`].
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    loop {
+        rest = rest.trim_start();
+        let column = expression[..expression.len() - rest.len()].chars().count() + 1;
+        let Some(c) = rest.chars().next() else {
+            tokens.push((Token::End, column));
+            return Ok(tokens);
+        };
+
+        let two = rest.get(..2).unwrap_or_default();
+        let (token, length) = match (two, c) {
+            ("[]", _) => (Token::Flatten, 2),
+            ("[?", _) => (Token::Filter, 2),
+            ("||", _) => (Token::Or, 2),
+            ("&&", _) => (Token::And, 2),
+            ("==", _) => (Token::Compare(Comparator::Equal), 2),
+            ("!=", _) => (Token::Compare(Comparator::NotEqual), 2),
+            ("<=", _) => (Token::Compare(Comparator::LessEqual), 2),
+            (">=", _) => (Token::Compare(Comparator::GreaterEqual), 2),
+            (_, '<') => (Token::Compare(Comparator::Less), 1),
+            (_, '>') => (Token::Compare(Comparator::Greater), 1),
+            (_, '.') => (Token::Dot, 1),
+            (_, '*') => (Token::Star, 1),
+            (_, '[') => (Token::LBracket, 1),
+            (_, ']') => (Token::RBracket, 1),
+            (_, '{') => (Token::LBrace, 1),
+            (_, '}') => (Token::RBrace, 1),
+            (_, '(') => (Token::LParen, 1),
+            (_, ')') => (Token::RParen, 1),
+            (_, ',') => (Token::Comma, 1),
+            (_, ':') => (Token::Colon, 1),
+            (_, '|') => (Token::Pipe, 1),
+            (_, '!') => (Token::Not, 1),
+            (_, '&') => (Token::Ampersand, 1),
+            (_, '@') => (Token::At, 1),
+            (_, '"') => {
+                let length = quoted_length(rest, '"', column)?;
+                let name = serde_json::from_str(&rest[..length])
+                    .map_err(|_| format!("invalid quoted identifier at column {}", column))?;
+                (Token::QuotedIdentifier(name), length)
+            }
+            (_, '\'') => {
+                let length = quoted_length(rest, '\'', column)?;
+                let text = rest[1..length - 1].replace("\\'", "'");
+                (Token::Literal(Value::String(text)), length)
+            }
+            (_, '`') => {
+                let length = quoted_length(rest, '`', column)?;
+                let json = rest[1..length - 1].replace("\\`", "`");
+                let value = serde_json::from_str(json.trim())
+                    .map_err(|_| format!("invalid JSON literal at column {}", column))?;
+                (Token::Literal(value), length)
+            }
+            (_, '-' | '0'..='9') => {
+                let digits = rest[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                let number = rest[..1 + digits]
+                    .parse()
+                    .map_err(|_| format!("invalid number at column {}", column))?;
+                (Token::Number(number), 1 + digits)
+            }
+            (_, 'a'..='z' | 'A'..='Z' | '_') => {
+                let length = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (Token::Identifier(rest[..length].to_string()), length)
+            }
+            _ => return Err(format!("unexpected '{}' at column {}", c, column)),
+        };
+        tokens.push((token, column));
+        rest = &rest[length..];
+    }
+}
+
+/// Returns the length of the quoted text `text` starts with, quotes
+/// included, skipping quotes escaped with a backslash.
+fn quoted_length(text: &str, quote: char, column: usize) -> Result<usize, String> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Ok(i + 1),
+            _ => {}
+        }
+    }
+    Err(format!("unterminated {} at column {}", quote, column))
+}
+
+/// A top-down operator precedence parser, following the JMESPath grammar.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn parse(expression: &str) -> Result<Node, String> {
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+        };
+        let node = parser.expression(0)?;
+        match parser.current() {
+            Token::End => Ok(node),
+            _ => Err(parser.unexpected()),
+        }
+    }
+
+    fn current(&self) -> &Token {
+        self.lookahead(0)
+    }
+
+    /// Returns the token `offset` places ahead, [`Token::End`] past the end.
+    fn lookahead(&self, offset: usize) -> &Token {
+        let position = (self.position + offset).min(self.tokens.len() - 1);
+        &self.tokens[position].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.current().clone();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        if *self.current() == token {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Describes the current token as unexpected.
+    fn unexpected(&self) -> String {
+        match &self.tokens[self.position.min(self.tokens.len() - 1)] {
+            (Token::End, _) => "unexpected end of expression".to_string(),
+            (_, column) => format!("unexpected token at column {}", column),
+        }
+    }
+
+    fn expression(&mut self, binding_power: u8) -> Result<Node, String> {
+        let token = self.advance();
+        let mut left = self.prefix(token)?;
+        while binding_power < self.current().binding_power() {
+            let token = self.advance();
+            left = self.infix(token, left)?;
+        }
+        Ok(left)
+    }
+
+    /// Parses an expression that starts with `token`.
+    fn prefix(&mut self, token: Token) -> Result<Node, String> {
+        match token {
+            Token::Literal(value) => Ok(Node::Literal(value)),
+            Token::Identifier(name) => Ok(Node::Field(name)),
+            Token::QuotedIdentifier(name) => match self.current() {
+                Token::LParen => Err("a quoted identifier cannot name a function".to_string()),
+                _ => Ok(Node::Field(name)),
+            },
+            Token::Star => {
+                let right = match self.current() {
+                    Token::RBracket => Node::Current,
+                    _ => self.projection_rhs(Token::Star.binding_power())?,
+                };
+                Ok(Node::ValueProjection(
+                    Box::new(Node::Current),
+                    Box::new(right),
+                ))
+            }
+            Token::Filter => self.filter(Node::Current),
+            Token::LBrace => self.multi_hash(),
+            Token::LParen => {
+                let node = self.expression(0)?;
+                self.expect(Token::RParen)?;
+                Ok(node)
+            }
+            Token::Flatten => {
+                let left = Node::Flatten(Box::new(Node::Current));
+                let right = self.projection_rhs(Token::Flatten.binding_power())?;
+                Ok(Node::Projection(Box::new(left), Box::new(right)))
+            }
+            Token::Not => {
+                let node = self.expression(Token::Not.binding_power())?;
+                Ok(Node::Not(Box::new(node)))
+            }
+            Token::LBracket => match (self.current(), self.lookahead(1)) {
+                (Token::Number(_) | Token::Colon, _) => self.index(Node::Current),
+                (Token::Star, Token::RBracket) => {
+                    self.position += 2;
+                    let right = self.projection_rhs(Token::Star.binding_power())?;
+                    Ok(Node::Projection(Box::new(Node::Current), Box::new(right)))
+                }
+                _ => self.multi_list(),
+            },
+            Token::At => Ok(Node::Current),
+            Token::Ampersand => Ok(Node::ExprRef(Box::new(self.expression(0)?))),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Parses the rest of an expression where `token` follows `left`.
+    fn infix(&mut self, token: Token, left: Node) -> Result<Node, String> {
+        let power = token.binding_power();
+        match token {
+            Token::Dot if *self.current() == Token::Star => {
+                self.advance();
+                let right = self.projection_rhs(power)?;
+                Ok(Node::ValueProjection(Box::new(left), Box::new(right)))
+            }
+            Token::Dot => {
+                let right = self.dot_rhs(power)?;
+                Ok(Node::Subexpr(Box::new(left), Box::new(right)))
+            }
+            Token::Pipe => Ok(Node::Pipe(
+                Box::new(left),
+                Box::new(self.expression(power)?),
+            )),
+            Token::Or => Ok(Node::Or(Box::new(left), Box::new(self.expression(power)?))),
+            Token::And => Ok(Node::And(Box::new(left), Box::new(self.expression(power)?))),
+            Token::Compare(comparator) => {
+                let right = self.expression(power)?;
+                Ok(Node::Compare(comparator, Box::new(left), Box::new(right)))
+            }
+            Token::LParen => self.function(left),
+            Token::Filter => self.filter(left),
+            Token::Flatten => {
+                let left = Node::Flatten(Box::new(left));
+                let right = self.projection_rhs(power)?;
+                Ok(Node::Projection(Box::new(left), Box::new(right)))
+            }
+            Token::LBracket => match self.current() {
+                Token::Number(_) | Token::Colon => self.index(left),
+                _ => {
+                    self.expect(Token::Star)?;
+                    self.expect(Token::RBracket)?;
+                    let right = self.projection_rhs(Token::Star.binding_power())?;
+                    Ok(Node::Projection(Box::new(left), Box::new(right)))
+                }
+            },
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Parses an index or slice of `left` after its `[`; a slice projects.
+    fn index(&mut self, left: Node) -> Result<Node, String> {
+        if *self.current() != Token::Colon && *self.lookahead(1) != Token::Colon {
+            let Token::Number(index) = self.advance() else {
+                unreachable!("only called before a number or colon")
+            };
+            self.expect(Token::RBracket)?;
+            return Ok(Node::Subexpr(Box::new(left), Box::new(Node::Index(index))));
+        }
+
+        let mut parts = [None; 3];
+        let mut part = 0;
+        while *self.current() != Token::RBracket {
+            match self.advance() {
+                Token::Colon if part < 2 => part += 1,
+                Token::Number(number) if parts[part].is_none() => parts[part] = Some(number),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected());
+                }
+            }
+        }
+        self.advance();
+        let slice = Node::Slice(parts[0], parts[1], parts[2]);
+        let right = self.projection_rhs(Token::Star.binding_power())?;
+        Ok(Node::Projection(
+            Box::new(Node::Subexpr(Box::new(left), Box::new(slice))),
+            Box::new(right),
+        ))
+    }
+
+    fn filter(&mut self, left: Node) -> Result<Node, String> {
+        let condition = self.expression(0)?;
+        self.expect(Token::RBracket)?;
+        let right = match self.current() {
+            Token::Flatten => Node::Current,
+            _ => self.projection_rhs(Token::Filter.binding_power())?,
+        };
+        Ok(Node::FilterProjection(
+            Box::new(left),
+            Box::new(right),
+            Box::new(condition),
+        ))
+    }
+
+    /// Parses a call of the function named by `left` after its `(`.
+    fn function(&mut self, left: Node) -> Result<Node, String> {
+        let Node::Field(name) = left else {
+            self.position -= 1;
+            return Err(self.unexpected());
+        };
+        let mut args = Vec::new();
+        while *self.current() != Token::RParen {
+            args.push(self.expression(0)?);
+            if *self.current() == Token::Comma {
+                self.advance();
+            }
+        }
+        self.advance();
+
+        let Some(&(function, arity, variadic)) = FUNCTIONS.iter().find(|f| f.0 == name) else {
+            return Err(format!("unknown function '{}'", name));
+        };
+        if args.len() < arity || (args.len() > arity && !variadic) {
+            return Err(format!(
+                "{}() takes {}{} argument{}, not {}",
+                name,
+                if variadic { "at least " } else { "" },
+                arity,
+                if arity == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        Ok(Node::Function(function, args))
+    }
+
+    /// Parses what follows a projection, up to the token that ends it.
+    fn projection_rhs(&mut self, binding_power: u8) -> Result<Node, String> {
+        match self.current() {
+            token if token.binding_power() < PROJECTION_STOP => Ok(Node::Current),
+            Token::LBracket | Token::Filter => self.expression(binding_power),
+            Token::Dot => {
+                self.advance();
+                self.dot_rhs(binding_power)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses what follows a `.`.
+    fn dot_rhs(&mut self, binding_power: u8) -> Result<Node, String> {
+        match self.current() {
+            Token::Identifier(_) | Token::QuotedIdentifier(_) | Token::Star => {
+                self.expression(binding_power)
+            }
+            Token::LBracket => {
+                self.advance();
+                self.multi_list()
+            }
+            Token::LBrace => {
+                self.advance();
+                self.multi_hash()
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses `[a, b, ...]` after its `[`.
+    fn multi_list(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        loop {
+            nodes.push(self.expression(0)?);
+            if *self.current() == Token::RBracket {
+                self.advance();
+                return Ok(Node::MultiList(nodes));
+            }
+            self.expect(Token::Comma)?;
+        }
+    }
+
+    /// Parses `{key: a, ...}` after its `{`.
+    fn multi_hash(&mut self) -> Result<Node, String> {
+        let mut pairs = Vec::new();
+        loop {
+            let key = match self.advance() {
+                Token::Identifier(key) | Token::QuotedIdentifier(key) => key,
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected());
+                }
+            };
+            self.expect(Token::Colon)?;
+            pairs.push((key, self.expression(0)?));
+            match self.advance() {
+                Token::Comma => {}
+                Token::RBrace => return Ok(Node::MultiHash(pairs)),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected());
+                }
+            }
+        }
+    }
+}
+
+fn eval(node: &Node, value: &Value) -> Result<Value, JqrError> {
+    match node {
+        Node::Current => Ok(value.clone()),
+        Node::Field(name) => Ok(value.get(name).cloned().unwrap_or_default()),
+        Node::Literal(literal) => Ok(literal.clone()),
+        Node::Subexpr(left, right) => eval(right, &eval(left, value)?),
+        Node::Index(index) => {
+            let Value::Array(items) = value else {
+                return Ok(Value::Null);
+            };
+            let index = if *index < 0 {
+                *index + items.len() as i64
+            } else {
+                *index
+            };
+            Ok(usize::try_from(index)
+                .ok()
+                .and_then(|index| items.get(index))
+                .cloned()
+                .unwrap_or_default())
+        }
+        Node::Slice(start, stop, step) => match value {
+            Value::Array(items) => {
+                let indices = slice_indices(items.len(), *start, *stop, *step)?;
+                Ok(Value::Array(
+                    indices.into_iter().map(|i| items[i].clone()).collect(),
+                ))
+            }
+            _ => Ok(Value::Null),
+        },
+        Node::Projection(left, right) => match eval(left, value)? {
+            Value::Array(items) => project(items.iter(), right),
+            _ => Ok(Value::Null),
+        },
+        Node::ValueProjection(left, right) => match eval(left, value)? {
+            Value::Object(map) => project(map.values(), right),
+            _ => Ok(Value::Null),
+        },
+        Node::FilterProjection(left, right, condition) => match eval(left, value)? {
+            Value::Array(items) => {
+                let mut kept = Vec::new();
+                for item in items {
+                    if truthy(&eval(condition, &item)?) {
+                        kept.push(item);
+                    }
+                }
+                project(kept.iter(), right)
+            }
+            _ => Ok(Value::Null),
+        },
+        Node::Flatten(node) => match eval(node, value)? {
+            Value::Array(items) => {
+                let mut flat = Vec::new();
+                for item in items {
+                    match item {
+                        Value::Array(inner) => flat.extend(inner),
+                        item => flat.push(item),
+                    }
+                }
+                Ok(Value::Array(flat))
+            }
+            _ => Ok(Value::Null),
+        },
+        Node::MultiList(nodes) if !value.is_null() => nodes
+            .iter()
+            .map(|node| eval(node, value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Node::MultiHash(pairs) if !value.is_null() => {
+            let mut object = Map::new();
+            for (key, node) in pairs {
+                object.insert(key.clone(), eval(node, value)?);
+            }
+            Ok(Value::Object(object))
+        }
+        Node::MultiList(_) | Node::MultiHash(_) => Ok(Value::Null),
+        Node::Pipe(left, right) => eval(right, &eval(left, value)?),
+        Node::Or(left, right) => {
+            let left = eval(left, value)?;
+            if truthy(&left) {
+                Ok(left)
+            } else {
+                eval(right, value)
+            }
+        }
+        Node::And(left, right) => {
+            let left = eval(left, value)?;
+            if truthy(&left) {
+                eval(right, value)
+            } else {
+                Ok(left)
+            }
+        }
+        Node::Not(node) => Ok(Value::Bool(!truthy(&eval(node, value)?))),
+        Node::Compare(comparator, left, right) => {
+            let (left, right) = (eval(left, value)?, eval(right, value)?);
+            let ordering = || match (left.as_f64(), right.as_f64()) {
+                (Some(a), Some(b)) if left.is_number() && right.is_number() => a.partial_cmp(&b),
+                _ => None,
+            };
+            // Only numbers are ordered; other comparisons are null
+            Ok(match comparator {
+                Comparator::Equal => Value::Bool(json_equal(&left, &right)),
+                Comparator::NotEqual => Value::Bool(!json_equal(&left, &right)),
+                Comparator::Less => ordering().map_or(Value::Null, |o| Value::Bool(o.is_lt())),
+                Comparator::LessEqual => ordering().map_or(Value::Null, |o| Value::Bool(o.is_le())),
+                Comparator::Greater => ordering().map_or(Value::Null, |o| Value::Bool(o.is_gt())),
+                Comparator::GreaterEqual => {
+                    ordering().map_or(Value::Null, |o| Value::Bool(o.is_ge()))
+                }
+            })
+        }
+        Node::Function(name, args) => call(name, args, value),
+        // Only functions can use an expression reference
+        Node::ExprRef(_) => Ok(Value::Null),
+    }
+}
+
+/// Runs `right` on every item, dropping `null` results.
+fn project<'a>(items: impl Iterator<Item = &'a Value>, right: &Node) -> Result<Value, JqrError> {
+    let mut projected = Vec::new();
+    for item in items {
+        let result = eval(right, item)?;
+        if !result.is_null() {
+            projected.push(result);
+        }
+    }
+    Ok(Value::Array(projected))
+}
+
+/// Returns the indices a `[start:stop:step]` slice selects from a sequence
+/// of `length` items, with the bounds handled as in Python.
+fn slice_indices(
+    length: usize,
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: Option<i64>,
+) -> Result<Vec<usize>, JqrError> {
+    let step = step.unwrap_or(1);
+    if step == 0 {
+        return Err(JqrError::UnsupportedValue(
+            "JMESPath slice step cannot be 0".to_string(),
+        ));
+    }
+    let length = length as i64;
+    let adjust = |bound: i64| {
+        if bound < 0 {
+            (bound + length).max(if step < 0 { -1 } else { 0 })
+        } else if bound >= length {
+            if step < 0 {
+                length - 1
+            } else {
+                length
+            }
+        } else {
+            bound
+        }
+    };
+    let start = start.map_or(if step < 0 { length - 1 } else { 0 }, adjust);
+    let stop = stop.map_or(if step < 0 { -1 } else { length }, adjust);
+
+    let mut indices = Vec::new();
+    let mut i = start;
+    while (step > 0 && i < stop) || (step < 0 && i > stop) {
+        indices.push(i as usize);
+        i += step;
+    }
+    Ok(indices)
+}
+
+/// Returns `false` for `null`, `false` and empty strings, arrays and
+/// objects, and `true` for everything else.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        Value::Number(_) => true,
+    }
+}
+
+/// Orders sort keys, which are all numbers or all strings.
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+    }
+}
+
+fn call(name: &str, args: &[Node], value: &Value) -> Result<Value, JqrError> {
+    let wrong = |expected: &str, got: &Value| {
+        JqrError::UnsupportedValue(format!(
+            "{}() expects {}, got {}",
+            name,
+            expected,
+            json_type(got)
+        ))
+    };
+    // Expression references are kept for the functions that run them
+    let expression = |index: usize| match &args[index] {
+        Node::ExprRef(node) => Ok(node.as_ref()),
+        _ => Err(JqrError::UnsupportedValue(format!(
+            "{}() expects an expression reference (&expr) as argument {}",
+            name,
+            index + 1
+        ))),
+    };
+    let values = args
+        .iter()
+        .map(|arg| match arg {
+            Node::ExprRef(_) => Ok(Value::Null),
+            arg => eval(arg, value),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let number = |value: &Value| value.as_f64().ok_or_else(|| wrong("a number", value));
+    let array = |value: &'_ Value| -> Result<Vec<Value>, JqrError> {
+        match value {
+            Value::Array(items) => Ok(items.clone()),
+            _ => Err(wrong("an array", value)),
+        }
+    };
+    let string = |value: &Value| -> Result<String, JqrError> {
+        match value {
+            Value::String(text) => Ok(text.clone()),
+            _ => Err(wrong("a string", value)),
+        }
+    };
+    // Sort keys must all be numbers or all strings
+    let sort_keys = |keys: &[Value]| {
+        let numbers = keys.iter().all(Value::is_number);
+        let strings = keys.iter().all(Value::is_string);
+        match keys
+            .iter()
+            .find(|key| !(key.is_number() || key.is_string()))
+        {
+            _ if numbers || strings => Ok(()),
+            Some(key) => Err(wrong("numbers or strings", key)),
+            None => Err(JqrError::UnsupportedValue(format!(
+                "{}() expects all numbers or all strings, not a mix",
+                name
+            ))),
+        }
+    };
+    // The results of `&expr` on every item of an array
+    let keyed = |items: &[Value], node: &Node| -> Result<Vec<Value>, JqrError> {
+        let keys = items
+            .iter()
+            .map(|item| eval(node, item))
+            .collect::<Result<Vec<_>, _>>()?;
+        sort_keys(&keys)?;
+        Ok(keys)
+    };
+
+    let arg = &values[0];
+    match name {
+        "abs" => Ok(to_number(number(arg)?.abs()).unwrap_or(Value::Null)),
+        "ceil" => Ok(to_number(number(arg)?.ceil()).unwrap_or(Value::Null)),
+        "floor" => Ok(to_number(number(arg)?.floor()).unwrap_or(Value::Null)),
+        "avg" | "sum" => {
+            let items = array(arg)?;
+            let total = items.iter().map(number).sum::<Result<f64, _>>()?;
+            Ok(match (name, items.len()) {
+                ("sum", _) => to_number(total).unwrap_or(Value::Null),
+                (_, 0) => Value::Null,
+                (_, count) => to_number(total / count as f64).unwrap_or(Value::Null),
+            })
+        }
+        "contains" => match arg {
+            Value::Array(items) => Ok(Value::Bool(
+                items.iter().any(|item| json_equal(item, &values[1])),
+            )),
+            Value::String(text) => Ok(Value::Bool(
+                values[1]
+                    .as_str()
+                    .is_some_and(|search| text.contains(search)),
+            )),
+            _ => Err(wrong("an array or a string", arg)),
+        },
+        "starts_with" => Ok(Value::Bool(string(arg)?.starts_with(&string(&values[1])?))),
+        "ends_with" => Ok(Value::Bool(string(arg)?.ends_with(&string(&values[1])?))),
+        "join" => {
+            let glue = string(arg)?;
+            let parts = array(&values[1])?
+                .iter()
+                .map(string)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::String(parts.join(&glue)))
+        }
+        "keys" | "values" => match arg {
+            Value::Object(map) if name == "keys" => {
+                Ok(map.keys().map(|key| Value::String(key.clone())).collect())
+            }
+            Value::Object(map) => Ok(map.values().cloned().collect()),
+            _ => Err(wrong("an object", arg)),
+        },
+        "length" => match arg {
+            Value::String(text) => Ok(Value::from(text.chars().count())),
+            Value::Array(items) => Ok(Value::from(items.len())),
+            Value::Object(map) => Ok(Value::from(map.len())),
+            _ => Err(wrong("a string, array or object", arg)),
+        },
+        "map" => {
+            let node = expression(0)?;
+            array(&values[1])?
+                .iter()
+                .map(|item| eval(node, item))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array)
+        }
+        "max" | "min" => {
+            let items = array(arg)?;
+            sort_keys(&items)?;
+            let best = if name == "max" {
+                items.into_iter().max_by(compare_keys)
+            } else {
+                items.into_iter().min_by(compare_keys)
+            };
+            Ok(best.unwrap_or_default())
+        }
+        "max_by" | "min_by" => {
+            let items = array(arg)?;
+            let keys = keyed(&items, expression(1)?)?;
+            let pairs = keys.iter().zip(items.iter());
+            let best = if name == "max_by" {
+                pairs.max_by(|a, b| compare_keys(a.0, b.0))
+            } else {
+                pairs.min_by(|a, b| compare_keys(a.0, b.0))
+            };
+            Ok(best.map(|(_, item)| item.clone()).unwrap_or_default())
+        }
+        "merge" => {
+            let mut merged = Map::new();
+            for value in &values {
+                match value {
+                    Value::Object(map) => merged.extend(map.clone()),
+                    _ => return Err(wrong("objects", value)),
+                }
+            }
+            Ok(Value::Object(merged))
+        }
+        "not_null" => Ok(values
+            .into_iter()
+            .find(|value| !value.is_null())
+            .unwrap_or_default()),
+        "reverse" => match arg {
+            Value::String(text) => Ok(Value::String(text.chars().rev().collect())),
+            Value::Array(items) => Ok(items.iter().rev().cloned().collect()),
+            _ => Err(wrong("an array or a string", arg)),
+        },
+        "sort" => {
+            let mut items = array(arg)?;
+            sort_keys(&items)?;
+            items.sort_by(compare_keys);
+            Ok(Value::Array(items))
+        }
+        "sort_by" => {
+            let items = array(arg)?;
+            let keys = keyed(&items, expression(1)?)?;
+            let mut pairs: Vec<(Value, Value)> = keys.into_iter().zip(items).collect();
+            pairs.sort_by(|a, b| compare_keys(&a.0, &b.0));
+            Ok(pairs.into_iter().map(|(_, item)| item).collect())
+        }
+        "to_array" => match arg {
+            Value::Array(_) => Ok(arg.clone()),
+            _ => Ok(Value::Array(vec![arg.clone()])),
+        },
+        "to_number" => match arg {
+            Value::Number(_) => Ok(arg.clone()),
+            Value::String(text) => Ok(text.parse::<Number>().map_or(Value::Null, Value::Number)),
+            _ => Ok(Value::Null),
+        },
+        "to_string" => match arg {
+            Value::String(_) => Ok(arg.clone()),
+            _ => Ok(Value::String(arg.to_string())),
+        },
+        "type" => Ok(Value::String(json_type(arg).to_string())),
+        _ => unreachable!("functions are checked when parsing"),
+    }
+}
@@ -0,0 +1,1155 @@
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+use crate::transform::to_number;
+use crate::{json_type, JqrError};
+
+/// A filter in a subset of jq's language, parsed once so it can be run
+/// against many documents.
+///
+/// The subset covers what most jq one-liners use:
+///
+/// - Paths: `.`, `.foo`, `."foo bar"`, `.[0]`, `.[-1]`, `.[2:4]`, `.[]`,
+///   `..` and the `?` suffix, which drops errors such as indexing a number.
+/// - Pipes `|` and commas `,`, which produce several results.
+/// - Literals, array construction `[...]` and object construction
+///   `{a: .x, "b": .y, c, (.key): .value}`.
+/// - Arithmetic `+ - * / %`, comparisons `== != < <= > >=`, `and`, `or`,
+///   the alternative `//` and `if ... then ... elif ... else ... end`.
+/// - The functions `select(f)`, `map(f)`, `has(key)`, `sort_by(f)`,
+///   `with_entries(f)`, `length`, `keys`, `type`, `not`, `empty`, `add`,
+///   `sort`, `tostring`, `tonumber`, `to_entries` and `from_entries`.
+///
+/// Variables, `reduce`, `def` and string interpolation are not supported.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::JqFilter;
+/// use serde_json::json;
+///
+/// let filter = JqFilter::new(".users[] | select(.age >= 30) | {name, city: .address.city}").unwrap();
+/// let doc = json!({"users": [
+///     {"name": "Alice", "age": 31, "address": {"city": "Oslo"}},
+///     {"name": "Bob", "age": 25, "address": {"city": "Rome"}},
+/// ]});
+///
+/// assert_eq!(filter.apply(&doc).unwrap(), vec![json!({"name": "Alice", "city": "Oslo"})]);
+///
+/// assert!(JqFilter::new(".users[").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct JqFilter {
+    source: String,
+    expr: Expr,
+}
+
+impl JqFilter {
+    /// Parses a jq filter.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the filter cannot be parsed,
+    ///   calls an unknown function or uses an unsupported part of jq.
+    pub fn new(filter: &str) -> Result<Self, JqrError> {
+        let invalid = |message: String| {
+            JqrError::InvalidQuery(format!("Invalid jq filter '{}': {}", filter, message))
+        };
+        let mut parser = Parser {
+            tokens: tokenize(filter).map_err(invalid)?,
+            position: 0,
+        };
+        let expr = parser.parse_pipe(true).map_err(invalid)?;
+        if parser.position < parser.tokens.len() {
+            return Err(invalid(parser.unexpected()));
+        }
+        Ok(JqFilter {
+            source: filter.to_string(),
+            expr,
+        })
+    }
+
+    /// Returns the filter text this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Runs the filter, returning every result it produces, in order.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::UnsupportedValue`] if the filter does something
+    ///   the value does not allow, such as indexing a number or adding a
+    ///   string to a number, outside a `?`.
+    pub fn apply(&self, json: &Value) -> Result<Vec<Value>, JqrError> {
+        eval(&self.expr, json)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Identity,
+    RecurseAll,
+    Literal(Value),
+    /// `target[index]`, with `.foo` as an index by the string `"foo"`.
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Iterate(Box<Expr>),
+    Try(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Alternative(Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    Call(&'static str, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// The functions the subset provides, with their number of arguments.
+const FUNCTIONS: [(&str, usize); 17] = [
+    ("select", 1),
+    ("map", 1),
+    ("has", 1),
+    ("sort_by", 1),
+    ("with_entries", 1),
+    ("length", 0),
+    ("keys", 0),
+    ("type", 0),
+    ("not", 0),
+    ("empty", 0),
+    ("add", 0),
+    ("sort", 0),
+    ("tostring", 0),
+    ("tonumber", 0),
+    ("to_entries", 0),
+    ("from_entries", 0),
+    ("error", 0),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Str(String),
+    Num(Number),
+    Op(&'static str),
+}
+
+/// Punctuation and operators, longest first.
+const OPERATORS: [&str; 23] = [
+    "//", "==", "!=", "<=", ">=", "|", ",", "(", ")", "[", "]", "{", "}", ":", "?", "<", ">", "+",
+    "-", "*", "/", "%", ";",
+];
+
+/// Splits a filter into tokens, each with the column it starts at.
+fn tokenize(filter: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = filter;
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with('#') {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            continue;
+        }
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        let column = filter[..filter.len() - rest.len()].chars().count() + 1;
+
+        let (token, length) = if rest.starts_with("..") {
+            (Token::DotDot, 2)
+        } else if c == '.' {
+            match identifier_length(&rest[1..]) {
+                0 => (Token::Dot, 1),
+                length => (Token::Field(rest[1..1 + length].to_string()), 1 + length),
+            }
+        } else if c == '"' {
+            let length = string_length(rest, column)?;
+            let text = serde_json::from_str(&rest[..length])
+                .map_err(|_| format!("invalid string at column {}", column))?;
+            (Token::Str(text), length)
+        } else if c.is_ascii_digit() {
+            let length = number_length(rest);
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("invalid number at column {}", column))?;
+            (Token::Num(number), length)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let length = identifier_length(rest);
+            (Token::Ident(rest[..length].to_string()), length)
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            (Token::Op(op), op.len())
+        } else if c == '$' {
+            return Err(format!(
+                "variables are not supported (at column {})",
+                column
+            ));
+        } else {
+            return Err(format!("unexpected '{}' at column {}", c, column));
+        };
+        tokens.push((token, column));
+        rest = &rest[length..];
+    }
+}
+
+fn identifier_length(text: &str) -> usize {
+    match text.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => text
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(text.len()),
+        _ => 0,
+    }
+}
+
+/// Returns the length of the string literal `text` starts with, quotes
+/// included.
+fn string_length(text: &str, column: usize) -> Result<usize, String> {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if bytes.get(i + 1) == Some(&b'(') => {
+                return Err(format!(
+                    "string interpolation is not supported (at column {})",
+                    column
+                ))
+            }
+            b'\\' => i += 1,
+            b'"' => return Ok(i + 1),
+            _ => {}
+        }
+        i += 1;
+    }
+    Err(format!("unterminated string at column {}", column))
+}
+
+fn number_length(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut length = digits(0);
+    if bytes.get(length) == Some(&b'.') && digits(length + 1) > 0 {
+        length += 1 + digits(length + 1);
+    }
+    if matches!(bytes.get(length), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(length + 1), Some(b'+' | b'-')));
+        if digits(length + 1 + sign) > 0 {
+            length += 1 + sign + digits(length + 1 + sign);
+        }
+    }
+    length
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the operator `op` if it comes next.
+    fn eat(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Op(next)) if *next == op);
+        self.position += usize::from(found);
+        found
+    }
+
+    /// Consumes the keyword `word` if it comes next.
+    fn eat_keyword(&mut self, word: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Ident(next)) if next == word);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn expect_keyword(&mut self, word: &str) -> Result<(), String> {
+        if self.eat_keyword(word) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Describes the token at the current position as unexpected.
+    fn unexpected(&self) -> String {
+        match self.tokens.get(self.position) {
+            None => "unexpected end of filter".to_string(),
+            Some((token, column)) => {
+                let text = match token {
+                    Token::Dot => ".".to_string(),
+                    Token::DotDot => "..".to_string(),
+                    Token::Field(name) => format!(".{}", name),
+                    Token::Ident(name) => name.clone(),
+                    Token::Str(text) => serde_json::to_string(text).unwrap_or_default(),
+                    Token::Num(number) => number.to_string(),
+                    Token::Op(op) => op.to_string(),
+                };
+                format!("unexpected '{}' at column {}", text, column)
+            }
+        }
+    }
+
+    /// Parses a pipeline, without top-level commas unless `comma`, as in
+    /// object values.
+    fn parse_pipe(&mut self, comma: bool) -> Result<Expr, String> {
+        let left = if comma {
+            self.parse_comma()?
+        } else {
+            self.parse_alternative()?
+        };
+        if self.eat("|") {
+            Ok(Expr::Pipe(
+                Box::new(left),
+                Box::new(self.parse_pipe(comma)?),
+            ))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_alternative()?;
+        while self.eat(",") {
+            expr = Expr::Comma(Box::new(expr), Box::new(self.parse_alternative()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_alternative(&mut self) -> Result<Expr, String> {
+        let left = self.parse_or()?;
+        if self.eat("//") {
+            Ok(Expr::Alternative(
+                Box::new(left),
+                Box::new(self.parse_alternative()?),
+            ))
+        } else {
+            Ok(left)
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_comparison()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let left = self.parse_additive()?;
+        let operator = match self.peek() {
+            Some(Token::Op("==")) => Operator::Equal,
+            Some(Token::Op("!=")) => Operator::NotEqual,
+            Some(Token::Op("<")) => Operator::Less,
+            Some(Token::Op("<=")) => Operator::LessEqual,
+            Some(Token::Op(">")) => Operator::Greater,
+            Some(Token::Op(">=")) => Operator::GreaterEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.parse_additive()?;
+        Ok(Expr::Binary(operator, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_multiplicative()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Op("+")) => Operator::Add,
+                Some(Token::Op("-")) => Operator::Subtract,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            let right = self.parse_multiplicative()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Op("*")) => Operator::Multiply,
+                Some(Token::Op("/")) => Operator::Divide,
+                Some(Token::Op("%")) => Operator::Remainder,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            let right = self.parse_unary()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Negate(Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_postfix()
+        }
+    }
+
+    /// Parses a term followed by any number of `.foo`, `[...]` and `?`.
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_term()?;
+        loop {
+            expr = match (self.peek(), self.tokens.get(self.position + 1)) {
+                (Some(Token::Field(name)), _) => {
+                    let name = Value::String(name.clone());
+                    self.position += 1;
+                    Expr::Index(Box::new(expr), Box::new(Expr::Literal(name)))
+                }
+                (Some(Token::Dot), Some((Token::Str(name), _))) => {
+                    let name = Value::String(name.clone());
+                    self.position += 2;
+                    Expr::Index(Box::new(expr), Box::new(Expr::Literal(name)))
+                }
+                (Some(Token::Dot), Some((Token::Op("["), _))) => {
+                    self.position += 1;
+                    continue;
+                }
+                (Some(Token::Op("[")), _) => {
+                    self.position += 1;
+                    self.parse_brackets(expr)?
+                }
+                (Some(Token::Op("?")), _) => {
+                    self.position += 1;
+                    Expr::Try(Box::new(expr))
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    /// Parses what follows the `[` of an index, slice or iteration of `target`.
+    fn parse_brackets(&mut self, target: Expr) -> Result<Expr, String> {
+        if self.eat("]") {
+            return Ok(Expr::Iterate(Box::new(target)));
+        }
+        let start = if matches!(self.peek(), Some(Token::Op(":"))) {
+            None
+        } else {
+            Some(Box::new(self.parse_pipe(true)?))
+        };
+        if self.eat(":") {
+            let end = if matches!(self.peek(), Some(Token::Op("]"))) {
+                None
+            } else {
+                Some(Box::new(self.parse_pipe(true)?))
+            };
+            self.expect("]")?;
+            return Ok(Expr::Slice(Box::new(target), start, end));
+        }
+        self.expect("]")?;
+        match start {
+            Some(index) => Ok(Expr::Index(Box::new(target), index)),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let start = self.position;
+        let expr = match self.next() {
+            Some(Token::Dot) => match self.peek() {
+                Some(Token::Str(name)) => {
+                    let name = Value::String(name.clone());
+                    self.position += 1;
+                    Expr::Index(Box::new(Expr::Identity), Box::new(Expr::Literal(name)))
+                }
+                _ => Expr::Identity,
+            },
+            Some(Token::DotDot) => Expr::RecurseAll,
+            Some(Token::Field(name)) => Expr::Index(
+                Box::new(Expr::Identity),
+                Box::new(Expr::Literal(Value::String(name))),
+            ),
+            Some(Token::Str(text)) => Expr::Literal(Value::String(text)),
+            Some(Token::Num(number)) => Expr::Literal(Value::Number(number)),
+            Some(Token::Op("(")) => {
+                let expr = self.parse_pipe(true)?;
+                self.expect(")")?;
+                expr
+            }
+            Some(Token::Op("[")) => {
+                if self.eat("]") {
+                    Expr::Array(None)
+                } else {
+                    let expr = self.parse_pipe(true)?;
+                    self.expect("]")?;
+                    Expr::Array(Some(Box::new(expr)))
+                }
+            }
+            Some(Token::Op("{")) => self.parse_object()?,
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                "if" => self.parse_if()?,
+                "reduce" | "foreach" | "def" | "as" | "try" | "label" | "import" | "include" => {
+                    return Err(format!("'{}' is not supported", name));
+                }
+                _ => self.parse_call(start, &name)?,
+            },
+            _ => {
+                self.position = start;
+                return Err(self.unexpected());
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Parses the arguments of a call to the function `name`, whose token
+    /// is at `start`.
+    fn parse_call(&mut self, start: usize, name: &str) -> Result<Expr, String> {
+        let mut args = Vec::new();
+        if self.eat("(") {
+            loop {
+                args.push(self.parse_pipe(true)?);
+                if !self.eat(";") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        match FUNCTIONS
+            .iter()
+            .find(|(function, arity)| *function == name && *arity == args.len())
+        {
+            Some((function, _)) => Ok(Expr::Call(function, args)),
+            None => Err(format!(
+                "{}/{} is not defined (at column {})",
+                name,
+                args.len(),
+                self.tokens[start].1
+            )),
+        }
+    }
+
+    /// Parses the rest of an `if` after `if` or `elif`.
+    fn parse_if(&mut self) -> Result<Expr, String> {
+        let condition = self.parse_pipe(true)?;
+        self.expect_keyword("then")?;
+        let then = self.parse_pipe(true)?;
+        let otherwise = if self.eat_keyword("elif") {
+            Some(Box::new(self.parse_if()?))
+        } else if self.eat_keyword("else") {
+            let otherwise = self.parse_pipe(true)?;
+            self.expect_keyword("end")?;
+            Some(Box::new(otherwise))
+        } else {
+            self.expect_keyword("end")?;
+            None
+        };
+        Ok(Expr::If(Box::new(condition), Box::new(then), otherwise))
+    }
+
+    /// Parses the entries of an object construction after its `{`.
+    fn parse_object(&mut self) -> Result<Expr, String> {
+        let mut entries = Vec::new();
+        if self.eat("}") {
+            return Ok(Expr::Object(entries));
+        }
+        loop {
+            let (key, shorthand) = match self.next() {
+                Some(Token::Ident(name) | Token::Str(name)) => {
+                    (Expr::Literal(Value::String(name.clone())), Some(name))
+                }
+                Some(Token::Op("(")) => {
+                    let key = self.parse_pipe(true)?;
+                    self.expect(")")?;
+                    (key, None)
+                }
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected());
+                }
+            };
+            let value = if self.eat(":") {
+                self.parse_pipe(false)?
+            } else {
+                match shorthand {
+                    // `{name}` is short for `{name: .name}`
+                    Some(name) => Expr::Index(
+                        Box::new(Expr::Identity),
+                        Box::new(Expr::Literal(Value::String(name))),
+                    ),
+                    None => return Err(self.unexpected()),
+                }
+            };
+            entries.push((key, value));
+            if !self.eat(",") {
+                self.expect("}")?;
+                return Ok(Expr::Object(entries));
+            }
+        }
+    }
+}
+
+fn eval(expr: &Expr, input: &Value) -> Result<Vec<Value>, JqrError> {
+    match expr {
+        Expr::Identity => Ok(vec![input.clone()]),
+        Expr::RecurseAll => {
+            let mut found = Vec::new();
+            descendants(input, &mut found);
+            Ok(found)
+        }
+        Expr::Literal(value) => Ok(vec![value.clone()]),
+        Expr::Index(target, index) => {
+            let indices = eval(index, input)?;
+            let mut found = Vec::new();
+            for value in eval(target, input)? {
+                for index in &indices {
+                    found.push(index_value(&value, index)?);
+                }
+            }
+            Ok(found)
+        }
+        Expr::Slice(target, start, end) => {
+            let bound = |bound: &Option<Box<Expr>>| match bound {
+                Some(bound) => eval(bound, input),
+                None => Ok(vec![Value::Null]),
+            };
+            let (starts, ends) = (bound(start)?, bound(end)?);
+            let mut found = Vec::new();
+            for value in eval(target, input)? {
+                for start in &starts {
+                    for end in &ends {
+                        found.push(slice_value(&value, start, end)?);
+                    }
+                }
+            }
+            Ok(found)
+        }
+        Expr::Iterate(target) => {
+            let mut found = Vec::new();
+            for value in eval(target, input)? {
+                found.extend(iterate(&value)?);
+            }
+            Ok(found)
+        }
+        Expr::Try(expr) => Ok(eval(expr, input).unwrap_or_default()),
+        Expr::Pipe(left, right) => {
+            let mut found = Vec::new();
+            for value in eval(left, input)? {
+                found.extend(eval(right, &value)?);
+            }
+            Ok(found)
+        }
+        Expr::Comma(left, right) => {
+            let mut found = eval(left, input)?;
+            found.extend(eval(right, input)?);
+            Ok(found)
+        }
+        Expr::Array(None) => Ok(vec![Value::Array(Vec::new())]),
+        Expr::Array(Some(items)) => Ok(vec![Value::Array(eval(items, input)?)]),
+        Expr::Object(entries) => {
+            // Every combination of the keys' and values' results is an object
+            let mut objects = vec![Map::new()];
+            for (key, value) in entries {
+                let (keys, values) = (eval(key, input)?, eval(value, input)?);
+                let mut combined = Vec::new();
+                for object in &objects {
+                    for key in &keys {
+                        let Value::String(key) = key else {
+                            return Err(JqrError::UnsupportedValue(format!(
+                                "Object keys must be strings, not {}",
+                                json_type(key)
+                            )));
+                        };
+                        for value in &values {
+                            let mut object = object.clone();
+                            object.insert(key.clone(), value.clone());
+                            combined.push(object);
+                        }
+                    }
+                }
+                objects = combined;
+            }
+            Ok(objects.into_iter().map(Value::Object).collect())
+        }
+        Expr::Negate(expr) => eval(expr, input)?
+            .iter()
+            .map(|value| match value.as_f64() {
+                Some(number) => to_number(-number),
+                None => Err(JqrError::UnsupportedValue(format!(
+                    "{} ({}) cannot be negated",
+                    json_type(value),
+                    brief(value)
+                ))),
+            })
+            .collect(),
+        Expr::Binary(operator, left, right) => {
+            let lefts = eval(left, input)?;
+            let mut found = Vec::new();
+            for right in eval(right, input)? {
+                for left in &lefts {
+                    found.push(binary(*operator, left, &right)?);
+                }
+            }
+            Ok(found)
+        }
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            let is_and = matches!(expr, Expr::And(..));
+            let mut found = Vec::new();
+            for left in eval(left, input)? {
+                // `and` is decided by a false left side, `or` by a true one
+                if truthy(&left) != is_and {
+                    found.push(Value::Bool(!is_and));
+                    continue;
+                }
+                for right in eval(right, input)? {
+                    found.push(Value::Bool(truthy(&right)));
+                }
+            }
+            Ok(found)
+        }
+        Expr::Alternative(left, right) => {
+            let found: Vec<Value> = eval(left, input)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(truthy)
+                .collect();
+            if found.is_empty() {
+                eval(right, input)
+            } else {
+                Ok(found)
+            }
+        }
+        Expr::If(condition, then, otherwise) => {
+            let mut found = Vec::new();
+            for condition in eval(condition, input)? {
+                match (truthy(&condition), otherwise) {
+                    (true, _) => found.extend(eval(then, input)?),
+                    (false, Some(otherwise)) => found.extend(eval(otherwise, input)?),
+                    (false, None) => found.push(input.clone()),
+                }
+            }
+            Ok(found)
+        }
+        Expr::Call(name, args) => call(name, args, input),
+    }
+}
+
+fn call(name: &str, args: &[Expr], input: &Value) -> Result<Vec<Value>, JqrError> {
+    let single = |value: Value| Ok(vec![value]);
+    match (name, args) {
+        ("select", [condition]) => Ok(eval(condition, input)?
+            .iter()
+            .filter(|result| truthy(result))
+            .map(|_| input.clone())
+            .collect()),
+        ("map", [function]) => {
+            let mut mapped = Vec::new();
+            for item in iterate(input)? {
+                mapped.extend(eval(function, &item)?);
+            }
+            single(Value::Array(mapped))
+        }
+        ("has", [key]) => eval(key, input)?
+            .iter()
+            .map(|key| has(input, key).map(Value::Bool))
+            .collect(),
+        ("sort_by", [function]) => {
+            let Value::Array(items) = input else {
+                return Err(cannot(input, "sorted, as it is not an array"));
+            };
+            let mut keyed = items
+                .iter()
+                .map(|item| Ok((Value::Array(eval(function, item)?), item.clone())))
+                .collect::<Result<Vec<_>, JqrError>>()?;
+            keyed.sort_by(|a, b| compare(&a.0, &b.0));
+            single(Value::Array(
+                keyed.into_iter().map(|(_, item)| item).collect(),
+            ))
+        }
+        ("with_entries", [function]) => {
+            let mut mapped = Vec::new();
+            for entry in to_entries(input)? {
+                mapped.extend(eval(function, &entry)?);
+            }
+            from_entries(&Value::Array(mapped)).and_then(single)
+        }
+        ("length", []) => match input {
+            Value::Null => single(Value::from(0)),
+            Value::Bool(_) => Err(cannot(input, "measured, as it has no length")),
+            Value::Number(_) => {
+                to_number(input.as_f64().unwrap_or_default().abs()).and_then(single)
+            }
+            Value::String(text) => single(Value::from(text.chars().count())),
+            Value::Array(items) => single(Value::from(items.len())),
+            Value::Object(map) => single(Value::from(map.len())),
+        },
+        ("keys", []) => match input {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                single(
+                    keys.into_iter()
+                        .map(|key| Value::String(key.clone()))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => single((0..items.len()).map(Value::from).collect()),
+            _ => Err(cannot(input, "listed, as it has no keys")),
+        },
+        ("type", []) => single(Value::String(json_type(input).to_string())),
+        ("not", []) => single(Value::Bool(!truthy(input))),
+        ("empty", []) => Ok(Vec::new()),
+        ("add", []) => iterate(input)?
+            .iter()
+            .try_fold(Value::Null, |sum, item| binary(Operator::Add, &sum, item))
+            .and_then(single),
+        ("sort", []) => match input {
+            Value::Array(items) => {
+                let mut items = items.clone();
+                items.sort_by(compare);
+                single(Value::Array(items))
+            }
+            _ => Err(cannot(input, "sorted, as it is not an array")),
+        },
+        ("tostring", []) => match input {
+            Value::String(_) => single(input.clone()),
+            _ => single(Value::String(input.to_string())),
+        },
+        ("tonumber", []) => match input {
+            Value::Number(_) => single(input.clone()),
+            Value::String(text) => match text.trim().parse::<Number>() {
+                Ok(number) => single(Value::Number(number)),
+                Err(_) => Err(cannot(input, "parsed as a number")),
+            },
+            _ => Err(cannot(input, "parsed as a number")),
+        },
+        ("to_entries", []) => single(Value::Array(to_entries(input)?)),
+        ("from_entries", []) => from_entries(input).and_then(single),
+        ("error", []) => Err(JqrError::UnsupportedValue(match input {
+            Value::String(message) => message.clone(),
+            _ => format!("{} (not a string)", brief(input)),
+        })),
+        _ => unreachable!("calls are checked when parsing"),
+    }
+}
+
+/// Builds the error for a value that cannot be `what`, as in "cannot be
+/// sorted, as it is not an array".
+fn cannot(value: &Value, what: &str) -> JqrError {
+    JqrError::UnsupportedValue(format!(
+        "{} ({}) cannot be {}",
+        json_type(value),
+        brief(value),
+        what
+    ))
+}
+
+/// Returns the value as compact JSON, shortened if it is long.
+fn brief(value: &Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(30) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+/// Returns `false` for `null` and `false`, and `true` for everything else.
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn descendants(value: &Value, found: &mut Vec<Value>) {
+    found.push(value.clone());
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| descendants(item, found)),
+        Value::Object(map) => map.values().for_each(|child| descendants(child, found)),
+        _ => {}
+    }
+}
+
+fn iterate(value: &Value) -> Result<Vec<Value>, JqrError> {
+    match value {
+        Value::Array(items) => Ok(items.clone()),
+        Value::Object(map) => Ok(map.values().cloned().collect()),
+        Value::Null => Err(JqrError::UnsupportedValue(
+            "Cannot iterate over null".to_string(),
+        )),
+        _ => Err(JqrError::UnsupportedValue(format!(
+            "Cannot iterate over {} ({})",
+            json_type(value),
+            brief(value)
+        ))),
+    }
+}
+
+fn index_value(value: &Value, index: &Value) -> Result<Value, JqrError> {
+    match (value, index) {
+        (Value::Object(map), Value::String(key)) => Ok(map.get(key).cloned().unwrap_or_default()),
+        (Value::Array(items), Value::Number(_)) => {
+            let position = index.as_f64().unwrap_or_default().floor() as i64;
+            let position = if position < 0 {
+                position + items.len() as i64
+            } else {
+                position
+            };
+            Ok(usize::try_from(position)
+                .ok()
+                .and_then(|position| items.get(position))
+                .cloned()
+                .unwrap_or_default())
+        }
+        (Value::Null, Value::String(_) | Value::Number(_)) => Ok(Value::Null),
+        (_, Value::String(key)) => Err(JqrError::UnsupportedValue(format!(
+            "Cannot index {} with \"{}\"",
+            json_type(value),
+            key
+        ))),
+        _ => Err(JqrError::UnsupportedValue(format!(
+            "Cannot index {} with {}",
+            json_type(value),
+            json_type(index)
+        ))),
+    }
+}
+
+fn slice_value(value: &Value, start: &Value, end: &Value) -> Result<Value, JqrError> {
+    let length = match value {
+        Value::Null => return Ok(Value::Null),
+        Value::Array(items) => items.len(),
+        Value::String(text) => text.chars().count(),
+        _ => return Err(cannot(value, "sliced")),
+    };
+    // Bounds count from the end when negative and are clamped to the value
+    let bound = |bound: &Value, default: usize| -> Result<usize, JqrError> {
+        match bound {
+            Value::Null => Ok(default),
+            Value::Number(_) => {
+                let bound = bound.as_f64().unwrap_or_default().floor();
+                let bound = if bound < 0.0 {
+                    bound + length as f64
+                } else {
+                    bound
+                };
+                Ok(bound.clamp(0.0, length as f64) as usize)
+            }
+            _ => Err(JqrError::UnsupportedValue(format!(
+                "Cannot slice with {}",
+                json_type(bound)
+            ))),
+        }
+    };
+    let start = bound(start, 0)?;
+    let end = bound(end, length)?.max(start);
+    Ok(match value {
+        Value::Array(items) => Value::Array(items[start..end].to_vec()),
+        Value::String(text) => Value::String(text.chars().skip(start).take(end - start).collect()),
+        _ => unreachable!(),
+    })
+}
+
+fn has(value: &Value, key: &Value) -> Result<bool, JqrError> {
+    match (value, key) {
+        (Value::Object(map), Value::String(key)) => Ok(map.contains_key(key)),
+        (Value::Array(items), Value::Number(_)) => {
+            let position = key.as_f64().unwrap_or(-1.0);
+            Ok(position >= 0.0 && position < items.len() as f64)
+        }
+        _ => Err(JqrError::UnsupportedValue(format!(
+            "Cannot check whether {} has a {} key",
+            json_type(value),
+            json_type(key)
+        ))),
+    }
+}
+
+fn to_entries(value: &Value) -> Result<Vec<Value>, JqrError> {
+    match value {
+        Value::Object(map) => Ok(map
+            .iter()
+            .map(|(key, value)| {
+                let mut entry = Map::new();
+                entry.insert("key".to_string(), Value::String(key.clone()));
+                entry.insert("value".to_string(), value.clone());
+                Value::Object(entry)
+            })
+            .collect()),
+        _ => Err(cannot(value, "turned into entries, as it is not an object")),
+    }
+}
+
+/// Builds an object from `{"key": ..., "value": ...}` entries, also
+/// accepting `k`/`name` for the key and `v` for the value as jq does.
+fn from_entries(value: &Value) -> Result<Value, JqrError> {
+    let mut object = Map::new();
+    for entry in iterate(value)? {
+        let field = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| entry.get(*name).filter(|value| truthy(value)))
+                .cloned()
+        };
+        let key = match field(&["key", "k", "name"]) {
+            Some(Value::String(key)) => key,
+            Some(key @ (Value::Number(_) | Value::Bool(_))) => key.to_string(),
+            _ => return Err(cannot(&entry, "read as an entry, as it has no key")),
+        };
+        object.insert(key, field(&["value", "v"]).unwrap_or_default());
+    }
+    Ok(Value::Object(object))
+}
+
+fn binary(operator: Operator, left: &Value, right: &Value) -> Result<Value, JqrError> {
+    let numbers = left.as_f64().zip(right.as_f64());
+    let failed = |what: &str| {
+        Err(JqrError::UnsupportedValue(format!(
+            "{} ({}) and {} ({}) cannot be {}",
+            json_type(left),
+            brief(left),
+            json_type(right),
+            brief(right),
+            what
+        )))
+    };
+    match operator {
+        Operator::Equal => Ok(Value::Bool(compare(left, right).is_eq())),
+        Operator::NotEqual => Ok(Value::Bool(compare(left, right).is_ne())),
+        Operator::Less => Ok(Value::Bool(compare(left, right).is_lt())),
+        Operator::LessEqual => Ok(Value::Bool(compare(left, right).is_le())),
+        Operator::Greater => Ok(Value::Bool(compare(left, right).is_gt())),
+        Operator::GreaterEqual => Ok(Value::Bool(compare(left, right).is_ge())),
+        Operator::Add => match (left, right) {
+            (Value::Null, other) | (other, Value::Null) => Ok(other.clone()),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+            (Value::Array(a), Value::Array(b)) => Ok(Value::Array([a.clone(), b.clone()].concat())),
+            (Value::Object(a), Value::Object(b)) => {
+                let mut merged = a.clone();
+                merged.extend(b.clone());
+                Ok(Value::Object(merged))
+            }
+            _ => match numbers {
+                Some((a, b)) => to_number(a + b),
+                None => failed("added"),
+            },
+        },
+        Operator::Subtract => match (left, right) {
+            (Value::Array(a), Value::Array(b)) => Ok(Value::Array(
+                a.iter()
+                    .filter(|item| !b.iter().any(|other| compare(item, other).is_eq()))
+                    .cloned()
+                    .collect(),
+            )),
+            _ => match numbers {
+                Some((a, b)) => to_number(a - b),
+                None => failed("subtracted"),
+            },
+        },
+        Operator::Multiply => match numbers {
+            Some((a, b)) => to_number(a * b),
+            None => failed("multiplied"),
+        },
+        Operator::Divide => match (left, right) {
+            (Value::String(text), Value::String(separator)) => Ok(Value::Array(
+                text.split(separator.as_str())
+                    .map(|part| Value::String(part.to_string()))
+                    .collect(),
+            )),
+            _ => match numbers {
+                Some((_, 0.0)) => failed("divided because the divisor is zero"),
+                Some((a, b)) => to_number(a / b),
+                None => failed("divided"),
+            },
+        },
+        Operator::Remainder => match numbers {
+            Some((_, b)) if b.trunc() == 0.0 => failed("divided because the divisor is zero"),
+            Some((a, b)) => to_number((a.trunc() as i64 % b.trunc() as i64) as f64),
+            None => failed("divided"),
+        },
+    }
+}
+
+/// Orders values as jq does: `null`, `false`, `true`, numbers, strings,
+/// arrays and objects, each kind by its contents.
+pub(crate) fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => {
+            let mut a_keys: Vec<&String> = a.keys().collect();
+            let mut b_keys: Vec<&String> = b.keys().collect();
+            a_keys.sort();
+            b_keys.sort();
+            a_keys.cmp(&b_keys).then_with(|| {
+                a_keys
+                    .iter()
+                    .map(|key| compare(&a[*key], &b[*key]))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
@@ -0,0 +1,1083 @@
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+use crate::transform::to_number;
+use crate::{json_equal, json_type, JqrError};
+
+/// A JSONata expression, parsed once so it can be run against many documents.
+///
+/// The supported part of JSONata covers navigation and aggregation:
+///
+/// - Paths such as `orders.amount`, which map over arrays, with `*` and `**`
+///   wildcards, `$` for the context and `$$` for the document.
+/// - Predicates: `orders[0]`, `orders[-1]`, `orders[amount > 100]`.
+/// - Array and object constructors, and grouping with a path followed by an
+///   object constructor: `orders{customer: $sum(amount)}`.
+/// - Arithmetic, `&` string concatenation, comparisons, `in`, `and`, `or`
+///   and the conditional `cond ? a : b`.
+/// - The functions `$sum`, `$count`, `$max`, `$min`, `$average`, `$string`,
+///   `$number`, `$length`, `$uppercase`, `$lowercase`, `$trim`,
+///   `$substring`, `$contains`, `$split`, `$join`, `$keys`, `$exists`,
+///   `$not`, `$boolean`, `$round`, `$abs`, `$floor`, `$ceil`, `$append`,
+///   `$sort`, `$reverse` and `$distinct`.
+///
+/// Variable binding, lambdas, regular expressions and sorting with `^()`
+/// are not supported.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::Jsonata;
+/// use serde_json::json;
+///
+/// let doc = json!({"orders": [
+///     {"customer": "acme", "amount": 120},
+///     {"customer": "initech", "amount": 80},
+///     {"customer": "acme", "amount": 30},
+/// ]});
+///
+/// let total = Jsonata::new("$sum(orders.amount)").unwrap();
+/// assert_eq!(total.apply(&doc).unwrap(), Some(json!(230)));
+///
+/// let by_customer = Jsonata::new("orders{customer: $sum(amount)}").unwrap();
+/// assert_eq!(by_customer.apply(&doc).unwrap(), Some(json!({"acme": 150, "initech": 80})));
+///
+/// // A path to nothing is undefined
+/// assert_eq!(Jsonata::new("invoices.amount").unwrap().apply(&doc).unwrap(), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Jsonata {
+    source: String,
+    expr: Expr,
+}
+
+impl Jsonata {
+    /// Parses a JSONata expression.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the expression cannot be
+    ///   parsed, calls an unknown function or with the wrong number of
+    ///   arguments, or uses an unsupported part of JSONata.
+    pub fn new(expression: &str) -> Result<Self, JqrError> {
+        let invalid = |message: String| {
+            JqrError::InvalidQuery(format!(
+                "Invalid JSONata expression '{}': {}",
+                expression, message
+            ))
+        };
+        let mut parser = Parser {
+            tokens: tokenize(expression).map_err(invalid)?,
+            position: 0,
+        };
+        let expr = parser.expression().map_err(invalid)?;
+        if parser.position < parser.tokens.len() {
+            return Err(invalid(parser.unexpected()));
+        }
+        Ok(Jsonata {
+            source: expression.to_string(),
+            expr,
+        })
+    }
+
+    /// Returns the expression text this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluates the expression, returning `None` when its result is
+    /// undefined, as for a path to nothing. A result of several values is
+    /// returned as an array.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::UnsupportedValue`] for operands or function
+    ///   arguments of the wrong type, such as `"a" + 1`.
+    pub fn apply(&self, json: &Value) -> Result<Option<Value>, JqrError> {
+        eval(&self.expr, json, json).map(collapse)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Field(String),
+    Wildcard,
+    Descendants,
+    Context,
+    Root,
+    Path(Vec<Step>),
+    Array(Vec<Expr>),
+    /// An object constructor, grouping the results of the path before it if
+    /// there is one.
+    Object(Option<Box<Expr>>, Vec<(Expr, Expr)>),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Condition(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    Call(&'static str, Vec<Expr>),
+}
+
+/// A step of a path with the predicates that filter its results.
+#[derive(Debug, Clone)]
+struct Step {
+    expr: Expr,
+    predicates: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    In,
+}
+
+/// The functions, with their least and greatest number of arguments.
+const FUNCTIONS: [(&str, usize, usize); 27] = [
+    ("sum", 1, 1),
+    ("count", 1, 1),
+    ("max", 1, 1),
+    ("min", 1, 1),
+    ("average", 1, 1),
+    ("string", 1, 1),
+    ("number", 1, 1),
+    ("length", 1, 1),
+    ("uppercase", 1, 1),
+    ("lowercase", 1, 1),
+    ("trim", 1, 1),
+    ("substring", 2, 3),
+    ("contains", 2, 2),
+    ("split", 2, 2),
+    ("join", 1, 2),
+    ("keys", 1, 1),
+    ("exists", 1, 1),
+    ("not", 1, 1),
+    ("boolean", 1, 1),
+    ("round", 1, 2),
+    ("abs", 1, 1),
+    ("floor", 1, 1),
+    ("ceil", 1, 1),
+    ("append", 2, 2),
+    ("sort", 1, 1),
+    ("reverse", 1, 1),
+    ("distinct", 1, 1),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    /// `$` (an empty name), `$$` (the name `$`) or `$name`.
+    Variable(String),
+    Str(String),
+    Num(Number),
+    Op(&'static str),
+}
+
+/// Punctuation and operators, longest first.
+const OPERATORS: [&str; 24] = [
+    "**", "!=", "<=", ">=", ":=", ".", "[", "]", "{", "}", "(", ")", ",", ":", "?", ";", "=", "<",
+    ">", "+", "-", "*", "/", "%",
+];
+
+/// Splits an expression into tokens, each with the column it starts at.
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    loop {
+        rest = rest.trim_start();
+        if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+            continue;
+        }
+        let Some(c) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        let column = expression[..expression.len() - rest.len()].chars().count() + 1;
+        let name_length = |text: &str| {
+            text.find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(text.len())
+        };
+
+        let (token, length) = if c == '"' || c == '\'' {
+            let length = quoted_length(rest, c)
+                .ok_or_else(|| format!("unterminated string at column {}", column))?;
+            let text = unquote(&rest[1..length - 1], c)
+                .ok_or_else(|| format!("invalid string at column {}", column))?;
+            (Token::Str(text), length)
+        } else if c == '`' {
+            let end = rest[1..]
+                .find('`')
+                .ok_or_else(|| format!("unterminated name at column {}", column))?;
+            (Token::Name(rest[1..1 + end].to_string()), end + 2)
+        } else if c.is_ascii_digit() {
+            let digits = |text: &str| {
+                text.find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(text.len())
+            };
+            let mut length = digits(rest);
+            if rest[length..].starts_with('.') && digits(&rest[length + 1..]) > 0 {
+                length += 1 + digits(&rest[length + 1..]);
+            }
+            if rest[length..].starts_with(['e', 'E']) {
+                let sign = usize::from(rest[length + 1..].starts_with(['+', '-']));
+                length += 1 + sign + digits(&rest[length + 1 + sign..]);
+            }
+            let number = rest[..length]
+                .parse()
+                .map_err(|_| format!("invalid number at column {}", column))?;
+            (Token::Num(number), length)
+        } else if c == '$' {
+            if rest.starts_with("$$") {
+                (Token::Variable("$".to_string()), 2)
+            } else {
+                let length = name_length(&rest[1..]);
+                (Token::Variable(rest[1..1 + length].to_string()), 1 + length)
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let length = name_length(rest);
+            (Token::Name(rest[..length].to_string()), length)
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            (Token::Op(op), op.len())
+        } else if c == '&' {
+            (Token::Op("&"), 1)
+        } else {
+            return Err(format!("unexpected '{}' at column {}", c, column));
+        };
+        tokens.push((token, column));
+        rest = &rest[length..];
+    }
+}
+
+/// Returns the length of the string literal `text` starts with, quotes
+/// included.
+fn quoted_length(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Decodes the escapes of a string literal's content, which are those of
+/// JSON plus `\'`.
+fn unquote(content: &str, quote: char) -> Option<String> {
+    let mut json = String::with_capacity(content.len() + 2);
+    json.push('"');
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\'' => json.push('\''),
+                escaped => {
+                    json.push('\\');
+                    json.push(escaped);
+                }
+            },
+            '"' if quote == '\'' => json.push_str("\\\""),
+            _ => json.push(c),
+        }
+    }
+    json.push('"');
+    serde_json::from_str(&json).ok()
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Some(Token::Op(next)) if *next == op)
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Name(next)) if next == word)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.is_op(op);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Describes the token at the current position as unexpected.
+    fn unexpected(&self) -> String {
+        match self.tokens.get(self.position) {
+            None => "unexpected end of expression".to_string(),
+            Some((Token::Op(":="), column)) => {
+                format!("variable binding is not supported (at column {})", column)
+            }
+            Some((_, column)) => format!("unexpected token at column {}", column),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let condition = self.or()?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.expression()?;
+        let otherwise = if self.eat(":") {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        Ok(Expr::Condition(
+            Box::new(condition),
+            Box::new(then),
+            otherwise,
+        ))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.is_keyword("or") {
+            self.position += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.comparison()?;
+        while self.is_keyword("and") {
+            self.position += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.additive()?;
+        let operator = match self.peek() {
+            Some(Token::Op("=")) => Operator::Equal,
+            Some(Token::Op("!=")) => Operator::NotEqual,
+            Some(Token::Op("<")) => Operator::Less,
+            Some(Token::Op("<=")) => Operator::LessEqual,
+            Some(Token::Op(">")) => Operator::Greater,
+            Some(Token::Op(">=")) => Operator::GreaterEqual,
+            Some(Token::Name(name)) if name == "in" => Operator::In,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.additive()?;
+        Ok(Expr::Binary(operator, Box::new(left), Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut expr = self.multiplicative()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Op("+")) => Operator::Add,
+                Some(Token::Op("-")) => Operator::Subtract,
+                Some(Token::Op("&")) => Operator::Concat,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            let right = self.multiplicative()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Op("*")) => Operator::Multiply,
+                Some(Token::Op("/")) => Operator::Divide,
+                Some(Token::Op("%")) => Operator::Remainder,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            let right = self.unary()?;
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Negate(Box::new(self.unary()?)))
+        } else {
+            self.path()
+        }
+    }
+
+    /// Parses steps separated by `.`, and an object constructor after them
+    /// that groups their results.
+    fn path(&mut self) -> Result<Expr, String> {
+        let mut steps = vec![self.step()?];
+        while self.eat(".") {
+            steps.push(self.step()?);
+        }
+        let expr = match steps.as_slice() {
+            [step] if step.predicates.is_empty() => steps.pop().unwrap().expr,
+            _ => Expr::Path(steps),
+        };
+        if self.eat("{") {
+            let pairs = self.object_pairs()?;
+            return Ok(Expr::Object(Some(Box::new(expr)), pairs));
+        }
+        Ok(expr)
+    }
+
+    fn step(&mut self) -> Result<Step, String> {
+        let expr = self.primary()?;
+        let mut predicates = Vec::new();
+        while self.eat("[") {
+            // `[]` only keeps a singleton result an array, which the
+            // output does anyway
+            if self.eat("]") {
+                continue;
+            }
+            predicates.push(self.expression()?);
+            self.expect("]")?;
+        }
+        Ok(Step { expr, predicates })
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected());
+        };
+        self.position += 1;
+        match token {
+            Token::Name(name) => Ok(match name.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                "function" => return Err("lambdas are not supported".to_string()),
+                _ => Expr::Field(name),
+            }),
+            Token::Str(text) => Ok(Expr::Literal(Value::String(text))),
+            Token::Num(number) => Ok(Expr::Literal(Value::Number(number))),
+            Token::Variable(name) if self.is_op("(") => self.call(&name),
+            Token::Variable(name) if name.is_empty() => Ok(Expr::Context),
+            Token::Variable(name) if name == "$" => Ok(Expr::Root),
+            Token::Variable(name) => Err(format!("variable ${} is not defined", name)),
+            Token::Op("*") => Ok(Expr::Wildcard),
+            Token::Op("**") => Ok(Expr::Descendants),
+            Token::Op("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Op("[") => {
+                let mut items = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.expression()?);
+                        if !self.eat(",") {
+                            break;
+                        }
+                    }
+                    self.expect("]")?;
+                }
+                Ok(Expr::Array(items))
+            }
+            Token::Op("{") => Ok(Expr::Object(None, self.object_pairs()?)),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// Parses `key: value` pairs after the `{` of an object constructor.
+    fn object_pairs(&mut self) -> Result<Vec<(Expr, Expr)>, String> {
+        let mut pairs = Vec::new();
+        if self.eat("}") {
+            return Ok(pairs);
+        }
+        loop {
+            let key = self.expression()?;
+            self.expect(":")?;
+            pairs.push((key, self.expression()?));
+            if !self.eat(",") {
+                self.expect("}")?;
+                return Ok(pairs);
+            }
+        }
+    }
+
+    /// Parses the arguments of a call to `$name` after the name.
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expression()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")")?;
+        }
+        let Some(&(function, least, most)) = FUNCTIONS.iter().find(|f| f.0 == name) else {
+            return Err(format!("unknown function ${}", name));
+        };
+        if args.len() < least || args.len() > most {
+            return Err(format!(
+                "${}() takes {} argument{}, not {}",
+                name,
+                if least == most {
+                    least.to_string()
+                } else {
+                    format!("{} to {}", least, most)
+                },
+                if most == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        Ok(Expr::Call(function, args))
+    }
+}
+
+/// Turns a sequence into a value: nothing is undefined, and several values
+/// are an array.
+fn collapse(mut sequence: Vec<Value>) -> Option<Value> {
+    match sequence.len() {
+        0 => None,
+        1 => sequence.pop(),
+        _ => Some(Value::Array(sequence)),
+    }
+}
+
+fn error(message: String) -> JqrError {
+    JqrError::UnsupportedValue(message)
+}
+
+/// Evaluates `expr` with `context` as `$` and `root` as `$$`, returning the
+/// sequence of its results.
+fn eval(expr: &Expr, context: &Value, root: &Value) -> Result<Vec<Value>, JqrError> {
+    match expr {
+        Expr::Literal(value) => Ok(vec![value.clone()]),
+        Expr::Field(name) => {
+            let mut found = Vec::new();
+            lookup(context, name, &mut found);
+            Ok(found)
+        }
+        Expr::Wildcard => {
+            let mut found = Vec::new();
+            wildcard(context, &mut found);
+            Ok(found)
+        }
+        Expr::Descendants => {
+            let mut found = Vec::new();
+            descendants(context, &mut found);
+            Ok(found)
+        }
+        Expr::Context => Ok(vec![context.clone()]),
+        Expr::Root => Ok(vec![root.clone()]),
+        Expr::Path(steps) => {
+            let mut sequence = vec![context.clone()];
+            for step in steps {
+                let mut next = Vec::new();
+                for item in &sequence {
+                    let results = eval(&step.expr, item, root)?;
+                    next.extend(filter(results, &step.predicates, root)?);
+                }
+                sequence = next;
+            }
+            Ok(sequence)
+        }
+        Expr::Array(items) => {
+            let mut array = Vec::new();
+            for item in items {
+                let mut results = eval(item, context, root)?;
+                match results.len() {
+                    1 => array.push(results.pop().unwrap()),
+                    _ => array.extend(results),
+                }
+            }
+            Ok(vec![Value::Array(array)])
+        }
+        Expr::Object(source, pairs) => {
+            let items = match source {
+                Some(source) => eval(source, context, root)?,
+                None => vec![context.clone()],
+            };
+            // Items with the same key are grouped, and the value is
+            // evaluated once per group
+            let mut groups: Vec<(String, usize, Vec<Value>)> = Vec::new();
+            for item in items {
+                for (index, (key, _)) in pairs.iter().enumerate() {
+                    let key = match collapse(eval(key, &item, root)?) {
+                        Some(Value::String(key)) => key,
+                        None => continue,
+                        Some(other) => {
+                            return Err(error(format!(
+                                "Object keys must be strings, not {}",
+                                json_type(&other)
+                            )))
+                        }
+                    };
+                    match groups.iter_mut().find(|group| group.0 == key) {
+                        Some(group) => group.2.push(item.clone()),
+                        None => groups.push((key, index, vec![item.clone()])),
+                    }
+                }
+            }
+            let mut object = Map::new();
+            for (key, index, items) in groups {
+                let context = collapse(items).unwrap_or_default();
+                if let Some(value) = collapse(eval(&pairs[index].1, &context, root)?) {
+                    object.insert(key, value);
+                }
+            }
+            Ok(vec![Value::Object(object)])
+        }
+        Expr::Negate(expr) => match collapse(eval(expr, context, root)?) {
+            None => Ok(Vec::new()),
+            Some(value) => match value.as_f64() {
+                Some(number) => Ok(vec![to_number(-number)?]),
+                None => Err(error(format!(
+                    "Cannot negate {}, which is not a number",
+                    json_type(&value)
+                ))),
+            },
+        },
+        Expr::Binary(operator, left, right) => {
+            let left = collapse(eval(left, context, root)?);
+            let right = collapse(eval(right, context, root)?);
+            binary(*operator, left, right)
+        }
+        Expr::And(left, right) => {
+            let result = truthy_sequence(&eval(left, context, root)?)
+                && truthy_sequence(&eval(right, context, root)?);
+            Ok(vec![Value::Bool(result)])
+        }
+        Expr::Or(left, right) => {
+            let result = truthy_sequence(&eval(left, context, root)?)
+                || truthy_sequence(&eval(right, context, root)?);
+            Ok(vec![Value::Bool(result)])
+        }
+        Expr::Condition(condition, then, otherwise) => {
+            if truthy_sequence(&eval(condition, context, root)?) {
+                eval(then, context, root)
+            } else if let Some(otherwise) = otherwise {
+                eval(otherwise, context, root)
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        Expr::Call(name, args) => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, context, root).map(collapse))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(name, args).map(|result| result.into_iter().collect())
+        }
+    }
+}
+
+/// Collects the `name` field of `value`, mapping over arrays and
+/// flattening array fields into the sequence.
+fn lookup(value: &Value, name: &str, found: &mut Vec<Value>) {
+    match value {
+        Value::Object(map) => match map.get(name) {
+            Some(Value::Array(items)) => found.extend(items.iter().cloned()),
+            Some(field) => found.push(field.clone()),
+            None => {}
+        },
+        Value::Array(items) => items.iter().for_each(|item| lookup(item, name, found)),
+        _ => {}
+    }
+}
+
+fn wildcard(value: &Value, found: &mut Vec<Value>) {
+    match value {
+        Value::Object(map) => {
+            for field in map.values() {
+                match field {
+                    Value::Array(items) => found.extend(items.iter().cloned()),
+                    field => found.push(field.clone()),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| wildcard(item, found)),
+        _ => {}
+    }
+}
+
+fn descendants(value: &Value, found: &mut Vec<Value>) {
+    let children: Vec<&Value> = match value {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(items) => items.iter().collect(),
+        _ => return,
+    };
+    for child in children {
+        if !child.is_array() {
+            found.push(child.clone());
+        }
+        descendants(child, found);
+    }
+}
+
+/// Keeps the items that every predicate selects: a number selects the item
+/// at that index, counting from the end when negative, and anything else
+/// selects the items for which it is true.
+fn filter(
+    mut items: Vec<Value>,
+    predicates: &[Expr],
+    root: &Value,
+) -> Result<Vec<Value>, JqrError> {
+    for predicate in predicates {
+        // A single array is a sequence of its items
+        if let [Value::Array(array)] = items.as_slice() {
+            items = array.clone();
+        }
+        let count = items.len() as f64;
+        let mut kept = Vec::new();
+        for (index, item) in items.into_iter().enumerate() {
+            let result = eval(predicate, &item, root)?;
+            let selected = match result.as_slice() {
+                [number @ Value::Number(_)] => {
+                    let wanted = number.as_f64().unwrap_or_default().floor();
+                    let wanted = if wanted < 0.0 { wanted + count } else { wanted };
+                    wanted == index as f64
+                }
+                _ => truthy_sequence(&result),
+            };
+            if selected {
+                kept.push(item);
+            }
+        }
+        items = kept;
+    }
+    Ok(items)
+}
+
+/// JSONata's boolean conversion: `false`, `null`, `0`, `""`, `{}` and
+/// arrays without a true item are false.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(_) => value.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(items) => items.iter().any(truthy),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn truthy_sequence(sequence: &[Value]) -> bool {
+    sequence.iter().any(truthy)
+}
+
+/// Converts a value to a string as `$string` does.
+fn stringify(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Orders two numbers or two strings.
+fn order(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(_), Value::Number(_)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn binary(
+    operator: Operator,
+    left: Option<Value>,
+    right: Option<Value>,
+) -> Result<Vec<Value>, JqrError> {
+    let symbol = match operator {
+        Operator::Add => "+",
+        Operator::Subtract => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Remainder => "%",
+        Operator::Concat => "&",
+        Operator::Equal => "=",
+        Operator::NotEqual => "!=",
+        Operator::Less => "<",
+        Operator::LessEqual => "<=",
+        Operator::Greater => ">",
+        Operator::GreaterEqual => ">=",
+        Operator::In => "in",
+    };
+    let result = match operator {
+        Operator::Concat => Value::String(format!(
+            "{}{}",
+            left.as_ref().map(stringify).unwrap_or_default(),
+            right.as_ref().map(stringify).unwrap_or_default()
+        )),
+        Operator::Equal | Operator::NotEqual => {
+            let same = match (&left, &right) {
+                (Some(left), Some(right)) => json_equal(left, right),
+                _ => false,
+            };
+            // Comparing with undefined is false either way
+            Value::Bool(left.is_some() && right.is_some() && same == (operator == Operator::Equal))
+        }
+        Operator::In => {
+            let found = match (&left, &right) {
+                (Some(left), Some(Value::Array(items))) => {
+                    items.iter().any(|item| json_equal(left, item))
+                }
+                (Some(left), Some(right)) => json_equal(left, right),
+                _ => false,
+            };
+            Value::Bool(found)
+        }
+        _ => {
+            let (Some(left), Some(right)) = (left, right) else {
+                return Ok(Vec::new());
+            };
+            if matches!(
+                operator,
+                Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual
+            ) {
+                let ordering = order(&left, &right).ok_or_else(|| {
+                    error(format!(
+                        "The operands of '{}' must both be numbers or both be strings, not {} and {}",
+                        symbol,
+                        json_type(&left),
+                        json_type(&right)
+                    ))
+                })?;
+                return Ok(vec![Value::Bool(match operator {
+                    Operator::Less => ordering.is_lt(),
+                    Operator::LessEqual => ordering.is_le(),
+                    Operator::Greater => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                })]);
+            }
+            let (Some(a), Some(b)) = (left.as_f64(), right.as_f64()) else {
+                return Err(error(format!(
+                    "The operands of '{}' must be numbers, not {} and {}",
+                    symbol,
+                    json_type(&left),
+                    json_type(&right)
+                )));
+            };
+            to_number(match operator {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide => a / b,
+                _ => a % b,
+            })?
+        }
+    };
+    Ok(vec![result])
+}
+
+fn call(name: &str, mut args: Vec<Option<Value>>) -> Result<Option<Value>, JqrError> {
+    let wrong = |expected: &str, got: &Value| {
+        error(format!(
+            "${}() expects {}, got {}",
+            name,
+            expected,
+            json_type(got)
+        ))
+    };
+    // Aggregates take an array, or a single value as an array of one
+    let items = |arg: &Option<Value>| match arg {
+        Some(Value::Array(items)) => items.clone(),
+        Some(value) => vec![value.clone()],
+        None => Vec::new(),
+    };
+    let numbers = |arg: &Option<Value>| {
+        items(arg)
+            .iter()
+            .map(|item| item.as_f64().ok_or_else(|| wrong("numbers", item)))
+            .collect::<Result<Vec<f64>, _>>()
+    };
+    let string = |value: &Value| match value {
+        Value::String(text) => Ok(text.clone()),
+        _ => Err(wrong("a string", value)),
+    };
+    let number = |value: &Value| value.as_f64().ok_or_else(|| wrong("a number", value));
+
+    match name {
+        "sum" => return to_number(numbers(&args[0])?.iter().sum()).map(Some),
+        "count" => return Ok(Some(Value::from(items(&args[0]).len()))),
+        "exists" => return Ok(Some(Value::Bool(args[0].is_some()))),
+        "max" | "min" | "average" => {
+            let numbers = numbers(&args[0])?;
+            let result = match name {
+                _ if numbers.is_empty() => return Ok(None),
+                "max" => numbers.iter().copied().fold(f64::MIN, f64::max),
+                "min" => numbers.iter().copied().fold(f64::MAX, f64::min),
+                _ => numbers.iter().sum::<f64>() / numbers.len() as f64,
+            };
+            return to_number(result).map(Some);
+        }
+        "append" => {
+            return Ok(match (&args[0], &args[1]) {
+                (None, other) | (other, None) => other.clone(),
+                _ => Some(Value::Array([items(&args[0]), items(&args[1])].concat())),
+            })
+        }
+        _ => {}
+    }
+
+    // The other functions are undefined for an undefined argument
+    let Some(arg) = args[0].take() else {
+        return Ok(None);
+    };
+    let result = match name {
+        "string" => Value::String(stringify(&arg)),
+        "number" => match &arg {
+            Value::Number(_) => arg,
+            Value::Bool(b) => Value::from(u8::from(*b)),
+            Value::String(text) => text
+                .trim()
+                .parse::<Number>()
+                .map(Value::Number)
+                .map_err(|_| error(format!("Cannot convert '{}' to a number", text)))?,
+            _ => return Err(wrong("a number, string or boolean", &arg)),
+        },
+        "length" => Value::from(string(&arg)?.chars().count()),
+        "uppercase" => Value::String(string(&arg)?.to_uppercase()),
+        "lowercase" => Value::String(string(&arg)?.to_lowercase()),
+        "trim" => Value::String(
+            string(&arg)?
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        "substring" => {
+            let chars: Vec<char> = string(&arg)?.chars().collect();
+            let start = args[1]
+                .as_ref()
+                .map(number)
+                .transpose()?
+                .unwrap_or_default() as i64;
+            let start = if start < 0 {
+                (chars.len() as i64 + start).max(0)
+            } else {
+                start.min(chars.len() as i64)
+            } as usize;
+            let length = match &args.get(2).cloned().flatten() {
+                Some(length) => number(length)?.max(0.0) as usize,
+                None => chars.len(),
+            };
+            Value::String(chars[start..].iter().take(length).collect())
+        }
+        "contains" => {
+            let pattern = args[1]
+                .as_ref()
+                .map(string)
+                .transpose()?
+                .unwrap_or_default();
+            Value::Bool(string(&arg)?.contains(&pattern))
+        }
+        "split" => {
+            let separator = args[1]
+                .as_ref()
+                .map(string)
+                .transpose()?
+                .unwrap_or_default();
+            Value::Array(
+                string(&arg)?
+                    .split(separator.as_str())
+                    .filter(|part| !separator.is_empty() || !part.is_empty())
+                    .map(|part| Value::String(part.to_string()))
+                    .collect(),
+            )
+        }
+        "join" => {
+            let separator = match args.get(1).cloned().flatten() {
+                Some(separator) => string(&separator)?,
+                None => String::new(),
+            };
+            let parts = items(&Some(arg))
+                .iter()
+                .map(string)
+                .collect::<Result<Vec<_>, _>>()?;
+            Value::String(parts.join(&separator))
+        }
+        "keys" => {
+            let mut keys: Vec<String> = Vec::new();
+            for item in items(&Some(arg)) {
+                if let Value::Object(map) = item {
+                    for key in map.keys() {
+                        if !keys.contains(key) {
+                            keys.push(key.clone());
+                        }
+                    }
+                }
+            }
+            return Ok(collapse(keys.into_iter().map(Value::String).collect()));
+        }
+        "not" => Value::Bool(!truthy(&arg)),
+        "boolean" => Value::Bool(truthy(&arg)),
+        "round" => {
+            let precision = match args.get(1).cloned().flatten() {
+                Some(precision) => number(&precision)?,
+                None => 0.0,
+            };
+            let scale = 10f64.powi(precision as i32);
+            let scaled = number(&arg)? * scale;
+            // Halves round to even, as in JSONata
+            let mut rounded = scaled.round();
+            if (scaled - scaled.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+                rounded -= scaled.signum();
+            }
+            to_number(rounded / scale)?
+        }
+        "abs" => to_number(number(&arg)?.abs())?,
+        "floor" => to_number(number(&arg)?.floor())?,
+        "ceil" => to_number(number(&arg)?.ceil())?,
+        "sort" => {
+            let mut sorted = items(&Some(arg));
+            let comparable =
+                sorted.iter().all(Value::is_number) || sorted.iter().all(Value::is_string);
+            if !comparable {
+                return Err(error(
+                    "$sort() expects all numbers or all strings".to_string(),
+                ));
+            }
+            sorted.sort_by(|a, b| order(a, b).unwrap_or(Ordering::Equal));
+            Value::Array(sorted)
+        }
+        "reverse" => Value::Array(items(&Some(arg)).into_iter().rev().collect()),
+        "distinct" => {
+            let mut distinct: Vec<Value> = Vec::new();
+            for item in items(&Some(arg)) {
+                if !distinct.iter().any(|kept| json_equal(kept, &item)) {
+                    distinct.push(item);
+                }
+            }
+            Value::Array(distinct)
+        }
+        _ => unreachable!("functions are checked when parsing"),
+    };
+    Ok(Some(result))
+}
@@ -0,0 +1,320 @@
+use serde_json::Value;
+
+use crate::{format_json, jsonc_to_json, FormatOptions, JqrError};
+
+/// A value of a JSONC document together with the comments around it.
+struct Entry {
+    /// Comments on the lines before the entry.
+    leading: Vec<String>,
+    /// The key, for an entry of an object.
+    key: Option<String>,
+    node: Node,
+    /// Comments after the entry on the line where it ends.
+    trailing: Vec<String>,
+}
+
+enum Node {
+    Scalar(Value),
+    /// Arrays and objects, with the comments after their last entry.
+    Array(Vec<Entry>, Vec<String>),
+    Object(Vec<Entry>, Vec<String>),
+}
+
+/// A comment, and whether a line break separates it from what came before
+/// and from what comes after.
+struct Comment {
+    text: String,
+    own_line: bool,
+    ends_line: bool,
+}
+
+/// Reformats a JSONC document (JSON with `//` and `/* */` comments and
+/// trailing commas) according to `options`, keeping its comments.
+///
+/// A comment before a key or array element stays attached to it, also when
+/// keys are sorted, and is written on its own lines above it. A comment that
+/// ends the line of an entry stays at the end of that line, and comments
+/// before a closing bracket stay at the end of the container.
+/// Trailing commas are dropped. Indentation, key order, `ascii_output` and
+/// `trailing_newline` apply; the layout is always one entry per line.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] if the input is not valid JSONC, as
+///   with [`jsonc_to_json`].
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{format_jsonc, FormatOptions};
+///
+/// let settings = "{\n  // Wrap long lines\n  \"editor.wordWrap\": \"on\", // or \"off\"\n  \"editor.tabSize\": 4,\n}";
+/// let options = FormatOptions::new().indent(4);
+///
+/// assert_eq!(
+///     format_jsonc(settings, &options).unwrap(),
+///     "{\n    \"editor.tabSize\": 4,\n    // Wrap long lines\n    \"editor.wordWrap\": \"on\" // or \"off\"\n}"
+/// );
+/// ```
+pub fn format_jsonc(content: &str, options: &FormatOptions) -> Result<String, JqrError> {
+    // Report syntax errors the way plain JSONC parsing does
+    jsonc_to_json(content)?;
+
+    let mut parser = Parser {
+        bytes: content.as_bytes(),
+        content,
+        position: 0,
+    };
+    let leading = parser.comments();
+    let mut root = parser.entry(leading)?;
+    // Comments after the document stay on its last line or below it
+    let mut after = parser.comments();
+    let same_line = after
+        .iter()
+        .take_while(|comment| !comment.own_line && comment.ends_line)
+        .count();
+    let dangling = after.split_off(same_line);
+    root.trailing = after.into_iter().map(|comment| comment.text).collect();
+
+    if options.sorts_keys() {
+        sort_entries(&mut root);
+    }
+
+    let indent = options.indent_string();
+    let mut output = String::new();
+    write_entry(&root, &indent, 0, false, options, &mut output)?;
+    for comment in dangling {
+        output.push('\n');
+        output.push_str(&comment.text);
+    }
+    if options.trailing_newline {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// A parser for input already known to be valid JSONC.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    content: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// Skips whitespace, returning the comments in it.
+    fn comments(&mut self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        let mut own_line = false;
+        while let Some(&byte) = self.bytes.get(self.position) {
+            let end = match (byte, self.bytes.get(self.position + 1)) {
+                (b'/', Some(b'/')) => self.bytes[self.position..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(self.bytes.len(), |length| self.position + length),
+                (b'/', Some(b'*')) => self.bytes[self.position + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(self.bytes.len(), |length| self.position + length + 4),
+                _ if byte.is_ascii_whitespace() => {
+                    own_line |= byte == b'\n';
+                    self.position += 1;
+                    continue;
+                }
+                _ => break,
+            };
+            let text = self.content[self.position..end].trim_end().to_string();
+            let ends_line = self.bytes[end..]
+                .iter()
+                .find(|b| !matches!(b, b' ' | b'\t'))
+                .is_none_or(|&b| b == b'\n' || b == b'\r');
+            comments.push(Comment {
+                text,
+                own_line,
+                ends_line,
+            });
+            self.position = end;
+        }
+        comments
+    }
+
+    fn peek(&self) -> u8 {
+        self.bytes.get(self.position).copied().unwrap_or_default()
+    }
+
+    /// Parses the value at the current position, with `before` as its
+    /// leading comments.
+    fn entry(&mut self, before: Vec<Comment>) -> Result<Entry, JqrError> {
+        let node = match self.peek() {
+            b'[' => {
+                let (entries, dangling) = self.container(b']', false)?;
+                Node::Array(entries, dangling)
+            }
+            b'{' => {
+                let (entries, dangling) = self.container(b'}', true)?;
+                Node::Object(entries, dangling)
+            }
+            _ => Node::Scalar(self.scalar()?),
+        };
+        Ok(Entry {
+            leading: before.into_iter().map(|comment| comment.text).collect(),
+            key: None,
+            node,
+            trailing: Vec::new(),
+        })
+    }
+
+    /// Parses an array or object, returning its entries and the comments
+    /// after the last one.
+    fn container(
+        &mut self,
+        close: u8,
+        object: bool,
+    ) -> Result<(Vec<Entry>, Vec<String>), JqrError> {
+        self.position += 1;
+        let mut entries = Vec::new();
+        let mut before = self.comments();
+        loop {
+            if self.peek() == close {
+                self.position += 1;
+                let dangling = before.into_iter().map(|comment| comment.text).collect();
+                return Ok((entries, dangling));
+            }
+
+            let key = if object {
+                let key = match self.scalar()? {
+                    Value::String(key) => key,
+                    _ => return Err(malformed()),
+                };
+                before.extend(self.comments());
+                self.position += 1; // the colon
+                before.extend(self.comments());
+                Some(key)
+            } else {
+                None
+            };
+
+            let mut entry = self.entry(before)?;
+            entry.key = key;
+            let mut after = self.comments();
+            if self.peek() == b',' {
+                self.position += 1;
+                after.extend(self.comments());
+            }
+
+            // Comments that end this entry's line stay there; the others
+            // belong to the next entry
+            let same_line = after
+                .iter()
+                .take_while(|comment| !comment.own_line && comment.ends_line)
+                .count();
+            before = after.split_off(same_line);
+            entry.trailing = after.into_iter().map(|comment| comment.text).collect();
+            entries.push(entry);
+        }
+    }
+
+    /// Parses a string, number or literal.
+    fn scalar(&mut self) -> Result<Value, JqrError> {
+        let start = self.position;
+        if self.peek() == b'"' {
+            self.position += 1;
+            while let Some(&byte) = self.bytes.get(self.position) {
+                self.position += 1;
+                match byte {
+                    b'\\' => self.position += 1,
+                    b'"' => break,
+                    _ => {}
+                }
+            }
+        } else {
+            while !matches!(self.peek(), 0 | b',' | b']' | b'}' | b'/')
+                && !self.peek().is_ascii_whitespace()
+            {
+                self.position += 1;
+            }
+        }
+        serde_json::from_str(&self.content[start..self.position]).map_err(|_| malformed())
+    }
+}
+
+fn malformed() -> JqrError {
+    JqrError::Serialization("Malformed JSONC document".to_string())
+}
+
+/// Sorts the keys of every object, keeping each key's comments with it.
+fn sort_entries(entry: &mut Entry) {
+    match &mut entry.node {
+        Node::Object(entries, _) => {
+            entries.sort_by(|a, b| a.key.cmp(&b.key));
+            entries.iter_mut().for_each(sort_entries);
+        }
+        Node::Array(entries, _) => entries.iter_mut().for_each(sort_entries),
+        Node::Scalar(_) => {}
+    }
+}
+
+/// Writes `entry` at nesting level `depth`, followed by a comma if `comma`.
+fn write_entry(
+    entry: &Entry,
+    indent: &str,
+    depth: usize,
+    comma: bool,
+    options: &FormatOptions,
+    output: &mut String,
+) -> Result<(), JqrError> {
+    let prefix = indent.repeat(depth);
+    for comment in &entry.leading {
+        output.push_str(comment);
+        output.push('\n');
+        output.push_str(&prefix);
+    }
+
+    let compact = options.clone().compact(true).trailing_newline(false);
+    if let Some(key) = &entry.key {
+        output.push_str(&format_json(&Value::String(key.clone()), &compact)?);
+        output.push_str(": ");
+    }
+
+    match &entry.node {
+        Node::Scalar(value) => output.push_str(&format_json(value, &compact)?),
+        Node::Array(entries, dangling) | Node::Object(entries, dangling) => {
+            let (open, close) = match entry.node {
+                Node::Array(..) => ('[', ']'),
+                _ => ('{', '}'),
+            };
+            output.push(open);
+            if !entries.is_empty() || !dangling.is_empty() {
+                let inner = indent.repeat(depth + 1);
+                for (i, child) in entries.iter().enumerate() {
+                    output.push('\n');
+                    output.push_str(&inner);
+                    write_entry(
+                        child,
+                        indent,
+                        depth + 1,
+                        i + 1 < entries.len(),
+                        options,
+                        output,
+                    )?;
+                }
+                for comment in dangling {
+                    output.push('\n');
+                    output.push_str(&inner);
+                    output.push_str(comment);
+                }
+                output.push('\n');
+                output.push_str(&prefix);
+            }
+            output.push(close);
+        }
+    }
+
+    if comma {
+        output.push(',');
+    }
+    for comment in &entry.trailing {
+        output.push(' ');
+        output.push_str(comment);
+    }
+    Ok(())
+}
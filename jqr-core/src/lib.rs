@@ -0,0 +1,587 @@
+use jsonpath_rust::JsonPathValue;
+use serde_json::Value;
+
+mod aggregate;
+mod binary;
+mod compare;
+mod convert;
+mod detect;
+mod display;
+mod edit;
+mod error;
+mod flatten;
+mod format;
+mod jmespath;
+mod jq;
+#[cfg(feature = "jsonata")]
+mod jsonata;
+mod jsonc;
+mod parse;
+mod pivot;
+mod projection;
+mod query;
+mod rfc9535;
+mod sort;
+mod tabular;
+mod transform;
+mod xml;
+mod yaml;
+
+pub use aggregate::*;
+pub use binary::*;
+pub use compare::*;
+pub use convert::*;
+pub use detect::*;
+pub use display::*;
+pub use edit::*;
+pub use error::JqrError;
+pub use flatten::*;
+pub use format::*;
+pub use jmespath::*;
+pub use jq::*;
+#[cfg(feature = "jsonata")]
+pub use jsonata::*;
+pub use jsonc::*;
+pub use parse::*;
+pub use pivot::*;
+pub use projection::*;
+pub use query::*;
+pub use sort::*;
+pub use tabular::*;
+pub use transform::*;
+pub use xml::*;
+pub use yaml::*;
+
+/// Pretty prints a JSON string with optional JSONPath querying.
+/// 
+/// # Arguments
+/// 
+/// * `content` - A string slice containing the JSON content to format.
+/// * `query` - An optional JSONPath query string to filter the JSON data.
+/// 
+/// # Returns
+/// 
+/// * `Ok(String)` - The formatted JSON string.
+/// * `Err(JqrError)` - An error if parsing or formatting fails.
+///
+/// # Dependencies
+/// 
+/// This function relies on `serde_json` for JSON parsing and serialization.
+/// It also assumes the existence of an `extract_jsonpath()` function that
+/// applies a JSONPath query to filter the JSON data. A query that matches
+/// nothing is formatted as `null`.
+/// 
+/// # Errors
+/// 
+/// * Returns an error if the input JSON is invalid.
+/// * Returns an error if the query is not valid JSONPath.
+/// * Returns an error if serialization to pretty-printed JSON fails.
+/// 
+/// # Example
+/// 
+/// ```
+/// use jqr_core::pretty_print_json; // Ensure this is correctly importing from your crate
+/// let json_str = r#"{"name": "Alice", "age": 25}"#;
+/// let formatted = pretty_print_json(json_str, None);
+/// println!("{}", formatted.unwrap());
+/// ```
+pub fn pretty_print_json(content: &str, query: Option<&String>) -> Result<String, JqrError> {
+    pretty_print_json_with(content, query, &FormatOptions::default())
+}
+
+/// Like [`pretty_print_json`], but formats the result according to `options`.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::{pretty_print_json_with, FormatOptions};
+///
+/// let options = FormatOptions::new().compact(true);
+/// let formatted = pretty_print_json_with(r#"{"a": [1, 2]}"#, None, &options).unwrap();
+///
+/// assert_eq!(formatted, r#"{"a":[1,2]}"#);
+/// ```
+pub fn pretty_print_json_with(
+    content: &str,
+    query: Option<&String>,
+    options: &FormatOptions,
+) -> Result<String, JqrError> {
+    // Attempt to parse the input string into a JSON `Value`
+    let json = parse_json(content)?;
+
+    // If a query is provided, extract the relevant JSON data (null if nothing matches)
+    let result = if let Some(q) = query {
+        extract_jsonpath(&json, q)?.unwrap_or(Value::Null)
+    } else {
+        json
+    };
+
+    format_json(&result, options)
+}
+
+/// Parses a JSON string into a `serde_json::Value`.
+///
+/// This is the parsing step shared by `pretty_print_json` and the CLI, which
+/// needs the parsed value to run post-query stages before serializing.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidJson`] if the input is not valid JSON.
+/// * If the input is not JSON but parses as a YAML mapping or sequence, the
+///   error's `looks_like_yaml` flag is set, so a caller can point out that
+///   the input is YAML, since giving YAML where JSON is expected is a common
+///   mistake.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::{parse_json, JqrError};
+/// use serde_json::json;
+///
+/// let value = parse_json(r#"{"name": "Alice"}"#).unwrap();
+/// assert_eq!(value, json!({"name": "Alice"}));
+///
+/// assert!(parse_json("{name: Alice}").is_err());
+///
+/// let err = parse_json("name: Alice\nage: 25").unwrap_err();
+/// assert!(matches!(err, JqrError::InvalidJson { looks_like_yaml: true, .. }));
+/// ```
+pub fn parse_json(content: &str) -> Result<Value, JqrError> {
+    serde_json::from_str::<Value>(content).map_err(|e| invalid_json(content, e))
+}
+
+/// Parses a YAML document into a `serde_json::Value`, so it can be queried
+/// like JSON.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidYaml`] if the input is not valid YAML, or has
+///   a mapping key that is not a string, number or boolean.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::{extract_jsonpath, parse_yaml};
+/// use serde_json::json;
+///
+/// let yaml = "spec:\n  containers:\n    - image: nginx:1.27\n    - image: redis:7\n";
+/// let value = parse_yaml(yaml).unwrap();
+///
+/// assert_eq!(
+///     extract_jsonpath(&value, "$.spec.containers[*].image").unwrap(),
+///     Some(json!(["nginx:1.27", "redis:7"]))
+/// );
+/// assert!(parse_yaml("spec: [nginx").is_err());
+/// ```
+pub fn parse_yaml(content: &str) -> Result<Value, JqrError> {
+    serde_yaml::from_str::<Value>(content).map_err(|e| JqrError::InvalidYaml(e.to_string()))
+}
+
+/// Parses every document of a YAML stream, separated by `---` lines as in
+/// Kubernetes manifests, in order.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidYaml`] if any document is not valid YAML.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::parse_yaml_documents;
+/// use serde_json::json;
+///
+/// let yaml = "kind: Service\n---\nkind: Deployment\n";
+///
+/// assert_eq!(
+///     parse_yaml_documents(yaml).unwrap(),
+///     vec![json!({"kind": "Service"}), json!({"kind": "Deployment"})]
+/// );
+/// ```
+pub fn parse_yaml_documents(content: &str) -> Result<Vec<Value>, JqrError> {
+    parse_yaml_documents_with(content, &YamlOptions::default())
+}
+
+/// The nesting depth accepted by default before parsing is refused.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Parses a JSON string like [`parse_json`], refusing input nested deeper than `max_depth`.
+///
+/// The depth is checked by [`check_nesting_depth`] before parsing, so a
+/// pathologically nested document yields an error instead of overflowing the
+/// stack in the parser or in the recursive post-query stages. Within that
+/// bound, the parser's own fixed recursion limit is lifted, so `max_depth` may
+/// be raised above [`DEFAULT_MAX_DEPTH`].
+///
+/// # Errors
+///
+/// * Returns [`JqrError::NestingTooDeep`] if the input is nested deeper than `max_depth`.
+/// * Returns the same errors as [`parse_json`] for invalid JSON.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::{parse_json_with_max_depth, JqrError};
+///
+/// assert!(parse_json_with_max_depth("[[[1]]]", 3).is_ok());
+///
+/// let err = parse_json_with_max_depth("[[[1]]]", 2).unwrap_err();
+/// assert!(matches!(err, JqrError::NestingTooDeep { max_depth: 2, line: 1, column: 3 }));
+/// ```
+pub fn parse_json_with_max_depth(content: &str, max_depth: usize) -> Result<Value, JqrError> {
+    parse_json_with(content, &ParseOptions::new().max_depth(max_depth))
+}
+
+/// Checks that no array or object in `content` is nested deeper than `max_depth`.
+///
+/// The text is scanned iteratively without being parsed, so this is safe to run
+/// on arbitrarily deep input. Brackets inside strings are ignored; the input is
+/// otherwise not validated.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::NestingTooDeep`] with the line and column where the
+///   limit is exceeded.
+///
+/// # Example
+///
+/// ```
+/// use jqr_core::check_nesting_depth;
+///
+/// assert!(check_nesting_depth(r#"{"a": ["[[[", {"b": 1}]}"#, 3).is_ok());
+///
+/// let deep = "[".repeat(1_000_000);
+/// let err = check_nesting_depth(&deep, 128).unwrap_err();
+/// assert_eq!(err.to_string(), "Maximum nesting depth of 128 exceeded at line 1, column 129");
+/// ```
+pub fn check_nesting_depth(content: &str, max_depth: usize) -> Result<(), JqrError> {
+    let mut scanner = DepthScanner::new(max_depth);
+    content.bytes().try_for_each(|byte| scanner.feed(byte))
+}
+
+/// The state of [`check_nesting_depth`], fed one byte at a time so that
+/// streamed input can be checked as it is read.
+pub(crate) struct DepthScanner {
+    max_depth: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    line: usize,
+    column: usize,
+}
+
+impl DepthScanner {
+    pub(crate) fn new(max_depth: usize) -> Self {
+        DepthScanner {
+            max_depth,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            line: 1,
+            column: 0,
+        }
+    }
+
+    /// Scans the next byte of the input. Columns count characters, so UTF-8
+    /// continuation bytes do not advance them.
+    pub(crate) fn feed(&mut self, byte: u8) -> Result<(), JqrError> {
+        if byte == b'\n' {
+            self.line += 1;
+            self.column = 0;
+        } else if byte & 0xC0 != 0x80 {
+            self.column += 1;
+        }
+
+        if self.in_string {
+            match byte {
+                _ if self.escaped => self.escaped = false,
+                b'\\' => self.escaped = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match byte {
+            b'"' => self.in_string = true,
+            b'[' | b'{' => {
+                self.depth += 1;
+                if self.depth > self.max_depth {
+                    return Err(JqrError::NestingTooDeep {
+                        max_depth: self.max_depth,
+                        line: self.line,
+                        column: self.column,
+                    });
+                }
+            }
+            b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Converts a JSON parse error, noting whether the input looks like YAML.
+pub(crate) fn invalid_json(content: &str, error: serde_json::Error) -> JqrError {
+    JqrError::invalid_json(error, looks_like_yaml(content))
+}
+
+/// Returns `true` if content that failed to parse as JSON is a block-style YAML
+/// mapping or sequence.
+///
+/// Plain text is also valid YAML (as a scalar string), and YAML flow syntax
+/// overlaps with malformed JSON, so only block collections count as YAML here.
+pub(crate) fn looks_like_yaml(content: &str) -> bool {
+    let starts_like_json = content.trim_start().starts_with(['{', '[']);
+
+    !starts_like_json
+        && matches!(
+            serde_yaml::from_str::<Value>(content),
+            Ok(Value::Object(_)) | Ok(Value::Array(_))
+        )
+}
+
+
+/// Extracts data from a JSON structure based on a JSONPath query.
+///
+/// This function takes a `serde_json::Value` (parsed JSON) and a JSONPath query,
+/// then attempts to extract matching values from the JSON structure.
+///
+/// - If the JSONPath query is **valid**, it searches for matching values:
+///   - If no matches are found, it returns `None`.
+///   - If exactly **one** match is found, it returns the single extracted value.
+///   - If **multiple** matches are found, it returns an array of extracted values.
+/// - If the JSONPath query is **invalid**, it returns an error.
+///
+/// # Parameters
+///
+/// - `json`: A reference to a `serde_json::Value` containing the JSON data.
+/// - `query`: A string slice representing the JSONPath query.
+///
+/// # Returns
+///
+/// - `Ok(Some(value))` with the **single value**, if one match is found
+///   (which may be `null` if the document contains a `null` there).
+/// - `Ok(Some(array))` with an **array of values**, if multiple matches are found.
+/// - `Ok(None)` if nothing matches, including a path to a missing field.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if the query cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::extract_jsonpath;
+/// use serde_json::json;
+///
+/// let json_data = json!({
+///     "name": "Alice",
+///     "age": 25,
+///     "pets": [
+///         { "type": "dog", "name": "Buddy" },
+///         { "type": "cat", "name": "Whiskers" }
+///     ]
+/// });
+///
+/// let query = "$.pets[*].name"; // JSONPath query to get all pet names
+/// let result = extract_jsonpath(&json_data, query).unwrap();
+///
+/// assert_eq!(result, Some(json!(["Buddy", "Whiskers"]))); // Expected output
+/// ```
+///
+/// ```
+/// use jqr_core::extract_jsonpath;
+/// use serde_json::json;
+///
+/// let json_data = json!({ "name": "Alice", "nickname": null });
+///
+/// assert_eq!(extract_jsonpath(&json_data, "$.nickname").unwrap(), Some(json!(null)));
+/// assert_eq!(extract_jsonpath(&json_data, "$.age").unwrap(), None);
+/// assert!(extract_jsonpath(&json_data, "$.pets[").is_err()); // Invalid JSONPath query
+/// ```
+pub fn extract_jsonpath(json: &Value, query: &str) -> Result<Option<Value>, JqrError> {
+    Ok(CompiledQuery::new(query)?.apply(json))
+}
+
+/// Runs a JSONPath query and returns every matched value.
+///
+/// Unlike `extract_jsonpath`, this does not fold the results into a single
+/// value: a query that matches nothing (including a path to a missing field)
+/// yields an empty vector, and a query that matches a `null` yields `[null]`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if the query cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::find_jsonpath;
+/// use serde_json::json;
+///
+/// let json_data = json!({"user": {"name": "Alice", "email": null}});
+///
+/// assert_eq!(find_jsonpath(&json_data, "$.user.name").unwrap(), vec![json!("Alice")]);
+/// assert_eq!(find_jsonpath(&json_data, "$.user.email").unwrap(), vec![json!(null)]);
+/// assert!(find_jsonpath(&json_data, "$.user.age").unwrap().is_empty());
+/// ```
+pub fn find_jsonpath(json: &Value, query: &str) -> Result<Vec<Value>, JqrError> {
+    Ok(CompiledQuery::new(query)?.find(json))
+}
+
+/// Converts a `JsonPathValue<Value>` into a `serde_json::Value`.
+///
+/// This function takes a `JsonPathValue<Value>` and returns a `serde_json::Value`,
+/// handling different cases based on the input variant:
+///
+/// - `JsonPathValue::Slice(v, _)`: Clones the provided value.
+/// - `JsonPathValue::NewValue(v)`: Takes ownership of the new value.
+/// - `JsonPathValue::NoValue`: Returns `Value::Null` when no value exists.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_path_value_to_json;
+/// use jsonpath_rust::JsonPathValue;
+/// use serde_json::Value;
+///
+/// let value = JsonPathValue::NewValue(Value::String("example".to_string()));
+/// let result = json_path_value_to_json(value);
+///
+/// assert_eq!(result, Value::String("example".to_string()));
+/// ```
+pub fn json_path_value_to_json(value: JsonPathValue<Value>) -> Value {
+    match value {
+        JsonPathValue::Slice(v, _) => v.clone(), // Clone the reference, ignore path
+        JsonPathValue::NewValue(v) => v, // Take ownership and return
+        JsonPathValue::NoValue => Value::Null, // No value found, return JSON null
+    }
+}
+
+/// Converts a JSON string into a YAML-formatted string.
+///
+/// This function takes a JSON string as input and attempts to convert it into YAML.
+/// If the input is valid JSON, it will be serialized to YAML.
+/// If the input is not valid JSON, an error message is returned.
+///
+/// # Errors
+///
+/// - Returns [`JqrError::InvalidJson`] if the input is not valid JSON.
+/// - Returns [`JqrError::Serialization`] if the YAML conversion fails.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::convert_to_yaml;
+///
+/// let json_str = r#"{"name": "Alice", "age": 25}"#;
+/// let yaml_result = convert_to_yaml(json_str);
+///
+/// assert!(yaml_result.is_ok());
+/// assert!(yaml_result.unwrap().contains("name: Alice"));
+/// ```
+///
+/// ```
+/// use jqr_core::convert_to_yaml;
+///
+/// let invalid_json = r#"{name: Alice, age:}"#;
+/// let result = convert_to_yaml(invalid_json);
+///
+/// assert!(result.is_err());
+/// ```
+pub fn convert_to_yaml(content: &str) -> Result<String, JqrError> {
+    convert_to_yaml_with(content, &FormatOptions::new().trailing_newline(true))
+}
+
+/// Like [`convert_to_yaml`], but formats the YAML according to `options`.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{convert_to_yaml_with, FormatOptions};
+///
+/// let options = FormatOptions::new().sort_keys(true);
+/// let yaml = convert_to_yaml_with(r#"{"b": 1, "a": 2}"#, &options).unwrap();
+///
+/// assert_eq!(yaml, "a: 2\nb: 1");
+/// ```
+pub fn convert_to_yaml_with(content: &str, options: &FormatOptions) -> Result<String, JqrError> {
+    match serde_json::from_str::<Value>(content) {
+        Ok(json) => {
+            // Convert JSON to YAML, return error if conversion fails
+            format_yaml(&json, options)
+        }
+        Err(e) => {
+            // Return an error indicating invalid JSON
+            Err(JqrError::invalid_json(e, false))
+        }
+    }
+}
+
+/// Converts a YAML string into a pretty-printed JSON string.
+///
+/// This function takes a YAML-formatted string as input and converts it into a
+/// pretty-printed JSON format. Printing the result is left to the caller.
+///
+/// # Errors
+///
+/// - Returns [`JqrError::InvalidYaml`] if the input is not valid YAML.
+/// - Returns [`JqrError::Serialization`] if the value cannot be written as
+///   JSON, e.g. a mapping with non-string keys.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::convert_to_json;
+///
+/// let yaml_str = r#"
+/// name: Alice
+/// age: 25
+/// "#;
+///
+/// let json = convert_to_json(yaml_str).unwrap();
+/// assert_eq!(json, "{\n  \"age\": 25,\n  \"name\": \"Alice\"\n}");
+/// ```
+///
+/// ```
+/// use jqr_core::convert_to_json;
+///
+/// let invalid_yaml = r#"
+/// name: [Alice
+/// "#;
+///
+/// assert!(convert_to_json(invalid_yaml).is_err());
+/// ```
+pub fn convert_to_json(content: &str) -> Result<String, JqrError> {
+    convert_to_json_with(content, &FormatOptions::default())
+}
+
+/// Like [`convert_to_json`], but formats the JSON according to `options`.
+///
+/// A YAML stream of several documents becomes an array of them.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{convert_to_json_with, FormatOptions};
+///
+/// let options = FormatOptions::new().compact(true);
+/// let json = convert_to_json_with("name: Alice\ntags: [a, b]\n", &options).unwrap();
+///
+/// assert_eq!(json, r#"{"name":"Alice","tags":["a","b"]}"#);
+///
+/// let json = convert_to_json_with("name: Alice\n---\nname: Bob\n", &options).unwrap();
+///
+/// assert_eq!(json, r#"[{"name":"Alice"},{"name":"Bob"}]"#);
+/// ```
+pub fn convert_to_json_with(content: &str, options: &FormatOptions) -> Result<String, JqrError> {
+    let mut documents = parse_yaml_documents(content)?;
+    let yaml = if documents.len() == 1 {
+        documents.remove(0)
+    } else {
+        Value::Array(documents)
+    };
+
+    // Convert YAML to JSON in the requested format
+    format_json(&yaml, options)
+}
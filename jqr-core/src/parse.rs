@@ -0,0 +1,349 @@
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Number, Value};
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::{check_nesting_depth, invalid_json, strip_jsonc, JqrError, DEFAULT_MAX_DEPTH};
+
+/// Which JSON syntax to accept.
+///
+/// Raw control characters in strings are refused by every profile.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_json_with, ParseOptions, ParseProfile};
+/// use serde_json::json;
+///
+/// let lenient = ParseOptions::new().profile(ParseProfile::Lenient);
+/// assert_eq!(
+///     parse_json_with("{'ratio': NaN, /* to do */ 'tags': ['a',],}", &lenient).unwrap(),
+///     json!({"ratio": null, "tags": ["a"]})
+/// );
+///
+/// // Linting: the strict profile refuses what standard parsing lets through
+/// let strict = ParseOptions::new().profile(ParseProfile::Strict);
+/// let err = parse_json_with("\u{feff}{\"a\": 1}", &strict).unwrap_err();
+/// assert_eq!(err.to_string(), "Invalid JSON: byte order mark is not allowed at line 1 column 1");
+/// assert!(parse_json_with("{\"a\": 1, \"a\": 2}", &strict).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseProfile {
+    /// Standard JSON (RFC 8259), skipping a leading byte order mark as the
+    /// RFC allows.
+    #[default]
+    Standard,
+    /// Standard JSON and nothing else: a byte order mark is refused, and so
+    /// is a repeated key, whatever the duplicate-key policy.
+    Strict,
+    /// Standard JSON plus `//` and `/* */` comments, trailing commas,
+    /// single-quoted strings and keys, and `NaN` and `Infinity`, which JSON
+    /// cannot represent and are read as `null`.
+    Lenient,
+}
+
+/// What to do when an object repeats a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Refuse the input with [`JqrError::DuplicateKey`].
+    Error,
+    /// Keep the value of the first occurrence.
+    First,
+    /// Keep the value of the last occurrence, at the position of the first,
+    /// as most JSON parsers do.
+    #[default]
+    Last,
+}
+
+/// Controls how JSON input is parsed.
+///
+/// The defaults accept standard JSON, refuse input nested deeper than
+/// [`DEFAULT_MAX_DEPTH`] and keep the last value of a repeated key. Options are set with chained
+/// builder methods.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_json_with, DuplicateKeys, ParseOptions};
+/// use serde_json::json;
+///
+/// let options = ParseOptions::new().duplicate_keys(DuplicateKeys::First);
+/// let value = parse_json_with(r#"{"port": 80, "host": "a", "port": 8080}"#, &options).unwrap();
+///
+/// assert_eq!(value, json!({"port": 80, "host": "a"}));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    profile: ParseProfile,
+    max_depth: usize,
+    duplicate_keys: DuplicateKeys,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            profile: ParseProfile::Standard,
+            max_depth: DEFAULT_MAX_DEPTH,
+            duplicate_keys: DuplicateKeys::Last,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets which JSON syntax to accept.
+    pub fn profile(mut self, profile: ParseProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Refuses input with arrays or objects nested deeper than `max_depth`.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets what to do when an object repeats a key.
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+}
+
+/// Parses a JSON string according to `options`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::NestingTooDeep`] if the input is nested deeper than
+///   the limit.
+/// * Returns [`JqrError::DuplicateKey`] for the first repeated key of an
+///   object with [`DuplicateKeys::Error`] or [`ParseProfile::Strict`]. Its
+///   line and column are where the repeated key ends.
+/// * Returns the same errors as [`parse_json`](crate::parse_json) for
+///   input the profile does not accept. Positions in lenient input are
+///   those of its standard JSON rewrite, which may differ slightly where a
+///   line holds a single-quoted string or `NaN`.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_json_with, DuplicateKeys, JqrError, ParseOptions};
+///
+/// let config = "{\n  \"port\": 80,\n  \"port\": 8080\n}";
+/// let options = ParseOptions::new().duplicate_keys(DuplicateKeys::Error);
+///
+/// let err = parse_json_with(config, &options).unwrap_err();
+/// assert!(matches!(&err, JqrError::DuplicateKey { key, line: 3, column: 8 } if key == "port"));
+/// assert_eq!(err.to_string(), "Duplicate key 'port' at line 3 column 8");
+/// ```
+pub fn parse_json_with(content: &str, options: &ParseOptions) -> Result<Value, JqrError> {
+    let without_bom = content.strip_prefix('\u{feff}');
+    let relaxed;
+    let (content, duplicate_keys) = match options.profile {
+        ParseProfile::Standard => (without_bom.unwrap_or(content), options.duplicate_keys),
+        ParseProfile::Strict if without_bom.is_some() => {
+            return Err(JqrError::InvalidJson {
+                line: 1,
+                column: 1,
+                message: "byte order mark is not allowed".to_string(),
+                looks_like_yaml: false,
+            })
+        }
+        ParseProfile::Strict => (content, DuplicateKeys::Error),
+        ParseProfile::Lenient => {
+            relaxed = relax(without_bom.unwrap_or(content));
+            (relaxed.as_str(), options.duplicate_keys)
+        }
+    };
+    check_nesting_depth(content, options.max_depth)?;
+
+    let mut deserializer = serde_json::Deserializer::from_str(content);
+    deserializer.disable_recursion_limit();
+
+    // Keeping the last value is what serde_json does by itself
+    let duplicate = RefCell::new(None);
+    let parsed = match duplicate_keys {
+        DuplicateKeys::Last => Value::deserialize(&mut deserializer),
+        duplicate_keys => ValueSeed {
+            duplicate_keys,
+            duplicate: &duplicate,
+        }
+        .deserialize(&mut deserializer),
+    };
+    parsed
+        .and_then(|value| deserializer.end().map(|_| value))
+        .map_err(|e| match duplicate.take() {
+            Some(key) => JqrError::DuplicateKey {
+                key,
+                line: e.line(),
+                column: e.column(),
+            },
+            None => invalid_json(content, e),
+        })
+}
+
+/// Rewrites lenient input as standard JSON: single-quoted strings become
+/// double-quoted ones, `NaN` and `Infinity` become `null`, and comments and
+/// trailing commas are blanked by [`strip_jsonc`].
+fn relax(content: &str) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '"' | '\'' => string_end(rest),
+            // Comments are copied as they are, quotes and all
+            '/' if rest.starts_with("//") => rest.find('\n').unwrap_or(rest.len()),
+            '/' if rest.starts_with("/*") => rest[2..].find("*/").map_or(rest.len(), |end| end + 4),
+            _ if c.is_ascii_alphabetic() => rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len()),
+            _ => c.len_utf8(),
+        };
+        let (token, after) = rest.split_at(end);
+        match token {
+            "NaN" | "Infinity" => {
+                if output.ends_with(['+', '-']) {
+                    output.pop();
+                }
+                output.push_str("null");
+            }
+            _ if c == '\'' => push_double_quoted(token, &mut output),
+            _ => output.push_str(token),
+        }
+        rest = after;
+    }
+    strip_jsonc(&output)
+}
+
+/// Returns the length of the string literal that `text` starts with,
+/// including its quotes, or of all of `text` if the string is not closed.
+fn string_end(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            quote if quote == bytes[0] => return i + 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    text.len()
+}
+
+/// Writes the single-quoted string literal `token` as a double-quoted one.
+fn push_double_quoted(token: &str, output: &mut String) {
+    output.push('"');
+    let mut chars = token[1..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\'') => output.push('\''),
+                Some(escaped) => {
+                    output.push('\\');
+                    output.push(escaped);
+                }
+                None => output.push('\\'),
+            },
+            // Only the closing quote can be unescaped
+            '\'' => output.push('"'),
+            '"' => output.push_str("\\\""),
+            _ => output.push(c),
+        }
+    }
+}
+
+/// The key that `arbitrary_precision` numbers are passed to visitors under.
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Deserializes a value, handling repeated keys by `duplicate_keys` and
+/// recording a refused key in `duplicate`.
+#[derive(Clone, Copy)]
+struct ValueSeed<'a> {
+    duplicate_keys: DuplicateKeys,
+    duplicate: &'a RefCell<Option<String>>,
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::from(i))
+    }
+
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Value, E> {
+        Ok(Value::from(u))
+    }
+
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(f).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(self)? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            // Exact numbers arrive as a map holding their literal
+            if key == NUMBER_TOKEN {
+                let literal: String = map.next_value()?;
+                return literal
+                    .parse::<Number>()
+                    .map(Value::Number)
+                    .map_err(de::Error::custom);
+            }
+
+            let exists = object.contains_key(&key);
+            if exists && self.duplicate_keys == DuplicateKeys::Error {
+                let message = format!("duplicate key '{}'", key);
+                *self.duplicate.borrow_mut() = Some(key);
+                return Err(de::Error::custom(message));
+            }
+            // Only the first value is kept; the last is left to serde_json
+            let value = map.next_value_seed(self)?;
+            if !exists {
+                object.insert(key, value);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+}
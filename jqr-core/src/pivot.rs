@@ -0,0 +1,92 @@
+use serde_json::{Map, Value};
+
+use crate::{json_type, JqrError};
+
+/// Pivots between rows and columns: an array of objects becomes an object of
+/// arrays, and an object of arrays becomes an array of objects.
+///
+/// Going to columns, every key any row has gets a column, in the order keys
+/// first appear, and a row without the key gives `null` in it, so every
+/// column is as long as the array. Going back to rows, there are as many
+/// rows as the longest column has elements, and a row has no member for a
+/// column that is too short for it. An empty array and an empty object pivot
+/// into each other.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is an array with an
+///   element that is not an object, an object with a member that is not an
+///   array, or neither an array nor an object.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::pivot;
+/// use serde_json::json;
+///
+/// let rows = json!([{"a": 1, "b": 2}, {"a": 3, "c": true}]);
+/// let columns = pivot(&rows).unwrap();
+/// assert_eq!(columns, json!({"a": [1, 3], "b": [2, null], "c": [null, true]}));
+///
+/// assert_eq!(
+///     pivot(&json!({"a": [1, 3], "b": [2, 4]})).unwrap(),
+///     json!([{"a": 1, "b": 2}, {"a": 3, "b": 4}])
+/// );
+/// assert!(pivot(&json!([1, 2])).is_err());
+/// ```
+pub fn pivot(value: &Value) -> Result<Value, JqrError> {
+    match value {
+        Value::Array(rows) => to_columns(rows).map(Value::Object),
+        Value::Object(columns) => to_rows(columns).map(Value::Array),
+        other => Err(JqrError::UnsupportedValue(format!(
+            "Pivoting requires an array of objects or an object of arrays, but the value is of type {}",
+            json_type(other)
+        ))),
+    }
+}
+
+/// Pivots an array of objects into an object of arrays.
+fn to_columns(rows: &[Value]) -> Result<Map<String, Value>, JqrError> {
+    let mut columns: Map<String, Value> = Map::new();
+    for (i, row) in rows.iter().enumerate() {
+        let Value::Object(row) = row else {
+            return Err(JqrError::UnsupportedValue(format!(
+                "Pivoting an array requires objects, but element {} is of type {}",
+                i,
+                json_type(row)
+            )));
+        };
+        for key in row.keys() {
+            columns
+                .entry(key.clone())
+                .or_insert_with(|| Value::Array(vec![Value::Null; rows.len()]));
+        }
+        for (key, column) in columns.iter_mut() {
+            if let (Value::Array(cells), Some(cell)) = (column, row.get(key)) {
+                cells[i] = cell.clone();
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Pivots an object of arrays into an array of objects.
+fn to_rows(columns: &Map<String, Value>) -> Result<Vec<Value>, JqrError> {
+    let mut rows: Vec<Map<String, Value>> = Vec::new();
+    for (key, column) in columns {
+        let Value::Array(cells) = column else {
+            return Err(JqrError::UnsupportedValue(format!(
+                "Pivoting an object requires arrays, but the member '{}' is of type {}",
+                key,
+                json_type(column)
+            )));
+        };
+        if rows.len() < cells.len() {
+            rows.resize(cells.len(), Map::new());
+        }
+        for (row, cell) in rows.iter_mut().zip(cells) {
+            row.insert(key.clone(), cell.clone());
+        }
+    }
+    Ok(rows.into_iter().map(Value::Object).collect())
+}
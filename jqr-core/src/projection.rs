@@ -0,0 +1,308 @@
+use serde_json::{Map, Value};
+
+use crate::{shorthand_to_jsonpath, CompiledQuery, JqrError};
+
+/// A template that builds a new value from each query result, such as
+/// `{id: $.id, name: $.profile.name}`.
+///
+/// A template is an object, an array, a JSON literal or a JSONPath query.
+/// Object keys are names or quoted strings, and a key alone, as in
+/// `{id, name}`, takes the member of the same name. Queries start with `$`
+/// or `@`, both the value being projected, or with `.` as a shorthand; a
+/// query that matches nothing gives `null`, and one that matches several
+/// values gives the array of them.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::Projection;
+/// use serde_json::json;
+///
+/// let projection = Projection::new("{id, name: $.profile.name, tags: [@.role, 'user']}").unwrap();
+/// let user = json!({"id": 7, "profile": {"name": "Ada"}, "role": "admin"});
+///
+/// assert_eq!(
+///     projection.apply(&user),
+///     json!({"id": 7, "name": "Ada", "tags": ["admin", "user"]})
+/// );
+/// assert!(Projection::new("{id: }").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Projection {
+    source: String,
+    template: Template,
+}
+
+/// A parsed template node.
+#[derive(Debug, Clone)]
+enum Template {
+    Object(Vec<(String, Template)>),
+    Array(Vec<Template>),
+    Query(CompiledQuery),
+    Literal(Value),
+}
+
+impl Projection {
+    /// Parses a template.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the template is malformed or
+    ///   holds a query that is not valid JSONPath.
+    pub fn new(template: &str) -> Result<Self, JqrError> {
+        let invalid = |reason: String| {
+            JqrError::InvalidQuery(format!("Invalid map template '{}': {}", template, reason))
+        };
+        let mut parser = Parser {
+            chars: template.chars().collect(),
+            position: 0,
+        };
+        let parsed = parser.value().map_err(invalid)?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(invalid(parser.unexpected()));
+        }
+        Ok(Projection {
+            source: template.to_string(),
+            template: parsed,
+        })
+    }
+
+    /// Returns the template text this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Builds the template's value for one input value.
+    pub fn apply(&self, value: &Value) -> Value {
+        build(&self.template, value)
+    }
+
+    /// Builds the template's value for every element of an array result, or
+    /// for the result itself if it is not an array.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jqr_core::Projection;
+    /// use serde_json::json;
+    ///
+    /// let projection = Projection::new("{user: $.name}").unwrap();
+    /// let users = json!([{"name": "Ada", "id": 1}, {"id": 2}]);
+    ///
+    /// assert_eq!(projection.map(&users), json!([{"user": "Ada"}, {"user": null}]));
+    /// assert_eq!(projection.map(&json!({"name": "Bob"})), json!({"user": "Bob"}));
+    /// ```
+    pub fn map(&self, result: &Value) -> Value {
+        match result {
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| self.apply(item)).collect())
+            }
+            other => self.apply(other),
+        }
+    }
+}
+
+/// Builds the value of a template node for `value`.
+fn build(template: &Template, value: &Value) -> Value {
+    match template {
+        Template::Object(members) => Value::Object(
+            members
+                .iter()
+                .map(|(key, member)| (key.clone(), build(member, value)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Template::Array(items) => {
+            Value::Array(items.iter().map(|item| build(item, value)).collect())
+        }
+        Template::Query(query) => query.apply(value).unwrap_or(Value::Null),
+        Template::Literal(literal) => literal.clone(),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("unexpected '{}' at column {}", c, self.position + 1),
+            None => "unexpected end of template".to_string(),
+        }
+    }
+
+    /// Skips whitespace and consumes `c` if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(c);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn value(&mut self) -> Result<Template, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('$' | '@' | '.') => self.query(),
+            Some('"' | '\'') => self.string().map(|s| Template::Literal(Value::String(s))),
+            Some(_) => self.literal(),
+            None => Err(self.unexpected()),
+        }
+    }
+
+    fn object(&mut self) -> Result<Template, String> {
+        self.position += 1;
+        let mut members: Vec<(String, Template)> = Vec::new();
+        while !self.eat('}') {
+            self.skip_whitespace();
+            let key = match self.peek() {
+                Some('"' | '\'') => self.string()?,
+                Some(c) if c.is_alphanumeric() || c == '_' => self.name(),
+                _ => return Err(self.unexpected()),
+            };
+            let member = if self.eat(':') {
+                self.value()?
+            } else {
+                // `{id}` takes the member of the same name
+                let query = CompiledQuery::new(&member_query(&key)).map_err(|e| e.to_string())?;
+                Template::Query(query)
+            };
+            if members.iter().any(|(seen, _)| *seen == key) {
+                return Err(format!("the key '{}' is given twice", key));
+            }
+            members.push((key, member));
+            if !self.eat(',') {
+                if !self.eat('}') {
+                    return Err(self.unexpected());
+                }
+                break;
+            }
+        }
+        Ok(Template::Object(members))
+    }
+
+    fn array(&mut self) -> Result<Template, String> {
+        self.position += 1;
+        let mut items = Vec::new();
+        while !self.eat(']') {
+            items.push(self.value()?);
+            if !self.eat(',') {
+                if !self.eat(']') {
+                    return Err(self.unexpected());
+                }
+                break;
+            }
+        }
+        Ok(Template::Array(items))
+    }
+
+    /// Reads a query up to the `,`, `}` or `]` that ends it, outside brackets
+    /// and quotes.
+    fn query(&mut self) -> Result<Template, String> {
+        let start = self.position;
+        let (mut depth, mut quote, mut escaped) = (0usize, None, false);
+        while let Some(c) = self.peek() {
+            match (quote, c) {
+                (Some(_), _) if escaped => escaped = false,
+                (Some(_), '\\') => escaped = true,
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '[' | '(') => depth += 1,
+                (None, ']' | ')') if depth > 0 => depth -= 1,
+                (None, ',' | '}' | ']') => break,
+                _ => {}
+            }
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        let text = text.trim();
+        let query = match text.strip_prefix('@') {
+            Some(rest) => format!("${}", rest),
+            None => shorthand_to_jsonpath(text),
+        };
+        CompiledQuery::new(&query)
+            .map(Template::Query)
+            .map_err(|e| e.to_string())
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    /// Reads a string in single or double quotes, with JSON escapes.
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.chars[self.position];
+        let start = self.position;
+        self.position += 1;
+        let mut text = String::from('"');
+        loop {
+            match self.peek() {
+                None => return Err(format!("unterminated string at column {}", start + 1)),
+                Some(c) if c == quote => break,
+                Some('\\') => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some('\'') => text.push('\''),
+                        Some(c) => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                        None => continue,
+                    }
+                }
+                Some('"') => text.push_str("\\\""),
+                Some(c) => text.push(c),
+            }
+            self.position += 1;
+        }
+        self.position += 1;
+        text.push('"');
+        serde_json::from_str(&text)
+            .map_err(|e| format!("invalid string at column {}: {}", start + 1, e))
+    }
+
+    /// Reads a number, `true`, `false` or `null`.
+    fn literal(&mut self) -> Result<Template, String> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '-' | '+' | '.'))
+        {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        match serde_json::from_str::<Value>(&text) {
+            Ok(literal) if !text.is_empty() => Ok(Template::Literal(literal)),
+            _ => {
+                self.position = start;
+                Err(self.unexpected())
+            }
+        }
+    }
+}
+
+/// The query for the member `key` of the value being projected.
+fn member_query(key: &str) -> String {
+    format!("$['{}']", key.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,894 @@
+use jsonpath_rust::JsonPath;
+use serde::de::{Deserializer, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufReader, Read};
+
+use crate::compare::child_path;
+use crate::format::starts_with_array;
+use crate::rfc9535::{Rfc9535Filter, Rfc9535Path};
+use crate::{json_path_value_to_json, JqrError};
+
+/// The JSONPath dialects a [`CompiledQuery`] can be compiled with.
+///
+/// The two differ mostly in filters and slices. For example, RFC 9535
+/// filters need no parentheses and compare a missing member as Nothing
+/// rather than failing the filter, `length()` and `count()` are functions,
+/// and malformed selectors such as `$[01]` or `$['a]` are errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonPathDialect {
+    /// The dialect of the `jsonpath-rust` library, as jqr has always used.
+    #[default]
+    Legacy,
+    /// JSONPath as standardized in RFC 9535.
+    Rfc9535,
+}
+
+/// A JSONPath expression parsed once, so it can be run against many documents.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::CompiledQuery;
+/// use serde_json::json;
+///
+/// let query = CompiledQuery::new("$.user.name").unwrap();
+///
+/// for (doc, expected) in [
+///     (json!({"user": {"name": "Alice"}}), Some(json!("Alice"))),
+///     (json!({"user": {}}), None),
+/// ] {
+///     assert_eq!(query.apply(&doc), expected);
+/// }
+///
+/// assert!(CompiledQuery::new("$.user[").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    source: String,
+    path: QueryEngine,
+}
+
+#[derive(Debug, Clone)]
+enum QueryEngine {
+    Legacy(JsonPath),
+    Rfc9535(Rfc9535Path),
+}
+
+impl CompiledQuery {
+    /// Parses a JSONPath expression in the legacy dialect.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the query cannot be parsed.
+    pub fn new(query: &str) -> Result<Self, JqrError> {
+        Self::with_dialect(query, JsonPathDialect::Legacy)
+    }
+
+    /// Parses a JSONPath expression in the given dialect.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the query cannot be parsed or,
+    ///   in the RFC 9535 dialect, is not well-typed, such as a comparison
+    ///   with a query that can select several nodes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jqr_core::{CompiledQuery, JsonPathDialect};
+    /// use serde_json::json;
+    ///
+    /// let doc = json!({"books": [
+    ///     {"title": "Dune", "tags": ["classic", "sf"]},
+    ///     {"title": "Emma", "tags": ["classic"]},
+    /// ]});
+    ///
+    /// let query = CompiledQuery::with_dialect(
+    ///     "$.books[?length(@.tags) > 1].title",
+    ///     JsonPathDialect::Rfc9535,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(query.apply(&doc), Some(json!("Dune")));
+    ///
+    /// let err = CompiledQuery::with_dialect("$.books[?@.tags[*] == 'sf']", JsonPathDialect::Rfc9535)
+    ///     .unwrap_err();
+    /// assert!(err.to_string().contains("can select several nodes"));
+    /// ```
+    pub fn with_dialect(query: &str, dialect: JsonPathDialect) -> Result<Self, JqrError> {
+        let path = match dialect {
+            JsonPathDialect::Legacy => QueryEngine::Legacy(compile_legacy(query)?),
+            JsonPathDialect::Rfc9535 => {
+                QueryEngine::Rfc9535(Rfc9535Path::new(query).map_err(|e| {
+                    JqrError::InvalidQuery(format!("Invalid JSONPath query '{}': {}", query, e))
+                })?)
+            }
+        };
+        Ok(CompiledQuery {
+            source: query.to_string(),
+            path,
+        })
+    }
+
+    /// Returns the query text this was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Runs the query with the semantics of `extract_jsonpath`: a single match
+    /// is returned as is, several are returned as an array, and no match
+    /// (including a path to a missing field) is `None`.
+    pub fn apply(&self, json: &Value) -> Option<Value> {
+        let mut found = self.find(json);
+
+        match found.len() {
+            0 => None,
+            1 => found.pop(),
+            _ => Some(Value::Array(found)),
+        }
+    }
+
+    /// Runs the query with the semantics of `find_jsonpath`, returning every matched value.
+    pub fn find(&self, json: &Value) -> Vec<Value> {
+        match &self.path {
+            QueryEngine::Legacy(path) => path
+                .find_slice(json)
+                .into_iter()
+                .filter(|jp_value| jp_value.has_value())
+                .map(json_path_value_to_json)
+                .collect(),
+            QueryEngine::Rfc9535(path) => path.find(json).into_iter().cloned().collect(),
+        }
+    }
+
+    /// Runs the query like [`find`](Self::find), pairing every matched
+    /// value with its location, a JSONPath such as `$.users[3].email`.
+    /// Values computed by the query (such as `length()`) have no location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jqr_core::CompiledQuery;
+    /// use serde_json::json;
+    ///
+    /// let doc = json!({"users": [{"email": "a@x.org"}, {"name": "Bob", "e-mail": "b@x.org"}]});
+    /// let query = CompiledQuery::new("$.users[*]['email','e-mail']").unwrap();
+    ///
+    /// assert_eq!(
+    ///     query.find_with_paths(&doc),
+    ///     vec![
+    ///         (Some("$.users[0].email".to_string()), json!("a@x.org")),
+    ///         (Some("$.users[1]['e-mail']".to_string()), json!("b@x.org")),
+    ///     ]
+    /// );
+    /// ```
+    pub fn find_with_paths(&self, json: &Value) -> Vec<(Option<String>, Value)> {
+        match &self.path {
+            QueryEngine::Legacy(path) => path
+                .find_slice(json)
+                .into_iter()
+                .filter(|jp_value| jp_value.has_value())
+                .map(|jp_value| {
+                    let location = jp_value
+                        .clone()
+                        .to_path()
+                        .and_then(|path| display_path(&path));
+                    (location, json_path_value_to_json(jp_value))
+                })
+                .collect(),
+            QueryEngine::Rfc9535(path) => {
+                let mut paths = HashMap::new();
+                node_paths(json, "$".to_string(), &mut paths);
+                path.find(json)
+                    .into_iter()
+                    .map(|node| (paths.get(&(node as *const Value)).cloned(), node.clone()))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Renumbers the array indexes in a path that
+/// [`CompiledQuery::find_with_paths`] reports to count from `base` rather
+/// than 0. This only changes how a location is shown: queries still index
+/// arrays from 0. Quoted keys are kept as they are, brackets and all.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::rebase_path_indexes;
+///
+/// assert_eq!(rebase_path_indexes("$.users[0].tags[9]", 1), "$.users[1].tags[10]");
+/// assert_eq!(rebase_path_indexes("$['a[0]'][2]", 1), "$['a[0]'][3]");
+/// ```
+pub fn rebase_path_indexes(path: &str, base: usize) -> String {
+    let mut rebased = String::new();
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        rebased.push(c);
+        match c {
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    rebased.push(c);
+                    match c {
+                        '\\' => rebased.extend(chars.next()),
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '[' => {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                match digits.parse::<usize>() {
+                    Ok(index) => rebased.push_str(&(index + base).to_string()),
+                    Err(_) => rebased.push_str(&digits),
+                }
+            }
+            _ => {}
+        }
+    }
+    rebased
+}
+
+/// Rewrites a normalized path reported by the JSONPath engine in the
+/// shorter form jqr prints, with dotted keys where possible.
+fn display_path(path: &str) -> Option<String> {
+    let mut display = "$".to_string();
+    for step in engine_path_steps(path)? {
+        display = match step {
+            PathStep::Key(key) => child_path(&display, key),
+            PathStep::Index(index) => format!("{}[{}]", display, index),
+        };
+    }
+    Some(display)
+}
+
+/// Parses a query in the legacy dialect, which the functions working with
+/// normalized paths rely on.
+fn compile_legacy(query: &str) -> Result<JsonPath, JqrError> {
+    JsonPath::try_from(query)
+        .map_err(|e| JqrError::InvalidQuery(format!("Invalid JSONPath query '{}': {}", query, e)))
+}
+
+/// Turns the contents of a query file into a single-line query expression.
+///
+/// This lets long JSONPath expressions be kept in documented, multi-line
+/// files. The preprocessing rules are:
+///
+/// - Lines whose first non-whitespace character is `#` are comments and are
+///   dropped, as are blank lines. A `#` anywhere else is kept as-is, so
+///   trailing comments are **not** supported; this keeps `#` inside filter
+///   expressions and quoted strings intact.
+/// - Remaining lines are trimmed and joined with a single space, except that a
+///   line starting with `.` or `[` is appended directly, so a path can be split
+///   across lines at segment boundaries.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if no query remains after removing comments.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::normalize_query_text;
+///
+/// let text = "# Titles of cheap books\n$.store.book\n  [?(@.price < 10)]\n  .title\n";
+/// assert_eq!(normalize_query_text(text).unwrap(), "$.store.book[?(@.price < 10)].title");
+///
+/// assert!(normalize_query_text("# only a comment\n").is_err());
+/// ```
+pub fn normalize_query_text(text: &str) -> Result<String, JqrError> {
+    let mut query = String::new();
+
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !query.is_empty() && !line.starts_with(['.', '[']) {
+            query.push(' ');
+        }
+        query.push_str(line);
+    }
+
+    if query.is_empty() {
+        Err(JqrError::InvalidQuery(
+            "Query file contains no query".to_string(),
+        ))
+    } else {
+        Ok(query)
+    }
+}
+
+/// Converts a match location reported by the JSONPath engine into a JSON Pointer.
+///
+/// The engine describes locations as normalized paths such as
+/// `$.['users'][0].['name']`; this turns them into RFC 6901 pointers like
+/// `/users/0/name`, escaping `~` and `/` in keys. Returns `None` if the path is
+/// not in the expected form.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::jsonpath_to_pointer;
+///
+/// assert_eq!(jsonpath_to_pointer("$.['users'][0].['a/b']").unwrap(), "/users/0/a~1b");
+/// assert_eq!(jsonpath_to_pointer("$").unwrap(), "");
+/// ```
+pub fn jsonpath_to_pointer(path: &str) -> Option<String> {
+    let mut pointer = String::new();
+    for step in engine_path_steps(path)? {
+        pointer.push('/');
+        match step {
+            PathStep::Key(key) => pointer.push_str(&key.replace('~', "~0").replace('/', "~1")),
+            PathStep::Index(index) => pointer.push_str(index),
+        }
+    }
+    Some(pointer)
+}
+
+/// A step of a normalized path reported by the JSONPath engine.
+enum PathStep<'a> {
+    Key(&'a str),
+    Index(&'a str),
+}
+
+/// Splits a normalized path reported by the JSONPath engine, such as
+/// `$.['users'][0].['name']`, into its steps.
+fn engine_path_steps(path: &str) -> Option<Vec<PathStep<'_>>> {
+    let mut rest = path.strip_prefix('$')?;
+    let mut steps = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(".['") {
+            // The key ends at the first `']` that is followed by another segment or the end
+            let end = after.match_indices("']").map(|(i, _)| i).find(|&i| {
+                let tail = &after[i + 2..];
+                tail.is_empty() || tail.starts_with('.') || tail.starts_with('[')
+            })?;
+            steps.push(PathStep::Key(&after[..end]));
+            rest = &after[end + 2..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let index = &after[..end];
+            if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            steps.push(PathStep::Index(index));
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+
+    Some(steps)
+}
+
+/// Collects the JSONPath of every node of a document, by address, so the
+/// nodes a query returns by reference can be located.
+fn node_paths(node: &Value, path: String, paths: &mut HashMap<*const Value, String>) {
+    match node {
+        Value::Object(map) => {
+            for (key, child) in map {
+                node_paths(child, child_path(&path, key), paths);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                node_paths(item, format!("{}[{}]", path, i), paths);
+            }
+        }
+        _ => {}
+    }
+    paths.insert(node as *const Value, path);
+}
+
+/// Runs a JSONPath query and returns the JSON Pointer of every matched node.
+///
+/// Values computed by the query (such as `length()`) do not exist in the
+/// document and therefore have no pointer; they are skipped.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if the query cannot be parsed.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::find_pointer_paths;
+/// use serde_json::json;
+///
+/// let json_data = json!({"users": [{"name": "Alice"}, {"name": "Bob"}]});
+/// let paths = find_pointer_paths(&json_data, "$..name").unwrap();
+///
+/// assert_eq!(paths, vec!["/users/0/name", "/users/1/name"]);
+/// ```
+pub fn find_pointer_paths(json: &Value, query: &str) -> Result<Vec<String>, JqrError> {
+    let path = compile_legacy(query)?;
+
+    Ok(path
+        .find_slice(json)
+        .into_iter()
+        .filter_map(|jp_value| jp_value.to_path())
+        .filter_map(|p| jsonpath_to_pointer(&p))
+        .collect())
+}
+
+/// Counts how often each node of a document is matched by a set of queries.
+///
+/// Returns `(pointer, count)` pairs sorted by descending count and then by
+/// pointer, which shows which parts of a document a set of queries actually
+/// uses.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] naming the first query that is not valid JSONPath.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::profile_paths;
+/// use serde_json::json;
+///
+/// let json_data = json!({"user": {"name": "Alice", "age": 25}});
+/// let queries = vec!["$.user.name".to_string(), "$..name".to_string(), "$.user.age".to_string()];
+///
+/// assert_eq!(
+///     profile_paths(&json_data, &queries).unwrap(),
+///     vec![("/user/name".to_string(), 2), ("/user/age".to_string(), 1)]
+/// );
+/// ```
+pub fn profile_paths(json: &Value, queries: &[String]) -> Result<Vec<(String, usize)>, JqrError> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for query in queries {
+        let paths = find_pointer_paths(json, query)?;
+        for path in paths {
+            *counts.entry(path).or_insert(0) += 1;
+        }
+    }
+
+    let mut profile: Vec<(String, usize)> = counts.into_iter().collect();
+    profile.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(profile)
+}
+
+/// An RFC 6901 JSON Pointer such as `/users/0/name`, checked once so it can
+/// be resolved against many documents.
+///
+/// The empty pointer refers to the whole document. In a reference token,
+/// `~1` stands for `/` and `~0` for `~`.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::JsonPointer;
+/// use serde_json::json;
+///
+/// let doc = json!({"users": [{"name": "Alice", "a/b": true}]});
+///
+/// let pointer = JsonPointer::new("/users/0/name").unwrap();
+/// assert_eq!(pointer.resolve(&doc), Some(&json!("Alice")));
+/// assert_eq!(JsonPointer::new("/users/0/a~1b").unwrap().resolve(&doc), Some(&json!(true)));
+///
+/// // Not found is not an error
+/// assert_eq!(JsonPointer::new("/users/1/name").unwrap().resolve(&doc), None);
+///
+/// assert!(JsonPointer::new("users/0").is_err());
+/// assert!(JsonPointer::new("/users/~2").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonPointer {
+    source: String,
+    tokens: Vec<String>,
+}
+
+impl JsonPointer {
+    /// Parses a JSON Pointer.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the pointer is not empty and
+    ///   does not start with `/`, or has a `~` not followed by `0` or `1`.
+    pub fn new(pointer: &str) -> Result<Self, JqrError> {
+        let invalid = |reason: &str| {
+            JqrError::InvalidQuery(format!("Invalid JSON Pointer '{}': {}", pointer, reason))
+        };
+        let tokens = match pointer.strip_prefix('/') {
+            _ if pointer.is_empty() => Vec::new(),
+            Some(rest) => rest
+                .split('/')
+                .map(unescape_pointer_token)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| invalid("'~' must be followed by '0' or '1'"))?,
+            None => return Err(invalid("it must be empty or start with '/'")),
+        };
+        Ok(JsonPointer {
+            source: pointer.to_string(),
+            tokens,
+        })
+    }
+
+    /// Returns the pointer text this was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the value the pointer refers to, or `None` if the document has
+    /// no such value. Array indexes are decimal without leading zeros, as
+    /// the RFC requires.
+    pub fn resolve<'a>(&self, json: &'a Value) -> Option<&'a Value> {
+        self.tokens
+            .iter()
+            .try_fold(json, |value, token| match value {
+                Value::Object(map) => map.get(token),
+                Value::Array(items) => {
+                    let canonical = token == "0"
+                        || (!token.starts_with('0')
+                            && !token.is_empty()
+                            && token.chars().all(|c| c.is_ascii_digit()));
+                    canonical
+                        .then(|| token.parse::<usize>().ok())
+                        .flatten()
+                        .and_then(|index| items.get(index))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Unescapes `~1` and `~0` in a JSON Pointer reference token, or returns
+/// `None` for any other `~` sequence.
+fn unescape_pointer_token(token: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' => match chars.next()? {
+                '0' => unescaped.push('~'),
+                '1' => unescaped.push('/'),
+                _ => return None,
+            },
+            _ => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+/// A compiled predicate that tests whether a document matches, such as
+/// `$.level == "error"`.
+///
+/// A predicate is an RFC 9535 filter expression, as written inside
+/// `[?...]`, in which both `$` and `@` refer to the document tested. It can
+/// compare values with `==`, `!=`, `<`, `<=`, `>` and `>=`, combine tests
+/// with `&&`, `||` and `!`, test whether a path exists (`$.error`), and call
+/// the RFC 9535 functions `length`, `count`, `match`, `search` and `value`.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::Predicate;
+/// use serde_json::json;
+///
+/// let predicate = Predicate::new(r#"$.level == "error" && $.code >= 500"#).unwrap();
+/// assert!(predicate.matches(&json!({"level": "error", "code": 503})));
+/// assert!(!predicate.matches(&json!({"level": "error", "code": 404})));
+///
+/// let has_user = Predicate::new("@.user").unwrap();
+/// assert!(!has_user.matches(&json!({"level": "info"})));
+///
+/// assert!(Predicate::new("$.level = 'error'").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Predicate {
+    source: String,
+    filter: Rfc9535Filter,
+}
+
+impl Predicate {
+    /// Compiles a predicate.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if the expression is not a valid
+    ///   RFC 9535 filter expression.
+    pub fn new(expression: &str) -> Result<Self, JqrError> {
+        let filter = Rfc9535Filter::new(expression).map_err(|e| {
+            JqrError::InvalidQuery(format!("Invalid predicate '{}': {}", expression, e))
+        })?;
+        Ok(Predicate {
+            source: expression.to_string(),
+            filter,
+        })
+    }
+
+    /// Returns the expression this was compiled from.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns whether the predicate holds for a document.
+    pub fn matches(&self, json: &Value) -> bool {
+        self.filter.matches(json)
+    }
+}
+
+/// The query syntaxes recognized by `detect_query_syntax`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuerySyntax {
+    /// A JSONPath expression, starting with `$` (e.g. `$.users[0].name`).
+    JsonPath,
+    /// An RFC 6901 JSON Pointer, starting with `/` (e.g. `/users/0/name`).
+    JsonPointer,
+    /// A shorthand path, a JSONPath without the `$`: jq-like (e.g.
+    /// `.users[0].name`), or starting with a field name or `[` (e.g.
+    /// `users[0].name`).
+    Shorthand,
+}
+
+/// Detects which syntax a query is written in from its first character.
+///
+/// The rules, applied after trimming surrounding whitespace, are:
+///
+/// - `$` starts a JSONPath expression.
+/// - `/` starts a JSON Pointer.
+/// - `.`, `[`, a letter or `_` starts a shorthand path, which is a JSONPath
+///   without the leading `$` (see `shorthand_to_jsonpath`).
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if the query starts with anything else.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{detect_query_syntax, QuerySyntax};
+///
+/// assert_eq!(detect_query_syntax("$.user.name").unwrap(), QuerySyntax::JsonPath);
+/// assert_eq!(detect_query_syntax("/user/name").unwrap(), QuerySyntax::JsonPointer);
+/// assert_eq!(detect_query_syntax(".user.name").unwrap(), QuerySyntax::Shorthand);
+/// assert_eq!(detect_query_syntax("user.name").unwrap(), QuerySyntax::Shorthand);
+/// assert!(detect_query_syntax("#user").is_err());
+/// ```
+pub fn detect_query_syntax(query: &str) -> Result<QuerySyntax, JqrError> {
+    match query.trim_start().chars().next() {
+        Some('$') => Ok(QuerySyntax::JsonPath),
+        Some('/') => Ok(QuerySyntax::JsonPointer),
+        Some(c) if c == '.' || c == '[' || c == '_' || c.is_alphabetic() => {
+            Ok(QuerySyntax::Shorthand)
+        }
+        _ => Err(JqrError::InvalidQuery(format!(
+            "Cannot detect the syntax of query '{}': expected a JSONPath ('$...'), a JSON Pointer ('/...') or a shorthand path ('.field' or 'field')",
+            query
+        ))),
+    }
+}
+
+/// Converts a shorthand path into a JSONPath expression.
+///
+/// The shorthand is a JSONPath without the leading `$`, so `.` alone refers
+/// to the whole document. A shorthand starting with a field name gets the
+/// `.` it lacks as well. A query that already starts with `$` is returned
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::shorthand_to_jsonpath;
+///
+/// assert_eq!(shorthand_to_jsonpath(".users[0].name"), "$.users[0].name");
+/// assert_eq!(shorthand_to_jsonpath("users[0].email"), "$.users[0].email");
+/// assert_eq!(shorthand_to_jsonpath("[0]"), "$[0]");
+/// assert_eq!(shorthand_to_jsonpath("."), "$");
+/// assert_eq!(shorthand_to_jsonpath("$.users"), "$.users");
+/// ```
+pub fn shorthand_to_jsonpath(query: &str) -> String {
+    let query = query.trim();
+    if query == "." {
+        "$".to_string()
+    } else if query.starts_with('$') {
+        query.to_string()
+    } else if query.starts_with(['.', '[']) {
+        format!("${}", query)
+    } else {
+        format!("$.{}", query)
+    }
+}
+
+/// Substitutes named variables into a query before it is compiled.
+///
+/// Every `$name` outside a string literal, where `name` is one of
+/// `variables`, is replaced by the variable's value written as a JSON
+/// literal. Any other `$`, such as the JSONPath root or a variable that is
+/// not given, is left alone, so the query can still fail to compile.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::substitute_query_variables;
+/// use serde_json::json;
+///
+/// let variables = [("env".to_string(), json!("prod")), ("min".to_string(), json!(2))];
+/// assert_eq!(
+///     substitute_query_variables("$.deployments[?(@.env == $env && @.replicas >= $min)]", &variables),
+///     r#"$.deployments[?(@.env == "prod" && @.replicas >= 2)]"#
+/// );
+///
+/// // Strings and longer names are not variables
+/// assert_eq!(
+///     substitute_query_variables("$[?(@.note == '$env' || @.x == $envx)]", &variables),
+///     "$[?(@.note == '$env' || @.x == $envx)]"
+/// );
+/// ```
+pub fn substitute_query_variables(query: &str, variables: &[(String, Value)]) -> String {
+    let mut substituted = String::with_capacity(query.len());
+    let mut chars = query.char_indices();
+    let mut quote = None;
+    while let Some((i, c)) = chars.next() {
+        substituted.push(c);
+        match quote {
+            Some(_) if c == '\\' => substituted.extend(chars.next().map(|(_, c)| c)),
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '$' => {
+                let rest = &query[i + 1..];
+                let name = &rest[..rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len())];
+                let value = variables
+                    .iter()
+                    .find(|(n, _)| !name.is_empty() && n == name);
+                if let Some((_, value)) = value {
+                    substituted.pop();
+                    substituted.push_str(&value.to_string());
+                    chars.nth(name.chars().count() - 1);
+                }
+            }
+            None => {}
+        }
+    }
+    substituted
+}
+
+/// Splits a labeled query, `name=QUERY`, into its label and query.
+///
+/// A label starts with a letter or `_`, followed by letters, digits, `_` or
+/// `-`. Anything else before the first `=` means the query is not labeled,
+/// so a JSONPath filter such as `$[?(@.a == 1)]` is left alone.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::split_query_label;
+///
+/// assert_eq!(split_query_label("emails=$..email"), Some(("emails", "$..email")));
+/// assert_eq!(split_query_label("user_id=.user.id"), Some(("user_id", ".user.id")));
+/// assert_eq!(split_query_label("$[?(@.a == 1)]"), None);
+/// assert_eq!(split_query_label("$.user.name"), None);
+/// ```
+pub fn split_query_label(query: &str) -> Option<(&str, &str)> {
+    let (label, query) = query.split_once('=')?;
+    let mut chars = label.chars();
+    let first = chars.next()?;
+    let is_label = (first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    is_label.then_some((label, query))
+}
+
+/// Counts JSONPath matches in a JSON input without loading the whole document.
+///
+/// If the input is a top-level array, its elements are deserialized one at a
+/// time, keeping only a single element in memory, so the count is the same
+/// as with the whole array loaded. This needs the query to start by picking
+/// elements of the array, with `$[*]`, `$.*` or `$[N]`; the rest of it is
+/// then evaluated against each element picked. Any other top-level value is
+/// parsed as a whole and queried once. This makes it possible to count
+/// matches in inputs larger than the available memory, e.g. `$[*].level` or
+/// `$[*]..error` over a huge log export.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if the query is not valid JSONPath,
+///   or if the input is a top-level array and the query does not start by
+///   picking its elements.
+/// * Returns [`JqrError::Io`] if reading fails, or [`JqrError::InvalidJson`]
+///   if the input is not valid JSON.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::count_matches_streaming;
+///
+/// let input = r#"[{"level": "error"}, {"level": "info"}, {"msg": "x"}]"#;
+/// assert_eq!(count_matches_streaming(input.as_bytes(), "$[*].level").unwrap(), 2);
+/// assert_eq!(count_matches_streaming(input.as_bytes(), "$[2].msg").unwrap(), 1);
+/// assert_eq!(count_matches_streaming(input.as_bytes(), "$[*]").unwrap(), 3);
+/// assert!(count_matches_streaming(input.as_bytes(), "$.level").is_err());
+/// assert_eq!(count_matches_streaming(r#"{"a": [1, 2, 3]}"#.as_bytes(), "$.a[*]").unwrap(), 3);
+/// ```
+pub fn count_matches_streaming<R: Read>(reader: R, query: &str) -> Result<u64, JqrError> {
+    let path = compile_legacy(query)?;
+
+    // Peek at the first significant byte to decide whether to stream
+    let mut reader = BufReader::new(reader);
+    let is_array = starts_with_array(&mut reader)?;
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let count = if is_array {
+        let (element, rest) = split_element_selector(query).ok_or_else(|| {
+            JqrError::InvalidQuery(format!(
+                "Counting the matches in a top-level array one element at a time needs a query \
+                 starting with $[*], $.* or $[N], but the query is '{}'",
+                query
+            ))
+        })?;
+        let rest = compile_legacy(&rest)?;
+        let count_in = |i: usize, value: &Value| {
+            if element.is_some_and(|element| element != i) {
+                return 0;
+            }
+            count_found(&rest, value)
+        };
+        deserializer.deserialize_seq(CountVisitor(count_in))
+    } else {
+        Value::deserialize(&mut deserializer).map(|value| count_found(&path, &value))
+    }
+    .and_then(|count| deserializer.end().map(|_| count))
+    .map_err(|e| JqrError::invalid_json(e, false))?;
+
+    Ok(count)
+}
+
+/// The number of values `path` finds in `value`.
+fn count_found(path: &JsonPath, value: &Value) -> u64 {
+    path.find_slice(value)
+        .iter()
+        .filter(|jp_value| jp_value.has_value())
+        .count() as u64
+}
+
+/// Splits a query starting with `$[*]`, `$.*` or `$[N]` into the elements of
+/// the root array it picks (`None` for all of them) and the rest of the
+/// query, with `$` standing for an element.
+fn split_element_selector(query: &str) -> Option<(Option<usize>, String)> {
+    let after_root = query.trim().strip_prefix('$')?;
+    let (element, rest) = if let Some(rest) = after_root
+        .strip_prefix("[*]")
+        .or_else(|| after_root.strip_prefix(".*"))
+    {
+        (None, rest)
+    } else {
+        let (index, rest) = after_root.strip_prefix('[')?.split_once(']')?;
+        if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        (Some(index.parse().ok()?), rest)
+    };
+    (rest.is_empty() || rest.starts_with(['.', '['])).then(|| (element, format!("${}", rest)))
+}
+
+/// Sums a per-element match count over a JSON array, one element at a time.
+struct CountVisitor<F>(F);
+
+impl<'de, F: Fn(usize, &Value) -> u64> Visitor<'de> for CountVisitor<F> {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u64, A::Error> {
+        let mut total = 0;
+        let mut i = 0;
+        while let Some(element) = seq.next_element::<Value>()? {
+            total += (self.0)(i, &element);
+            i += 1;
+        }
+        Ok(total)
+    }
+}
@@ -0,0 +1,953 @@
+//! JSONPath as specified by RFC 9535, used by [`crate::CompiledQuery`] for
+//! [`crate::JsonPathDialect::Rfc9535`].
+
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+
+use crate::json_equal;
+
+/// Indexes and slice bounds must be I-JSON integers, in ±(2^53 - 1).
+const MAX_INT: i64 = (1 << 53) - 1;
+
+/// A parsed RFC 9535 query.
+#[derive(Debug, Clone)]
+pub(crate) struct Rfc9535Path {
+    segments: Vec<Segment>,
+}
+
+impl Rfc9535Path {
+    /// Parses a query, which must start with `$` and has no surrounding
+    /// whitespace. The error describes what is wrong and where.
+    pub(crate) fn new(query: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: query.chars().collect(),
+            position: 0,
+        };
+        if !parser.eat('$') {
+            return Err("a query must start with '$'".to_string());
+        }
+        let segments = parser.segments()?;
+        if parser.position < parser.chars.len() {
+            return Err(parser.unexpected());
+        }
+        Ok(Rfc9535Path { segments })
+    }
+
+    /// Returns the nodes the query selects, in order.
+    pub(crate) fn find<'a>(&self, json: &'a Value) -> Vec<&'a Value> {
+        select(&self.segments, json, json)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    /// `..`: the selectors apply to the node and all its descendants.
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Logical),
+}
+
+/// A query in a filter, relative to the current node (`@`) or the root
+/// (`$`).
+#[derive(Debug, Clone)]
+struct FilterQuery {
+    relative: bool,
+    segments: Vec<Segment>,
+}
+
+impl FilterQuery {
+    /// A singular query selects at most one node: it only has name and
+    /// index segments.
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| {
+            !segment.descendant
+                && matches!(
+                    segment.selectors.as_slice(),
+                    [Selector::Name(_) | Selector::Index(_)]
+                )
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Logical {
+    Or(Vec<Logical>),
+    And(Vec<Logical>),
+    Not(Box<Logical>),
+    Compare(Comparable, Comparison, Comparable),
+    /// A query used as a test: true if it selects any node.
+    Exists(FilterQuery),
+    /// A function returning a logical value.
+    Test(Box<Function>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// Something with a value, or Nothing, that can be compared.
+#[derive(Debug, Clone)]
+enum Comparable {
+    Literal(Value),
+    /// A singular query.
+    Query(FilterQuery),
+    /// A function returning a value.
+    Function(Box<Function>),
+}
+
+/// The types of function parameters and results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Value,
+    Logical,
+    Nodes,
+}
+
+#[derive(Debug, Clone)]
+enum Argument {
+    Value(Comparable),
+    Nodes(FilterQuery),
+}
+
+#[derive(Debug, Clone)]
+struct Function {
+    name: &'static str,
+    args: Vec<Argument>,
+    /// The regular expression of `match` and `search`, compiled when parsing
+    /// if it is a literal.
+    pattern: Option<Regex>,
+}
+
+/// The function extensions of RFC 9535, with their parameter and result
+/// types.
+const FUNCTIONS: [(&str, &[Type], Type); 5] = [
+    ("length", &[Type::Value], Type::Value),
+    ("count", &[Type::Nodes], Type::Value),
+    ("match", &[Type::Value, Type::Value], Type::Logical),
+    ("search", &[Type::Value, Type::Value], Type::Logical),
+    ("value", &[Type::Nodes], Type::Value),
+];
+
+fn result_type(function: &Function) -> Type {
+    FUNCTIONS
+        .iter()
+        .find(|f| f.0 == function.name)
+        .map_or(Type::Value, |f| f.2)
+}
+
+/// A parsed RFC 9535 filter expression, tested on a value that is both its
+/// root `$` and its current node `@`.
+#[derive(Debug, Clone)]
+pub(crate) struct Rfc9535Filter {
+    expr: Logical,
+}
+
+impl Rfc9535Filter {
+    /// Parses a logical expression, as found inside `[?...]`, which may have
+    /// surrounding whitespace. The error describes what is wrong and where.
+    pub(crate) fn new(expression: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: expression.chars().collect(),
+            position: 0,
+        };
+        parser.skip_whitespace();
+        let expr = parser.logical_or()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.unexpected());
+        }
+        Ok(Rfc9535Filter { expr })
+    }
+
+    /// Returns whether the expression holds for `json`.
+    pub(crate) fn matches(&self, json: &Value) -> bool {
+        test(&self.expr, json, json)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.position + i) == Some(&c))
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips whitespace if `found` follows it, and returns whether it does.
+    fn eat_after_whitespace(&mut self, found: &str) -> bool {
+        let start = self.position;
+        self.skip_whitespace();
+        if self.starts_with(found) {
+            self.position += found.chars().count();
+            true
+        } else {
+            self.position = start;
+            false
+        }
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("unexpected '{}' at column {}", c, self.position + 1),
+            None => "unexpected end of query".to_string(),
+        }
+    }
+
+    /// Parses the segments after `$` or `@`, which may be separated by
+    /// whitespace.
+    fn segments(&mut self) -> Result<Vec<Segment>, String> {
+        let mut segments = Vec::new();
+        loop {
+            let start = self.position;
+            self.skip_whitespace();
+            match self.peek() {
+                Some('.' | '[') => segments.push(self.segment()?),
+                _ => {
+                    self.position = start;
+                    return Ok(segments);
+                }
+            }
+        }
+    }
+
+    fn segment(&mut self) -> Result<Segment, String> {
+        if self.eat('[') {
+            return Ok(Segment {
+                descendant: false,
+                selectors: self.bracketed()?,
+            });
+        }
+        self.expect('.')?;
+        let descendant = self.eat('.');
+        let selectors = if self.eat('*') {
+            vec![Selector::Wildcard]
+        } else if descendant && self.eat('[') {
+            self.bracketed()?
+        } else if self.peek().is_some_and(is_name_first) {
+            let start = self.position;
+            while self
+                .peek()
+                .is_some_and(|c| is_name_first(c) || c.is_ascii_digit())
+            {
+                self.position += 1;
+            }
+            vec![Selector::Name(
+                self.chars[start..self.position].iter().collect(),
+            )]
+        } else {
+            return Err(self.unexpected());
+        };
+        Ok(Segment {
+            descendant,
+            selectors,
+        })
+    }
+
+    /// Parses the selectors of a bracketed selection after the `[`.
+    fn bracketed(&mut self) -> Result<Vec<Selector>, String> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.selector()?);
+            if self.eat_after_whitespace("]") {
+                return Ok(selectors);
+            }
+            self.skip_whitespace();
+            self.expect(',')?;
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, String> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.position += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.position += 1;
+                self.skip_whitespace();
+                Ok(Selector::Filter(self.logical_or()?))
+            }
+            Some('-' | '0'..='9' | ':') => {
+                let start = self.integer()?;
+                if !self.eat_after_whitespace(":") {
+                    return start.map(Selector::Index).ok_or_else(|| self.unexpected());
+                }
+                self.skip_whitespace();
+                let end = self.integer()?;
+                let step = if self.eat_after_whitespace(":") {
+                    self.skip_whitespace();
+                    self.integer()?
+                } else {
+                    None
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses an integer if one starts here: no leading zeros, no `-0`, and
+    /// within the I-JSON range.
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        let start = self.position;
+        let negative = self.eat('-');
+        let digits_start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[digits_start..self.position].iter().collect();
+        if digits.is_empty() {
+            if negative {
+                return Err(self.unexpected());
+            }
+            return Ok(None);
+        }
+        if (digits.len() > 1 && digits.starts_with('0')) || (negative && digits == "0") {
+            return Err(format!("invalid integer at column {}", start + 1));
+        }
+        let value = digits
+            .parse::<i64>()
+            .ok()
+            .filter(|value| *value <= MAX_INT)
+            .ok_or_else(|| format!("integer out of range at column {}", start + 1))?;
+        Ok(Some(if negative { -value } else { value }))
+    }
+
+    /// Parses a single- or double-quoted string literal.
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.position;
+        let quote = self.peek().ok_or_else(|| self.unexpected())?;
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| format!("unterminated string at column {}", start + 1))?;
+            self.position += 1;
+            match c {
+                _ if c == quote => return Ok(text),
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.unexpected())?;
+                    self.position += 1;
+                    text.push(match escaped {
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        '/' | '\\' => escaped,
+                        _ if escaped == quote => escaped,
+                        'u' => self.unicode_escape()?,
+                        _ => {
+                            return Err(format!(
+                                "invalid escape '\\{}' at column {}",
+                                escaped, self.position
+                            ))
+                        }
+                    });
+                }
+                '\u{0}'..='\u{1f}' => {
+                    return Err(format!(
+                        "control character in string at column {}",
+                        self.position
+                    ))
+                }
+                _ => text.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a `\u` escape, and of the low surrogate
+    /// escape following a high one.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let column = self.position - 1;
+        let invalid = || format!("invalid unicode escape at column {}", column);
+        let hex = |parser: &mut Parser| {
+            let digits: String = parser
+                .chars
+                .get(parser.position..parser.position + 4)
+                .ok_or_else(invalid)?
+                .iter()
+                .collect();
+            parser.position += 4;
+            u32::from_str_radix(&digits, 16).map_err(|_| invalid())
+        };
+        let high = hex(self)?;
+        let code = match high {
+            0xd800..=0xdbff => {
+                if !self.starts_with("\\u") {
+                    return Err(invalid());
+                }
+                self.position += 2;
+                let low = hex(self)?;
+                if !(0xdc00..=0xdfff).contains(&low) {
+                    return Err(invalid());
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            }
+            0xdc00..=0xdfff => return Err(invalid()),
+            _ => high,
+        };
+        char::from_u32(code).ok_or_else(invalid)
+    }
+
+    fn logical_or(&mut self) -> Result<Logical, String> {
+        let mut operands = vec![self.logical_and()?];
+        while self.eat_after_whitespace("||") {
+            self.skip_whitespace();
+            operands.push(self.logical_and()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Logical::Or(operands),
+        })
+    }
+
+    fn logical_and(&mut self) -> Result<Logical, String> {
+        let mut operands = vec![self.basic()?];
+        while self.eat_after_whitespace("&&") {
+            self.skip_whitespace();
+            operands.push(self.basic()?);
+        }
+        Ok(match operands.len() {
+            1 => operands.pop().unwrap(),
+            _ => Logical::And(operands),
+        })
+    }
+
+    /// Parses a parenthesized expression, a comparison or a test, with an
+    /// optional `!` before a parenthesized expression or a test.
+    fn basic(&mut self) -> Result<Logical, String> {
+        if self.eat('!') {
+            self.skip_whitespace();
+            let operand = if self.peek() == Some('(') {
+                self.parenthesized()?
+            } else {
+                let column = self.position + 1;
+                let operand = self.operand()?;
+                self.test(operand, column)?
+            };
+            return Ok(Logical::Not(Box::new(operand)));
+        }
+        if self.peek() == Some('(') {
+            return self.parenthesized();
+        }
+
+        let column = self.position + 1;
+        let left = self.operand()?;
+        let start = self.position;
+        self.skip_whitespace();
+        let Some(comparison) = self.comparison() else {
+            self.position = start;
+            return self.test(left, column);
+        };
+        self.skip_whitespace();
+        let right_column = self.position + 1;
+        let right = self.operand()?;
+        Ok(Logical::Compare(
+            comparable(left, column)?,
+            comparison,
+            comparable(right, right_column)?,
+        ))
+    }
+
+    fn parenthesized(&mut self) -> Result<Logical, String> {
+        self.expect('(')?;
+        self.skip_whitespace();
+        let expr = self.logical_or()?;
+        self.skip_whitespace();
+        self.expect(')')?;
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Option<Comparison> {
+        let (comparison, length) = [
+            ("==", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+            ("<=", Comparison::LessEqual),
+            (">=", Comparison::GreaterEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+        ]
+        .into_iter()
+        .find(|(op, _)| self.starts_with(op))
+        .map(|(op, comparison)| (comparison, op.len()))?;
+        self.position += length;
+        Some(comparison)
+    }
+
+    /// Turns an operand not followed by a comparison into a test.
+    fn test(&self, operand: Operand, column: usize) -> Result<Logical, String> {
+        match operand {
+            Operand::Query(query) => Ok(Logical::Exists(query)),
+            Operand::Function(function) if result_type(&function) == Type::Logical => {
+                Ok(Logical::Test(function))
+            }
+            Operand::Function(function) => Err(format!(
+                "{}() at column {} returns a value, which must be compared",
+                function.name, column
+            )),
+            Operand::Literal(_) => {
+                Err(format!("the literal at column {} must be compared", column))
+            }
+        }
+    }
+
+    /// Parses a literal, a query or a function call.
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.peek() {
+            Some('@' | '$') => Ok(Operand::Query(self.filter_query()?)),
+            Some('\'' | '"') => Ok(Operand::Literal(Value::String(self.string()?))),
+            Some('-' | '0'..='9') => Ok(Operand::Literal(self.number()?)),
+            Some('a'..='z') => {
+                let start = self.position;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                if self.peek() == Some('(') {
+                    return Ok(Operand::Function(Box::new(self.function(&name, start)?)));
+                }
+                match name.as_str() {
+                    "true" => Ok(Operand::Literal(Value::Bool(true))),
+                    "false" => Ok(Operand::Literal(Value::Bool(false))),
+                    "null" => Ok(Operand::Literal(Value::Null)),
+                    _ => {
+                        self.position = start;
+                        Err(self.unexpected())
+                    }
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn filter_query(&mut self) -> Result<FilterQuery, String> {
+        let relative = self.eat('@');
+        if !relative {
+            self.expect('$')?;
+        }
+        Ok(FilterQuery {
+            relative,
+            segments: self.segments()?,
+        })
+    }
+
+    /// Parses a JSON number literal.
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        let invalid = || format!("invalid number at column {}", start + 1);
+        self.eat('-');
+        let digits = |parser: &mut Parser| {
+            let from = parser.position;
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.position += 1;
+            }
+            parser.position - from
+        };
+        let integer_start = self.position;
+        let integer_digits = digits(self);
+        if integer_digits == 0 || (integer_digits > 1 && self.chars[integer_start] == '0') {
+            return Err(invalid());
+        }
+        if self.eat('.') && digits(self) == 0 {
+            return Err(invalid());
+        }
+        if self.eat('e') || self.eat('E') {
+            let _ = self.eat('+') || self.eat('-');
+            if digits(self) == 0 {
+                return Err(invalid());
+            }
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        serde_json::from_str(&text).map_err(|_| invalid())
+    }
+
+    /// Parses the arguments of a call to `name`, which starts at `start`,
+    /// checking them against the parameter types.
+    fn function(&mut self, name: &str, start: usize) -> Result<Function, String> {
+        let Some(&(name, parameters, _)) = FUNCTIONS.iter().find(|f| f.0 == name) else {
+            return Err(format!(
+                "unknown function {}() at column {}",
+                name,
+                start + 1
+            ));
+        };
+        self.expect('(')?;
+        let mut args = Vec::new();
+        self.skip_whitespace();
+        if self.peek() != Some(')') {
+            loop {
+                let column = self.position + 1;
+                let operand = self.operand()?;
+                let arg = match parameters.get(args.len()) {
+                    Some(Type::Nodes) => match operand {
+                        Operand::Query(query) => Argument::Nodes(query),
+                        _ => {
+                            return Err(format!(
+                                "argument {} of {}() at column {} must be a query",
+                                args.len() + 1,
+                                name,
+                                column
+                            ))
+                        }
+                    },
+                    _ => Argument::Value(comparable(operand, column)?),
+                };
+                args.push(arg);
+                if !self.eat_after_whitespace(",") {
+                    break;
+                }
+                self.skip_whitespace();
+            }
+        }
+        self.skip_whitespace();
+        self.expect(')')?;
+        if args.len() != parameters.len() {
+            return Err(format!(
+                "{}() takes {} argument{}, not {}",
+                name,
+                parameters.len(),
+                if parameters.len() == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        let pattern = match args.get(1) {
+            Some(Argument::Value(Comparable::Literal(Value::String(pattern)))) => {
+                iregexp(pattern, name == "match")
+            }
+            _ => None,
+        };
+        Ok(Function {
+            name,
+            args,
+            pattern,
+        })
+    }
+}
+
+/// An operand before its use as a comparable or a test is known.
+enum Operand {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(Box<Function>),
+}
+
+/// Checks that an operand has a value: a literal, a singular query or a
+/// function returning a value.
+fn comparable(operand: Operand, column: usize) -> Result<Comparable, String> {
+    match operand {
+        Operand::Literal(value) => Ok(Comparable::Literal(value)),
+        Operand::Query(query) if query.is_singular() => Ok(Comparable::Query(query)),
+        Operand::Query(_) => Err(format!(
+            "the query at column {} can select several nodes, so it has no single value",
+            column
+        )),
+        Operand::Function(function) if result_type(&function) == Type::Value => {
+            Ok(Comparable::Function(function))
+        }
+        Operand::Function(function) => Err(format!(
+            "{}() at column {} returns a logical value, which cannot be compared",
+            function.name, column
+        )),
+    }
+}
+
+fn is_name_first(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+/// Compiles an I-Regexp (RFC 9485) pattern, matching whole strings if
+/// `whole` is set. I-Regexp's `.` does not match line breaks, where the
+/// regex crate's only excludes `\n`.
+fn iregexp(pattern: &str, whole: bool) -> Option<Regex> {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                translated.push(c);
+                translated.push(chars.next()?);
+            }
+            '[' => {
+                in_class = true;
+                translated.push(c);
+            }
+            ']' => {
+                in_class = false;
+                translated.push(c);
+            }
+            '.' if !in_class => translated.push_str("[^\\n\\r]"),
+            _ => translated.push(c),
+        }
+    }
+    let translated = if whole {
+        format!("\\A(?:{})\\z", translated)
+    } else {
+        translated
+    };
+    Regex::new(&translated).ok()
+}
+
+/// Applies segments to `input`, with `root` as the node of `$`.
+fn select<'a>(segments: &[Segment], input: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+    let mut nodes = vec![input];
+    for segment in segments {
+        let mut selected = Vec::new();
+        for node in nodes {
+            if segment.descendant {
+                let mut visited = Vec::new();
+                descendants(node, &mut visited);
+                for node in visited {
+                    apply_selectors(&segment.selectors, node, root, &mut selected);
+                }
+            } else {
+                apply_selectors(&segment.selectors, node, root, &mut selected);
+            }
+        }
+        nodes = selected;
+    }
+    nodes
+}
+
+/// Collects `node` and its descendants, in document order.
+fn descendants<'a>(node: &'a Value, visited: &mut Vec<&'a Value>) {
+    visited.push(node);
+    match node {
+        Value::Array(items) => items.iter().for_each(|item| descendants(item, visited)),
+        Value::Object(map) => map.values().for_each(|value| descendants(value, visited)),
+        _ => {}
+    }
+}
+
+fn children(node: &Value) -> Vec<&Value> {
+    match node {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(map) => map.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn apply_selectors<'a>(
+    selectors: &[Selector],
+    node: &'a Value,
+    root: &'a Value,
+    selected: &mut Vec<&'a Value>,
+) {
+    for selector in selectors {
+        match selector {
+            Selector::Name(name) => selected.extend(node.get(name)),
+            Selector::Wildcard => selected.extend(children(node)),
+            Selector::Index(index) => {
+                if let Value::Array(items) = node {
+                    let index = if *index < 0 {
+                        items.len() as i64 + index
+                    } else {
+                        *index
+                    };
+                    selected.extend(usize::try_from(index).ok().and_then(|i| items.get(i)));
+                }
+            }
+            Selector::Slice(start, end, step) => {
+                if let Value::Array(items) = node {
+                    selected
+                        .extend(slice(items.len() as i64, *start, *end, *step).map(|i| &items[i]));
+                }
+            }
+            Selector::Filter(expr) => selected.extend(
+                children(node)
+                    .into_iter()
+                    .filter(|child| test(expr, child, root)),
+            ),
+        }
+    }
+}
+
+/// The indexes a slice selects in an array of `length` items, following
+/// section 2.3.4.2.2 of the RFC.
+fn slice(
+    length: i64,
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+) -> impl Iterator<Item = usize> {
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i >= 0 { i } else { length + i };
+    let (mut i, bound) = if step >= 0 {
+        (
+            normalize(start.unwrap_or(0)).clamp(0, length),
+            normalize(end.unwrap_or(length)).clamp(0, length),
+        )
+    } else {
+        (
+            normalize(start.unwrap_or(length - 1)).clamp(-1, length - 1),
+            normalize(end.unwrap_or(-length - 1)).clamp(-1, length - 1),
+        )
+    };
+    std::iter::from_fn(move || {
+        let inside = match step {
+            0 => false,
+            _ if step > 0 => i < bound,
+            _ => bound < i,
+        };
+        if !inside {
+            return None;
+        }
+        let index = i as usize;
+        i += step;
+        Some(index)
+    })
+}
+
+fn test(expr: &Logical, current: &Value, root: &Value) -> bool {
+    match expr {
+        Logical::Or(operands) => operands.iter().any(|expr| test(expr, current, root)),
+        Logical::And(operands) => operands.iter().all(|expr| test(expr, current, root)),
+        Logical::Not(expr) => !test(expr, current, root),
+        Logical::Exists(query) => !query_nodes(query, current, root).is_empty(),
+        Logical::Test(function) => {
+            let text = |arg: &Argument| match arg {
+                Argument::Value(comparable) => match value_of(comparable, current, root) {
+                    Some(Cow::Borrowed(Value::String(text))) => Some(text.clone()),
+                    Some(Cow::Owned(Value::String(text))) => Some(text),
+                    _ => None,
+                },
+                Argument::Nodes(_) => None,
+            };
+            let (Some(subject), Some(pattern)) = (text(&function.args[0]), text(&function.args[1]))
+            else {
+                return false;
+            };
+            let regex = match &function.pattern {
+                Some(regex) => Cow::Borrowed(regex),
+                None => match iregexp(&pattern, function.name == "match") {
+                    Some(regex) => Cow::Owned(regex),
+                    None => return false,
+                },
+            };
+            regex.is_match(&subject)
+        }
+        Logical::Compare(left, comparison, right) => {
+            let left = value_of(left, current, root);
+            let right = value_of(right, current, root);
+            let (left, right) = (left.as_deref(), right.as_deref());
+            match comparison {
+                Comparison::Equal => equal(left, right),
+                Comparison::NotEqual => !equal(left, right),
+                Comparison::Less => less(left, right),
+                Comparison::LessEqual => less(left, right) || equal(left, right),
+                Comparison::Greater => less(right, left),
+                Comparison::GreaterEqual => less(right, left) || equal(left, right),
+            }
+        }
+    }
+}
+
+fn query_nodes<'a>(query: &FilterQuery, current: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+    let input = if query.relative { current } else { root };
+    select(&query.segments, input, root)
+}
+
+/// Evaluates a comparable to its value, or `None` for Nothing.
+fn value_of<'a>(
+    comparable: &Comparable,
+    current: &'a Value,
+    root: &'a Value,
+) -> Option<Cow<'a, Value>> {
+    match comparable {
+        Comparable::Literal(value) => Some(Cow::Owned(value.clone())),
+        Comparable::Query(query) => query_nodes(query, current, root)
+            .first()
+            .map(|node| Cow::Borrowed(*node)),
+        Comparable::Function(function) => match (function.name, &function.args[0]) {
+            ("length", Argument::Value(arg)) => {
+                let length = match value_of(arg, current, root)?.as_ref() {
+                    Value::String(text) => text.chars().count(),
+                    Value::Array(items) => items.len(),
+                    Value::Object(map) => map.len(),
+                    _ => return None,
+                };
+                Some(Cow::Owned(Value::from(length)))
+            }
+            ("count", Argument::Nodes(query)) => Some(Cow::Owned(Value::from(
+                query_nodes(query, current, root).len(),
+            ))),
+            ("value", Argument::Nodes(query)) => match query_nodes(query, current, root).as_slice()
+            {
+                [node] => Some(Cow::Borrowed(*node)),
+                _ => None,
+            },
+            _ => None,
+        },
+    }
+}
+
+/// Equality of values or Nothing, with numbers equal by value.
+fn equal(left: Option<&Value>, right: Option<&Value>) -> bool {
+    match (left, right) {
+        (None, None) => true,
+        (Some(left), Some(right)) => json_equal(left, right),
+        _ => false,
+    }
+}
+
+/// `<` holds between two numbers or two strings; anything else is false.
+fn less(left: Option<&Value>, right: Option<&Value>) -> bool {
+    match (left, right) {
+        (Some(left @ Value::Number(_)), Some(right @ Value::Number(_))) => {
+            left.as_f64() < right.as_f64()
+        }
+        // UTF-8 byte order is Unicode scalar value order
+        (Some(Value::String(left)), Some(Value::String(right))) => left < right,
+        _ => false,
+    }
+}
@@ -0,0 +1,322 @@
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::SeedableRng;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::{find_pointer_paths, jq, json_type, shorthand_to_jsonpath, CompiledQuery, JqrError};
+
+/// One key of a `--sort-by` specification: a query run on each element and
+/// the direction to sort its values in.
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    query: CompiledQuery,
+    descending: bool,
+}
+
+impl SortKey {
+    /// Creates a sort key from a JSONPath query run on each element.
+    ///
+    /// # Errors
+    ///
+    /// * Returns [`JqrError::InvalidQuery`] if `query` is not valid JSONPath.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use jqr_core::SortKey;
+    ///
+    /// let key = SortKey::new("$.age", true).unwrap();
+    /// assert_eq!(key.query(), "$.age");
+    /// assert!(key.is_descending());
+    /// ```
+    pub fn new(query: &str, descending: bool) -> Result<Self, JqrError> {
+        Ok(SortKey {
+            query: CompiledQuery::new(query)?,
+            descending,
+        })
+    }
+
+    /// Returns the query text of this key.
+    pub fn query(&self) -> &str {
+        self.query.as_str()
+    }
+
+    /// Returns `true` if this key sorts from the largest value down.
+    pub fn is_descending(&self) -> bool {
+        self.descending
+    }
+}
+
+/// Parses a sort specification such as `"$.age:desc,$.name"`.
+///
+/// Keys are separated by commas outside brackets and quotes, and each may end
+/// with `:asc` (the default) or `:desc`. A key may be written without the
+/// `$` prefix, as `age` or `.age`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidArgument`] if a key is empty.
+/// * Returns [`JqrError::InvalidQuery`] if a key is not valid JSONPath.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::parse_sort_keys;
+///
+/// let keys = parse_sort_keys("$.age:desc, name").unwrap();
+/// assert_eq!(keys.len(), 2);
+/// assert_eq!((keys[0].query(), keys[0].is_descending()), ("$.age", true));
+/// assert_eq!((keys[1].query(), keys[1].is_descending()), ("$.name", false));
+///
+/// // Commas inside a filter do not separate keys
+/// assert_eq!(parse_sort_keys("$.tags[?(@ in ['a','b'])]").unwrap().len(), 1);
+/// assert!(parse_sort_keys("$.age,").is_err());
+/// ```
+pub fn parse_sort_keys(spec: &str) -> Result<Vec<SortKey>, JqrError> {
+    split_keys(spec)
+        .into_iter()
+        .map(|key| {
+            let key = key.trim();
+            let (query, descending) = if let Some(query) = key.strip_suffix(":desc") {
+                (query, true)
+            } else {
+                (key.strip_suffix(":asc").unwrap_or(key), false)
+            };
+            if query.trim().is_empty() {
+                return Err(JqrError::InvalidArgument(format!(
+                    "Invalid sort key '{}' in '{}' (expected PATH, PATH:asc or PATH:desc)",
+                    key, spec
+                )));
+            }
+            SortKey::new(&shorthand_to_jsonpath(query), descending)
+        })
+        .collect()
+}
+
+/// Splits a sort specification at the commas outside brackets and quotes.
+fn split_keys(spec: &str) -> Vec<&str> {
+    let mut keys = Vec::new();
+    let (mut depth, mut quote, mut start) = (0usize, None, 0);
+    for (i, c) in spec.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[' | '(') => depth += 1,
+            (None, ']' | ')') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                keys.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    keys.push(&spec[start..]);
+    keys
+}
+
+/// Sorts an array by its elements' values at one or more keys.
+///
+/// Elements are compared by the first key, then by the next on a tie, and
+/// elements that tie on every key keep their order. A key that matches
+/// nothing in an element counts as `null`, and one that matches several
+/// values as the array of them. Values of different types are ordered as in
+/// jq: `null`, `false`, `true`, numbers, strings, arrays, then objects.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_sort_keys, sort_by};
+/// use serde_json::json;
+///
+/// let mut people = json!([
+///     {"name": "Carol", "age": 30},
+///     {"name": "Alice", "age": 25},
+///     {"name": "Bob", "age": 30},
+///     {"name": "Dan"}
+/// ]);
+/// sort_by(&mut people, &parse_sort_keys("$.age:desc,$.name").unwrap()).unwrap();
+///
+/// assert_eq!(
+///     people,
+///     json!([
+///         {"name": "Bob", "age": 30},
+///         {"name": "Carol", "age": 30},
+///         {"name": "Alice", "age": 25},
+///         {"name": "Dan"}
+///     ])
+/// );
+/// assert!(sort_by(&mut json!({"a": 1}), &parse_sort_keys("$.a").unwrap()).is_err());
+/// ```
+pub fn sort_by(value: &mut Value, keys: &[SortKey]) -> Result<(), JqrError> {
+    let items = elements(value, "Sorting")?;
+    let mut keyed: Vec<(Vec<Value>, Value)> = std::mem::take(items)
+        .into_iter()
+        .map(|item| {
+            let values = keys
+                .iter()
+                .map(|key| key.query.apply(&item).unwrap_or(Value::Null))
+                .collect();
+            (values, item)
+        })
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        keys.iter()
+            .zip(a.iter().zip(b))
+            .map(|(key, (a, b))| {
+                let ordering = jq::compare(a, b);
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+    *items = keyed.into_iter().map(|(_, item)| item).collect();
+    Ok(())
+}
+
+/// Sorts every array a JSONPath query matches inside a document, in place,
+/// as [`sort_by`] does.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidQuery`] if `target` is not valid JSONPath.
+/// * Returns [`JqrError::UnsupportedValue`] if `target` matches a value that
+///   is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_sort_keys, sort_at};
+/// use serde_json::json;
+///
+/// let mut doc = json!({"team": "core", "members": [{"name": "Bob"}, {"name": "Alice"}]});
+/// sort_at(&mut doc, "$.members", &parse_sort_keys("$.name").unwrap()).unwrap();
+///
+/// assert_eq!(
+///     doc,
+///     json!({"team": "core", "members": [{"name": "Alice"}, {"name": "Bob"}]})
+/// );
+/// ```
+pub fn sort_at(value: &mut Value, target: &str, keys: &[SortKey]) -> Result<(), JqrError> {
+    for pointer in find_pointer_paths(value, target)? {
+        if let Some(array) = value.pointer_mut(&pointer) {
+            sort_by(array, keys)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reverses the order of an array's elements.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::reverse;
+/// use serde_json::json;
+///
+/// let mut value = json!([1, "two", [3]]);
+/// reverse(&mut value).unwrap();
+/// assert_eq!(value, json!([[3], "two", 1]));
+/// ```
+pub fn reverse(value: &mut Value) -> Result<(), JqrError> {
+    elements(value, "Reversing")?.reverse();
+    Ok(())
+}
+
+/// Shuffles an array's elements into a random order.
+///
+/// The same `seed` always gives the same order for the same array, for
+/// repeatable fixtures; without one, every shuffle differs.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::shuffle;
+/// use serde_json::json;
+///
+/// let mut first = json!([1, 2, 3, 4, 5, 6, 7, 8]);
+/// let mut second = first.clone();
+/// shuffle(&mut first, Some(42)).unwrap();
+/// shuffle(&mut second, Some(42)).unwrap();
+///
+/// assert_eq!(first, second);
+/// assert_eq!(first.as_array().unwrap().len(), 8);
+/// ```
+pub fn shuffle(value: &mut Value, seed: Option<u64>) -> Result<(), JqrError> {
+    elements(value, "Shuffling")?.shuffle(&mut rng(seed));
+    Ok(())
+}
+
+/// Keeps `n` elements of an array, chosen at random, in their original
+/// order. An array of at most `n` elements is kept whole.
+///
+/// Like [`shuffle`], the same `seed` always picks the same elements.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::sample;
+/// use serde_json::json;
+///
+/// let mut value = json!([10, 20, 30, 40, 50]);
+/// sample(&mut value, 2, None).unwrap();
+///
+/// let kept = value.as_array().unwrap();
+/// assert_eq!(kept.len(), 2);
+/// assert!(kept[0].as_u64() < kept[1].as_u64(), "the input order is kept");
+/// ```
+pub fn sample(value: &mut Value, n: usize, seed: Option<u64>) -> Result<(), JqrError> {
+    let items = elements(value, "Sampling")?;
+    if items.len() <= n {
+        return Ok(());
+    }
+    let mut keep = vec![false; items.len()];
+    for i in index::sample(&mut rng(seed), items.len(), n) {
+        keep[i] = true;
+    }
+    let mut keep = keep.into_iter();
+    items.retain(|_| keep.next().unwrap_or(false));
+    Ok(())
+}
+
+/// Returns the elements of an array, or an error naming `operation` if
+/// `value` is not one.
+fn elements<'a>(value: &'a mut Value, operation: &str) -> Result<&'a mut Vec<Value>, JqrError> {
+    match value {
+        Value::Array(items) => Ok(items),
+        other => Err(JqrError::UnsupportedValue(format!(
+            "{} requires an array, but the value is of type {}",
+            operation,
+            json_type(other)
+        ))),
+    }
+}
+
+/// A random number generator, repeatable if seeded.
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    }
+}
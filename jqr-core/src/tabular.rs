@@ -0,0 +1,256 @@
+use serde_json::{Map, Value};
+
+use crate::{coerce_boolean_strings, coerce_numeric_strings, JqrError};
+
+/// Controls how CSV and TSV tables are read and written.
+///
+/// The defaults are comma-separated fields, every cell read as a string and
+/// all columns written. Options are set with chained builder methods.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{csv_to_json, CsvOptions};
+/// use serde_json::json;
+///
+/// let options = CsvOptions::new().delimiter(b'\t').infer_types(true);
+/// let value = csv_to_json("id\tname\n1\tAlice\n", &options).unwrap();
+///
+/// assert_eq!(value, json!([{"id": 1, "name": "Alice"}]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    delimiter: u8,
+    infer_types: bool,
+    columns: Vec<String>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: b',',
+            infer_types: false,
+            columns: Vec::new(),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the field delimiter, e.g. `b'\t'` for TSV.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Reads cells that are exactly JSON numbers as numbers, `true` and
+    /// `false` as booleans and empty cells as `null`, instead of as strings.
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    /// Writes only these columns, in this order, instead of every column in
+    /// order of first appearance.
+    pub fn columns(mut self, columns: Vec<String>) -> Self {
+        self.columns = columns;
+        self
+    }
+}
+
+/// Parses a CSV (or, with another delimiter, TSV) table into an array of
+/// objects, one per row, keyed by the header row in column order.
+///
+/// Quoted fields may contain delimiters and line breaks. A leading byte order
+/// mark, as written by spreadsheet exports, is ignored. Numbers are only
+/// inferred by the rules of [`coerce_numeric_strings`], so identifiers such as
+/// `"007"` stay strings.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidCsv`] if a row has a different number of
+///   fields than the header, a field is not valid UTF-8, or the header
+///   repeats a column name.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{csv_to_json, CsvOptions};
+/// use serde_json::json;
+///
+/// let csv = "name,zip,active,note\n\"Smith, J\",02134,true,\n";
+///
+/// assert_eq!(
+///     csv_to_json(csv, &CsvOptions::new()).unwrap(),
+///     json!([{"name": "Smith, J", "zip": "02134", "active": "true", "note": ""}])
+/// );
+/// assert_eq!(
+///     csv_to_json(csv, &CsvOptions::new().infer_types(true)).unwrap(),
+///     json!([{"name": "Smith, J", "zip": "02134", "active": true, "note": null}])
+/// );
+/// ```
+pub fn csv_to_json(content: &str, options: &CsvOptions) -> Result<Value, JqrError> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .from_reader(content.as_bytes());
+    let invalid = |e: csv::Error| JqrError::InvalidCsv(e.to_string());
+
+    let headers = reader.headers().map_err(invalid)?.clone();
+    for (i, header) in headers.iter().enumerate() {
+        if headers.iter().take(i).any(|previous| previous == header) {
+            return Err(JqrError::InvalidCsv(format!(
+                "the header repeats the column '{}'",
+                header
+            )));
+        }
+    }
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(invalid)?;
+        let row: Map<String, Value> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, field)| (header.to_string(), cell_value(field, options)))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+
+    Ok(Value::Array(rows))
+}
+
+fn cell_value(field: &str, options: &CsvOptions) -> Value {
+    let mut value = Value::String(field.to_string());
+    if options.infer_types {
+        if field.is_empty() {
+            return Value::Null;
+        }
+        coerce_numeric_strings(&mut value);
+        coerce_boolean_strings(&mut value);
+    }
+    value
+}
+
+/// Writes an array of objects (or a single object) as a CSV table with a
+/// header row, or as TSV with another delimiter.
+///
+/// Nested objects and arrays are flattened into dotted and indexed column
+/// names (`address.city`, `tags[0]`). The columns are the union of all
+/// flattened keys in order of first appearance, unless
+/// [`CsvOptions::columns`] picks them. Strings are written without quotes
+/// where possible, `null` and missing values as empty cells, and empty arrays
+/// or objects as compact JSON.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if the value is not an object or
+///   an array of objects.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{json_to_csv, CsvOptions};
+/// use serde_json::json;
+///
+/// let value = json!([
+///     {"name": "Alice", "address": {"city": "Oslo"}, "tags": ["a", "b"]},
+///     {"name": "Smith, J", "tags": []}
+/// ]);
+///
+/// assert_eq!(
+///     json_to_csv(&value, &CsvOptions::new()).unwrap(),
+///     "name,address.city,tags[0],tags[1],tags\n\
+///      Alice,Oslo,a,b,\n\
+///      \"Smith, J\",,,,[]\n"
+/// );
+///
+/// let options = CsvOptions::new().columns(vec!["tags[0]".into(), "name".into()]);
+/// assert_eq!(
+///     json_to_csv(&value, &options).unwrap(),
+///     "tags[0],name\na,Alice\n,\"Smith, J\"\n"
+/// );
+/// ```
+pub fn json_to_csv(value: &Value, options: &CsvOptions) -> Result<String, JqrError> {
+    let objects = match value {
+        Value::Object(_) => std::slice::from_ref(value),
+        Value::Array(items) if items.iter().all(Value::is_object) => items.as_slice(),
+        _ => {
+            return Err(JqrError::UnsupportedValue(
+                "CSV output requires an object or an array of objects".to_string(),
+            ))
+        }
+    };
+
+    let rows: Vec<Map<String, Value>> = objects
+        .iter()
+        .map(|object| {
+            let mut cells = Map::new();
+            for (key, child) in object.as_object().unwrap() {
+                collect_cells(key.clone(), child, &mut cells);
+            }
+            cells
+        })
+        .collect();
+
+    let columns = if options.columns.is_empty() {
+        let mut columns: Vec<String> = Vec::new();
+        for row in &rows {
+            for key in row.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+        columns
+    } else {
+        options.columns.clone()
+    };
+
+    // An empty table has no columns to write a header for
+    if columns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(Vec::new());
+    let serialization = |e: csv::Error| JqrError::Serialization(e.to_string());
+    writer.write_record(&columns).map_err(serialization)?;
+    for row in &rows {
+        let record = columns.iter().map(|column| match row.get(column) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        });
+        writer.write_record(record).map_err(serialization)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| JqrError::Serialization(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| JqrError::Serialization(e.to_string()))
+}
+
+/// Flattens `value` below `key` into cells keyed by column name.
+fn collect_cells(key: String, value: &Value, cells: &mut Map<String, Value>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (child_key, child) in map {
+                collect_cells(format!("{}.{}", key, child_key), child, cells);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                collect_cells(format!("{}[{}]", key, i), item, cells);
+            }
+        }
+        leaf => {
+            cells.insert(key, leaf.clone());
+        }
+    }
+}
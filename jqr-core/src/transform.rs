@@ -0,0 +1,615 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::percent_decode_str;
+use serde_json::{Map, Number, Value};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::{sort_object_keys, CompiledQuery, JqrError};
+
+/// A named string transform that can be applied to query results.
+///
+/// Transforms are selected on the command line with `--apply`, e.g.
+/// `--apply base64d,trim,upper`, and are applied in the order given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    /// Decode a standard (padded) base64 string as UTF-8 text (`base64d`).
+    Base64Decode,
+    /// Strip leading and trailing whitespace (`trim`).
+    Trim,
+    /// Convert to uppercase (`upper`).
+    Upper,
+    /// Convert to lowercase (`lower`).
+    Lower,
+    /// Decode `%XX` percent-escapes as UTF-8 text (`urldecode`).
+    UrlDecode,
+}
+
+impl FromStr for Transform {
+    type Err = JqrError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim() {
+            "base64d" => Ok(Transform::Base64Decode),
+            "trim" => Ok(Transform::Trim),
+            "upper" => Ok(Transform::Upper),
+            "lower" => Ok(Transform::Lower),
+            "urldecode" => Ok(Transform::UrlDecode),
+            other => Err(JqrError::InvalidArgument(format!(
+                "Unknown transform '{}' (expected one of: base64d, trim, upper, lower, urldecode)",
+                other
+            ))),
+        }
+    }
+}
+
+impl Transform {
+    /// Applies this transform to a single string.
+    fn apply_str(self, input: &str) -> Result<String, JqrError> {
+        match self {
+            Transform::Base64Decode => {
+                let bytes = STANDARD
+                    .decode(input)
+                    .map_err(|e| JqrError::Transform(format!("base64d: {}", e)))?;
+                String::from_utf8(bytes).map_err(|e| JqrError::Transform(format!("base64d: {}", e)))
+            }
+            Transform::Trim => Ok(input.trim().to_string()),
+            Transform::Upper => Ok(input.to_uppercase()),
+            Transform::Lower => Ok(input.to_lowercase()),
+            Transform::UrlDecode => percent_decode_str(input)
+                .decode_utf8()
+                .map(|s| s.into_owned())
+                .map_err(|e| JqrError::Transform(format!("urldecode: {}", e))),
+        }
+    }
+}
+
+/// Parses a comma-separated list of transform names such as `"base64d,trim,upper"`.
+///
+/// # Errors
+///
+/// Returns [`JqrError::InvalidArgument`] naming the first unknown transform.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_transforms, Transform};
+///
+/// let transforms = parse_transforms("trim,upper").unwrap();
+/// assert_eq!(transforms, vec![Transform::Trim, Transform::Upper]);
+/// ```
+pub fn parse_transforms(spec: &str) -> Result<Vec<Transform>, JqrError> {
+    spec.split(',').map(Transform::from_str).collect()
+}
+
+/// Applies a pipeline of string transforms to a query result.
+///
+/// If the value is a string, each transform is applied to it in order. If the
+/// value is an array (as produced by a query with multiple matches), every
+/// string element is transformed the same way. All other values, including
+/// strings nested inside objects, pass through untouched.
+///
+/// # Errors
+///
+/// Returns [`JqrError::Transform`] if a transform cannot be applied, for example when
+/// `base64d` is given a string that is not valid base64.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{apply_transforms, Transform};
+/// use serde_json::json;
+///
+/// let value = json!(["  aGVsbG8= ", 42]);
+/// let result = apply_transforms(value, &[Transform::Trim, Transform::Base64Decode, Transform::Upper]);
+///
+/// assert_eq!(result.unwrap(), json!(["HELLO", 42]));
+/// ```
+pub fn apply_transforms(value: Value, transforms: &[Transform]) -> Result<Value, JqrError> {
+    match value {
+        Value::String(s) => apply_all(s, transforms).map(Value::String),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(s) => apply_all(s, transforms).map(Value::String),
+                other => Ok(other),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        other => Ok(other),
+    }
+}
+
+/// Runs every transform over a single string, short-circuiting on the first error.
+fn apply_all(input: String, transforms: &[Transform]) -> Result<String, JqrError> {
+    transforms
+        .iter()
+        .try_fold(input, |acc, transform| transform.apply_str(&acc))
+}
+
+/// An operation applied to every node of a document by `walk`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkOp {
+    /// Strip leading and trailing whitespace from every string (`trim-strings`).
+    TrimStrings,
+    /// Round every number to the nearest integer (`round`).
+    Round,
+    /// Lowercase every object key (`lowercase-keys`). If two keys collide
+    /// after lowercasing, the one that sorts last in the original wins.
+    LowercaseKeys,
+    /// Drop `null` object members and `null` array elements (`remove-nulls`).
+    RemoveNulls,
+}
+
+impl FromStr for WalkOp {
+    type Err = JqrError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "trim-strings" => Ok(WalkOp::TrimStrings),
+            "round" => Ok(WalkOp::Round),
+            "lowercase-keys" => Ok(WalkOp::LowercaseKeys),
+            "remove-nulls" => Ok(WalkOp::RemoveNulls),
+            other => Err(JqrError::InvalidArgument(format!(
+                "Unknown walk operation '{}' (expected one of: trim-strings, round, lowercase-keys, remove-nulls)",
+                other
+            ))),
+        }
+    }
+}
+
+/// Applies an operation to every node of a JSON value, bottom-up.
+///
+/// Inspired by jq's `walk`, children are processed before their parent, so
+/// for example `remove-nulls` sees containers whose members have already been
+/// cleaned up.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{walk, WalkOp};
+/// use serde_json::json;
+///
+/// let value = json!({"Name": " Alice ", "Tags": [null, " a "], "Score": 9.6});
+///
+/// assert_eq!(
+///     walk(value.clone(), WalkOp::TrimStrings),
+///     json!({"Name": "Alice", "Tags": [null, "a"], "Score": 9.6})
+/// );
+/// assert_eq!(
+///     walk(value.clone(), WalkOp::RemoveNulls),
+///     json!({"Name": " Alice ", "Tags": [" a "], "Score": 9.6})
+/// );
+/// assert_eq!(walk(json!([9.6, -1.5, 3]), WalkOp::Round), json!([10, -2, 3]));
+/// assert_eq!(walk(json!({"A": {"B": 1}}), WalkOp::LowercaseKeys), json!({"a": {"b": 1}}));
+/// ```
+pub fn walk(value: Value, op: WalkOp) -> Value {
+    // Recurse into children first so the operation is applied bottom-up
+    let value = match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| walk(v, op)).collect()),
+        Value::Object(map) => {
+            Value::Object(map.into_iter().map(|(k, v)| (k, walk(v, op))).collect())
+        }
+        other => other,
+    };
+
+    match (op, value) {
+        (WalkOp::TrimStrings, Value::String(s)) => Value::String(s.trim().to_string()),
+        (WalkOp::Round, Value::Number(n)) if n.is_f64() => {
+            let rounded = n.as_f64().unwrap_or_default().round();
+            if rounded >= i64::MIN as f64 && rounded <= i64::MAX as f64 {
+                Value::from(rounded as i64)
+            } else {
+                Value::from(rounded)
+            }
+        }
+        (WalkOp::LowercaseKeys, Value::Object(map)) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k.to_lowercase(), v))
+                .collect(),
+        ),
+        (WalkOp::RemoveNulls, Value::Array(items)) => {
+            Value::Array(items.into_iter().filter(|v| !v.is_null()).collect())
+        }
+        (WalkOp::RemoveNulls, Value::Object(map)) => {
+            Value::Object(map.into_iter().filter(|(_, v)| !v.is_null()).collect())
+        }
+        (_, other) => other,
+    }
+}
+
+/// Recursively removes `null`, `""`, `[]` and `{}` from objects and arrays.
+///
+/// Pruning is applied bottom-up, so containers that only held empty values
+/// are removed as well. The top-level value itself is never removed; it may
+/// end up as an empty container.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::prune_empty;
+/// use serde_json::json;
+///
+/// let value = json!({"name": "Alice", "nick": "", "meta": {"tags": [], "notes": [null]}});
+/// assert_eq!(prune_empty(value), json!({"name": "Alice"}));
+/// ```
+pub fn prune_empty(value: Value) -> Value {
+    prune(value, false)
+}
+
+/// Like `prune_empty`, but keeps `null` values while still removing empty
+/// strings and empty containers.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::prune_empty_keep_null;
+/// use serde_json::json;
+///
+/// let value = json!({"id": null, "tags": [], "meta": {"a": ""}});
+/// assert_eq!(prune_empty_keep_null(value), json!({"id": null}));
+/// ```
+pub fn prune_empty_keep_null(value: Value) -> Value {
+    prune(value, true)
+}
+
+/// Prunes the children of a container, then drops the ones that are empty.
+fn prune(value: Value, keep_null: bool) -> Value {
+    let is_empty = |v: &Value| match v {
+        Value::Null => !keep_null,
+        Value::String(s) => s.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    };
+
+    match value {
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|v| prune(v, keep_null))
+                .filter(|v| !is_empty(v))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (k, prune(v, keep_null)))
+                .filter(|(_, v)| !is_empty(v))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Removes duplicate elements from an array, keeping the first of each.
+///
+/// Objects are equal regardless of the order of their keys.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::unique;
+/// use serde_json::json;
+///
+/// let mut value = json!([{"a": 1, "b": 2}, 3, {"b": 2, "a": 1}, 3, "3"]);
+/// unique(&mut value).unwrap();
+/// assert_eq!(value, json!([{"a": 1, "b": 2}, 3, "3"]));
+/// ```
+pub fn unique(value: &mut Value) -> Result<(), JqrError> {
+    dedupe(value, |item| Some(item.clone()))
+}
+
+/// Removes the elements of an array whose value at a key query was already
+/// seen, keeping the first of each. Elements the query matches nothing in
+/// share the `null` key.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if `value` is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{unique_by, CompiledQuery};
+/// use serde_json::json;
+///
+/// let mut users = json!([
+///     {"name": "Alice", "email": "alice@example.com"},
+///     {"name": "Bob", "email": "bob@example.com"},
+///     {"name": "Alice B.", "email": "alice@example.com"}
+/// ]);
+/// unique_by(&mut users, &CompiledQuery::new("$.email").unwrap()).unwrap();
+/// assert_eq!(
+///     users,
+///     json!([
+///         {"name": "Alice", "email": "alice@example.com"},
+///         {"name": "Bob", "email": "bob@example.com"}
+///     ])
+/// );
+/// ```
+pub fn unique_by(value: &mut Value, key: &CompiledQuery) -> Result<(), JqrError> {
+    dedupe(value, |item| key.apply(item))
+}
+
+/// Keeps the array elements whose key, in canonical form, is new.
+fn dedupe(value: &mut Value, key: impl Fn(&Value) -> Option<Value>) -> Result<(), JqrError> {
+    let Value::Array(items) = value else {
+        return Err(JqrError::UnsupportedValue(format!(
+            "Removing duplicates requires an array, but the value is of type {}",
+            json_type(value)
+        )));
+    };
+    let mut seen = HashSet::new();
+    items.retain(|item| {
+        let key = key(item).unwrap_or(Value::Null);
+        seen.insert(sort_object_keys(key).to_string())
+    });
+    Ok(())
+}
+
+/// The JSON type names understood by `json_type` and `select_type`.
+const JSON_TYPES: [&str; 6] = ["object", "array", "string", "number", "boolean", "null"];
+
+/// Returns the JSON type name of a value, as used by jq's `type`.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::json_type;
+/// use serde_json::json;
+///
+/// assert_eq!(json_type(&json!({"a": 1})), "object");
+/// assert_eq!(json_type(&json!(1.5)), "number");
+/// assert_eq!(json_type(&json!(null)), "null");
+/// ```
+pub fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+/// Makes a JSON number of a computed result, as an integer when it is one.
+///
+/// Query engines share this for arithmetic and aggregate results, which are
+/// computed as `f64`.
+pub(crate) fn to_number(number: f64) -> Result<Value, JqrError> {
+    if number.fract() == 0.0 && number.abs() < 9e15 {
+        return Ok(Value::from(number as i64));
+    }
+    Number::from_f64(number)
+        .map(Value::Number)
+        .ok_or_else(|| JqrError::UnsupportedValue(format!("{} is not a JSON number", number)))
+}
+
+/// Keeps only the elements of an array whose JSON type is in a comma-separated list.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidArgument`] if the list contains an unknown type name.
+/// * Returns [`JqrError::UnsupportedValue`] if the value is not an array.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::select_type;
+/// use serde_json::json;
+///
+/// let value = json!(["a", 1, null, "b", {"c": true}]);
+///
+/// assert_eq!(select_type(value.clone(), "string").unwrap(), json!(["a", "b"]));
+/// assert_eq!(select_type(value, "number,object").unwrap(), json!([1, {"c": true}]));
+/// assert!(select_type(json!({"a": 1}), "string").is_err());
+/// ```
+pub fn select_type(value: Value, types: &str) -> Result<Value, JqrError> {
+    let wanted: Vec<&str> = types.split(',').map(str::trim).collect();
+    if let Some(unknown) = wanted.iter().find(|t| !JSON_TYPES.contains(t)) {
+        return Err(JqrError::InvalidArgument(format!(
+            "Unknown type '{}' (expected one of: {})",
+            unknown,
+            JSON_TYPES.join(", ")
+        )));
+    }
+
+    match value {
+        Value::Array(items) => Ok(Value::Array(
+            items
+                .into_iter()
+                .filter(|item| wanted.contains(&json_type(item)))
+                .collect(),
+        )),
+        other => Err(JqrError::UnsupportedValue(format!(
+            "Type selection requires an array, but the result is of type {}",
+            json_type(&other)
+        ))),
+    }
+}
+
+/// Parses a key rename mapping such as `"old=new,old2=new2"`.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidArgument`] if an entry is not of the form `old=new`
+///   or either side is empty.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::parse_rename_mapping;
+///
+/// let mapping = parse_rename_mapping("userName=user_name,id=user_id").unwrap();
+/// assert_eq!(mapping["userName"], "user_name");
+/// assert!(parse_rename_mapping("id").is_err());
+/// ```
+pub fn parse_rename_mapping(spec: &str) -> Result<HashMap<String, String>, JqrError> {
+    spec.split(',')
+        .map(|entry| match entry.split_once('=') {
+            Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+                Ok((old.trim().to_string(), new.trim().to_string()))
+            }
+            _ => Err(JqrError::InvalidArgument(format!(
+                "Invalid rename '{}' (expected old=new)",
+                entry.trim()
+            ))),
+        })
+        .collect()
+}
+
+/// Recursively renames object keys throughout a document.
+///
+/// Only keys that exactly match an entry of the mapping are renamed; values
+/// are never touched. Renaming a key to a name that already exists in the
+/// same object is an error rather than a silent overwrite, unless that
+/// existing key is itself being renamed away.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::Transform`] naming the colliding key if a rename would
+///   overwrite another member.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::rename_keys;
+/// use serde_json::json;
+/// use std::collections::HashMap;
+///
+/// let mut value = json!({"userName": "alice", "friends": [{"userName": "bob"}]});
+/// let mapping = HashMap::from([("userName".to_string(), "user_name".to_string())]);
+///
+/// rename_keys(&mut value, &mapping).unwrap();
+/// assert_eq!(value, json!({"user_name": "alice", "friends": [{"user_name": "bob"}]}));
+///
+/// let mut value = json!({"a": 1, "b": 2});
+/// let mapping = HashMap::from([("a".to_string(), "b".to_string())]);
+/// assert!(rename_keys(&mut value, &mapping).is_err());
+/// ```
+pub fn rename_keys(value: &mut Value, mapping: &HashMap<String, String>) -> Result<(), JqrError> {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                rename_keys(item, mapping)?;
+            }
+        }
+        Value::Object(map) => {
+            for child in map.values_mut() {
+                rename_keys(child, mapping)?;
+            }
+
+            let mut renamed = Map::new();
+            for (key, child) in std::mem::take(map) {
+                let key = mapping.get(&key).cloned().unwrap_or(key);
+                if renamed.contains_key(&key) {
+                    return Err(JqrError::Transform(format!(
+                        "Renaming would overwrite the existing key '{}'",
+                        key
+                    )));
+                }
+                renamed.insert(key, child);
+            }
+            *map = renamed;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Converts string leaves that are exactly JSON numbers into numbers.
+///
+/// A string is converted only if the whole string follows the JSON number
+/// grammar, so `"42"`, `"-1.5"` and `"1e3"` become numbers while `" 42"`,
+/// `"007"`, `"+1"`, `"0x1F"` and `"NaN"` are left alone (leading zeros usually
+/// mean an identifier such as a ZIP code). The number keeps the exact
+/// digits of the string, however large or precise.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::coerce_numeric_strings;
+/// use serde_json::json;
+///
+/// let mut value = json!({"id": "42", "price": "9.99", "zip": "02134", "name": "Bob"});
+/// coerce_numeric_strings(&mut value);
+///
+/// assert_eq!(value, json!({"id": 42, "price": 9.99, "zip": "02134", "name": "Bob"}));
+/// ```
+pub fn coerce_numeric_strings(value: &mut Value) {
+    match value {
+        Value::String(s) => {
+            if let Some(number) = parse_exact_number(s) {
+                *value = Value::Number(number);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(coerce_numeric_strings),
+        Value::Object(map) => map.values_mut().for_each(coerce_numeric_strings),
+        _ => {}
+    }
+}
+
+/// Converts the string leaves `"true"` and `"false"` into booleans.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::coerce_boolean_strings;
+/// use serde_json::json;
+///
+/// let mut value = json!(["true", "false", "True", "yes"]);
+/// coerce_boolean_strings(&mut value);
+///
+/// assert_eq!(value, json!([true, false, "True", "yes"]));
+/// ```
+pub fn coerce_boolean_strings(value: &mut Value) {
+    match value {
+        Value::String(s) if s == "true" || s == "false" => *value = Value::Bool(s == "true"),
+        Value::Array(items) => items.iter_mut().for_each(coerce_boolean_strings),
+        Value::Object(map) => map.values_mut().for_each(coerce_boolean_strings),
+        _ => {}
+    }
+}
+
+/// Parses a string that is exactly a JSON number.
+fn parse_exact_number(s: &str) -> Option<Number> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let int_len = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let (int_part, rest) = digits.split_at(int_len);
+
+    // JSON forbids empty integer parts and leading zeros
+    if int_part.is_empty() || (int_part.len() > 1 && int_part.starts_with('0')) {
+        return None;
+    }
+
+    let rest = match rest.strip_prefix('.') {
+        Some(fraction) => {
+            let len = fraction
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(fraction.len());
+            if len == 0 {
+                return None;
+            }
+            &fraction[len..]
+        }
+        None => rest,
+    };
+
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        if exponent.is_empty() || !exponent.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    } else if !rest.is_empty() {
+        return None;
+    }
+
+    serde_json::from_str(s).ok()
+}
@@ -0,0 +1,393 @@
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use serde_json::{Map, Value};
+
+use crate::compare::child_path;
+use crate::JqrError;
+
+/// Controls how XML elements, attributes and text map to JSON.
+///
+/// An element becomes a key holding its text, or an object when it has
+/// attributes or child elements. Attributes are keys with a prefix (`@` by
+/// default), text next to attributes or children is kept under a text key
+/// (`#text` by default), and repeated sibling elements fold into an array.
+/// Options are set with chained builder methods.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{xml_to_json, XmlOptions};
+/// use serde_json::json;
+///
+/// let options = XmlOptions::new().attribute_prefix("_").force_array("item");
+/// let value = xml_to_json(r#"<list id="7"><item>a</item></list>"#, &options).unwrap();
+///
+/// assert_eq!(value, json!({"list": {"_id": "7", "item": ["a"]}}));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlOptions {
+    attribute_prefix: String,
+    text_key: String,
+    force_arrays: Vec<String>,
+}
+
+impl Default for XmlOptions {
+    fn default() -> Self {
+        XmlOptions {
+            attribute_prefix: "@".to_string(),
+            text_key: "#text".to_string(),
+            force_arrays: Vec::new(),
+        }
+    }
+}
+
+impl XmlOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix that marks attribute keys.
+    pub fn attribute_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.attribute_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the key holding the text of an element that also has attributes
+    /// or children.
+    pub fn text_key(mut self, key: impl Into<String>) -> Self {
+        self.text_key = key.into();
+        self
+    }
+
+    /// Always reads elements named `name` into an array, even when there is
+    /// only one, so the shape does not depend on the number of siblings.
+    pub fn force_array(mut self, name: impl Into<String>) -> Self {
+        self.force_arrays.push(name.into());
+        self
+    }
+
+    /// Returns the attribute name if `key` is an attribute key.
+    fn attribute_name<'a>(&self, key: &'a str) -> Option<&'a str> {
+        if self.attribute_prefix.is_empty() {
+            None
+        } else {
+            key.strip_prefix(self.attribute_prefix.as_str())
+        }
+    }
+}
+
+/// An element being read, until its end tag.
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+impl Element {
+    /// The JSON value of the finished element: its text if it has neither
+    /// attributes nor children, otherwise an object.
+    fn into_value(mut self, options: &XmlOptions) -> Value {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            if text.is_empty() {
+                Value::Null
+            } else {
+                Value::String(text.to_string())
+            }
+        } else {
+            if !text.is_empty() {
+                self.fields
+                    .insert(options.text_key.clone(), Value::String(text.to_string()));
+            }
+            Value::Object(self.fields)
+        }
+    }
+}
+
+/// Parses an XML document into JSON according to `options`.
+///
+/// The result is an object with the root element as its only key. All
+/// attribute values and text are strings, whitespace around text is trimmed
+/// and an empty element is `null`. Comments, processing instructions and the
+/// XML declaration are skipped; prefixed names such as `soap:Body` are kept as
+/// they are.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidXml`] if the input is not well-formed XML
+///   with a single root element.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{xml_to_json, XmlOptions};
+/// use serde_json::json;
+///
+/// let xml = r#"<users><user id="1">Alice</user><user id="2">Bob</user></users>"#;
+/// let value = xml_to_json(xml, &XmlOptions::new()).unwrap();
+///
+/// assert_eq!(
+///     value,
+///     json!({"users": {"user": [
+///         {"@id": "1", "#text": "Alice"},
+///         {"@id": "2", "#text": "Bob"}
+///     ]}})
+/// );
+/// ```
+pub fn xml_to_json(content: &str, options: &XmlOptions) -> Result<Value, JqrError> {
+    let mut reader = Reader::from_str(content);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root: Option<Value> = None;
+
+    loop {
+        let event = reader.read_event().map_err(|e| {
+            JqrError::InvalidXml(format!("{} at byte {}", e, reader.error_position()))
+        })?;
+        let invalid = |message: &str| {
+            JqrError::InvalidXml(format!("{} at byte {}", message, reader.buffer_position()))
+        };
+
+        match event {
+            Event::Start(_) | Event::Empty(_) if root.is_some() => {
+                return Err(invalid("more than one root element"));
+            }
+            Event::Start(start) => stack.push(start_element(&start, options)?),
+            Event::Empty(start) => {
+                let element = start_element(&start, options)?;
+                close_element(element, &mut stack, &mut root, options);
+            }
+            Event::End(_) => {
+                // Unbalanced end tags are rejected by the reader itself
+                let element = stack.pop().ok_or_else(|| invalid("unexpected end tag"))?;
+                close_element(element, &mut stack, &mut root, options);
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| JqrError::InvalidXml(e.to_string()))?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(&text),
+                    None if text.trim().is_empty() => {}
+                    None => return Err(invalid("text outside the root element")),
+                }
+            }
+            Event::CData(data) => {
+                let data = String::from_utf8_lossy(&data).into_owned();
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(&data),
+                    None => return Err(invalid("text outside the root element")),
+                }
+            }
+            Event::Eof => break,
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => {}
+        }
+    }
+
+    match (root, stack.is_empty()) {
+        (Some(root), true) => Ok(root),
+        (_, false) => Err(JqrError::InvalidXml(
+            "unexpected end of input inside an element".to_string(),
+        )),
+        (None, true) => Err(JqrError::InvalidXml("no root element".to_string())),
+    }
+}
+
+/// Starts an element, reading its attributes into prefixed keys.
+fn start_element(start: &BytesStart, options: &XmlOptions) -> Result<Element, JqrError> {
+    let mut fields = Map::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| JqrError::InvalidXml(e.to_string()))?;
+        let value = attribute
+            .unescape_value()
+            .map_err(|e| JqrError::InvalidXml(e.to_string()))?;
+        let name = String::from_utf8_lossy(attribute.key.as_ref());
+        fields.insert(
+            format!("{}{}", options.attribute_prefix, name),
+            Value::String(value.into_owned()),
+        );
+    }
+
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        fields,
+        text: String::new(),
+    })
+}
+
+/// Adds a finished element to its parent, folding repeated names into an
+/// array, or makes it the root.
+fn close_element(
+    element: Element,
+    stack: &mut [Element],
+    root: &mut Option<Value>,
+    options: &XmlOptions,
+) {
+    let name = element.name.clone();
+    let value = element.into_value(options);
+
+    let Some(parent) = stack.last_mut() else {
+        let mut document = Map::new();
+        document.insert(name, value);
+        *root = Some(Value::Object(document));
+        return;
+    };
+
+    match parent.fields.get_mut(&name) {
+        // Element values are never arrays themselves, so an array was folded here
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        None if options.force_arrays.contains(&name) => {
+            parent.fields.insert(name, Value::Array(vec![value]));
+        }
+        None => {
+            parent.fields.insert(name, value);
+        }
+    }
+}
+
+/// Serializes a value as an indented XML document, the inverse of
+/// [`xml_to_json`] with the same `options`.
+///
+/// An object with a single key that does not hold an array becomes the root
+/// element; any other value is wrapped in a `<root>` element, with array
+/// elements as `<item>` children. Arrays repeat the element for each item,
+/// keys with the attribute prefix become attributes, the text key becomes the
+/// element's text and `null` an empty element.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::UnsupportedValue`] if a key is not a valid XML name
+///   or an attribute holds an array or object. The message gives the path of
+///   the offending value.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{json_to_xml, XmlOptions};
+/// use serde_json::json;
+///
+/// let value = json!({"users": {"user": [{"@id": "1", "#text": "Alice"}, {"@id": "2"}]}});
+/// let xml = json_to_xml(&value, &XmlOptions::new()).unwrap();
+///
+/// assert_eq!(
+///     xml,
+///     "<users>\n  <user id=\"1\">Alice</user>\n  <user id=\"2\"/>\n</users>"
+/// );
+/// ```
+pub fn json_to_xml(value: &Value, options: &XmlOptions) -> Result<String, JqrError> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    match value {
+        Value::Object(map) if map.len() == 1 && map.values().all(|v| !v.is_array()) => {
+            let (name, value) = map.iter().next().unwrap();
+            write_element(&mut writer, name, value, &child_path("$", name), options)?;
+        }
+        Value::Array(items) => {
+            writer.write_event(Event::Start(BytesStart::new("root")))?;
+            for (i, item) in items.iter().enumerate() {
+                write_element(&mut writer, "item", item, &format!("$[{}]", i), options)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new("root")))?;
+        }
+        _ => write_element(&mut writer, "root", value, "$", options)?,
+    }
+
+    String::from_utf8(writer.into_inner()).map_err(|e| JqrError::Serialization(e.to_string()))
+}
+
+fn write_element(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    value: &Value,
+    path: &str,
+    options: &XmlOptions,
+) -> Result<(), JqrError> {
+    if !is_xml_name(name) {
+        return Err(JqrError::UnsupportedValue(format!(
+            "'{}' is not a valid XML element name (at {})",
+            name, path
+        )));
+    }
+
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                write_element(writer, name, item, &format!("{}[{}]", path, i), options)?;
+            }
+        }
+        Value::Null => writer.write_event(Event::Empty(BytesStart::new(name)))?,
+        Value::Object(map) => {
+            let mut start = BytesStart::new(name);
+            let mut text = None;
+            let mut children = Vec::new();
+            for (key, value) in map {
+                let key_path = child_path(path, key);
+                if let Some(attribute) = options.attribute_name(key) {
+                    if !is_xml_name(attribute) {
+                        return Err(JqrError::UnsupportedValue(format!(
+                            "'{}' is not a valid XML attribute name (at {})",
+                            attribute, key_path
+                        )));
+                    }
+                    match scalar_text(value) {
+                        Some(text) => start.push_attribute((attribute, text.as_str())),
+                        None => {
+                            return Err(JqrError::UnsupportedValue(format!(
+                                "An XML attribute cannot hold an array or object (at {})",
+                                key_path
+                            )))
+                        }
+                    }
+                } else if *key == options.text_key {
+                    text = scalar_text(value);
+                } else {
+                    children.push((key, value, key_path));
+                }
+            }
+
+            if text.is_none() && children.is_empty() {
+                writer.write_event(Event::Empty(start))?;
+                return Ok(());
+            }
+            writer.write_event(Event::Start(start))?;
+            if let Some(text) = text {
+                writer.write_event(Event::Text(BytesText::new(&text)))?;
+            }
+            for (key, value, key_path) in children {
+                write_element(writer, key, value, &key_path, options)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new(name)))?;
+        }
+        scalar => {
+            let text = scalar_text(scalar).unwrap_or_default();
+            writer.write_event(Event::Start(BytesStart::new(name)))?;
+            writer.write_event(Event::Text(BytesText::new(&text)))?;
+            writer.write_event(Event::End(BytesEnd::new(name)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The text of a scalar as written in XML; `None` for arrays and objects.
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Some(value.to_string()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// Returns `true` if `name` can be used as an element or attribute name.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
@@ -0,0 +1,245 @@
+use serde::Deserialize;
+use serde_json::Value;
+use yaml_rust2::parser::{Event, Parser};
+
+use crate::JqrError;
+
+/// Controls how YAML anchors, aliases and `<<` merge keys are read.
+///
+/// The defaults expand aliases into copies of their anchored node, merge
+/// `<<` keys into the surrounding mapping, and refuse input whose aliases
+/// expand to more than 100,000 nodes, which stops "billion laughs" alias
+/// bombs. Options are set with chained builder methods.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_yaml_documents_with, YamlOptions};
+/// use serde_json::json;
+///
+/// let yaml = "base: &base {retries: 3}\nprod: {<<: *base, host: db}\n";
+///
+/// assert_eq!(
+///     parse_yaml_documents_with(yaml, &YamlOptions::new()).unwrap(),
+///     vec![json!({"base": {"retries": 3}, "prod": {"host": "db", "retries": 3}})]
+/// );
+/// assert_eq!(
+///     parse_yaml_documents_with(yaml, &YamlOptions::new().keep_aliases(true)).unwrap(),
+///     vec![json!({"base": {"retries": 3}, "prod": {"<<": "*base", "host": "db"}})]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YamlOptions {
+    keep_aliases: bool,
+    keep_merge_keys: bool,
+    alias_limit: usize,
+}
+
+impl Default for YamlOptions {
+    fn default() -> Self {
+        YamlOptions {
+            keep_aliases: false,
+            keep_merge_keys: false,
+            alias_limit: 100_000,
+        }
+    }
+}
+
+impl YamlOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads each alias as the string `"*name"` instead of a copy of the
+    /// anchored node. Merge keys are then kept as written too, since there is
+    /// nothing to merge.
+    pub fn keep_aliases(mut self, keep_aliases: bool) -> Self {
+        self.keep_aliases = keep_aliases;
+        self
+    }
+
+    /// Keeps `<<` as an ordinary key instead of merging its mappings into
+    /// the surrounding one.
+    pub fn keep_merge_keys(mut self, keep_merge_keys: bool) -> Self {
+        self.keep_merge_keys = keep_merge_keys;
+        self
+    }
+
+    /// Sets how many nodes the aliases of a document may expand to in total.
+    pub fn alias_limit(mut self, alias_limit: usize) -> Self {
+        self.alias_limit = alias_limit;
+        self
+    }
+}
+
+/// Like [`parse_yaml_documents`](crate::parse_yaml_documents), but reads
+/// anchors, aliases and merge keys according to `options`.
+///
+/// The aliases are counted before anything is expanded, so an alias bomb is
+/// refused without allocating its expansion.
+///
+/// # Errors
+///
+/// * Returns [`JqrError::InvalidYaml`] if a document is not valid YAML, a
+///   merge key is not followed by mappings, or the aliases of a document
+///   expand to more nodes than the limit.
+///
+/// # Examples
+///
+/// ```
+/// use jqr_core::{parse_yaml_documents_with, YamlOptions};
+///
+/// let bomb = "a: &a [x, x, x, x, x, x, x, x, x, x]\n\
+///             b: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a, *a]\n\
+///             c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b, *b]\n";
+///
+/// assert!(parse_yaml_documents_with(bomb, &YamlOptions::new()).is_ok());
+///
+/// let err = parse_yaml_documents_with(bomb, &YamlOptions::new().alias_limit(1_000)).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Invalid YAML: aliases expand to more than 1000 nodes (at line 3 column 40)"
+/// );
+/// ```
+pub fn parse_yaml_documents_with(
+    content: &str,
+    options: &YamlOptions,
+) -> Result<Vec<Value>, JqrError> {
+    // Kept aliases expand to nothing, so only their positions are needed
+    let limit = if options.keep_aliases {
+        usize::MAX
+    } else {
+        options.alias_limit
+    };
+    let aliases = scan_aliases(content, limit)?;
+
+    // A kept alias is rewritten into a quoted string before parsing
+    let content = if options.keep_aliases && !aliases.is_empty() {
+        quote_aliases(content, &aliases)
+    } else {
+        content.to_string()
+    };
+
+    serde_yaml::Deserializer::from_str(&content)
+        .map(|document| {
+            let mut value =
+                Value::deserialize(document).map_err(|e| JqrError::InvalidYaml(e.to_string()))?;
+            if !options.keep_aliases && !options.keep_merge_keys {
+                merge_keys(&mut value)?;
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+/// Replaces every `<<` key with the entries of the mappings it names that the
+/// surrounding mapping does not set itself, earlier mappings winning.
+fn merge_keys(value: &mut Value) -> Result<(), JqrError> {
+    match value {
+        Value::Array(items) => items.iter_mut().try_for_each(merge_keys),
+        Value::Object(map) => {
+            map.values_mut().try_for_each(merge_keys)?;
+            let sources = match map.shift_remove("<<") {
+                None => return Ok(()),
+                Some(Value::Object(source)) => vec![source],
+                Some(Value::Array(items)) if items.iter().all(Value::is_object) => items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Value::Object(source) => Some(source),
+                        _ => None,
+                    })
+                    .collect(),
+                Some(_) => {
+                    return Err(JqrError::InvalidYaml(
+                        "a merge key must be followed by a mapping or a sequence of mappings"
+                            .to_string(),
+                    ))
+                }
+            };
+            for source in sources {
+                for (key, child) in source {
+                    if !map.contains_key(&key) {
+                        map.insert(key, child);
+                    }
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the byte offset of every alias in `content`, failing once the
+/// aliases of a document expand to more than `limit` nodes.
+fn scan_aliases(content: &str, limit: usize) -> Result<Vec<usize>, JqrError> {
+    let mut parser = Parser::new_from_str(content);
+    let mut aliases = Vec::new();
+    // The size of each anchored node, and of each open collection with its anchor
+    let mut anchored: Vec<usize> = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut expanded = 0usize;
+
+    loop {
+        let (event, mark) = parser
+            .next_token()
+            .map_err(|e| JqrError::InvalidYaml(e.to_string()))?;
+        let (size, anchor) = match event {
+            Event::StreamEnd => return Ok(aliases),
+            Event::DocumentStart => {
+                expanded = 0;
+                continue;
+            }
+            Event::SequenceStart(anchor, _) | Event::MappingStart(anchor, _) => {
+                open.push((anchor, 1));
+                continue;
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (anchor, size) = open.pop().unwrap_or_default();
+                (size, anchor)
+            }
+            Event::Scalar(_, _, anchor, _) => (1, anchor),
+            Event::Alias(anchor) => {
+                let size = anchored.get(anchor).copied().unwrap_or(1);
+                expanded = expanded.saturating_add(size);
+                if expanded > limit {
+                    return Err(JqrError::InvalidYaml(format!(
+                        "aliases expand to more than {} nodes (at line {} column {})",
+                        limit,
+                        mark.line(),
+                        mark.col() + 1
+                    )));
+                }
+                aliases.push(mark.index());
+                (size, 0)
+            }
+            _ => continue,
+        };
+
+        if anchor > 0 {
+            if anchored.len() <= anchor {
+                anchored.resize(anchor + 1, 1);
+            }
+            anchored[anchor] = size;
+        }
+        if let Some((_, parent)) = open.last_mut() {
+            *parent = parent.saturating_add(size);
+        }
+    }
+}
+
+/// Replaces the aliases at the byte offsets `aliases` with quoted strings.
+fn quote_aliases(content: &str, aliases: &[usize]) -> String {
+    let mut quoted = String::with_capacity(content.len());
+    let mut copied = 0;
+    for &start in aliases {
+        let end = content[start..]
+            .find(|c: char| c.is_whitespace() || ",[]{}".contains(c))
+            .map_or(content.len(), |length| start + length);
+        quoted.push_str(&content[copied..start]);
+        quoted.push_str(&serde_json::to_string(&content[start..end]).unwrap());
+        copied = end;
+    }
+    quoted.push_str(&content[copied..]);
+    quoted
+}
@@ -0,0 +1,1798 @@
+// jqr-core/tests/lib_tests.rs
+
+#[cfg(test)]
+mod tests {
+    use jqr_core::*;
+    use serde_json::json;
+    use std::io;
+
+    #[test]
+    fn test_pretty_print_json() {
+        let input = r#"{"age": 30, "name": "Alice"}"#;
+        let expected = "{\n  \"age\": 30,\n  \"name\": \"Alice\"\n}";
+        assert_eq!(pretty_print_json(input, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_jsonpath_query() {
+        let input = json!({"user": {"name": "Alice"}});
+        let query = "$.user.name".to_string();
+        let result = extract_jsonpath(&input, &query).unwrap();
+        assert_eq!(result, Some(json!("Alice")));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let input = "invalid json";
+        let result = serde_json::from_str::<serde_json::Value>(input);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_convert_to_yaml() {
+        let input = r#"{"name": "Alice", "age": 30}"#;
+        let yaml_output = convert_to_yaml(input).unwrap();
+        assert!(yaml_output.contains("name: Alice"));
+        assert!(yaml_output.contains("age: 30"));
+    }
+
+    #[test]
+    fn test_empty_json() {
+        let input = "{}";
+        let expected = "{}";
+        assert_eq!(pretty_print_json(input, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_nested_json_query() {
+        let input = json!({"user": {"profile": {"name": "Bob"}}});
+        let query = "$.user.profile.name".to_string();
+        let result = extract_jsonpath(&input, &query).unwrap();
+        assert_eq!(result, Some(json!("Bob")));
+    }
+
+    #[test]
+    fn test_array_json_query() {
+        let input = json!({"users": [{"name": "Alice"}, {"name": "Bob"}]});
+        let query = "$.users[*].name".to_string();
+        let result = extract_jsonpath(&input, &query).unwrap();
+        assert_eq!(result, Some(json!(vec!["Alice", "Bob"])));
+    }
+
+    #[test]
+    fn test_convert_to_yaml_invalid_json() {
+        let input = "invalid json";
+        let _result = convert_to_yaml(input);
+        assert!(_result.is_err());
+
+    }
+
+    #[test]
+    fn test_jsonpath_query_non_existent_field() {
+        let input = json!({"user": {"name": "Alice"}});
+        let query = "$.user.age".to_string();
+        let result = extract_jsonpath(&input, &query).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_json_with_special_characters() {
+        let input = r#"{"text": "Hello \"World\"!"}"#;
+        let expected = "{\n  \"text\": \"Hello \\\"World\\\"!\"\n}";
+        assert_eq!(pretty_print_json(input, None).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_large_json_handling() {
+        let data: Vec<_> = (0..1000).map(|i| json!({"id": i, "value": i * 2})).collect();
+        let input = json!({"data": data});
+        let query = "$.data[999].value".to_string();
+        let result = extract_jsonpath(&input, &query).unwrap();
+        assert_eq!(result, Some(json!(1998)));
+    }
+
+    #[test]
+    fn test_apply_transforms_pipeline() {
+        let transforms = parse_transforms("base64d,trim,upper").unwrap();
+        let result = apply_transforms(json!("ICBhbGljZSAg"), &transforms).unwrap();
+        assert_eq!(result, json!("ALICE"));
+    }
+
+    #[test]
+    fn test_apply_transforms_non_strings_untouched() {
+        let input = json!(["Hello%20World", 1, null, {"k": "V"}]);
+        let result = apply_transforms(input, &[Transform::UrlDecode, Transform::Lower]).unwrap();
+        assert_eq!(result, json!(["hello world", 1, null, {"k": "V"}]));
+    }
+
+    #[test]
+    fn test_apply_transforms_errors() {
+        assert!(parse_transforms("trim,reverse").is_err());
+        assert!(apply_transforms(json!("not base64!"), &[Transform::Base64Decode]).is_err());
+    }
+
+    #[test]
+    fn test_number_lines_width_grows() {
+        let text = (1..=10)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let numbered = number_lines(&text);
+        let lines: Vec<&str> = numbered.lines().collect();
+        assert_eq!(lines[0], " 1 | 1");
+        assert_eq!(lines[9], "10 | 10");
+    }
+
+    #[test]
+    fn test_fold_large_nested() {
+        let input = json!({"a": {"b": [1, 2, 3]}, "c": [1]});
+        assert_eq!(
+            fold_large(input.clone(), 2),
+            json!({"a": {"b": "[... 3 items]"}, "c": [1]})
+        );
+        assert_eq!(fold_large(input.clone(), 1), json!("{... 2 keys}"));
+        assert_eq!(fold_large(input.clone(), 3), input);
+    }
+
+    #[test]
+    fn test_yaml_input_hint() {
+        let yaml = "user:\n  name: Alice\n  roles:\n    - admin\n";
+        let err = pretty_print_json(yaml, None).unwrap_err();
+        assert!(matches!(
+            err,
+            JqrError::InvalidJson {
+                looks_like_yaml: true,
+                ..
+            }
+        ));
+        assert!(err.to_string().starts_with("Invalid JSON"));
+
+        let err = pretty_print_json("invalid json", None).unwrap_err();
+        assert!(matches!(
+            err,
+            JqrError::InvalidJson {
+                looks_like_yaml: false,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_json_equal_semantic() {
+        assert!(json_equal(&json!({"a": [1, 2.0]}), &json!({"a": [1.0, 2]})));
+        assert!(!json_equal(&json!([1, 2]), &json!([2, 1])));
+        assert!(!json_equal(&json!({"a": null}), &json!({})));
+    }
+
+    #[test]
+    fn test_json_diff_reports_paths() {
+        let expected = json!({"users": [{"name": "Alice"}, {"name": "Bob"}], "a b": 1});
+        let actual = json!({"users": [{"name": "Alice"}], "a b": 2});
+        assert_eq!(
+            json_diff(&expected, &actual),
+            vec![
+                "$['a b']: expected 1, found 2".to_string(),
+                "$.users[1]: missing, expected {\"name\":\"Bob\"}".to_string(),
+            ]
+        );
+        assert!(json_diff(&expected, &expected).is_empty());
+    }
+
+    #[test]
+    fn test_json_to_properties_escaping() {
+        let input = json!({"app name": "a=b\nc", "empty": {}, "greeting": " héllo", "none": null});
+        let expected = "app\\ name=a\\=b\\nc\nempty={}\ngreeting=\\ h\\u00E9llo\nnone=\n";
+        assert_eq!(json_to_properties(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_json_to_properties_requires_object() {
+        assert!(json_to_properties(&json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_query_file_comments_and_whitespace() {
+        let text = "# users\n\n   $.users[?(@.name == 'Bob')]\n   .email  \n";
+        let query = normalize_query_text(text).unwrap();
+        assert_eq!(query, "$.users[?(@.name == 'Bob')].email");
+
+        let input = serde_json::from_str(include_str!("../../data/users.json")).unwrap();
+        assert_eq!(
+            extract_jsonpath(&input, &query).unwrap(),
+            Some(json!("bob@example.com"))
+        );
+    }
+
+    #[test]
+    fn test_json_to_table_nested_and_truncated() {
+        let long = "x".repeat(50);
+        let input = json!([{"id": 1, "tags": ["a", "b"]}, {"id": 22, "note": long}]);
+        let table = json_to_table(&input).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines[1], format!("│ id │ tags      │ {:<40} │", "note"));
+        assert_eq!(lines[3], format!("│  1 │ [\"a\",\"b\"] │ {:<40} │", ""));
+        assert!(lines[4].contains(&format!("{}…", "x".repeat(39))));
+    }
+
+    #[test]
+    fn test_json_to_table_rejects_non_objects() {
+        assert!(json_to_table(&json!({"a": 1})).is_err());
+        assert!(json_to_table(&json!([{"a": 1}, 2])).is_err());
+    }
+
+    #[test]
+    fn test_find_jsonpath_distinguishes_no_match() {
+        let input = json!({"users": [{"name": "Alice", "email": null}]});
+        assert!(find_jsonpath(&input, "$.users[?(@.name == 'Bob')]")
+            .unwrap()
+            .is_empty());
+        assert!(find_jsonpath(&input, "$.users[0].age").unwrap().is_empty());
+        assert_eq!(
+            find_jsonpath(&input, "$.users[0].email").unwrap(),
+            vec![json!(null)]
+        );
+        assert!(find_jsonpath(&input, "$.users[").is_err());
+    }
+
+    #[test]
+    fn test_profile_paths_counts_and_order() {
+        let input: serde_json::Value =
+            serde_json::from_str(include_str!("../../data/users.json")).unwrap();
+        let queries = vec![
+            "$.users[*].email".to_string(),
+            "$.users[?(@.id == 2)].email".to_string(),
+            "$.users.length()".to_string(),
+        ];
+        let profile = profile_paths(&input, &queries).unwrap();
+        assert_eq!(profile[0], ("/users/1/email".to_string(), 2));
+        assert_eq!(profile[1], ("/users/0/email".to_string(), 1));
+        assert_eq!(profile.len(), 3);
+
+        assert!(profile_paths(&input, &["$[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_jsonpath_to_pointer_escaping() {
+        assert_eq!(
+            jsonpath_to_pointer("$.['a~b'].['x'][12]").unwrap(),
+            "/a~0b/x/12"
+        );
+        assert_eq!(
+            jsonpath_to_pointer("$.['it']s'].['k']").unwrap(),
+            "/it']s/k"
+        );
+        assert!(jsonpath_to_pointer("users[0]").is_none());
+    }
+
+    #[test]
+    fn test_walk_remove_nulls_bottom_up() {
+        let input = json!({"a": [null, {"b": null, "c": 1}], "d": null});
+        assert_eq!(walk(input, WalkOp::RemoveNulls), json!({"a": [{"c": 1}]}));
+    }
+
+    #[test]
+    fn test_walk_op_parsing() {
+        assert_eq!("round".parse::<WalkOp>().unwrap(), WalkOp::Round);
+        assert!("uppercase".parse::<WalkOp>().is_err());
+    }
+
+    #[test]
+    fn test_prune_empty_collapses_nested_structures() {
+        let input = json!({"a": {"b": {"c": [{}, [], null, ""]}}, "d": [[[]]]});
+        assert_eq!(prune_empty(input), json!({}));
+
+        let input = json!({"a": {"b": {"c": [null, {"d": ""}]}}, "e": 0, "f": false});
+        assert_eq!(prune_empty(input.clone()), json!({"e": 0, "f": false}));
+        assert_eq!(
+            prune_empty_keep_null(input),
+            json!({"a": {"b": {"c": [null]}}, "e": 0, "f": false})
+        );
+    }
+
+    #[test]
+    fn test_json_seq_round_trip() {
+        let values = vec![json!({"a": [1, 2]}), json!(null), json!("x\u{1e}y")];
+        let seq = to_json_seq(&values).unwrap();
+        assert_eq!(seq.matches(JSON_SEQ_RS).count(), 3);
+        assert_eq!(parse_json_seq(&seq).unwrap(), values);
+    }
+
+    #[test]
+    fn test_select_type_errors() {
+        assert!(select_type(json!([1, "a"]), "string,text").is_err());
+        let err = select_type(json!("a"), "string").unwrap_err();
+        assert!(err.to_string().contains("requires an array"));
+        assert_eq!(
+            select_type(json!([true, [1], false]), "boolean").unwrap(),
+            json!([true, false])
+        );
+    }
+
+    #[test]
+    fn test_detect_query_syntax_rules() {
+        assert_eq!(
+            detect_query_syntax("  $..name").unwrap(),
+            QuerySyntax::JsonPath
+        );
+        assert_eq!(detect_query_syntax("/").unwrap(), QuerySyntax::JsonPointer);
+        assert_eq!(detect_query_syntax(".").unwrap(), QuerySyntax::Shorthand);
+        assert!(detect_query_syntax("").is_err());
+        assert_eq!(
+            detect_query_syntax("users[0]").unwrap(),
+            QuerySyntax::Shorthand
+        );
+        assert!(detect_query_syntax("#users").is_err());
+
+        let input = json!({"users": [{"name": "Alice"}]});
+        let query = shorthand_to_jsonpath(".users[0].name");
+        assert_eq!(
+            extract_jsonpath(&input, &query).unwrap(),
+            Some(json!("Alice"))
+        );
+    }
+
+    #[test]
+    fn test_count_matches_streaming_large_array() {
+        let records: Vec<String> = (0..10_000)
+            .map(|i| {
+                format!(
+                    r#"{{"id": {}, "tags": [{}]}}"#,
+                    i,
+                    "\"t\",".repeat(i % 3) + "\"x\""
+                )
+            })
+            .collect();
+        let input = format!("  [{}]\n", records.join(","));
+
+        assert_eq!(
+            count_matches_streaming(input.as_bytes(), "$[*].id").unwrap(),
+            10_000
+        );
+        assert_eq!(
+            count_matches_streaming(input.as_bytes(), "$[*].tags[*]").unwrap(),
+            19_999
+        );
+        assert_eq!(
+            count_matches_streaming(input.as_bytes(), "$[9999].tags[*]").unwrap(),
+            1
+        );
+        assert!(matches!(
+            count_matches_streaming(input.as_bytes(), "$.id"),
+            Err(JqrError::InvalidQuery(_))
+        ));
+        assert!(count_matches_streaming("[1, 2".as_bytes(), "$[*]").is_err());
+        assert!(count_matches_streaming("[]".as_bytes(), "$[").is_err());
+    }
+
+    #[test]
+    fn test_rename_keys_nested_and_swaps() {
+        let mut input = json!({"a": {"a": [{"b": "a"}]}, "b": 1});
+        let mapping = parse_rename_mapping("a=b,b=a").unwrap();
+        rename_keys(&mut input, &mapping).unwrap();
+        assert_eq!(input, json!({"b": {"b": [{"a": "a"}]}, "a": 1}));
+
+        let mut input = json!([{"x": 1, "y": 2}]);
+        let err = rename_keys(&mut input, &parse_rename_mapping("x=y").unwrap()).unwrap_err();
+        assert!(err.to_string().contains("'y'"));
+    }
+
+    #[test]
+    fn test_coerce_numeric_strings_edge_cases() {
+        let mut input = json!([
+            "0",
+            "-0",
+            "12",
+            "-3.25",
+            "1e3",
+            "2E-2",
+            "007",
+            "1.",
+            ".5",
+            "1e",
+            "+1",
+            " 1",
+            "1 ",
+            "NaN",
+            "18446744073709551615",
+            "123456789012345678901234567890",
+            "-"
+        ]);
+        coerce_numeric_strings(&mut input);
+        // Numbers keep their exact digits, however large
+        assert_eq!(
+            input.to_string(),
+            r#"[0,-0,12,-3.25,1e+3,2e-2,"007","1.",".5","1e","+1"," 1","1 ","NaN",18446744073709551615,123456789012345678901234567890,"-"]"#
+        );
+    }
+
+    #[test]
+    fn test_dual_view_sections() {
+        let view = dual_view(&json!({"id": 1, "tags": ["a"]})).unwrap();
+
+        assert!(view.json.contains("\"tags\": [\n    \"a\"\n  ]"));
+        assert_eq!(view.yaml, "id: 1\ntags:\n- a");
+    }
+
+    #[test]
+    fn test_max_depth_guard_rejects_pathological_nesting() {
+        let depth = 1_000_000;
+        let deep = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        let err = parse_json_with_max_depth(&deep, DEFAULT_MAX_DEPTH).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Maximum nesting depth of 128 exceeded"));
+
+        let nested = format!("{}1{}", "[".repeat(200), "]".repeat(200));
+        assert!(parse_json(&nested).is_err());
+        assert!(parse_json_with_max_depth(&nested, 200).is_ok());
+    }
+
+    #[test]
+    fn test_single_quote_strings_escapes() {
+        let value = json!({"it's": ["a\"b", "back\\slash", "line\nbreak", "[{,:}]"]});
+        let json = serde_json::to_string(&value).unwrap();
+
+        assert_eq!(
+            single_quote_strings(&json),
+            r#"{'it\'s':['a"b','back\\slash','line\nbreak','[{,:}]']}"#
+        );
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        match parse_json("{\n  \"a\": }") {
+            Err(JqrError::InvalidJson {
+                line,
+                column,
+                looks_like_yaml,
+                ..
+            }) => {
+                assert_eq!((line, column), (2, 8));
+                assert!(!looks_like_yaml);
+            }
+            other => panic!("expected InvalidJson, got {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_json("a: 1\nb: 2"),
+            Err(JqrError::InvalidJson {
+                looks_like_yaml: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            find_jsonpath(&json!({}), "$.users["),
+            Err(JqrError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            json_to_properties(&json!([1])),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+        assert!(matches!(
+            parse_transforms("reverse"),
+            Err(JqrError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            count_matches_streaming("[1,".as_bytes(), "$[*]"),
+            Err(JqrError::InvalidJson { .. })
+        ));
+    }
+
+    #[test]
+    fn test_extract_jsonpath_outcomes() {
+        let input = json!({"a": null, "b": "No results found"});
+
+        assert_eq!(extract_jsonpath(&input, "$.a").unwrap(), Some(json!(null)));
+        assert_eq!(
+            extract_jsonpath(&input, "$.b").unwrap(),
+            Some(json!("No results found"))
+        );
+        assert_eq!(extract_jsonpath(&input, "$.c").unwrap(), None);
+        assert_eq!(extract_jsonpath(&input, "$[?(@ == 1)]").unwrap(), None);
+        assert!(matches!(
+            extract_jsonpath(&input, "$[?("),
+            Err(JqrError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_convert_to_json_returns_string() {
+        let json = convert_to_json("users:\n  - name: Alice\n    admin: true\n").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            json!({"users": [{"name": "Alice", "admin": true}]})
+        );
+
+        assert!(matches!(
+            convert_to_json("a: [1, 2"),
+            Err(JqrError::InvalidYaml(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_options() {
+        let value = json!({"z": {"y": 1, "x": [null]}, "a": "b"});
+
+        let four = FormatOptions::new().indent(4).sort_keys(true);
+        assert_eq!(
+            format_json(&value, &four).unwrap(),
+            "{\n    \"a\": \"b\",\n    \"z\": {\n        \"x\": [\n            null\n        ],\n        \"y\": 1\n    }\n}"
+        );
+
+        let compact = FormatOptions::new().compact(true).trailing_newline(true);
+        assert_eq!(
+            format_json(&value, &compact).unwrap(),
+            "{\"a\":\"b\",\"z\":{\"x\":[null],\"y\":1}}\n"
+        );
+
+        assert_eq!(
+            format_json(&value, &FormatOptions::default()).unwrap(),
+            serde_json::to_string_pretty(&sort_object_keys(value.clone())).unwrap()
+        );
+
+        let ordered = FormatOptions::new().compact(true).preserve_order(true);
+        assert_eq!(
+            format_json(&value, &ordered).unwrap(),
+            "{\"z\":{\"y\":1,\"x\":[null]},\"a\":\"b\"}"
+        );
+        assert_eq!(
+            format_json(&value, &ordered.clone().sort_keys(true)).unwrap(),
+            format_json(&value, &compact.trailing_newline(false)).unwrap()
+        );
+        assert_eq!(
+            format_yaml(&value, &FormatOptions::new().preserve_order(true)).unwrap(),
+            "z:\n  y: 1\n  x:\n  - null\na: b"
+        );
+        assert_eq!(
+            convert_to_yaml(r#"{"a": 1}"#).unwrap(),
+            convert_to_yaml_with(r#"{"a": 1}"#, &FormatOptions::new().trailing_newline(true))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_line_width() {
+        let value = json!({
+            "point": {"x": 1, "y": 2},
+            "nums": (1..=12).collect::<Vec<_>>(),
+            "nested": [{"a": ["é"]}, []]
+        });
+        let options = FormatOptions::new()
+            .line_width(30)
+            .preserve_order(true)
+            .ascii_output(true);
+
+        assert_eq!(
+            format_json(&value, &options).unwrap(),
+            "{\n  \"point\": {\"x\": 1, \"y\": 2},\n  \"nums\": [\n    1, 2, 3, 4, 5, 6, 7, 8, 9,\n    10, 11, 12\n  ],\n  \"nested\": [\n    {\n      \"a\": [\"\\u00e9\"]\n    },\n    []\n  ]\n}"
+        );
+
+        // Compact output takes precedence
+        assert_eq!(
+            format_json(&value, &options.compact(true)).unwrap(),
+            r#"{"point":{"x":1,"y":2},"nums":[1,2,3,4,5,6,7,8,9,10,11,12],"nested":[{"a":["\u00e9"]},[]]}"#
+        );
+    }
+
+    #[test]
+    fn test_compiled_query_reuse() {
+        let query = CompiledQuery::new("$.items[*].id").unwrap();
+        assert_eq!(query.as_str(), "$.items[*].id");
+
+        let docs = [
+            json!({"items": [{"id": 1}, {"id": 2}]}),
+            json!({"items": [{"id": 3}]}),
+            json!({"items": []}),
+        ];
+        let results: Vec<_> = docs.iter().map(|doc| query.apply(doc)).collect();
+        assert_eq!(results, vec![Some(json!([1, 2])), Some(json!(3)), None]);
+        assert_eq!(query.find(&docs[1]), vec![json!(3)]);
+
+        for doc in &docs {
+            assert_eq!(
+                query.apply(doc),
+                extract_jsonpath(doc, "$.items[*].id").unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_stream_matches_buffered_formatting() {
+        let input = r#"{"d": {"f": {}, "e": [1.10]}, "a": [1, {"c": "x😀", "\"bé": null}]}"#;
+        let value: serde_json::Value = serde_json::from_str(input).unwrap();
+
+        for options in [
+            FormatOptions::new(),
+            FormatOptions::new().indent(4).trailing_newline(true),
+            FormatOptions::new().tabs(true),
+            FormatOptions::new().compact(true),
+            FormatOptions::new().sort_keys(true),
+            FormatOptions::new().preserve_order(true),
+            FormatOptions::new().preserve_order(true).compact(true),
+            FormatOptions::new().preserve_order(true).ascii_output(true),
+        ] {
+            let mut output = Vec::new();
+            pretty_print_json_stream(input.as_bytes(), &mut output, &options).unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                format_json(&value, &options).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_exact_numbers() {
+        let input =
+            r#"{"id": 340282366920938463463374607431768211457, "price": 19.990, "tiny": 1e-400}"#;
+        let value = parse_json(input).unwrap();
+
+        let compact = FormatOptions::new().compact(true);
+        let expected =
+            r#"{"id":340282366920938463463374607431768211457,"price":19.990,"tiny":1e-400}"#;
+        assert_eq!(format_json(&value, &compact).unwrap(), expected);
+
+        let mut output = Vec::new();
+        pretty_print_json_stream(input.as_bytes(), &mut output, &compact.preserve_order(true))
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+
+        assert_eq!(
+            convert_to_yaml(input).unwrap(),
+            "id: 340282366920938463463374607431768211457\nprice: 19.990\ntiny: 1e-400\n"
+        );
+        assert_eq!(
+            convert_to_json("id: 340282366920938463463374607431768211455").unwrap(),
+            "{\n  \"id\": 340282366920938463463374607431768211455\n}"
+        );
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let mut output = Vec::new();
+        let err = pretty_print_json_stream(
+            "{\"a\": [1,}".as_bytes(),
+            &mut output,
+            &FormatOptions::new(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, JqrError::InvalidJson { line: 1, .. }));
+
+        let at_limit = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(
+            pretty_print_json_stream(at_limit.as_bytes(), io::sink(), &FormatOptions::new())
+                .is_ok()
+        );
+
+        let deep = "[".repeat(100_000);
+        let err = pretty_print_json_stream(deep.as_bytes(), io::sink(), &FormatOptions::new())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            JqrError::NestingTooDeep {
+                max_depth: 128,
+                line: 1,
+                column: 129
+            }
+        ));
+
+        let trailing =
+            pretty_print_json_stream("{} {}".as_bytes(), io::sink(), &FormatOptions::new());
+        assert!(trailing.is_err());
+    }
+
+    #[test]
+    fn test_toml_conversion() {
+        let value = json!({
+            "name": "svc",
+            "mixed": [1, "two", 3.5],
+            "servers": [{"host": "a"}, {"host": "b"}]
+        });
+        let toml = json_to_toml(&value).unwrap();
+        assert_eq!(toml_to_json(&toml).unwrap(), value);
+
+        let err = json_to_toml(&json!([1, 2])).unwrap_err();
+        assert!(matches!(err, JqrError::UnsupportedValue(_)));
+
+        let big: serde_json::Value =
+            serde_json::from_str(r#"{"id": 18446744073709551616}"#).unwrap();
+        let err = json_to_toml(&big).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Integer 18446744073709551616 is out of TOML's 64-bit range (at $.id)"
+        );
+
+        let err = toml_to_json("[servers]\nlimit = nan\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "JSON has no NaN value (at $.servers.limit)"
+        );
+        assert!(matches!(
+            toml_to_json("a = 1\na = 2\n"),
+            Err(JqrError::InvalidToml(_))
+        ));
+    }
+
+    #[test]
+    fn test_xml_conversion() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- order export -->
+<order xmlns:x="urn:x" id="42">
+  <item sku="a">Pen &amp; paper</item>
+  <item sku="b"/>
+  <note><![CDATA[<fragile>]]></note>
+  <x:ref/>
+</order>"#;
+        let value = xml_to_json(xml, &XmlOptions::new()).unwrap();
+        assert_eq!(
+            value,
+            json!({"order": {
+                "@xmlns:x": "urn:x",
+                "@id": "42",
+                "item": [{"@sku": "a", "#text": "Pen & paper"}, {"@sku": "b"}],
+                "note": "<fragile>",
+                "x:ref": null
+            }})
+        );
+
+        let xml = json_to_xml(&value, &XmlOptions::new()).unwrap();
+        assert_eq!(xml_to_json(&xml, &XmlOptions::new()).unwrap(), value);
+
+        let options = XmlOptions::new().attribute_prefix("-").text_key("_");
+        let value = xml_to_json(r#"<a b="1">text</a>"#, &options).unwrap();
+        assert_eq!(value, json!({"a": {"-b": "1", "_": "text"}}));
+
+        for invalid in ["<a><b></a>", "<a/><b/>", "<a>", "", "text"] {
+            let err = xml_to_json(invalid, &XmlOptions::new()).unwrap_err();
+            assert!(matches!(err, JqrError::InvalidXml(_)), "{}", invalid);
+        }
+
+        let err = json_to_xml(&json!({"a": {"1b": 1}}), &XmlOptions::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'1b' is not a valid XML element name (at $.a['1b'])"
+        );
+        assert_eq!(
+            json_to_xml(&json!([1, {"b": true}]), &XmlOptions::new()).unwrap(),
+            "<root>\n  <item>1</item>\n  <item>\n    <b>true</b>\n  </item>\n</root>"
+        );
+    }
+
+    #[test]
+    fn test_csv_input() {
+        let csv = "\u{feff}id,comment\n1,\"line one\nline two\"\n2,\"say \"\"hi\"\"\"\n";
+        assert_eq!(
+            csv_to_json(csv, &CsvOptions::new()).unwrap(),
+            json!([
+                {"id": "1", "comment": "line one\nline two"},
+                {"id": "2", "comment": "say \"hi\""}
+            ])
+        );
+        assert_eq!(csv_to_json("a,b\n", &CsvOptions::new()).unwrap(), json!([]));
+
+        let err = csv_to_json("a,b\n1,2,3\n", &CsvOptions::new()).unwrap_err();
+        assert!(matches!(err, JqrError::InvalidCsv(_)));
+        let err = csv_to_json("a,b,a\n1,2,3\n", &CsvOptions::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid CSV: the header repeats the column 'a'"
+        );
+    }
+
+    #[test]
+    fn test_csv_output() {
+        let value = json!({"id": 1, "meta": {"ok": true, "note": null}, "text": "a\"b"});
+        let csv = json_to_csv(&value, &CsvOptions::new()).unwrap();
+        assert_eq!(csv, "id,meta.ok,meta.note,text\n1,true,,\"a\"\"b\"\n");
+
+        let tsv = json_to_csv(&json!([{"a": "x y"}]), &CsvOptions::new().delimiter(b'\t')).unwrap();
+        assert_eq!(tsv, "a\nx y\n");
+
+        assert_eq!(json_to_csv(&json!([]), &CsvOptions::new()).unwrap(), "");
+        let err = json_to_csv(&json!([{"a": 1}, 2]), &CsvOptions::new()).unwrap_err();
+        assert!(matches!(err, JqrError::UnsupportedValue(_)));
+
+        // Written with types inferred, the table reads back as it was
+        let rows = json!([{"id": 1, "name": "Alice"}, {"id": 2, "name": null}]);
+        let csv = json_to_csv(&rows, &CsvOptions::new()).unwrap();
+        assert_eq!(
+            csv_to_json(&csv, &CsvOptions::new().infer_types(true)).unwrap(),
+            rows
+        );
+    }
+
+    #[test]
+    fn test_msgpack_keys_and_ranges() {
+        // fixmap {1: "one", nil: true}
+        assert_eq!(
+            msgpack_to_json(b"\x82\x01\xa3one\xc0\xc3").unwrap(),
+            vec![json!({"1": "one", "null": true})]
+        );
+        assert_eq!(
+            msgpack_to_json(b"\xcf\xff\xff\xff\xff\xff\xff\xff\xff").unwrap(),
+            vec![json!(u64::MAX)]
+        );
+        assert!(msgpack_to_json(b"").unwrap().is_empty());
+
+        let err =
+            json_to_msgpack(&serde_json::from_str(r#"{"n": [18446744073709551616]}"#).unwrap())
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Integer 18446744073709551616 is out of MessagePack's 64-bit range (at $.n[0])"
+        );
+    }
+
+    #[test]
+    fn test_cbor_integers_beyond_i64() {
+        // -2^64, the smallest integer CBOR encodes without a bignum tag
+        let values = cbor_to_json(b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff").unwrap();
+        assert_eq!(values[0].to_string(), "-18446744073709551616");
+        assert_eq!(
+            json_to_cbor(&values[0]).unwrap(),
+            b"\x3b\xff\xff\xff\xff\xff\xff\xff\xff"
+        );
+
+        let err =
+            json_to_cbor(&serde_json::from_str("[18446744073709551616]").unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Integer 18446744073709551616 is out of CBOR's 64-bit range (at $[0])"
+        );
+    }
+
+    #[test]
+    fn test_avro_logical_types() {
+        let schema = r#"{"type": "record", "name": "Payment", "fields": [
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 6, "scale": 2}},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["CARD", "CASH"]}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "note", "type": ["null", "string"]}
+        ]}"#;
+
+        // amount -12.34 as the unscaled bytes [0xfb, 0x2e], kind CASH, at 1000, note null
+        assert_eq!(
+            avro_to_json(b"\x04\xfb\x2e\x02\xd0\x0f\x00", Some(schema)).unwrap(),
+            vec![json!({"amount": -1234, "kind": "CASH", "at": 1000, "note": null})]
+        );
+
+        let err = avro_to_json(b"", Some("{")).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid Avro: "));
+    }
+
+    #[test]
+    fn test_yaml_alias_options() {
+        let yaml = "defaults: &defaults\n  - &port 8080\nports:\n  - *port\n  - [*port, *defaults]\n---\nonly: here\n";
+
+        assert_eq!(
+            parse_yaml_documents_with(yaml, &YamlOptions::new()).unwrap(),
+            vec![
+                json!({"defaults": [8080], "ports": [8080, [8080, [8080]]]}),
+                json!({"only": "here"})
+            ]
+        );
+        assert_eq!(
+            parse_yaml_documents_with(yaml, &YamlOptions::new().keep_aliases(true)).unwrap(),
+            vec![
+                json!({"defaults": [8080], "ports": ["*port", ["*port", "*defaults"]]}),
+                json!({"only": "here"})
+            ]
+        );
+
+        // The limit counts every node an alias copies: 1 + 1 + 2
+        assert!(parse_yaml_documents_with(yaml, &YamlOptions::new().alias_limit(4)).is_ok());
+        let err = parse_yaml_documents_with(yaml, &YamlOptions::new().alias_limit(3)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid YAML: aliases expand to more than 3 nodes (at line 5 column 13)"
+        );
+
+        let err = parse_yaml_documents_with("a: *missing\n", &YamlOptions::new()).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid YAML: "));
+    }
+
+    #[test]
+    fn test_jsonc_edge_cases() {
+        // Escaped quotes and comment markers inside strings are not comments
+        assert_eq!(
+            jsonc_to_json(r#"{"url": "http://x/*y*/", "q": "say \"//\"", /* é */ "n": [[1,],],}"#)
+                .unwrap(),
+            json!({"url": "http://x/*y*/", "q": "say \"//\"", "n": [[1]]})
+        );
+
+        // A comma before a comment before the closing bracket is still trailing
+        assert_eq!(jsonc_to_json("[1, // last\n]").unwrap(), json!([1]));
+
+        // Commas that are not trailing are left for the parser to reject
+        assert!(jsonc_to_json("[1,,2]").is_err());
+        assert!(jsonc_to_json("{,}").is_err());
+
+        let err = jsonc_to_json("{\"a\": 1 /* never closed\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON: expected `,` or `}` at line 1 column 9"
+        );
+    }
+
+    #[test]
+    fn test_format_jsonc_keeps_comments() {
+        let input = "// header\n{\"b\": [1, /* two */ 2,], // list\n  /* a's */ \"a\": {\n    // empty\n  },\n} // end\n// footer\n";
+
+        assert_eq!(
+            format_jsonc(input, &FormatOptions::new()).unwrap(),
+            "// header\n{\n  /* a's */\n  \"a\": {\n    // empty\n  },\n  \"b\": [\n    1,\n    /* two */\n    2\n  ] // list\n} // end\n// footer"
+        );
+
+        // Without comments, the layout matches the JSON output
+        let plain = "{\"b\": [1, {\"c\": null}], \"a\": \"é\"}";
+        let options = FormatOptions::new().indent(4).ascii_output(true);
+        assert_eq!(
+            format_jsonc(plain, &options).unwrap(),
+            format_json(&parse_json(plain).unwrap(), &options).unwrap()
+        );
+
+        let err = format_jsonc("{\"a\": 1 /* open", &FormatOptions::new()).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid JSON: "));
+    }
+
+    #[test]
+    fn test_duplicate_key_policies() {
+        let input = r#"{"a": {"x": 1, "x": 2}, "b": [{"y": 1, "y": [3], "z": 0}], "n": 1.50}"#;
+
+        assert_eq!(
+            parse_json_with(input, &ParseOptions::new()).unwrap(),
+            parse_json(input).unwrap()
+        );
+
+        let first = ParseOptions::new().duplicate_keys(DuplicateKeys::First);
+        let value = parse_json_with(input, &first).unwrap();
+        assert_eq!(
+            value,
+            parse_json(r#"{"a": {"x": 1}, "b": [{"y": 1, "z": 0}], "n": 1.50}"#).unwrap()
+        );
+        // Numbers stay exact
+        assert_eq!(value["n"].to_string(), "1.50");
+
+        let error = ParseOptions::new().duplicate_keys(DuplicateKeys::Error);
+        let err = parse_json_with(input, &error).unwrap_err();
+        assert_eq!(err.to_string(), "Duplicate key 'x' at line 1 column 18");
+        assert!(parse_json_with(r#"{"a": {"x": 1}, "b": {"x": 2}}"#, &error).is_ok());
+
+        // Syntax errors are still reported as invalid JSON
+        let err = parse_json_with(r#"{"a": 1,}"#, &error).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid JSON: "));
+
+        let deep = ParseOptions::new().max_depth(2);
+        assert!(matches!(
+            parse_json_with("[[[1]]]", &deep),
+            Err(JqrError::NestingTooDeep { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let strict = ParseOptions::new().profile(ParseProfile::Strict);
+        let lenient = ParseOptions::new().profile(ParseProfile::Lenient);
+
+        // A byte order mark is skipped unless parsing strictly
+        assert_eq!(
+            parse_json_with("\u{feff}[1]", &ParseOptions::new()).unwrap(),
+            json!([1])
+        );
+        assert!(matches!(
+            parse_json_with("\u{feff}[1]", &strict),
+            Err(JqrError::InvalidJson {
+                line: 1,
+                column: 1,
+                ..
+            })
+        ));
+        assert_eq!(
+            parse_json_with("\u{feff}[1]", &lenient).unwrap(),
+            json!([1])
+        );
+
+        // Strict parsing refuses repeated keys whatever the policy
+        let err = parse_json_with(
+            r#"{"a": 1, "a": 2}"#,
+            &strict.clone().duplicate_keys(DuplicateKeys::Last),
+        )
+        .unwrap_err();
+        assert!(matches!(err, JqrError::DuplicateKey { .. }));
+        assert!(parse_json_with("[\"a\tb\"]", &strict).is_err());
+        assert!(parse_json_with("[\"a\tb\"]", &lenient).is_err());
+
+        // Quotes and comment markers inside strings survive the lenient rewrite
+        let input =
+            "{'a': 'it\\'s \"x\" // not', // note\n  'b': [-Infinity, +NaN, 1.50,], /* end */}";
+        let value = parse_json_with(input, &lenient).unwrap();
+        assert_eq!(
+            value,
+            parse_json(r#"{"a": "it's \"x\" // not", "b": [null, null, 1.50]}"#).unwrap()
+        );
+        assert_eq!(value["b"][2].to_string(), "1.50");
+        assert!(parse_json_with(input, &ParseOptions::new()).is_err());
+
+        let first = lenient.clone().duplicate_keys(DuplicateKeys::First);
+        assert_eq!(
+            parse_json_with("{'a': 1, 'a': 2,}", &first).unwrap(),
+            json!({"a": 1})
+        );
+
+        let err = parse_json_with("{'a': 'open", &lenient).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON: EOF while parsing a string at line 1 column 11"
+        );
+    }
+
+    #[test]
+    fn test_detect_input_format() {
+        // The extension wins over the content
+        assert_eq!(
+            detect_input_format(Some("data.JSONL"), b"{}"),
+            InputFormat::Ndjson
+        );
+        assert_eq!(
+            detect_input_format(Some("a.toml"), b"a: 1"),
+            InputFormat::Toml
+        );
+        assert_eq!(
+            detect_input_format(Some("a.mpk"), b"{}"),
+            InputFormat::Msgpack
+        );
+
+        // A JSON array is not a TOML table header, and the reverse
+        assert_eq!(detect_input_format(None, b"[true]"), InputFormat::Json);
+        assert_eq!(detect_input_format(None, b"[[1], [2]]"), InputFormat::Json);
+        assert_eq!(
+            detect_input_format(None, b"[[bin]]\nname = \"jqr\"\n"),
+            InputFormat::Toml
+        );
+        assert_eq!(
+            detect_input_format(Some("Cargo"), b"\n# build\nedition = \"2021\""),
+            InputFormat::Toml
+        );
+
+        assert_eq!(detect_input_format(None, b"---\na: 1\n"), InputFormat::Yaml);
+        assert_eq!(detect_input_format(None, b"- a\n- b\n"), InputFormat::Yaml);
+        assert_eq!(
+            detect_input_format(None, b"\xef\xbb\xbf 42"),
+            InputFormat::Json
+        );
+        assert_eq!(detect_input_format(None, b" \n"), InputFormat::Json);
+        assert_eq!(
+            detect_input_format(None, &[0xdf, 0, 0, 0, 0]),
+            InputFormat::Msgpack
+        );
+    }
+
+    #[test]
+    fn test_jq_filter() {
+        let doc = json!({
+            "users": [
+                {"name": "Alice", "age": 31, "tags": ["admin", "ops"]},
+                {"name": "Bob", "age": 25, "tags": []},
+            ],
+            "version": "1.2",
+        });
+        let run = |filter: &str| JqFilter::new(filter).unwrap().apply(&doc).unwrap();
+
+        assert_eq!(run(".users[].name"), vec![json!("Alice"), json!("Bob")]);
+        assert_eq!(run(".users | map(.age) | add"), vec![json!(56)]);
+        assert_eq!(
+            run(".users[] | select(.tags | length > 0) | {name, first: .tags[0]}"),
+            vec![json!({"name": "Alice", "first": "admin"})]
+        );
+        assert_eq!(
+            run(".users[-1].name, .version"),
+            vec![json!("Bob"), json!("1.2")]
+        );
+        assert_eq!(run("[.users[].age | . / 2]"), vec![json!([15.5, 12.5])]);
+        assert_eq!(run(".missing // \"none\""), vec![json!("none")]);
+        assert_eq!(run(".version.major?"), Vec::<serde_json::Value>::new());
+        assert_eq!(
+            run(".users | sort_by(-.age) | map(.name)"),
+            vec![json!(["Alice", "Bob"])]
+        );
+        assert_eq!(
+            run("{(.users[].name): .version}"),
+            vec![json!({"Alice": "1.2"}), json!({"Bob": "1.2"})]
+        );
+        assert_eq!(
+            run("if .version == \"1.2\" then \"current\" else \"old\" end"),
+            vec![json!("current")]
+        );
+        assert_eq!(
+            run(".users[0] | with_entries(select(.key != \"tags\"))"),
+            vec![json!({"name": "Alice", "age": 31})]
+        );
+
+        // jq ordering: null < false < true < numbers < strings < arrays < objects
+        assert_eq!(
+            JqFilter::new("sort")
+                .unwrap()
+                .apply(&json!([{}, [], "a", 1, true, false, null]))
+                .unwrap(),
+            vec![json!([null, false, true, 1, "a", [], {}])]
+        );
+
+        let err = JqFilter::new(".version.major")
+            .unwrap()
+            .apply(&doc)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Cannot index string with \"major\"");
+        assert!(matches!(
+            JqFilter::new("map(.a"),
+            Err(JqrError::InvalidQuery(_))
+        ));
+        assert!(JqFilter::new("reduce .[] as $x (0; . + $x)").is_err());
+        assert!(JqFilter::new("frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_jmespath() {
+        let doc = json!({
+            "people": [
+                {"name": "Ana", "age": 30, "tags": ["ops", "dev"]},
+                {"name": "Ben", "age": 20, "tags": []},
+                {"name": "Cy", "age": 40},
+            ],
+            "nested": [[1, 2], [3, [4]]],
+            "regions": {"eu": {"zones": 3}, "us": {"zones": 6}},
+        });
+        let run = |expression: &str| JmesPath::new(expression).unwrap().apply(&doc).unwrap();
+
+        assert_eq!(run("people[*].name"), json!(["Ana", "Ben", "Cy"]));
+        assert_eq!(run("people[?age > `25`].name"), json!(["Ana", "Cy"]));
+        assert_eq!(run("people[::-2].name"), json!(["Cy", "Ana"]));
+        assert_eq!(run("people[-1].name"), json!("Cy"));
+        assert_eq!(run("regions.*.zones"), json!([3, 6]));
+        assert_eq!(run("nested[]"), json!([1, 2, 3, [4]]));
+        assert_eq!(run("people[].tags[]"), json!(["ops", "dev"]));
+        assert_eq!(run("people[?!tags].name"), json!(["Ben", "Cy"]));
+        assert_eq!(
+            run("people[0].{who: name, first_tag: tags[0]}"),
+            json!({"who": "Ana", "first_tag": "ops"})
+        );
+        assert_eq!(run("people | [0].[name, age]"), json!(["Ana", 30]));
+        assert_eq!(run("missing || 'fallback'"), json!("fallback"));
+        assert_eq!(run("missing.path"), json!(null));
+
+        // Integers beyond 2^53 compare exactly, as with json_equal
+        let big = json!({"a": 9007199254740993u64, "b": 9007199254740992u64});
+        let compare = |expression: &str| JmesPath::new(expression).unwrap().apply(&big).unwrap();
+        assert_eq!(compare("a == b"), json!(false));
+        assert_eq!(compare("a == `9007199254740993`"), json!(true));
+
+        // Functions
+        assert_eq!(
+            run("sort_by(people, &age)[*].name"),
+            json!(["Ben", "Ana", "Cy"])
+        );
+        assert_eq!(run("max_by(people, &age).name"), json!("Cy"));
+        assert_eq!(run("length(people)"), json!(3));
+        assert_eq!(run("avg(people[*].age)"), json!(30));
+        assert_eq!(run("join('/', people[*].name)"), json!("Ana/Ben/Cy"));
+        assert_eq!(run("keys(regions)"), json!(["eu", "us"]));
+        assert_eq!(run("map(&length(tags || `[]`), people)"), json!([2, 0, 0]));
+        assert_eq!(run("to_string(`1.50`)"), json!("1.50"));
+
+        let err = JmesPath::new("sum(people[*].name)")
+            .unwrap()
+            .apply(&doc)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "sum() expects a number, got string");
+        assert!(matches!(
+            JmesPath::new("people[?"),
+            Err(JqrError::InvalidQuery(_))
+        ));
+        assert!(JmesPath::new("nope(people)").is_err());
+        assert!(JmesPath::new("length(people, people)").is_err());
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        // The examples of RFC 6901, section 5
+        let doc = json!({
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "m~n": 8,
+            " ": 7,
+        });
+        let resolve = |pointer: &str| JsonPointer::new(pointer).unwrap().resolve(&doc).cloned();
+
+        assert_eq!(resolve(""), Some(doc.clone()));
+        assert_eq!(resolve("/foo"), Some(json!(["bar", "baz"])));
+        assert_eq!(resolve("/foo/0"), Some(json!("bar")));
+        assert_eq!(resolve("/"), Some(json!(0)));
+        assert_eq!(resolve("/a~1b"), Some(json!(1)));
+        assert_eq!(resolve("/m~0n"), Some(json!(8)));
+        assert_eq!(resolve("/ "), Some(json!(7)));
+
+        // Missing values, and indexes that are not canonical
+        assert_eq!(resolve("/foo/2"), None);
+        assert_eq!(resolve("/foo/-"), None);
+        assert_eq!(resolve("/foo/01"), None);
+        assert_eq!(resolve("/foo/0/bar"), None);
+        assert_eq!(resolve("/missing"), None);
+
+        // `~01` is `~1` unescaped, not `/`
+        let tilde = json!({"~1": "tilde", "/": "slash"});
+        let pointer = JsonPointer::new("/~01").unwrap();
+        assert_eq!(pointer.resolve(&tilde), Some(&json!("tilde")));
+        assert_eq!(pointer.as_str(), "/~01");
+
+        let err = JsonPointer::new("foo").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid JSON Pointer 'foo': it must be empty or start with '/'"
+        );
+        assert!(matches!(
+            JsonPointer::new("/a~"),
+            Err(JqrError::InvalidQuery(_))
+        ));
+    }
+
+    #[cfg(feature = "jsonata")]
+    #[test]
+    fn test_jsonata() {
+        let doc = json!({"orders": [
+            {"id": 1, "customer": "acme", "amount": 120, "tags": ["rush"]},
+            {"id": 2, "customer": "initech", "amount": 80, "tags": []},
+            {"id": 3, "customer": "acme", "amount": 30, "tags": ["gift", "rush"]},
+        ]});
+        let eval = |expression: &str| Jsonata::new(expression).unwrap().apply(&doc).unwrap();
+
+        // Paths map over arrays and flatten array fields
+        assert_eq!(eval("orders.amount"), Some(json!([120, 80, 30])));
+        assert_eq!(eval("orders.tags"), Some(json!(["rush", "gift", "rush"])));
+        assert_eq!(eval("orders[0].id"), Some(json!(1)));
+        assert_eq!(eval("orders[-1].id"), Some(json!(3)));
+
+        // Integers beyond 2^53 compare exactly, as with json_equal
+        let big = json!({"a": 9007199254740993u64, "b": 9007199254740992u64});
+        let compare = |expression: &str| Jsonata::new(expression).unwrap().apply(&big).unwrap();
+        assert_eq!(compare("a = b"), Some(json!(false)));
+        assert_eq!(compare("a != b"), Some(json!(true)));
+        assert_eq!(eval("orders[amount > 50].id"), Some(json!([1, 2])));
+        assert_eq!(eval("orders[customer = 'initech'].amount"), Some(json!(80)));
+        assert_eq!(eval("orders.missing"), None);
+
+        // Aggregation, arithmetic and strings
+        assert_eq!(eval("$sum(orders.amount)"), Some(json!(230)));
+        assert_eq!(eval("$count(orders)"), Some(json!(3)));
+        assert_eq!(
+            eval("$max(orders.amount) - $min(orders.amount)"),
+            Some(json!(90))
+        );
+        assert_eq!(eval("$average(orders.amount)"), Some(json!(230.0 / 3.0)));
+        assert_eq!(
+            eval("$distinct(orders.customer)"),
+            Some(json!(["acme", "initech"]))
+        );
+        assert_eq!(
+            eval("$join(orders.customer, ', ')"),
+            Some(json!("acme, initech, acme"))
+        );
+        assert_eq!(
+            eval("$uppercase(orders[0].customer) & '#' & orders[0].id"),
+            Some(json!("ACME#1"))
+        );
+        assert_eq!(eval("$round(2.5)"), Some(json!(2)));
+        assert_eq!(eval("$round(1.23456, 2)"), Some(json!(1.23)));
+        assert_eq!(
+            eval("'rush' in orders[2].tags ? 'yes' : 'no'"),
+            Some(json!("yes"))
+        );
+
+        // Constructors and grouping
+        assert_eq!(
+            eval("orders{customer: $sum(amount)}"),
+            Some(json!({"acme": 150, "initech": 80}))
+        );
+        assert_eq!(
+            eval("orders[0].{'id': id, 'big': amount > 100}"),
+            Some(json!({"id": 1, "big": true}))
+        );
+        assert_eq!(eval("[orders[0].id, orders[1].id]"), Some(json!([1, 2])));
+
+        // Parse errors, checked functions and runtime type errors
+        for expression in [
+            "orders[",
+            "$nosuch(1)",
+            "$sum(1, 2)",
+            "$x := 1",
+            "orders @ 1",
+        ] {
+            let err = Jsonata::new(expression).unwrap_err();
+            assert!(matches!(err, JqrError::InvalidQuery(_)), "{}", expression);
+        }
+        let err = Jsonata::new("$sum(1, 2)").unwrap_err();
+        assert!(err.to_string().contains("$sum() takes 1 argument, not 2"));
+        let err = Jsonata::new("orders[0].customer + 1")
+            .unwrap()
+            .apply(&doc)
+            .unwrap_err();
+        assert!(matches!(err, JqrError::UnsupportedValue(_)));
+    }
+
+    #[test]
+    fn test_rfc9535_dialect() {
+        let doc = json!({
+            "o": {"j": 1, "k": 2},
+            "a": [5, 3, {"b": "j"}, {"b": "k"}, {"b": {}}, {"b": "kilo"}],
+            "letters": ["a", "b", "c", "d", "e", "f", "g"],
+        });
+        let find = |query: &str| {
+            CompiledQuery::with_dialect(query, JsonPathDialect::Rfc9535)
+                .unwrap()
+                .find(&doc)
+        };
+
+        // Selectors and slices, from the examples of RFC 9535
+        assert_eq!(find("$.o['j', 'k']"), vec![json!(1), json!(2)]);
+        assert_eq!(find("$.letters[1:5:2]"), vec![json!("b"), json!("d")]);
+        assert_eq!(find("$.letters[5:1:-2]"), vec![json!("f"), json!("d")]);
+        assert_eq!(find("$.letters[-2:]"), vec![json!("f"), json!("g")]);
+        assert_eq!(find("$.letters[::0]"), Vec::<serde_json::Value>::new());
+        assert_eq!(find("$..j"), vec![json!(1)]);
+
+        // Integers beyond 2^53 compare exactly, as with json_equal
+        let big = json!([{"n": 9007199254740993u64}, {"n": 9007199254740992u64}]);
+        assert_eq!(
+            CompiledQuery::with_dialect("$[?@.n == 9007199254740992].n", JsonPathDialect::Rfc9535)
+                .unwrap()
+                .find(&big),
+            vec![json!(9007199254740992u64)]
+        );
+
+        // Filters, with and without parentheses, and functions
+        assert_eq!(find("$.a[?@ > 3]"), vec![json!(5)]);
+        assert_eq!(find("$.a[?(@.b == 'kilo')]"), vec![json!({"b": "kilo"})]);
+        assert_eq!(find("$.a[?@.b == $.missing]"), vec![json!(5), json!(3)]);
+        assert_eq!(
+            find("$.a[?match(@.b, '[jk]')]"),
+            vec![json!({"b": "j"}), json!({"b": "k"})]
+        );
+        assert_eq!(find("$.a[?search(@.b, '[jk]')]").len(), 3);
+        assert_eq!(find("$[?length(@) == 2]"), vec![json!({"j": 1, "k": 2})]);
+        assert_eq!(find("$[?count(@.*) > 5]").len(), 2);
+
+        // Malformed or ill-typed queries are errors
+        for query in [
+            "$[01]",
+            "$[-0]",
+            "$['a]",
+            "$.a ",
+            "$.a[?@.* == 1]",
+            "$[?count(@.*)]",
+            "$[?match(@.b, 'j') == true]",
+            "$[?nosuch(@)]",
+        ] {
+            let err = CompiledQuery::with_dialect(query, JsonPathDialect::Rfc9535).unwrap_err();
+            assert!(matches!(err, JqrError::InvalidQuery(_)), "{}", query);
+        }
+
+        // The legacy dialect stays the default
+        let legacy = CompiledQuery::new("$.a[?(@ > 3)]").unwrap();
+        assert_eq!(legacy.find(&doc), vec![json!(5)]);
+    }
+
+    #[test]
+    fn test_split_query_label() {
+        assert_eq!(split_query_label("id=$.id"), Some(("id", "$.id")));
+        assert_eq!(
+            split_query_label("first-name=$.names[?(@.n == 1)]"),
+            Some(("first-name", "$.names[?(@.n == 1)]"))
+        );
+        assert_eq!(split_query_label("=$.id"), None);
+        assert_eq!(split_query_label("1st=$.id"), None);
+        assert_eq!(split_query_label("$.a[?(@.b=='c')]"), None);
+        assert_eq!(split_query_label("users[?(@.id==1)]"), None);
+    }
+
+    #[test]
+    fn test_substitute_query_variables() {
+        let variables = [
+            ("name".to_string(), json!("O'Brien \"Jr\"")),
+            ("ids".to_string(), json!([1, 2])),
+        ];
+
+        // Values are written as JSON literals, escaped as needed
+        assert_eq!(
+            substitute_query_variables("$[?(@.name == $name)]", &variables),
+            r#"$[?(@.name == "O'Brien \"Jr\"")]"#
+        );
+        assert_eq!(
+            substitute_query_variables(".[] | select(.id | IN($ids[]))", &variables),
+            ".[] | select(.id | IN([1,2][]))"
+        );
+
+        // Roots, unknown variables and string contents are left alone
+        assert_eq!(substitute_query_variables("$..name", &variables), "$..name");
+        assert_eq!(
+            substitute_query_variables("$[?(@.a == $other)]", &variables),
+            "$[?(@.a == $other)]"
+        );
+        assert_eq!(
+            substitute_query_variables(r#"$[?(@.a == "\"$name")]"#, &variables),
+            r#"$[?(@.a == "\"$name")]"#
+        );
+    }
+
+    #[test]
+    fn test_find_with_paths() {
+        let doc = json!({"a b": [{"x": 1}, {"x": 2}], "n": {"x": 3}});
+        for dialect in [JsonPathDialect::Legacy, JsonPathDialect::Rfc9535] {
+            let query = CompiledQuery::with_dialect("$..x", dialect).unwrap();
+            let mut found = query.find_with_paths(&doc);
+            found.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(
+                found,
+                vec![
+                    (Some("$.n.x".to_string()), json!(3)),
+                    (Some("$['a b'][0].x".to_string()), json!(1)),
+                    (Some("$['a b'][1].x".to_string()), json!(2)),
+                ],
+                "{:?}",
+                dialect
+            );
+        }
+
+        // Computed values have no location
+        let query = CompiledQuery::new("$['a b'].length()").unwrap();
+        assert_eq!(query.find_with_paths(&doc), vec![(None, json!(2))]);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let values = json!([4, 1.5, -2]);
+        assert_eq!(aggregate(&values, Aggregate::Sum).unwrap(), json!(3.5));
+        assert_eq!(aggregate(&values, Aggregate::Min).unwrap(), json!(-2));
+        assert_eq!(aggregate(&values, Aggregate::Max).unwrap(), json!(4));
+        assert_eq!(
+            aggregate(&json!([1, 2]), Aggregate::Avg).unwrap(),
+            json!(1.5)
+        );
+        assert_eq!(aggregate(&json!(7), Aggregate::Count).unwrap(), json!(1));
+
+        // Integer sums stay exact beyond the range of f64 integers
+        let large = json!([9007199254740993i64, 1]);
+        assert_eq!(
+            aggregate(&large, Aggregate::Sum).unwrap(),
+            json!(9007199254740994i64)
+        );
+
+        assert_eq!(aggregate(&json!([]), Aggregate::Sum).unwrap(), json!(0));
+        assert_eq!(aggregate(&json!([]), Aggregate::Avg).unwrap(), json!(null));
+        assert!(matches!(
+            aggregate(&json!([1, null]), Aggregate::Max),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+        assert!("median".parse::<Aggregate>().is_err());
+        assert_eq!("avg".parse::<Aggregate>().unwrap(), Aggregate::Avg);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let values = [
+            json!({"level": "warn", "id": 1}),
+            json!({"level": 2, "id": 2}),
+            json!({"id": 3}),
+            json!({"level": "warn", "id": 4}),
+        ];
+        let key = CompiledQuery::new("$.level").unwrap();
+
+        let groups = group_by(&values, &key);
+        assert_eq!(
+            groups.keys().collect::<Vec<_>>(),
+            ["warn", "2", "null"],
+            "groups keep the order of their first value"
+        );
+        assert_eq!(
+            groups["warn"],
+            json!([{"level": "warn", "id": 1}, {"level": "warn", "id": 4}])
+        );
+
+        let counts = count_by(&values, &key);
+        assert_eq!(json!(counts), json!({"warn": 2, "2": 1, "null": 1}));
+        assert!(count_by(&[], &key).is_empty());
+    }
+
+    #[test]
+    fn test_sort_by() {
+        // Mixed types sort as in jq, and ties keep their order
+        let mut values = json!([
+            {"v": "b", "id": 1},
+            {"v": 2, "id": 2},
+            {"id": 3},
+            {"v": "b", "id": 4},
+            {"v": false, "id": 5}
+        ]);
+        sort_by(&mut values, &parse_sort_keys("v").unwrap()).unwrap();
+        let ids: Vec<_> = values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["id"].clone())
+            .collect();
+        assert_eq!(ids, [json!(3), json!(5), json!(2), json!(1), json!(4)]);
+
+        sort_by(&mut values, &parse_sort_keys("$.v:desc,$.id:desc").unwrap()).unwrap();
+        let ids: Vec<_> = values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["id"].clone())
+            .collect();
+        assert_eq!(ids, [json!(4), json!(1), json!(2), json!(5), json!(3)]);
+
+        let mut doc = json!({"groups": [{"items": [3, 1]}, {"items": [2, 0]}]});
+        sort_at(
+            &mut doc,
+            "$.groups[*].items",
+            &parse_sort_keys("$").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            doc,
+            json!({"groups": [{"items": [1, 3]}, {"items": [0, 2]}]})
+        );
+
+        assert!(sort_at(&mut doc, "$.groups[0]", &parse_sort_keys("$").unwrap()).is_err());
+        assert!(matches!(
+            parse_sort_keys(":desc"),
+            Err(JqrError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            parse_sort_keys("$.a[").unwrap_err(),
+            JqrError::InvalidQuery(_)
+        ));
+    }
+
+    #[test]
+    fn test_unique() {
+        let mut value = json!([{"x": {"b": 1, "a": 2}}, {"x": {"a": 2, "b": 1}}, null, null, []]);
+        unique(&mut value).unwrap();
+        assert_eq!(value, json!([{"x": {"b": 1, "a": 2}}, null, []]));
+
+        // Elements without the key share the null key
+        let mut value = json!([{"id": 1}, {"name": "a"}, {"id": 1}, {"name": "b"}, {"id": null}]);
+        unique_by(&mut value, &CompiledQuery::new("$.id").unwrap()).unwrap();
+        assert_eq!(value, json!([{"id": 1}, {"name": "a"}]));
+
+        assert!(matches!(
+            unique(&mut json!("a")),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_flatten_round_trip() {
+        let values = [
+            json!({"a": {"b.c": [1, [2, {"d[0]": "e"}]]}, "f\\g": [], "": {"": 0}}),
+            json!([[], {"a": null}, [[true]]]),
+            json!({}),
+        ];
+        for value in values {
+            let flat = serde_json::Value::Object(flatten(&value).unwrap());
+            assert_eq!(unflatten(&flat).unwrap(), value, "round trip of {}", value);
+        }
+
+        let flat = flatten(&json!({"a": {"b.c": [1]}})).unwrap();
+        assert_eq!(flat.keys().collect::<Vec<_>>(), ["a.b\\.c[0]"]);
+
+        assert_eq!(
+            unflatten(&json!({"a[2]": 1, "a[0].b": 2})).unwrap(),
+            json!({"a": [{"b": 2}, null, 1]})
+        );
+        for conflicting in [
+            json!({"a.b": 1, "a": 2}),
+            json!({"a": {}, "a.b": 1}),
+            json!({"[0]": 1, "a": 2}),
+        ] {
+            assert!(unflatten(&conflicting).is_err(), "{}", conflicting);
+        }
+        for invalid in ["a[x]", "a[1]b", "a\\", "a[-1]"] {
+            assert!(unflatten(&json!({ invalid: 1 })).is_err(), "{}", invalid);
+        }
+        assert!(unflatten(&json!([1])).is_err());
+    }
+
+    #[test]
+    fn test_predicate() {
+        let record = json!({"level": "warn", "tags": ["db", "slow"], "ms": 1200, "user": null});
+        let holds = |expression: &str| Predicate::new(expression).unwrap().matches(&record);
+
+        assert!(holds("$.level == 'warn'"));
+        assert!(holds("  @.ms > 1000 && !($.level == 'info')  "));
+        assert!(holds("$.user"), "an existing null field exists");
+        assert!(!holds("$.missing"));
+        assert!(holds("count($.tags[*]) == 2 || $.ms < 0"));
+        assert!(holds("search($.level, 'ar')"));
+        assert!(!holds("$.tags == 'db'"));
+
+        let predicate = Predicate::new("$.ms >= 1000").unwrap();
+        assert_eq!(predicate.as_str(), "$.ms >= 1000");
+        for invalid in ["", "$.ms >=", "$.a == 1 extra", "$.tags[*] == 'db'"] {
+            assert!(
+                matches!(Predicate::new(invalid), Err(JqrError::InvalidQuery(_))),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_projection() {
+        let order = json!({
+            "id": "A1",
+            "items": [{"sku": "x", "qty": 2}, {"sku": "y", "qty": 1}],
+            "customer": {"first name": "Ada"}
+        });
+
+        let projection = Projection::new(
+            r#"{order: $.id, skus: $.items[*].sku, "name": $.customer['first name'],
+                first: {sku: $.items[0].sku}, flags: [true, -1.5, null, "a,b"], 'it\'s': @.none}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            projection.apply(&order),
+            json!({
+                "order": "A1",
+                "skus": ["x", "y"],
+                "name": "Ada",
+                "first": {"sku": "x"},
+                "flags": [true, -1.5, null, "a,b"],
+                "it's": null
+            })
+        );
+
+        // A filter keeps its commas and brackets
+        let projection = Projection::new("{big: $.items[?(@.qty > 1)].sku, n: 1}").unwrap();
+        assert_eq!(projection.apply(&order), json!({"big": "x", "n": 1}));
+        assert_eq!(
+            Projection::new("[]").unwrap().map(&json!([1, 2])),
+            json!([[], []])
+        );
+
+        for invalid in ["{id: bare}", "{id", "{: 1}", "[1 2]", "{a: $.b[}", "'open"] {
+            assert!(
+                matches!(Projection::new(invalid), Err(JqrError::InvalidQuery(_))),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_reverse_shuffle_sample() {
+        let numbers: Vec<u64> = (0..50).collect();
+
+        let mut value = json!(numbers);
+        reverse(&mut value).unwrap();
+        assert_eq!(value, json!((0..50).rev().collect::<Vec<u64>>()));
+
+        let mut first = json!(numbers);
+        let mut second = json!(numbers);
+        shuffle(&mut first, Some(1)).unwrap();
+        shuffle(&mut second, Some(1)).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, json!(numbers), "50 elements are not left in order");
+        let mut elements: Vec<u64> = serde_json::from_value(first).unwrap();
+        elements.sort();
+        assert_eq!(elements, numbers);
+
+        let mut first = json!(numbers);
+        let mut second = json!(numbers);
+        sample(&mut first, 5, Some(9)).unwrap();
+        sample(&mut second, 5, Some(9)).unwrap();
+        assert_eq!(first, second);
+        let kept: Vec<u64> = serde_json::from_value(first).unwrap();
+        assert_eq!(kept.len(), 5);
+        assert!(kept.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let mut small = json!(["a", "b"]);
+        sample(&mut small, 3, None).unwrap();
+        assert_eq!(small, json!(["a", "b"]));
+        assert!(matches!(
+            sample(&mut json!({"a": 1}), 1, None),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_pivot() {
+        let rows = json!([{"a": 1, "b": [2]}, {"a": null}, {"c": "x"}]);
+        let columns = pivot(&rows).unwrap();
+        assert_eq!(
+            columns,
+            json!({"a": [1, null, null], "b": [[2], null, null], "c": [null, null, "x"]})
+        );
+
+        // Back to rows, with `null` for the keys a row did not have
+        assert_eq!(
+            pivot(&columns).unwrap(),
+            json!([
+                {"a": 1, "b": [2], "c": null},
+                {"a": null, "b": null, "c": null},
+                {"a": null, "b": null, "c": "x"}
+            ])
+        );
+
+        assert_eq!(
+            pivot(&json!({"a": [1, 2], "b": [3]})).unwrap(),
+            json!([{"a": 1, "b": 3}, {"a": 2}])
+        );
+        assert_eq!(pivot(&json!([])).unwrap(), json!({}));
+        assert_eq!(pivot(&json!({})).unwrap(), json!([]));
+        assert!(matches!(
+            pivot(&json!("rows")),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut doc = json!({"a": {"b": 1}, "list": [1, 2], "n": null});
+
+        set_path(&mut doc, "a.b", json!([true]), false).unwrap();
+        set_path(&mut doc, "$.a['c d']", json!("x"), false).unwrap();
+        set_path(&mut doc, "$.list[2]", json!(3), false).unwrap();
+        assert_eq!(
+            doc,
+            json!({"a": {"b": [true], "c d": "x"}, "list": [1, 2, 3], "n": null})
+        );
+
+        // Missing and null values along the path need `create_missing`
+        assert!(matches!(
+            set_path(&mut doc, "$.n.x", json!(1), false),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+        assert!(set_path(&mut doc, "$.list[5]", json!(6), false).is_err());
+        set_path(&mut doc, "$.n.x", json!(1), true).unwrap();
+        set_path(&mut doc, "$.list[5]", json!(6), true).unwrap();
+        set_path(&mut doc, "$.new[1].y", json!(2), true).unwrap();
+        assert_eq!(doc["n"], json!({"x": 1}));
+        assert_eq!(doc["list"], json!([1, 2, 3, null, null, 6]));
+        assert_eq!(doc["new"], json!([null, {"y": 2}]));
+
+        assert!(set_path(&mut doc, "$.list[0].x", json!(1), true).is_err());
+        assert!(set_path(&mut doc, "$..missing", json!(1), true).is_err());
+        assert!(matches!(
+            set_path(&mut doc, "$[", json!(1), true),
+            Err(JqrError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_paths() {
+        let mut doc = json!({
+            "b": [{"secret": 1, "keep": [0, 1, 2]}, {"secret": {"secret": 2}}],
+            "a/~": true,
+            "secret": 3
+        });
+
+        // Nested matches, and matches on both sides of a removed element
+        delete_paths(&mut doc, "$..secret").unwrap();
+        delete_paths(&mut doc, "$.b[0].keep[0,2]").unwrap();
+        assert_eq!(doc, json!({"b": [{"keep": [1]}, {}], "a/~": true}));
+
+        delete_paths(&mut doc, "$['a/~']").unwrap();
+        delete_paths(&mut doc, "$.missing").unwrap();
+        delete_paths(&mut doc, "b[*]").unwrap();
+        assert_eq!(doc, json!({"b": []}));
+
+        let mut long = json!((0..12).collect::<Vec<u64>>());
+        delete_paths(&mut long, "$[2,10]").unwrap();
+        assert_eq!(long, json!([0, 1, 3, 4, 5, 6, 7, 8, 9, 11]));
+
+        assert!(matches!(
+            delete_paths(&mut doc, "$"),
+            Err(JqrError::UnsupportedValue(_))
+        ));
+    }
+}
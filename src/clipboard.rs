@@ -0,0 +1,37 @@
+//! The system clipboard as a source of input and a destination for output.
+
+use std::io;
+
+/// Returns the text currently on the clipboard.
+#[cfg(feature = "clipboard")]
+pub fn get_text() -> io::Result<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(io::Error::other)
+}
+
+/// Replaces the clipboard content with `text`.
+#[cfg(feature = "clipboard")]
+pub fn set_text(text: String) -> io::Result<()> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(io::Error::other)
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn get_text() -> io::Result<String> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn set_text(_text: String) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "jqr was built without the clipboard feature",
+    )
+}
@@ -0,0 +1,62 @@
+//! Columnar files, Parquet and Arrow IPC, read as rows of JSON objects.
+
+use serde_json::Value;
+use std::io;
+
+/// Reads the rows of the Parquet or Arrow IPC file or stream in `content`,
+/// one object per row keyed by column name. Null cells are kept as `null`.
+#[cfg(feature = "parquet")]
+pub fn read_rows(content: Vec<u8>) -> io::Result<Vec<Value>> {
+    use arrow_array::RecordBatch;
+    use arrow_ipc::reader::{FileReader, StreamReader};
+    use arrow_json::writer::{JsonArray, WriterBuilder};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    const PARQUET_MAGIC: &[u8] = b"PAR1";
+    const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+    let invalid =
+        |e: &dyn std::error::Error| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+    let batches: Vec<RecordBatch> = if content.starts_with(PARQUET_MAGIC) {
+        ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(content))
+            .and_then(|builder| builder.build())
+            .map_err(|e| invalid(&e))?
+            .collect::<Result<_, _>>()
+    } else if content.starts_with(ARROW_FILE_MAGIC) {
+        FileReader::try_new(io::Cursor::new(content), None)
+            .map_err(|e| invalid(&e))?
+            .collect::<Result<_, _>>()
+    } else {
+        StreamReader::try_new(io::Cursor::new(content), None)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("not a Parquet file or Arrow IPC file or stream ({})", e),
+                )
+            })?
+            .collect::<Result<_, _>>()
+    }
+    .map_err(|e| invalid(&e))?;
+
+    let mut writer = WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, JsonArray>(Vec::new());
+    writer
+        .write_batches(&batches.iter().collect::<Vec<_>>())
+        .and_then(|()| writer.finish())
+        .map_err(|e| invalid(&e))?;
+
+    let json = writer.into_inner();
+    if json.is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice(&json).map_err(|e| invalid(&e))
+}
+
+#[cfg(not(feature = "parquet"))]
+pub fn read_rows(_content: Vec<u8>) -> io::Result<Vec<Value>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "jqr was built without the parquet feature",
+    ))
+}
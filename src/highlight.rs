@@ -0,0 +1,72 @@
+//! Syntax highlighting of formatted JSON for terminal output.
+
+use colored::*;
+
+/// Colors the tokens of already formatted JSON text: keys, strings, numbers,
+/// booleans and `null` each get their own color, while punctuation and
+/// whitespace are copied unchanged. Works with any indentation, including
+/// compact output.
+///
+/// The input is expected to be valid JSON as written by the formatter; any
+/// other text is copied through as it is.
+pub fn highlight_json(json: &str) -> String {
+    let mut output = String::with_capacity(json.len() * 2);
+    let mut rest = json;
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '"' => {
+                let len = string_len(rest);
+                let token = &rest[..len];
+                let is_key = rest[len..].trim_start().starts_with(':');
+                if is_key {
+                    output.push_str(&token.blue().bold().to_string());
+                } else {
+                    output.push_str(&token.green().to_string());
+                }
+                len
+            }
+            '-' | '0'..='9' => {
+                let len = rest
+                    .find(|c: char| !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+                    .unwrap_or(rest.len());
+                output.push_str(&rest[..len].cyan().to_string());
+                len
+            }
+            _ => {
+                if let Some(literal) = ["true", "false", "null"]
+                    .into_iter()
+                    .find(|literal| rest.starts_with(literal))
+                {
+                    if literal == "null" {
+                        output.push_str(&literal.bright_black().to_string());
+                    } else {
+                        output.push_str(&literal.yellow().to_string());
+                    }
+                    literal.len()
+                } else {
+                    output.push(c);
+                    c.len_utf8()
+                }
+            }
+        };
+        rest = &rest[len..];
+    }
+
+    output
+}
+
+/// Length in bytes of the string literal at the start of `text`, including
+/// both quotes (or the rest of `text` if it is unterminated).
+fn string_len(text: &str) -> usize {
+    let mut escaped = false;
+    for (i, b) in text.bytes().enumerate().skip(1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return i + 1,
+            _ => {}
+        }
+    }
+    text.len()
+}
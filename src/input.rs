@@ -0,0 +1,126 @@
+//! Where input is read from: a file, an HTTP(S) URL or stdin, decompressed
+//! on the fly when it is gzip, zstd or bzip2 compressed.
+
+use crate::clipboard;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::time::Duration;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// How URLs given as input are fetched.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub struct HttpOptions {
+    /// Extra request headers as name and value.
+    pub headers: Vec<(String, String)>,
+    /// Sent as `Authorization: Bearer <token>`.
+    pub bearer_token: Option<String>,
+    /// Limit for the whole request, including reading the body.
+    pub timeout: Option<Duration>,
+    /// How many redirects to follow; `0` fails on the first one.
+    pub max_redirects: u32,
+}
+
+/// Returns `true` if `path` is an `http://` or `https://` URL.
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Opens the file or URL at `path`, or stdin if `None`, for reading its
+/// decompressed content.
+pub fn open(path: Option<&str>, http: &HttpOptions) -> io::Result<BufReader<Box<dyn Read>>> {
+    let source = match path {
+        Some(path) => source(path, http)?,
+        None => Box::new(io::stdin().lock()),
+    };
+    decompress(source).map(BufReader::new)
+}
+
+/// Opens the text on the system clipboard for reading.
+pub fn clipboard() -> io::Result<BufReader<Box<dyn Read>>> {
+    let text = clipboard::get_text()?;
+    Ok(BufReader::new(Box::new(io::Cursor::new(text.into_bytes()))))
+}
+
+/// Reads the decompressed content of the file or URL at `path` into a string.
+pub fn read_to_string(path: &str, http: &HttpOptions) -> io::Result<String> {
+    let mut content = String::new();
+    decompress(source(path, http)?)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Reads the decompressed content of the file or URL at `path` as bytes.
+pub fn read(path: &str, http: &HttpOptions) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    decompress(source(path, http)?)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn source(path: &str, http: &HttpOptions) -> io::Result<Box<dyn Read>> {
+    if is_url(path) {
+        fetch(path, http)
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// Sends a GET request for `url` and returns the response body. Responses
+/// with an error status fail with the status in the message.
+#[cfg(feature = "http")]
+fn fetch(url: &str, http: &HttpOptions) -> io::Result<Box<dyn Read>> {
+    let mut agent = ureq::AgentBuilder::new().redirects(http.max_redirects);
+    if let Some(timeout) = http.timeout {
+        agent = agent.timeout(timeout);
+    }
+
+    let mut request = agent.build().get(url);
+    for (name, value) in &http.headers {
+        request = request.set(name, value);
+    }
+    if let Some(token) = &http.bearer_token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+
+    match request.call() {
+        // Past the redirect limit, ureq hands back the redirect itself
+        Ok(response) if (300..400).contains(&response.status()) => Err(io::Error::other(format!(
+            "{}: too many redirects (redirected to {})",
+            url,
+            response.header("Location").unwrap_or("nowhere")
+        ))),
+        Ok(response) => Ok(response.into_reader()),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch(url: &str, _http: &HttpOptions) -> io::Result<Box<dyn Read>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "cannot fetch {}: jqr was built without the http feature",
+            url
+        ),
+    ))
+}
+
+/// Wraps `source` in a decoder chosen by its first bytes, so compressed input
+/// is recognized whatever the file is called, and on stdin too. Anything else
+/// is passed through unchanged.
+fn decompress(source: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+    let mut source = BufReader::new(source);
+    let head = source.fill_buf()?;
+
+    Ok(if head.starts_with(GZIP_MAGIC) {
+        Box::new(flate2::bufread::MultiGzDecoder::new(source))
+    } else if head.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::stream::read::Decoder::with_buffer(source)?)
+    } else if head.starts_with(BZIP2_MAGIC) {
+        Box::new(bzip2::bufread::MultiBzDecoder::new(source))
+    } else {
+        Box::new(source)
+    })
+}
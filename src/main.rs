@@ -1,71 +1,3367 @@
-use clap::{Arg, Command};
-use jqr::*;
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use colored::*;
+use jqr_core::*;
+use rayon::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, BufRead, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 
-fn main() {
+mod clipboard;
+mod columnar;
+mod highlight;
+mod input;
+mod output;
+
+use highlight::highlight_json;
+use input::HttpOptions;
+use output::Output;
+
+/// Exit status for usage, input and processing errors.
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
     let matches = Command::new("jqr")
         .version("0.1.0")
         .author("Author <Daniel Morlim>")
         .about("Pretty-print and query JSON data")
-        .arg(Arg::new("file").help("Path to JSON file. If omitted, reads from stdin."))
-        .arg(Arg::new("query").help("JSONPath query (e.g., '$.user.name')"))
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("group-by")
+                .about("Group the values a query matches by a key into an object of arrays")
+                .arg(
+                    Arg::new("query")
+                        .required(true)
+                        .help("JSONPath query selecting the values to group (e.g., '$.items[*]')"),
+                )
+                .arg(Arg::new("file").help("Path to JSON file. If omitted, reads from stdin."))
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("QUERY")
+                        .required(true)
+                        .help("JSONPath query run on each value for its group (e.g., '$.category')"),
+                )
+                .arg(
+                    Arg::new("count")
+                        .long("count")
+                        .action(ArgAction::SetTrue)
+                        .help("Print the number of values in each group instead of the values"),
+                ),
+        )
+        .subcommand(
+            Command::new("flatten")
+                .about("Flatten a document into an object keyed by dot-notation paths (a.b[0].c)")
+                .arg(Arg::new("file").help("Path to JSON file. If omitted, reads from stdin.")),
+        )
+        .subcommand(
+            Command::new("unflatten")
+                .about("Rebuild a nested document from an object keyed by dot-notation paths")
+                .arg(Arg::new("file").help("Path to JSON file. If omitted, reads from stdin.")),
+        )
+        .subcommand(
+            Command::new("pivot")
+                .about("Turn an array of objects into an object of arrays, or back")
+                .arg(Arg::new("file").help("Path to JSON file. If omitted, reads from stdin.")),
+        )
+        .subcommand(
+            Command::new("set")
+                .about("Set the values a query matches, or the value at a path, and print the document")
+                .arg(Arg::new("file").required(true).help("Path to JSON file"))
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .help("JSONPath query of the values to set (e.g., '$.server.port')"),
+                )
+                .arg(
+                    Arg::new("value")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .help("The new value: JSON if it parses as JSON, otherwise a string"),
+                )
+                .arg(
+                    Arg::new("string")
+                        .long("string")
+                        .action(ArgAction::SetTrue)
+                        .help("Take VALUE as a string, even if it parses as JSON"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("string")
+                        .help("Take VALUE as JSON, failing if it does not parse"),
+                )
+                .arg(
+                    Arg::new("create-missing")
+                        .long("create-missing")
+                        .action(ArgAction::SetTrue)
+                        .help("Create missing objects and arrays along the path"),
+                )
+                .args(in_place_args()),
+        )
+        .subcommand(
+            Command::new("delete")
+                .about("Remove the values a query matches and print the rest of the document")
+                .arg(Arg::new("file").required(true).help("Path to JSON file"))
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .help("JSONPath query of the values to remove (e.g., '$..password')"),
+                )
+                .args(in_place_args()),
+        )
+        .arg(Arg::new("file").help("Path to JSON file, or an http(s) URL with the http feature. If omitted, reads from stdin."))
+        .arg(Arg::new("query").help("JSONPath query (e.g., '$.user.name'); another input file when the query is given with --query or --query-file"))
+        .arg(
+            Arg::new("files")
+                .num_args(1..)
+                .action(ArgAction::Append)
+                .help("More input files, each queried separately"),
+        )
+        .arg(
+            Arg::new("queries")
+                .short('q')
+                .long("query")
+                .value_name("QUERY")
+                .action(ArgAction::Append)
+                .help("JSONPath query; repeat it as name=QUERY to gather labeled results into one object, or with --profile-paths"),
+        )
+        .arg(
+            Arg::new("profile-paths")
+                .long("profile-paths")
+                .action(ArgAction::SetTrue)
+                .requires("queries")
+                .help("Report on stderr which paths the --query options match, by frequency"),
+        )
+        .arg(
+            Arg::new("query-file")
+                .long("query-file")
+                .value_name("PATH")
+                .conflicts_with("queries")
+                .help("Read the query from a file ('#' comment lines are ignored); a .jq file holds a jq filter"),
+        )
+        .arg(
+            Arg::new("auto-query")
+                .long("auto-query")
+                .action(ArgAction::SetTrue)
+                .help("Detect the query syntax: '$...' is JSONPath, '/...' a JSON Pointer, '.field' a shorthand path"),
+        )
+        .arg(
+            Arg::new("jsonpath-dialect")
+                .long("jsonpath-dialect")
+                .value_name("DIALECT")
+                .value_parser(["rfc9535", "legacy"])
+                .conflicts_with_all(["stream-count", "profile-paths"])
+                .help("JSONPath dialect: 'rfc9535' follows the standard's filter and slice semantics, 'legacy' (the default) jqr's historical ones"),
+        )
+        .arg(
+            Arg::new("arg")
+                .long("arg")
+                .num_args(2)
+                .value_names(["NAME", "VALUE"])
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append)
+                .conflicts_with_all(["jmespath", "pointer", "profile-paths"])
+                .help("Replace $NAME in the query with the string VALUE; may be repeated"),
+        )
+        .arg(
+            Arg::new("argjson")
+                .long("argjson")
+                .num_args(2)
+                .value_names(["NAME", "JSON"])
+                .allow_hyphen_values(true)
+                .action(ArgAction::Append)
+                .conflicts_with_all(["jmespath", "pointer", "profile-paths"])
+                .help("Replace $NAME in the query with a JSON value; may be repeated"),
+        )
+        .arg(
+            Arg::new("jq")
+                .long("jq")
+                .value_name("FILTER")
+                .conflicts_with_all(["queries", "query-file", "auto-query", "stream-count", "profile-paths", "keep-comments", "jsonpath-dialect"])
+                .help("Query with a jq filter (a subset: paths, pipes, select, map, object construction, ...); each result is printed"),
+        )
+        .arg(
+            Arg::new("jmespath")
+                .long("jmespath")
+                .value_name("EXPR")
+                .conflicts_with_all(["queries", "query-file", "auto-query", "stream-count", "profile-paths", "keep-comments", "jsonpath-dialect", "jq"])
+                .help("Query with a JMESPath expression, as the AWS CLI does; a null result counts as no match"),
+        )
+        .arg(
+            Arg::new("pointer")
+                .long("pointer")
+                .value_name("POINTER")
+                .allow_hyphen_values(true)
+                .conflicts_with_all(["queries", "query-file", "auto-query", "stream-count", "profile-paths", "keep-comments", "jsonpath-dialect", "jq", "jmespath"])
+                .help("Select the value at an RFC 6901 JSON Pointer (e.g. /users/0/name); a missing value counts as no match"),
+        )
+        .arg(
+            Arg::new("jsonata")
+                .long("jsonata")
+                .value_name("EXPR")
+                .conflicts_with_all(["queries", "query-file", "auto-query", "stream-count", "profile-paths", "keep-comments", "jsonpath-dialect", "jq", "jmespath", "pointer"])
+                .help("Query with a JSONata expression such as '$sum(orders.amount)' (requires the jsonata feature); an undefined result counts as no match"),
+        )
+        .arg(
+            Arg::new("paths")
+                .long("paths")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["jq", "jmespath", "pointer", "jsonata", "stream-count", "profile-paths"])
+                .help("Print every JSONPath match as {\"path\": ..., \"value\": ...}, giving where in the document it was found"),
+        )
+        .arg(
+            Arg::new("index-base")
+                .long("index-base")
+                .value_name("N")
+                .value_parser(["0", "1"])
+                .default_value("0")
+                .requires("paths")
+                .help("Count array indexes in the paths --paths prints from N (0 or 1); queries still index from 0"),
+        )
+        .arg(
+            Arg::new("stream-count")
+                .long("stream-count")
+                .action(ArgAction::SetTrue)
+                .help("Print the number of matches, streaming the elements of a top-level array so huge inputs need not fit in memory; the query must then start with $[*], $.* or $[N], and the rest of it runs on each element"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .value_name("PATH")
+                .conflicts_with_all([
+                    "file",
+                    "profile-paths",
+                    "stream-count",
+                    "to-yaml",
+                    "to-json",
+                    "from-json-seq",
+                    "ndjson",
+                    "slurp",
+                ])
+                .help("Run the query against every *.json file in a directory and print an object keyed by file name"),
+        )
+        .arg(
+            Arg::new("glob")
+                .long("glob")
+                .value_name("PATTERN")
+                .action(ArgAction::Append)
+                .conflicts_with_all(["dir", "null-input"])
+                .help("Query every JSON file (*.json, *.jsonl, *.ndjson) matching PATTERN; '**' matches any number of directories"),
+        )
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .action(ArgAction::SetTrue)
+                .requires("dir")
+                .help("With --dir, also read *.json files in subdirectories"),
+        )
+        .arg(
+            Arg::new("no-results-empty")
+                .long("no-results-empty")
+                .action(ArgAction::SetTrue)
+                .help("Print nothing and exit with status 1 when the query matches nothing"),
+        )
+        .arg(
+            Arg::new("no-results-null")
+                .long("no-results-null")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("no-results-empty")
+                .help("Print null when the query matches nothing"),
+        )
+        .arg(
+            Arg::new("default")
+                .long("default")
+                .value_name("JSON")
+                .conflicts_with_all(["no-results-empty", "no-results-null"])
+                .help("JSON value to print when the query matches nothing (strings need quotes)"),
+        )
         .arg(
             Arg::new("to-yaml")
                 .long("to-yaml")
-                .help("Convert JSON to YAML"),
+                .action(ArgAction::SetTrue)
+                .help("Convert JSON to YAML (with --yaml, write the query results as YAML)"),
         )
         .arg(
             Arg::new("to-json")
                 .long("to-json")
+                .action(ArgAction::SetTrue)
                 .help("Convert YAML to JSON"),
         )
+        .arg(
+            Arg::new("yaml")
+                .long("yaml")
+                .action(ArgAction::SetTrue)
+                .help("Read YAML input and query it like JSON, each `---` separated document on its own"),
+        )
+        .arg(
+            Arg::new("yaml-out")
+                .long("yaml-out")
+                .action(ArgAction::SetTrue)
+                .help("Read YAML input and write it back as YAML, re-indented by --indent and with keys sorted unless --preserve-order; a query is optional"),
+        )
+        .group(
+            ArgGroup::new("yaml-input")
+                .args(["yaml", "yaml-out"])
+                .multiple(true)
+                .conflicts_with_all([
+                    "to-json",
+                    "profile-paths",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                ]),
+        )
+        .arg(
+            Arg::new("document")
+                .long("document")
+                .value_name("INDEX")
+                .value_parser(value_parser!(usize))
+                .requires("yaml-input")
+                .conflicts_with("slurp")
+                .help("With --yaml or --yaml-out, query only the document at INDEX, counting from 0"),
+        )
+        .arg(
+            Arg::new("keep-aliases")
+                .long("keep-aliases")
+                .action(ArgAction::SetTrue)
+                .requires("reads-yaml")
+                .help("With --yaml, --yaml-out or --to-json, read each YAML alias as the string \"*name\" instead of expanding it"),
+        )
+        .arg(
+            Arg::new("keep-merge-keys")
+                .long("keep-merge-keys")
+                .action(ArgAction::SetTrue)
+                .requires("reads-yaml")
+                .help("With --yaml, --yaml-out or --to-json, keep YAML `<<` merge keys as ordinary keys instead of merging them"),
+        )
+        .arg(
+            Arg::new("alias-limit")
+                .long("alias-limit")
+                .value_name("NODES")
+                .value_parser(value_parser!(usize))
+                .requires("reads-yaml")
+                .help("With --yaml, --yaml-out or --to-json, refuse YAML whose aliases expand to more than NODES nodes [default: 100000]"),
+        )
+        .group(
+            ArgGroup::new("reads-yaml")
+                .args(["yaml", "yaml-out", "to-json"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::new("from-json-seq")
+                .long("from-json-seq")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "equal", "profile-paths"])
+                .help("Read an RFC 7464 JSON text sequence (records prefixed by 0x1E; detected automatically when the input starts with one)"),
+        )
+        .arg(
+            Arg::new("ndjson")
+                .long("ndjson")
+                .alias("jsonl")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "equal", "profile-paths", "from-json-seq"])
+                .help("Read newline-delimited JSON, one document per line (detected automatically when the first line is a complete document)"),
+        )
+        .arg(
+            Arg::new("null-input")
+                .short('n')
+                .long("null-input")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "dir",
+                    "stream-count",
+                    "profile-paths",
+                    "to-yaml",
+                    "to-json",
+                    "from-json-seq",
+                    "ndjson",
+                    "in-place",
+                ])
+                .help("Read no input and use null as the document; the first argument is then the query"),
+        )
+        .arg(
+            Arg::new("raw-input")
+                .short('R')
+                .long("raw-input")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "from-json-seq",
+                    "ndjson",
+                    "null-input",
+                    "dir",
+                ])
+                .help("Read each input line as a JSON string instead of parsing it (with --slurp, the whole input as one string)"),
+        )
+        .arg(
+            Arg::new("slurp")
+                .short('s')
+                .long("slurp")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "profile-paths", "ndjson"])
+                .help("Read all input documents into one array before running the query"),
+        )
+        .arg(
+            Arg::new("to-json-seq")
+                .long("to-json-seq")
+                .visible_alias("seq")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "line-numbers", "equal"])
+                .help("Write results as an RFC 7464 JSON text sequence"),
+        )
+        .arg(
+            Arg::new("to-properties")
+                .long("to-properties")
+                .action(ArgAction::SetTrue)
+                .help("Convert JSON to Java .properties format"),
+        )
+        .arg(
+            Arg::new("to-toml")
+                .long("to-toml")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "to-properties", "table", "dual", "to-json-seq"])
+                .help("Write results as TOML documents (each must be an object without nulls)"),
+        )
+        .arg(
+            Arg::new("input-format")
+                .long("input-format")
+                .value_name("FORMAT")
+                .value_parser(["json", "yaml", "toml", "ndjson", "msgpack"])
+                .conflicts_with_all([
+                    "to-json",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "json5",
+                    "jsonc",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                    "from-parquet",
+                ])
+                .help("Read the input as json, yaml, toml, ndjson or msgpack instead of detecting its format from the file extension and content"),
+        )
+        .arg(
+            Arg::new("from-toml")
+                .long("from-toml")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                ])
+                .help("Read TOML input instead of JSON"),
+        )
+        .arg(
+            Arg::new("to-xml")
+                .long("to-xml")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "to-properties", "to-toml", "table", "dual", "to-json-seq"])
+                .help("Write results as XML documents"),
+        )
+        .arg(
+            Arg::new("from-xml")
+                .long("from-xml")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                ])
+                .help("Read XML input instead of JSON"),
+        )
+        .arg(
+            Arg::new("xml-attribute-prefix")
+                .long("xml-attribute-prefix")
+                .value_name("PREFIX")
+                .default_value("@")
+                .help("Prefix of the keys that hold XML attributes"),
+        )
+        .arg(
+            Arg::new("xml-text-key")
+                .long("xml-text-key")
+                .value_name("KEY")
+                .default_value("#text")
+                .help("Key holding the text of XML elements that also have attributes or children"),
+        )
+        .arg(
+            Arg::new("xml-array")
+                .long("xml-array")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .requires("from-xml")
+                .help("Always read XML elements named NAME into an array; may be repeated"),
+        )
+        .arg(
+            Arg::new("to-csv")
+                .long("to-csv")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "to-properties", "to-toml", "to-xml", "table", "dual", "to-json-seq"])
+                .help("Write results (arrays of objects) as CSV tables, flattening nested keys into dotted column names"),
+        )
+        .arg(
+            Arg::new("to-tsv")
+                .long("to-tsv")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-yaml", "to-json", "to-properties", "to-toml", "to-xml", "table", "dual", "to-json-seq", "to-csv"])
+                .help("Write results (arrays of objects) as tab-separated tables"),
+        )
+        .arg(
+            Arg::new("column")
+                .long("column")
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("With --to-csv or --to-tsv, write only this column, in the order given; may be repeated"),
+        )
+        .arg(
+            Arg::new("from-csv")
+                .long("from-csv")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                ])
+                .help("Read a CSV table with a header row as an array of objects"),
+        )
+        .arg(
+            Arg::new("from-tsv")
+                .long("from-tsv")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                ])
+                .help("Read a tab-separated table with a header row as an array of objects"),
+        )
+        .arg(
+            Arg::new("to-msgpack")
+                .long("to-msgpack")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "to-properties",
+                    "to-toml",
+                    "to-xml",
+                    "to-csv",
+                    "to-tsv",
+                    "table",
+                    "dual",
+                    "to-json-seq",
+                    "line-numbers",
+                    "with-filename",
+                    "equal",
+                    "clipboard-out",
+                ])
+                .help("Write results as concatenated MessagePack values (not to a terminal)"),
+        )
+        .arg(
+            Arg::new("from-msgpack")
+                .long("from-msgpack")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                ])
+                .help("Read MessagePack input, one document per encoded value; binary data becomes base64 strings"),
+        )
+        .arg(
+            Arg::new("to-cbor")
+                .long("to-cbor")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "to-properties",
+                    "to-toml",
+                    "to-xml",
+                    "to-csv",
+                    "to-tsv",
+                    "table",
+                    "dual",
+                    "to-json-seq",
+                    "line-numbers",
+                    "with-filename",
+                    "equal",
+                    "clipboard-out",
+                    "to-msgpack",
+                ])
+                .help("Write results as a CBOR sequence (not to a terminal)"),
+        )
+        .arg(
+            Arg::new("from-cbor")
+                .long("from-cbor")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                ])
+                .help("Read CBOR input, one document per data item; byte strings become base64 strings"),
+        )
+        .arg(
+            Arg::new("from-bson")
+                .long("from-bson")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                ])
+                .help("Read BSON documents, such as mongodump output, as MongoDB relaxed Extended JSON"),
+        )
+        .arg(
+            Arg::new("from-avro")
+                .long("from-avro")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                ])
+                .help("Read an Avro object container file, one document per record"),
+        )
+        .arg(
+            Arg::new("from-proto")
+                .long("from-proto")
+                .action(ArgAction::SetTrue)
+                .requires_all(["descriptor", "message-type"])
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                ])
+                .help("Read a binary protobuf message, decoded with --descriptor and --message-type"),
+        )
+        .arg(
+            Arg::new("descriptor")
+                .long("descriptor")
+                .value_name("FILE")
+                .requires("from-proto")
+                .help("With --from-proto, the compiled FileDescriptorSet (protoc --descriptor_set_out --include_imports)"),
+        )
+        .arg(
+            Arg::new("message-type")
+                .long("message-type")
+                .value_name("NAME")
+                .requires("from-proto")
+                .help("With --from-proto, the fully qualified type of the message, e.g. pkg.Type"),
+        )
+        .arg(
+            Arg::new("avro-schema")
+                .long("avro-schema")
+                .value_name("FILE")
+                .requires("from-avro")
+                .help("With --from-avro, read raw datums written with the .avsc schema in FILE instead of a container file"),
+        )
+        .arg(
+            Arg::new("from-parquet")
+                .long("from-parquet")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                ])
+                .help("Read a Parquet or Arrow IPC file as an array of row objects, or one document per row with --ndjson (needs the parquet feature)"),
+        )
+        .arg(
+            Arg::new("json5")
+                .long("json5")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                    "from-parquet",
+                ])
+                .help("Read JSON5 input (comments, unquoted keys, single quotes, hex numbers, trailing commas); *.json5 files are read as JSON5 without it"),
+        )
+        .arg(
+            Arg::new("jsonc")
+                .long("jsonc")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                    "from-parquet",
+                    "json5",
+                ])
+                .help("Read JSON with comments (JSONC): `//` and `/* */` comments and trailing commas are allowed; *.jsonc files are read as JSONC without it"),
+        )
+        .arg(
+            Arg::new("keep-comments")
+                .long("keep-comments")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["query", "queries", "query-file", "compact", "width", "to-yaml", "to-json"])
+                .help("Reformat JSONC input keeping its comments, each attached to the key or element after it (no query)"),
+        )
+        .arg(
+            Arg::new("infer-types")
+                .long("infer-types")
+                .action(ArgAction::SetTrue)
+                .help("With --from-csv or --from-tsv, read number, true/false and empty cells as numbers, booleans and null"),
+        )
+        .arg(
+            Arg::new("table")
+                .long("table")
+                .action(ArgAction::SetTrue)
+                .help("Render an array of objects as an aligned table (for terminal viewing only)"),
+        )
+        .arg(
+            Arg::new("dual")
+                .long("dual")
+                .alias("both")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-properties", "table"])
+                .help("Print the result as pretty JSON followed by its YAML representation"),
+        )
+        .arg(
+            Arg::new("indent")
+                .global(true)
+                .long("indent")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Indent JSON and YAML output with N spaces per level (default: 2)"),
+        )
+        .arg(
+            Arg::new("tab")
+                .global(true)
+                .long("tab")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("indent")
+                .help("Indent JSON output with tabs"),
+        )
+        .arg(
+            Arg::new("compact")
+                .global(true)
+                .short('c')
+                .long("compact")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["indent", "tab"])
+                .help("Write JSON output on a single line"),
+        )
+        .arg(
+            Arg::new("width")
+                .global(true)
+                .long("width")
+                .value_name("COLUMNS")
+                .value_parser(value_parser!(usize))
+                .conflicts_with("compact")
+                .help("Keep arrays and objects of scalars on one line when they fit in COLUMNS"),
+        )
+        .arg(
+            Arg::new("sort-keys")
+                .global(true)
+                .short('S')
+                .long("sort-keys")
+                .action(ArgAction::SetTrue)
+                .help("Sort object keys alphabetically in JSON and YAML output, even with --preserve-order"),
+        )
+        .arg(
+            Arg::new("preserve-order")
+                .global(true)
+                .long("preserve-order")
+                .action(ArgAction::SetTrue)
+                .help("Keep object keys in input order instead of sorting them, unless --sort-keys is given"),
+        )
+        .arg(
+            Arg::new("ascii-output")
+                .global(true)
+                .short('a')
+                .long("ascii-output")
+                .action(ArgAction::SetTrue)
+                .help("Escape non-ASCII characters in JSON output as \\uXXXX"),
+        )
+        .arg(
+            Arg::new("raw-output")
+                .short('r')
+                .long("raw-output")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["to-properties", "to-toml", "to-xml", "to-csv", "to-tsv", "table", "dual", "to-json-seq"])
+                .help("Print string results without quotes, and arrays of strings one per line"),
+        )
+        .arg(
+            Arg::new("quote-style")
+                .long("quote-style")
+                .value_name("STYLE")
+                .value_parser(["double", "single"])
+                .default_value("double")
+                .conflicts_with_all(["to-properties", "to-toml", "to-xml", "to-csv", "to-tsv", "table", "dual", "to-json-seq", "raw-output"])
+                .help("Quote strings with double quotes (JSON) or single quotes (JavaScript literal, not valid JSON)"),
+        )
+        .arg(
+            Arg::new("select")
+                .long("select")
+                .value_name("EXPR")
+                .conflicts_with_all(["stream-count", "profile-paths"])
+                .help("Keep only the input records for which EXPR holds, an RFC 9535 filter expression such as '$.level == \"error\"'"),
+        )
+        .arg(
+            Arg::new("map")
+                .long("map")
+                .value_name("TEMPLATE")
+                .help("Build a new value from each result, or each element of an array result (e.g. '{id: $.id, name: $.profile.name}')"),
+        )
+        .arg(
+            Arg::new("select-type")
+                .long("select-type")
+                .value_name("TYPES")
+                .help("Keep only array elements of the given comma-separated JSON types (object, array, string, number, boolean, null)"),
+        )
+        .arg(
+            Arg::new("rename")
+                .long("rename")
+                .value_name("OLD=NEW,...")
+                .help("Recursively rename object keys; renaming onto an existing key is an error"),
+        )
+        .arg(
+            Arg::new("coerce-numbers")
+                .long("coerce-numbers")
+                .action(ArgAction::SetTrue)
+                .help("Convert strings that are exactly JSON numbers (e.g. \"42\") into numbers"),
+        )
+        .arg(
+            Arg::new("coerce-booleans")
+                .long("coerce-booleans")
+                .action(ArgAction::SetTrue)
+                .help("Convert the strings \"true\" and \"false\" into booleans"),
+        )
+        .arg(
+            Arg::new("walk")
+                .long("walk")
+                .value_name("OP")
+                .action(ArgAction::Append)
+                .value_parser(["trim-strings", "round", "lowercase-keys", "remove-nulls"])
+                .help("Apply an operation to every node of the result; may be repeated"),
+        )
+        .arg(
+            Arg::new("unique")
+                .long("unique")
+                .value_name("PATH")
+                .num_args(0..=1)
+                .require_equals(true)
+                .help("Remove duplicate elements from the array result, or those with a duplicate value at PATH"),
+        )
+        .arg(
+            Arg::new("sort-by")
+                .long("sort-by")
+                .value_name("KEYS")
+                .help("Sort the array result by comma-separated key paths, each optionally :desc (e.g. '$.age:desc,$.name')"),
+        )
+        .arg(
+            Arg::new("sort-at")
+                .long("sort-at")
+                .value_name("PATH")
+                .requires("sort-by")
+                .help("With --sort-by, sort the arrays at PATH inside the result instead of the result itself"),
+        )
+        .arg(
+            Arg::new("sample")
+                .long("sample")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Keep N elements of the array result chosen at random, in their order"),
+        )
+        .arg(
+            Arg::new("shuffle")
+                .long("shuffle")
+                .value_name("SEED")
+                .num_args(0..=1)
+                .require_equals(true)
+                .value_parser(value_parser!(u64))
+                .help("Shuffle the array result; a SEED gives the same order every time"),
+        )
+        .arg(
+            Arg::new("reverse")
+                .long("reverse")
+                .action(ArgAction::SetTrue)
+                .help("Reverse the array result"),
+        )
+        .arg(
+            Arg::new("agg")
+                .long("agg")
+                .value_name("OP")
+                .value_parser(["sum", "min", "max", "avg", "count"])
+                .conflicts_with("paths")
+                .help("Aggregate the query results into one value (sum, min, max, avg, count)"),
+        )
+        .arg(
+            Arg::new("remove-empty")
+                .long("remove-empty")
+                .action(ArgAction::SetTrue)
+                .help("Recursively remove nulls, empty strings, empty arrays and empty objects"),
+        )
+        .arg(
+            Arg::new("keep-null")
+                .long("keep-null")
+                .action(ArgAction::SetTrue)
+                .requires("remove-empty")
+                .help("With --remove-empty, keep null values"),
+        )
+        .arg(
+            Arg::new("apply")
+                .long("apply")
+                .value_name("TRANSFORMS")
+                .help("Comma-separated string transforms applied to the result (base64d, trim, upper, lower, urldecode)"),
+        )
+        .arg(
+            Arg::new("line-numbers")
+                .long("line-numbers")
+                .action(ArgAction::SetTrue)
+                .help("Prefix each output line with its line number (for terminal viewing only)"),
+        )
+        .arg(
+            Arg::new("limit")
+                .long("limit")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Print at most N elements of the array result, or N results when there are several"),
+        )
+        .arg(
+            Arg::new("offset")
+                .long("offset")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Skip the first N elements of the array result, or the first N results when there are several"),
+        )
+        .arg(
+            Arg::new("fold-threshold")
+                .long("fold-threshold")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Collapse arrays/objects with more than N children into a summary string"),
+        )
+        .arg(
+            Arg::new("max-depth-guard")
+                .long("max-depth-guard")
+                .value_name("N")
+                .value_parser(value_parser!(usize))
+                .help("Refuse input with arrays/objects nested deeper than N (default: 128)"),
+        )
+        .arg(
+            Arg::new("duplicate-keys")
+                .long("duplicate-keys")
+                .value_name("POLICY")
+                .value_parser(["error", "first", "last"])
+                .default_value("last")
+                .help("Refuse objects that repeat a key, or keep the first or last value"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                    "from-parquet",
+                    "json5",
+                    "jsonc",
+                    "duplicate-keys",
+                ])
+                .help("Accept standard JSON only (RFC 8259), refusing a byte order mark and repeated keys"),
+        )
+        .arg(
+            Arg::new("lenient")
+                .long("lenient")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "to-yaml",
+                    "to-json",
+                    "profile-paths",
+                    "yaml-input",
+                    "from-json-seq",
+                    "ndjson",
+                    "raw-input",
+                    "slurp",
+                    "from-toml",
+                    "from-xml",
+                    "from-csv",
+                    "from-tsv",
+                    "from-msgpack",
+                    "from-cbor",
+                    "from-bson",
+                    "from-avro",
+                    "from-proto",
+                    "from-parquet",
+                    "json5",
+                    "jsonc",
+                    "strict",
+                ])
+                .help("Also accept comments, trailing commas, single-quoted strings, and NaN and Infinity (read as null)"),
+        )
+        .arg(
+            Arg::new("equal")
+                .long("equal")
+                .value_name("JSON")
+                .help("Compare the result to a JSON value; exit 0 on match, 1 with a diff on mismatch, 2 if the query gives several results"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("FILE")
+                .help("Write results to FILE, replacing it only once all output is written"),
+        )
+        .args({
+            let [in_place, backup, numbered_backup] = in_place_args();
+            [
+                in_place
+                    .requires("file")
+                    .conflicts_with_all(["output", "stream-count", "profile-paths", "equal"]),
+                backup,
+                numbered_backup,
+            ]
+        })
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .value_name("NAME: VALUE")
+                .action(ArgAction::Append)
+                .value_parser(parse_header)
+                .help("Send an HTTP header when the input is a URL; may be repeated"),
+        )
+        .arg(
+            Arg::new("bearer")
+                .long("bearer")
+                .value_name("TOKEN")
+                .help("Send 'Authorization: Bearer TOKEN' when the input is a URL"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .help("Give up on a URL after SECONDS, including reading the response"),
+        )
+        .arg(
+            Arg::new("max-redirects")
+                .long("max-redirects")
+                .value_name("N")
+                .value_parser(value_parser!(u32))
+                .default_value("5")
+                .help("Follow at most N redirects when the input is a URL; 0 disables them"),
+        )
+        .arg(
+            Arg::new("clipboard-in")
+                .long("clipboard-in")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["dir", "glob", "null-input", "in-place"])
+                .help("Read the input from the system clipboard; the first argument is then the query (needs the clipboard feature)"),
+        )
+        .arg(
+            Arg::new("clipboard-out")
+                .long("clipboard-out")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["output", "in-place"])
+                .help("Put the output on the system clipboard instead of printing it (needs the clipboard feature)"),
+        )
+        .arg(
+            Arg::new("with-filename")
+                .short('H')
+                .long("with-filename")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["dir", "to-json-seq", "equal", "in-place"])
+                .help("Prefix every output line with the name of the file it came from"),
+        )
+        .arg(
+            Arg::new("collect-errors")
+                .long("collect-errors")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["in-place", "equal"])
+                .help("Skip malformed NDJSON lines and unreadable files, and list them all after the results; exits with status 2 if there were any"),
+        )
+        .arg(
+            Arg::new("exit-status")
+                .short('e')
+                .long("exit-status")
+                .action(ArgAction::SetTrue)
+                .help("Exit with status 1 if the last result is null or false, or there is none"),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .value_parser(["auto", "always", "never"])
+                .default_value("auto")
+                .help("Color output: auto (terminals only, honors NO_COLOR), always or never"),
+        )
         .get_matches();
 
-    // If no arguments are provided, display help message
-    if !matches.args_present() {
+    match matches.subcommand() {
+        Some(("group-by", matches)) => return group_by_command(matches),
+        Some(("flatten", matches)) => {
+            return run_subcommand(matches, |json| flatten(&json).map(Value::Object))
+        }
+        Some(("unflatten", matches)) => return run_subcommand(matches, |json| unflatten(&json)),
+        Some(("pivot", matches)) => return run_subcommand(matches, |json| pivot(&json)),
+        Some(("set", matches)) => return set_command(matches),
+        Some(("delete", matches)) => {
+            let path = matches.get_one::<String>("path").expect("path is required");
+            return run_subcommand(matches, |mut json| {
+                delete_paths(&mut json, path)?;
+                Ok(json)
+            });
+        }
+        _ => {}
+    }
+
+    // If no arguments are provided and nothing is piped in, display help message
+    if !matches.args_present() && io::stdin().is_terminal() {
         let mut cmd = Command::new("jqr");
         cmd.print_long_help().unwrap();
-        return;
+        return ExitCode::SUCCESS;
     }
 
-    let file_path = matches.get_one::<String>("file");
-    let query = matches.get_one::<String>("query");
-
-    let content = if let Some(path) = file_path {
-        match fs::read_to_string(path) {
-            Ok(data) => data,
+    let colors = Colors::from_matches(&matches);
+    let null_input = matches.get_flag("null-input");
+    let files = if no_input_file(&matches).is_some() {
+        Vec::new()
+    } else {
+        match input_files(&matches) {
+            Ok(files) => files,
             Err(e) => {
-                eprintln!("Error reading file: {}", e);
-                return;
+                eprintln!("Error: {}", e);
+                return ExitCode::from(EXIT_ERROR);
             }
         }
-    } else {
-        let mut buffer = String::new();
-        if io::stdin().read_to_string(&mut buffer).is_ok() {
-            buffer
-        } else {
-            eprintln!("Failed to read from stdin");
-            return;
-        }
     };
+    let file_path = files.first();
+    let in_place = matches.get_flag("in-place");
+    if in_place && file_path.is_some_and(|path| input::is_url(path)) {
+        eprintln!("Error: --in-place cannot rewrite a URL");
+        return ExitCode::from(EXIT_ERROR);
+    }
+    let http = http_options(&matches);
+    let mut out = match file_path {
+        Some(path) if in_place => Output::in_place(
+            path,
+            matches.get_one::<String>("backup").map(String::as_str),
+            matches.get_flag("numbered-backup"),
+        ),
+        _ if matches.get_flag("clipboard-out") => Output::clipboard(),
+        _ => Output::new(matches.get_one::<String>("output").map(String::as_str)),
+    };
+    if binary_encoder(&matches).is_some()
+        && matches!(out, Output::Stdout(_))
+        && io::stdout().is_terminal()
+    {
+        eprintln!(
+            "Error: refusing to write binary output to a terminal; redirect stdout or use --output"
+        );
+        return ExitCode::from(EXIT_ERROR);
+    }
 
-    if matches.contains_id("to-yaml") {
-        if let Err(e) = convert_to_yaml(&content) {
-            eprintln!("Error converting to YAML: {}", e);
-            return;
+    // Validate the post-query options before reading any input
+    let pipeline = match Pipeline::from_matches(&matches) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_ERROR);
         }
-    } else if matches.contains_id("to-json") {
-        convert_to_json(&content)
-    } else {
+    };
 
-        let result = pretty_print_json(&content, query);
-        match result {
-            Ok(output) => println!("{}", output),
-            Err(e) => eprintln!("Error processing JSON: {}", e),
+    if files.len() > 1 {
+        let single_input = [
+            "in-place",
+            "stream-count",
+            "profile-paths",
+            "to-yaml",
+            "to-json",
+            "keep-comments",
+        ];
+        // With --yaml, --to-yaml only renders the results
+        let renders_yaml = reads_yaml(&matches);
+        if let Some(mode) = single_input
+            .iter()
+            .find(|mode| matches.get_flag(mode) && !(renders_yaml && **mode == "to-yaml"))
+        {
+            eprintln!("Error: --{} takes a single input file", mode);
+            return ExitCode::from(EXIT_ERROR);
         }
+    }
 
-        return;
-
+    // Counting matches streams the input instead of reading it into memory
+    if matches.get_flag("stream-count") {
+        let Some(Query::JsonPath(query)) = &pipeline.query else {
+            eprintln!("Error: --stream-count requires a JSONPath query");
+            return ExitCode::from(EXIT_ERROR);
+        };
+        let counted = input::open(file_path.map(String::as_str), &http)
+            .map_err(JqrError::Io)
+            .and_then(|input| count_matches_streaming(input, query.as_str()));
+        return match counted {
+            Ok(count) => {
+                let written = writeln!(out, "{}", count);
+                finish_output(out, written, ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("Error counting matches: {}", e);
+                ExitCode::from(EXIT_ERROR)
+            }
+        };
     }
+
+    let line_numbers = matches.get_flag("line-numbers");
+    let format = format_options(&matches);
+
+    // --dir reads its own files, one document per file
+    let dir = matches.get_one::<String>("dir");
+    let content = if dir.is_some() || null_input || files.len() > 1 {
+        Vec::new()
+    } else {
+        let opened = if matches.get_flag("clipboard-in") {
+            input::clipboard()
+        } else {
+            input::open(file_path.map(String::as_str), &http)
+        };
+        let mut input = match opened {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Error reading file: {}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        };
+
+        // NDJSON is queried and printed line by line as it is read
+        if streams_ndjson(&matches, file_path.map(String::as_str)) {
+            let file_name = file_path.map_or("(standard input)", String::as_str);
+            return print_ndjson_stream(input, out, file_name, &matches, &pipeline, &colors);
+        }
+
+        // Plain reformatting streams the document instead of reading it into memory
+        if pipeline.is_passthrough()
+            && is_plain_output(&matches)
+            && json_dialect(&matches, file_path.map(String::as_str)).is_none()
+            && !colors.stdout
+            && starts_with_container(&mut input, &matches, file_path.map(String::as_str))
+        {
+            let format = format.clone().trailing_newline(true);
+            return match pretty_print_json_stream(input, BufWriter::new(&mut out), &format) {
+                Ok(()) => finish_output(out, Ok(()), ExitCode::SUCCESS),
+                Err(e) => {
+                    eprintln!("Error processing JSON: {}", colors.error(e));
+                    ExitCode::from(EXIT_ERROR)
+                }
+            };
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(e) = input.read_to_end(&mut buffer) {
+            eprintln!("Error reading input: {}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+        // Only binary formats may be anything but UTF-8 text
+        if let Err(e) = std::str::from_utf8(&buffer) {
+            let format = input_format(&matches, file_path.map(String::as_str), &buffer);
+            if !is_binary_input(&matches) && format != Some(InputFormat::Msgpack) {
+                eprintln!("Error reading input: {}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        }
+        buffer
+    };
+    let text = String::from_utf8_lossy(&content);
+
+    // Other formats than JSON are converted to YAML through the pipeline
+    let converts_json = || {
+        matches!(
+            input_format(&matches, file_path.map(String::as_str), &content),
+            None | Some(InputFormat::Json)
+        )
+    };
+    if matches.get_flag("to-yaml") && !reads_yaml(&matches) && converts_json() {
+        match convert_to_yaml_with(&text, &format) {
+            Ok(yaml) => {
+                let written = print_output(&mut out, &yaml, line_numbers, None);
+                finish_output(out, written, ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("Error converting to YAML: {}", colors.error(&e));
+                print_hint(&e);
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    } else if matches.get_flag("to-json") {
+        let converted =
+            parse_yaml_documents_with(&text, &yaml_options(&matches)).and_then(|mut documents| {
+                let yaml = if documents.len() == 1 {
+                    documents.remove(0)
+                } else {
+                    Value::Array(documents)
+                };
+                format_json(&yaml, &format)
+            });
+        match converted {
+            Ok(json) => {
+                let json = if colors.stdout {
+                    highlight_json(&json)
+                } else {
+                    json
+                };
+                let written = print_output(&mut out, &json, line_numbers, None);
+                finish_output(out, written, ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("Error converting to JSON: {}", colors.error(e));
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    } else if matches.get_flag("keep-comments") {
+        let dialect = json_dialect(&matches, file_path.map(String::as_str));
+        if !matches!(dialect, Some(JsonDialect::Jsonc)) {
+            eprintln!("Error: --keep-comments needs JSONC input; use --jsonc or a .jsonc file");
+            return ExitCode::from(EXIT_ERROR);
+        }
+        match format_jsonc(&text, &format) {
+            Ok(jsonc) => {
+                let written = print_output(&mut out, &jsonc, line_numbers, None);
+                finish_output(out, written, ExitCode::SUCCESS)
+            }
+            Err(e) => {
+                eprintln!("Error reformatting JSONC: {}", colors.error(e));
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    } else if matches.get_flag("profile-paths") {
+        let queries: Vec<String> = matches
+            .get_many::<String>("queries")
+            .unwrap()
+            .cloned()
+            .map(expand_shorthand)
+            .collect();
+        match profile_queries(&mut out, &text, &queries, &format) {
+            Ok(()) => finish_output(out, Ok(()), ExitCode::SUCCESS),
+            Err(e) => {
+                eprintln!("Error profiling paths: {}", e);
+                ExitCode::from(EXIT_ERROR)
+            }
+        }
+    } else {
+        // Every input document goes through the pipeline independently; with
+        // several files, `sources` records which file each result came from,
+        // and `skipped` the inputs --collect-errors lists at the end
+        let mut sources: Vec<&str> = Vec::new();
+        let mut skipped: Vec<(&str, JqrError)> = Vec::new();
+        let mut input_failed = false;
+        let evaluated = if let Some(dir) = dir {
+            query_dir(
+                Path::new(dir),
+                matches.get_flag("recursive"),
+                &pipeline,
+                &colors,
+            )
+            .map(|result| vec![result])
+        } else if null_input {
+            pipeline.evaluate_value(Value::Null)
+        } else if files.len() > 1 {
+            // Files are read and queried in parallel, results kept in file order
+            let evaluated: Vec<_> = files
+                .par_iter()
+                .map(|path| {
+                    let mut skipped_lines = Vec::new();
+                    let evaluated =
+                        input::read(path, &http)
+                            .map_err(JqrError::Io)
+                            .and_then(|content| {
+                                evaluate_input(
+                                    content,
+                                    Some(path),
+                                    &matches,
+                                    &pipeline,
+                                    &mut skipped_lines,
+                                )
+                            });
+                    (evaluated, skipped_lines)
+                })
+                .collect();
+
+            let mut results = Vec::new();
+            for (path, (evaluated, skipped_lines)) in files.iter().zip(evaluated) {
+                skipped.extend(skipped_lines.into_iter().map(|e| (path.as_str(), e)));
+                match evaluated {
+                    Ok(found) => {
+                        for result in found {
+                            results.push(result);
+                            sources.push(path.as_str());
+                        }
+                    }
+                    Err(e) if matches.get_flag("collect-errors") => skipped.push((path, e)),
+                    // Like grep, report the file and go on with the others
+                    Err(e) => {
+                        eprintln!("{}: {}", path, colors.error(&e));
+                        print_hint(&e);
+                        input_failed = true;
+                    }
+                }
+            }
+            Ok(results)
+        } else {
+            let mut skipped_lines = Vec::new();
+            let evaluated = evaluate_input(
+                content,
+                file_path.map(String::as_str),
+                &matches,
+                &pipeline,
+                &mut skipped_lines,
+            );
+            let file_name = file_path.map_or("(standard input)", String::as_str);
+            skipped.extend(skipped_lines.into_iter().map(|e| (file_name, e)));
+            evaluated
+        };
+        input_failed |= !skipped.is_empty();
+
+        let mut results: Vec<Value> = match evaluated {
+            Ok(results) => results,
+            Err(e) => {
+                eprintln!("Error processing JSON: {}", colors.error(&e));
+                print_hint(&e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        };
+
+        // A single array result is paged by element, several results by result
+        let offset = matches.get_one::<usize>("offset").copied().unwrap_or(0);
+        let limit = matches.get_one::<usize>("limit").copied();
+        if offset > 0 || limit.is_some() {
+            if let [Value::Array(items)] = results.as_mut_slice() {
+                *items = page(std::mem::take(items), offset, limit);
+            } else {
+                results = page(results, offset, limit);
+                if !sources.is_empty() {
+                    sources = page(sources, offset, limit);
+                }
+            }
+        }
+
+        // With --no-results-empty a query without matches prints nothing
+        if results.is_empty() && matches.get_flag("no-results-empty") {
+            print_skipped(&skipped, &colors);
+            return if input_failed {
+                ExitCode::from(EXIT_ERROR)
+            } else {
+                ExitCode::FAILURE
+            };
+        }
+
+        // Never replace a file with nothing
+        if results.is_empty() && in_place {
+            eprintln!("{} was not modified", file_path.unwrap());
+            return ExitCode::FAILURE;
+        }
+
+        if let Some(expected) = matches.get_one::<String>("equal") {
+            let expected = match parse_json(expected) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("Error parsing --equal value: {}", e);
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            };
+            // One value is compared to one result, never just the first of several
+            let result = match results.as_slice() {
+                [] => {
+                    eprintln!("$: expected {}, found no results", expected);
+                    return ExitCode::FAILURE;
+                }
+                [result] => result,
+                _ => {
+                    eprintln!(
+                        "Error: --equal needs a single result, but the query gave {}",
+                        results.len()
+                    );
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            };
+            if !json_equal(&expected, result) {
+                for difference in json_diff(&expected, result) {
+                    eprintln!("{}", difference);
+                }
+                return ExitCode::FAILURE;
+            }
+            return ExitCode::SUCCESS;
+        }
+
+        report_no_results(&pipeline, results.len());
+        let status = if input_failed {
+            ExitCode::from(EXIT_ERROR)
+        } else {
+            exit_status(&matches, results.last())
+        };
+
+        if matches.get_flag("to-json-seq") {
+            let results: Vec<Value> = results
+                .iter()
+                .map(|result| key_order(&matches, result))
+                .collect();
+            return match to_json_seq(&results) {
+                Ok(seq) => {
+                    let written = out.write_all(seq.as_bytes());
+                    print_skipped(&skipped, &colors);
+                    finish_output(out, written, status)
+                }
+                Err(e) => {
+                    eprintln!("Error writing output: {}", e);
+                    ExitCode::from(EXIT_ERROR)
+                }
+            };
+        }
+
+        if let Some(encode) = binary_encoder(&matches) {
+            let encoded: Result<Vec<Vec<u8>>, JqrError> = results
+                .iter()
+                .map(|result| encode(&key_order(&matches, result)))
+                .collect();
+            return match encoded {
+                Ok(messages) => {
+                    let written = out.write_all(&messages.concat());
+                    print_skipped(&skipped, &colors);
+                    finish_output(out, written, status)
+                }
+                Err(e) => {
+                    eprintln!("Error writing output: {}", e);
+                    ExitCode::from(EXIT_ERROR)
+                }
+            };
+        }
+
+        // --collect-errors keeps the results of the inputs that could be read
+        let mut failed = input_failed && !matches.get_flag("collect-errors");
+        let stdin_name = "(standard input)";
+        let file_name = file_path.map_or(stdin_name, String::as_str);
+
+        for (i, result) in results.iter().enumerate() {
+            let rendered = render_result(result, i, &matches, &format, &colors);
+
+            match rendered {
+                Ok(output) => {
+                    let filename = matches
+                        .get_flag("with-filename")
+                        .then(|| colors.filename(sources.get(i).copied().unwrap_or(file_name)));
+                    let written =
+                        print_output(&mut out, &output, line_numbers, filename.as_deref());
+                    if let Err(e) = written {
+                        eprintln!("Error writing output: {}", e);
+                        return ExitCode::from(EXIT_ERROR);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error rendering output: {}", e);
+                    failed = true;
+                }
+            }
+        }
+
+        print_skipped(&skipped, &colors);
+        if failed {
+            return ExitCode::from(EXIT_ERROR);
+        }
+        finish_output(out, Ok(()), status)
+    }
+}
+
+/// Prints a hint after an input error, if one helps: JSON input that looks
+/// like YAML can be read with `--yaml`.
+fn print_hint(error: &JqrError) {
+    if let JqrError::InvalidJson {
+        looks_like_yaml: true,
+        ..
+    } = error
+    {
+        eprintln!("The input looks like YAML; read it with --yaml or --input-format yaml");
+    }
+}
+
+/// Prints the `--collect-errors` summary: every NDJSON line and input file
+/// that was skipped, after the file it is in.
+fn print_skipped(skipped: &[(&str, JqrError)], colors: &Colors) {
+    if skipped.is_empty() {
+        return;
+    }
+    let plural = if skipped.len() == 1 { "" } else { "s" };
+    eprintln!("{} error{}:", skipped.len(), plural);
+    for (name, e) in skipped {
+        eprintln!("{}: {}", name, colors.error(e));
+        print_hint(e);
+    }
+}
+
+/// Renders one result in the output format; `i` is the number of results
+/// rendered before it.
+fn render_result(
+    result: &Value,
+    i: usize,
+    matches: &ArgMatches,
+    format: &FormatOptions,
+    colors: &Colors,
+) -> Result<String, JqrError> {
+    if matches.get_flag("to-properties") {
+        json_to_properties(&key_order(matches, result)).map(|p| p.trim_end().to_string())
+    } else if writes_yaml(matches) {
+        // Results form a YAML stream, one `---` separated document each
+        format_yaml(result, format).map(|yaml| {
+            if i > 0 {
+                format!("---\n{}", yaml)
+            } else {
+                yaml
+            }
+        })
+    } else if matches.get_flag("to-toml") {
+        json_to_toml(&key_order(matches, result)).map(|t| t.trim_end().to_string())
+    } else if matches.get_flag("to-csv") || matches.get_flag("to-tsv") {
+        // Only the final line break goes; a TSV row may end with an empty cell
+        json_to_csv(&key_order(matches, result), &csv_output_options(matches))
+            .map(|t| t.strip_suffix('\n').unwrap_or(&t).to_string())
+    } else if matches.get_flag("to-xml") {
+        json_to_xml(&key_order(matches, result), &xml_options(matches))
+    } else if matches.get_flag("table") {
+        json_to_table(&key_order(matches, result))
+    } else if matches.get_flag("dual") {
+        dual_view(&key_order(matches, result)).map(|view| {
+            format!(
+                "{}\n{}\n{}\n{}",
+                colors.section_header("JSON"),
+                view.json,
+                colors.section_header("YAML"),
+                view.yaml
+            )
+        })
+    } else if matches.get_flag("raw-output") {
+        format_raw(result, format)
+    } else {
+        format_json(result, format).map(|json| {
+            if matches.get_one::<String>("quote-style").unwrap() == "single" {
+                single_quote_strings(&json)
+            } else if colors.stdout {
+                highlight_json(&json)
+            } else {
+                json
+            }
+        })
+    }
+}
+
+/// `value` with its keys in the order renderers without format options
+/// write them: sorted, unless `--preserve-order` without `--sort-keys`
+/// keeps the order it holds.
+fn key_order(matches: &ArgMatches, value: &Value) -> Value {
+    if matches.get_flag("preserve-order") && !matches.get_flag("sort-keys") {
+        value.clone()
+    } else {
+        sort_object_keys(value.clone())
+    }
+}
+
+/// Reports on stderr that a query found nothing in the whole run, once
+/// rather than for every document, under the default no-results policy.
+fn report_no_results(pipeline: &Pipeline, results: usize) {
+    if results == 0 && pipeline.query.is_some() && matches!(pipeline.no_results, NoResults::Report)
+    {
+        eprintln!("No results found");
+    }
+}
+
+/// The exit status after the `last` result: with `--exit-status`, a null or
+/// false final result is a failure like no result at all.
+fn exit_status(matches: &ArgMatches, last: Option<&Value>) -> ExitCode {
+    match last {
+        Some(Value::Null | Value::Bool(false)) | None if matches.get_flag("exit-status") => {
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
+
+/// Runs `jqr group-by`: groups the values the query matches by `--key` and
+/// prints the groups, or their sizes with `--count`.
+fn group_by_command(matches: &ArgMatches) -> ExitCode {
+    let compile = |id: &str| {
+        let query = matches.get_one::<String>(id).unwrap().clone();
+        CompiledQuery::new(&expand_shorthand(query))
+    };
+    let (query, key) = match compile("query").and_then(|query| Ok((query, compile("key")?))) {
+        Ok(queries) => queries,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    run_subcommand(matches, |json| {
+        let values = query.find(&json);
+        Ok(Value::Object(if matches.get_flag("count") {
+            count_by(&values, &key)
+        } else {
+            group_by(&values, &key)
+        }))
+    })
+}
+
+/// Runs the `set` subcommand: sets a path of the input document to a value.
+fn set_command(matches: &ArgMatches) -> ExitCode {
+    let path = matches.get_one::<String>("path").expect("path is required");
+    let text = matches
+        .get_one::<String>("value")
+        .expect("value is required");
+    let new = if matches.get_flag("string") {
+        Ok(Value::String(text.clone()))
+    } else {
+        match (serde_json::from_str(text), matches.get_flag("json")) {
+            (Ok(value), _) => Ok(value),
+            (Err(e), true) => Err(JqrError::InvalidArgument(format!(
+                "Invalid JSON value '{}': {}",
+                text, e
+            ))),
+            (Err(_), false) => Ok(Value::String(text.clone())),
+        }
+    };
+    let new = match new {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    let create_missing = matches.get_flag("create-missing");
+    run_subcommand(matches, |mut json| {
+        set_path(&mut json, path, new, create_missing)?;
+        Ok(json)
+    })
+}
+
+/// The `-i`/`--in-place`, `--backup` and `--numbered-backup` options, shared
+/// by the main command and the subcommands that edit a document.
+fn in_place_args() -> [Arg; 3] {
+    [
+        Arg::new("in-place")
+            .short('i')
+            .long("in-place")
+            .action(ArgAction::SetTrue)
+            .help("Rewrite the input file with the results"),
+        Arg::new("backup")
+            .long("backup")
+            .value_name("SUFFIX")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value(".bak")
+            .requires("in-place")
+            .help("With --in-place, keep the original file with SUFFIX appended (default: .bak)"),
+        Arg::new("numbered-backup")
+            .long("numbered-backup")
+            .action(ArgAction::SetTrue)
+            .requires("backup")
+            .help("With --backup, keep an existing backup and number the new one (FILE.bak.1, FILE.bak.2, ...)"),
+    ]
+}
+
+/// Reads the input document of a subcommand, from its `FILE` argument or
+/// stdin, and prints what `run` makes of it.
+///
+/// A subcommand with an `--in-place` flag rewrites `FILE` instead when it is
+/// given, keeping the keys in the order the file has them unless
+/// `--sort-keys` is given too.
+fn run_subcommand(
+    matches: &ArgMatches,
+    run: impl FnOnce(Value) -> Result<Value, JqrError>,
+) -> ExitCode {
+    let http = HttpOptions {
+        max_redirects: 5,
+        ..HttpOptions::default()
+    };
+    let file = matches.get_one::<String>("file").map(String::as_str);
+    let in_place = matches!(matches.try_get_one::<bool>("in-place"), Ok(Some(true)));
+    if in_place && file.is_some_and(input::is_url) {
+        eprintln!("Error: --in-place cannot rewrite a URL");
+        return ExitCode::from(EXIT_ERROR);
+    }
+    let result = (|| {
+        let mut content = String::new();
+        input::open(file, &http)?.read_to_string(&mut content)?;
+        let result = run(parse_json(&content)?)?;
+        let format =
+            format_options(matches).preserve_order(in_place || matches.get_flag("preserve-order"));
+        format_json(&result, &format)
+    })();
+    match result {
+        Ok(json) => {
+            let mut out = match file {
+                Some(path) if in_place => Output::in_place(
+                    path,
+                    matches.get_one::<String>("backup").map(String::as_str),
+                    matches.get_flag("numbered-backup"),
+                ),
+                _ => Output::new(None),
+            };
+            let written = writeln!(out, "{}", json);
+            finish_output(out, written, ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Prints the result of every query, then reports on stderr how often each
+/// document path was matched across all of them.
+fn profile_queries(
+    out: &mut impl Write,
+    content: &str,
+    queries: &[String],
+    format: &FormatOptions,
+) -> Result<(), JqrError> {
+    let json = parse_json(content)?;
+    let profile = profile_paths(&json, queries)?;
+
+    for query in queries {
+        match extract_jsonpath(&json, query)? {
+            Some(result) => {
+                writeln!(out, "{}", format_json(&result, format)?)?;
+            }
+            None => eprintln!("No results found for {}", query),
+        }
+    }
+
+    eprintln!(
+        "Path profile ({} queries, {} paths):",
+        queries.len(),
+        profile.len()
+    );
+    let width = profile
+        .first()
+        .map_or(1, |(_, count)| count.to_string().len());
+    for (path, count) in profile {
+        let path = if path.is_empty() {
+            "(root)".to_string()
+        } else {
+            path
+        };
+        eprintln!("  {:>width$}  {}", count, path, width = width);
+    }
+
+    Ok(())
+}
+
+/// Runs the pipeline against every `*.json` file in `dir` and collects the
+/// results into an object keyed by the file's path relative to `dir`.
+///
+/// Files that cannot be read or parsed are reported on stderr and skipped, as
+/// are files for which the query has no result under the selected policy.
+fn query_dir(
+    dir: &Path,
+    recursive: bool,
+    pipeline: &Pipeline,
+    colors: &Colors,
+) -> Result<Value, JqrError> {
+    let files = json_files(dir, recursive)?;
+
+    let mut results = Map::new();
+    for path in files {
+        let name = path
+            .strip_prefix(dir)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let evaluated = input::read_to_string(&path.to_string_lossy(), &HttpOptions::default())
+            .map_err(JqrError::Io)
+            .and_then(|content| pipeline.evaluate(&content));
+        match evaluated {
+            Ok(found) if found.is_empty() => {}
+            Ok(mut found) if found.len() == 1 => {
+                results.insert(name, found.remove(0));
+            }
+            // A filter with several results keeps them all
+            Ok(found) => {
+                results.insert(name, Value::Array(found));
+            }
+            Err(e) => {
+                eprintln!("Skipping {}: {}", name, colors.error(&e));
+                print_hint(&e);
+            }
+        }
+    }
+
+    Ok(Value::Object(results))
+}
+
+/// Decodes one input read as bytes: MessagePack with `--from-msgpack` (or
+/// when its format is detected as such), CBOR with `--from-cbor`, BSON with `--from-bson` and Avro with `--from-avro`,
+/// one document per encoded value (or all of them in an array with
+/// `--slurp`), a protobuf message with `--from-proto`, Parquet and Arrow IPC
+/// with `--from-parquet`, and anything else as text with [`evaluate_content`].
+fn evaluate_input(
+    content: Vec<u8>,
+    path: Option<&str>,
+    matches: &ArgMatches,
+    pipeline: &Pipeline,
+    skipped: &mut Vec<JqrError>,
+) -> Result<Vec<Value>, JqrError> {
+    let format = input_format(matches, path, &content);
+    let documents = if matches.get_flag("from-msgpack") || format == Some(InputFormat::Msgpack) {
+        msgpack_to_json(&content)
+    } else if matches.get_flag("from-cbor") {
+        cbor_to_json(&content)
+    } else if matches.get_flag("from-bson") {
+        bson_to_json(&content)
+    } else if matches.get_flag("from-avro") {
+        avro_schema(matches).and_then(|schema| avro_to_json(&content, schema.as_deref()))
+    } else if matches.get_flag("from-proto") {
+        let path = matches.get_one::<String>("descriptor").unwrap();
+        let message_type = matches.get_one::<String>("message-type").unwrap();
+        fs::read(path)
+            .map_err(|e| {
+                JqrError::InvalidArgument(format!("Cannot read descriptor set {}: {}", path, e))
+            })
+            .and_then(|descriptors| proto_to_json(&content, &descriptors, message_type))
+            .map(|message| vec![message])
+    } else if matches.get_flag("from-parquet") {
+        // Rows form one table unless they are to be queried one by one
+        columnar::read_rows(content)
+            .map(|rows| {
+                if matches.get_flag("ndjson") {
+                    rows
+                } else {
+                    vec![Value::Array(rows)]
+                }
+            })
+            .map_err(JqrError::Io)
+    } else {
+        return String::from_utf8(content)
+            .map_err(|e| JqrError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+            .and_then(|content| {
+                evaluate_content(content, path, format, matches, pipeline, skipped)
+            });
+    };
+
+    documents.and_then(|documents| {
+        if matches.get_flag("slurp") {
+            pipeline.evaluate_value(Value::Array(documents))
+        } else {
+            pipeline.evaluate_all(documents)
+        }
+    })
+}
+
+/// The JSON text of the `--avro-schema` file, if one is given.
+fn avro_schema(matches: &ArgMatches) -> Result<Option<String>, JqrError> {
+    matches
+        .get_one::<String>("avro-schema")
+        .map(|path| {
+            fs::read_to_string(path).map_err(|e| {
+                JqrError::InvalidArgument(format!("Cannot read Avro schema {}: {}", path, e))
+            })
+        })
+        .transpose()
+}
+
+/// Returns `true` if the input is read in a binary format rather than as text.
+fn is_binary_input(matches: &ArgMatches) -> bool {
+    [
+        "from-msgpack",
+        "from-cbor",
+        "from-bson",
+        "from-avro",
+        "from-proto",
+        "from-parquet",
+    ]
+    .iter()
+    .any(|format| matches.get_flag(format))
+}
+
+/// Encodes one result in a binary output format.
+type BinaryEncoder = fn(&Value) -> Result<Vec<u8>, JqrError>;
+
+/// The encoder of the binary output format (`--to-msgpack` or `--to-cbor`),
+/// if one is selected.
+fn binary_encoder(matches: &ArgMatches) -> Option<BinaryEncoder> {
+    if matches.get_flag("to-msgpack") {
+        Some(json_to_msgpack)
+    } else if matches.get_flag("to-cbor") {
+        Some(json_to_cbor)
+    } else {
+        None
+    }
+}
+
+/// Parses one input according to the input mode (`--yaml`, `--from-toml`,
+/// `--from-csv`/`--from-tsv`, `--from-xml`, `--raw-input`, `--json5` or
+/// `--jsonc` (or a `path` with their extension), `--slurp`, JSON text
+/// sequences, NDJSON or a single document) or the detected input `format`,
+/// and runs every document through the pipeline. With `--collect-errors`,
+/// malformed NDJSON lines go to `skipped` instead of failing the input.
+fn evaluate_content(
+    content: String,
+    path: Option<&str>,
+    format: Option<InputFormat>,
+    matches: &ArgMatches,
+    pipeline: &Pipeline,
+    skipped: &mut Vec<JqrError>,
+) -> Result<Vec<Value>, JqrError> {
+    if reads_yaml(matches) || format == Some(InputFormat::Yaml) {
+        let mut documents = parse_yaml_documents_with(&content, &yaml_options(matches))?;
+        if let Some(&index) = matches.get_one::<usize>("document") {
+            if index >= documents.len() {
+                return Err(JqrError::InvalidArgument(format!(
+                    "--document {} is out of range; the input has {} YAML documents",
+                    index,
+                    documents.len()
+                )));
+            }
+            documents = vec![documents.swap_remove(index)];
+        }
+
+        if matches.get_flag("slurp") {
+            pipeline.evaluate_value(Value::Array(documents))
+        } else {
+            pipeline.evaluate_all(documents)
+        }
+    } else if matches.get_flag("from-toml") || format == Some(InputFormat::Toml) {
+        toml_to_json(&content).and_then(|document| pipeline.evaluate_value(document))
+    } else if matches.get_flag("from-csv") || matches.get_flag("from-tsv") {
+        let delimiter = if matches.get_flag("from-tsv") {
+            b'\t'
+        } else {
+            b','
+        };
+        let options = CsvOptions::new()
+            .delimiter(delimiter)
+            .infer_types(matches.get_flag("infer-types"));
+        csv_to_json(&content, &options).and_then(|table| pipeline.evaluate_value(table))
+    } else if matches.get_flag("from-xml") {
+        xml_to_json(&content, &xml_options(matches))
+            .and_then(|document| pipeline.evaluate_value(document))
+    } else if matches.get_flag("raw-input") {
+        if matches.get_flag("slurp") {
+            pipeline.evaluate_value(Value::String(content))
+        } else {
+            pipeline.evaluate_all(content.lines().map(|line| Value::String(line.to_string())))
+        }
+    } else if let Some(dialect) = json_dialect(matches, path) {
+        match dialect {
+            JsonDialect::Json5 => json5_to_json(&content),
+            JsonDialect::Jsonc => jsonc_to_json(&content),
+        }
+        .and_then(|document| pipeline.evaluate_value(document))
+    } else if matches.get_flag("slurp") {
+        let documents = if matches.get_flag("from-json-seq") || looks_like_json_seq(&content) {
+            parse_json_seq(&content)
+        } else {
+            parse_json_values(&content)
+        };
+        documents.and_then(|documents| pipeline.evaluate_value(Value::Array(documents)))
+    } else if matches.get_flag("from-json-seq") || looks_like_json_seq(&content) {
+        parse_json_seq(&content).and_then(|records| pipeline.evaluate_all(records))
+    } else if matches.get_flag("ndjson")
+        || format == Some(InputFormat::Ndjson)
+        || looks_like_ndjson(&content)
+    {
+        if matches.get_flag("collect-errors") {
+            let mut records = Vec::new();
+            for record in ndjson_records(content.as_bytes()) {
+                match record {
+                    Ok(record) => records.push(record),
+                    Err(e) => skipped.push(e),
+                }
+            }
+            pipeline.evaluate_all(records)
+        } else {
+            parse_ndjson(&content).and_then(|records| pipeline.evaluate_all(records))
+        }
+    } else {
+        pipeline.evaluate(&content)
+    }
+}
+
+/// The option that makes jqr read no input file (`--null-input` or
+/// `--clipboard-in`), if given; the first positional argument is then the query.
+fn no_input_file(matches: &ArgMatches) -> Option<&'static str> {
+    if matches.get_flag("null-input") {
+        Some("--null-input")
+    } else if matches.get_flag("clipboard-in") {
+        Some("--clipboard-in")
+    } else {
+        None
+    }
+}
+
+/// The input files named on the command line, in order, followed by the
+/// JSON files matching each `--glob` pattern. When the query is given with
+/// `--query` or `--query-file`, the second positional argument is a file as
+/// well; with `--glob` and a positional query, the first one is the query.
+fn input_files(matches: &ArgMatches) -> Result<Vec<String>, JqrError> {
+    let query_is_file = [
+        "queries",
+        "query-file",
+        "jq",
+        "jmespath",
+        "pointer",
+        "jsonata",
+    ]
+    .iter()
+    .any(|id| matches.contains_id(id));
+    let file_is_query = matches.contains_id("glob") && !query_is_file;
+    let file = matches.get_one::<String>("file").filter(|_| !file_is_query);
+    let query = matches
+        .get_one::<String>("query")
+        .filter(|_| query_is_file || file_is_query);
+    let mut files: Vec<String> = file
+        .into_iter()
+        .chain(query)
+        .chain(matches.get_many::<String>("files").unwrap_or_default())
+        .cloned()
+        .collect();
+
+    for pattern in matches.get_many::<String>("glob").unwrap_or_default() {
+        let paths = glob::glob(pattern).map_err(|e| {
+            JqrError::InvalidArgument(format!("Invalid glob pattern '{}': {}", pattern, e))
+        })?;
+        let matched = files.len();
+        for path in paths {
+            let path = path.map_err(|e| JqrError::Io(e.into()))?;
+            if path.is_file() && is_json_file(&path) {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+        if files.len() == matched {
+            return Err(JqrError::InvalidArgument(format!(
+                "No JSON files match '{}'",
+                pattern
+            )));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Returns `true` for the extensions `--glob` reads: `.json`, `.jsonl` and
+/// `.ndjson`, also when followed by `.gz`, `.zst` or `.bz2`.
+fn is_json_file(path: &Path) -> bool {
+    has_data_extension(path, &["json", "jsonl", "ndjson"])
+}
+
+/// Returns `true` if `path` has one of `extensions`, also when followed by
+/// a compression extension: `.gz`, `.zst` or `.bz2`.
+fn has_data_extension(path: &Path, extensions: &[&str]) -> bool {
+    let has_extension = |path: &Path, extensions: &[&str]| {
+        path.extension()
+            .is_some_and(|ext| extensions.iter().any(|e| ext == *e))
+    };
+    let path = match path.file_stem() {
+        Some(stem) if has_extension(path, &["gz", "zst", "bz2"]) => Path::new(stem),
+        _ => path,
+    };
+    has_extension(path, extensions)
+}
+
+/// Returns `true` if the input is NDJSON that can be queried line by line as
+/// it is read: given `--ndjson` or `--input-format ndjson`, or named `.ndjson`
+/// or `.jsonl`, also compressed, and with no option that needs all the
+/// results, or all the input, at once.
+fn streams_ndjson(matches: &ArgMatches, path: Option<&str>) -> bool {
+    let ndjson = match matches.get_one::<String>("input-format") {
+        Some(format) => format == "ndjson",
+        None if matches.get_flag("ndjson") => {
+            let other_modes = [
+                "yaml",
+                "yaml-out",
+                "raw-input",
+                "from-toml",
+                "from-xml",
+                "from-csv",
+                "from-tsv",
+            ];
+            !other_modes.iter().any(|mode| matches.get_flag(mode))
+                && json_dialect(matches, path).is_none()
+                && !is_binary_input(matches)
+        }
+        None => {
+            input_format(matches, path, &[]).is_some()
+                && path
+                    .is_some_and(|path| has_data_extension(Path::new(path), &["ndjson", "jsonl"]))
+        }
+    };
+    let whole_input = [
+        "slurp",
+        "in-place",
+        "to-json-seq",
+        "to-json",
+        "keep-comments",
+        "profile-paths",
+    ];
+    ndjson
+        && !whole_input.iter().any(|option| matches.get_flag(option))
+        && !["offset", "limit", "equal"]
+            .iter()
+            .any(|option| matches.contains_id(option))
+        && binary_encoder(matches).is_none()
+}
+
+/// Queries NDJSON `input` one line at a time, printing and flushing the
+/// results of each record before the next line is read. A line that cannot be read, such as
+/// a corrupt compressed stream, is reported apart from one that is not JSON.
+fn print_ndjson_stream(
+    input: impl BufRead,
+    mut out: Output,
+    file_name: &str,
+    matches: &ArgMatches,
+    pipeline: &Pipeline,
+    colors: &Colors,
+) -> ExitCode {
+    let format = format_options(matches);
+    let line_numbers = matches.get_flag("line-numbers");
+    let filename = matches
+        .get_flag("with-filename")
+        .then(|| colors.filename(file_name));
+    let collect_errors = matches.get_flag("collect-errors");
+    let mut printed = 0;
+    let mut last = None;
+    let mut skipped = Vec::new();
+    let mut failed = false;
+
+    for record in ndjson_records(input) {
+        let evaluated = match record {
+            Ok(record) => pipeline.evaluate_value(record),
+            Err(e) if collect_errors => {
+                skipped.push((file_name, e));
+                continue;
+            }
+            Err(JqrError::Io(e)) => {
+                eprintln!("Error reading input: {}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+            Err(e) => Err(e),
+        };
+        let results = match evaluated {
+            Ok(results) => results,
+            Err(e) => {
+                eprintln!("Error processing JSON: {}", colors.error(e));
+                return ExitCode::from(EXIT_ERROR);
+            }
+        };
+        for result in results {
+            match render_result(&result, printed, matches, &format, colors) {
+                Ok(output) => {
+                    let written =
+                        print_output(&mut out, &output, line_numbers, filename.as_deref());
+                    if let Err(e) = written {
+                        eprintln!("Error writing output: {}", e);
+                        return ExitCode::from(EXIT_ERROR);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error rendering output: {}", e);
+                    failed = true;
+                }
+            }
+            printed += 1;
+            last = Some(result);
+        }
+        // A record's results are out before the next line arrives, also in a pipe
+        if let Err(e) = out.flush() {
+            eprintln!("Error writing output: {}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    }
+
+    report_no_results(pipeline, printed);
+    print_skipped(&skipped, colors);
+    if failed {
+        return ExitCode::from(EXIT_ERROR);
+    }
+    let status = if !skipped.is_empty() {
+        ExitCode::from(EXIT_ERROR)
+    } else if printed == 0 && matches.get_flag("no-results-empty") {
+        // With --no-results-empty a query without matches prints nothing
+        return ExitCode::FAILURE;
+    } else {
+        exit_status(matches, last.as_ref())
+    };
+    finish_output(out, Ok(()), status)
+}
+
+/// A superset of JSON that an input is read as.
+enum JsonDialect {
+    Json5,
+    Jsonc,
+}
+
+/// The JSON superset to read the input at `path` as: the one given by
+/// `--json5` or `--jsonc`, or else the one its extension names, unless the
+/// input is explicitly a stream of JSON documents or a parse profile is given.
+fn json_dialect(matches: &ArgMatches, path: Option<&str>) -> Option<JsonDialect> {
+    if matches.get_flag("json5") {
+        return Some(JsonDialect::Json5);
+    }
+    if matches.get_flag("jsonc") {
+        return Some(JsonDialect::Jsonc);
+    }
+    if ["slurp", "ndjson", "from-json-seq", "strict", "lenient"]
+        .iter()
+        .any(|mode| matches.get_flag(mode))
+    {
+        return None;
+    }
+    match Path::new(path?).extension()?.to_str()? {
+        "json5" => Some(JsonDialect::Json5),
+        "jsonc" => Some(JsonDialect::Jsonc),
+        _ => None,
+    }
+}
+
+/// Lists the `*.json` files in `dir` (and its subdirectories if `recursive`), sorted by path.
+fn json_files(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                files.extend(json_files(&path, recursive)?);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns `true` if the result is printed as plain JSON, with no conversion
+/// or output mode that needs the whole document.
+fn is_plain_output(matches: &ArgMatches) -> bool {
+    let modes = [
+        "to-yaml",
+        "to-json",
+        "yaml",
+        "yaml-out",
+        "keep-comments",
+        "profile-paths",
+        "from-json-seq",
+        "ndjson",
+        "slurp",
+        "raw-input",
+        "with-filename",
+        "to-json-seq",
+        "to-properties",
+        "to-toml",
+        "from-toml",
+        "to-xml",
+        "from-xml",
+        "from-csv",
+        "from-tsv",
+        "to-csv",
+        "to-tsv",
+        "from-msgpack",
+        "to-msgpack",
+        "from-cbor",
+        "to-cbor",
+        "from-bson",
+        "from-avro",
+        "from-proto",
+        "from-parquet",
+        "table",
+        "dual",
+        "line-numbers",
+        "raw-output",
+    ];
+
+    !modes.iter().any(|mode| matches.get_flag(mode))
+        && !matches.contains_id("equal")
+        && matches.get_one::<String>("quote-style").unwrap() == "double"
+}
+
+/// Returns `true` if the buffered start of `input` opens an object or array
+/// and is read as a single JSON document, not as newline-delimited JSON, a
+/// TOML table or another format.
+///
+/// Nothing is consumed, so the input can still be read as a whole afterwards.
+fn starts_with_container(
+    input: &mut impl BufRead,
+    matches: &ArgMatches,
+    path: Option<&str>,
+) -> bool {
+    match input.fill_buf() {
+        Ok(buf) => {
+            matches!(
+                buf.iter().find(|b| !b.is_ascii_whitespace()),
+                Some(b'{' | b'[')
+            ) && matches!(
+                input_format(matches, path, buf),
+                None | Some(InputFormat::Json)
+            ) && !looks_like_ndjson(&String::from_utf8_lossy(buf))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Collects the output formatting options from the parsed arguments.
+fn format_options(matches: &ArgMatches) -> FormatOptions {
+    let mut format = FormatOptions::new()
+        .tabs(matches.get_flag("tab"))
+        .compact(matches.get_flag("compact"))
+        .sort_keys(matches.get_flag("sort-keys"))
+        .preserve_order(matches.get_flag("preserve-order"))
+        .ascii_output(matches.get_flag("ascii-output"));
+    if let Some(&width) = matches.get_one::<usize>("indent") {
+        format = format.indent(width);
+    }
+    if let Some(&columns) = matches.get_one::<usize>("width") {
+        format = format.line_width(columns);
+    }
+    format
+}
+
+/// The format to read an input in: the one given by `--input-format`, or
+/// else the one detected from its `path` and `content`. Returns `None` when
+/// an input option such as `--yaml`, `--from-toml` or `--strict` decides
+/// instead.
+fn input_format(matches: &ArgMatches, path: Option<&str>, content: &[u8]) -> Option<InputFormat> {
+    if let Some(format) = matches.get_one::<String>("input-format") {
+        return Some(match format.as_str() {
+            "yaml" => InputFormat::Yaml,
+            "toml" => InputFormat::Toml,
+            "ndjson" => InputFormat::Ndjson,
+            "msgpack" => InputFormat::Msgpack,
+            _ => InputFormat::Json,
+        });
+    }
+    let modes = [
+        "yaml",
+        "yaml-out",
+        "to-json",
+        "raw-input",
+        "from-json-seq",
+        "ndjson",
+        "json5",
+        "jsonc",
+        "strict",
+        "lenient",
+        "from-toml",
+        "from-xml",
+        "from-csv",
+        "from-tsv",
+    ];
+    let explicit = modes.iter().any(|mode| matches.get_flag(mode)) || is_binary_input(matches);
+    (!explicit).then(|| detect_input_format(path, content))
+}
+
+/// Returns `true` if the input is read as YAML documents.
+fn reads_yaml(matches: &ArgMatches) -> bool {
+    matches.get_flag("yaml") || matches.get_flag("yaml-out")
+}
+
+/// Returns `true` if the results are written as YAML.
+fn writes_yaml(matches: &ArgMatches) -> bool {
+    matches.get_flag("to-yaml") || matches.get_flag("yaml-out")
+}
+
+/// Collects the JSON parsing options from the parsed arguments.
+fn parse_options(matches: &ArgMatches) -> ParseOptions {
+    let duplicate_keys = match matches
+        .get_one::<String>("duplicate-keys")
+        .map(String::as_str)
+    {
+        Some("error") => DuplicateKeys::Error,
+        Some("first") => DuplicateKeys::First,
+        _ => DuplicateKeys::Last,
+    };
+    let profile = if matches.get_flag("strict") {
+        ParseProfile::Strict
+    } else if matches.get_flag("lenient") {
+        ParseProfile::Lenient
+    } else {
+        ParseProfile::Standard
+    };
+    ParseOptions::new()
+        .profile(profile)
+        .max_depth(
+            matches
+                .get_one::<usize>("max-depth-guard")
+                .copied()
+                .unwrap_or(DEFAULT_MAX_DEPTH),
+        )
+        .duplicate_keys(duplicate_keys)
+}
+
+/// Collects the YAML alias and merge-key options from the parsed arguments.
+fn yaml_options(matches: &ArgMatches) -> YamlOptions {
+    let mut options = YamlOptions::new()
+        .keep_aliases(matches.get_flag("keep-aliases"))
+        .keep_merge_keys(matches.get_flag("keep-merge-keys"));
+    if let Some(&limit) = matches.get_one::<usize>("alias-limit") {
+        options = options.alias_limit(limit);
+    }
+    options
+}
+
+/// Collects the `--to-csv`/`--to-tsv` options from the parsed arguments.
+fn csv_output_options(matches: &ArgMatches) -> CsvOptions {
+    let delimiter = if matches.get_flag("to-tsv") {
+        b'\t'
+    } else {
+        b','
+    };
+    CsvOptions::new().delimiter(delimiter).columns(
+        matches
+            .get_many::<String>("column")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Collects the XML conventions from the parsed arguments.
+fn xml_options(matches: &ArgMatches) -> XmlOptions {
+    let mut options = XmlOptions::new()
+        .attribute_prefix(matches.get_one::<String>("xml-attribute-prefix").unwrap())
+        .text_key(matches.get_one::<String>("xml-text-key").unwrap());
+    for name in matches.get_many::<String>("xml-array").unwrap_or_default() {
+        options = options.force_array(name);
+    }
+    options
+}
+
+/// Collects the options for fetching URLs from the parsed arguments.
+fn http_options(matches: &ArgMatches) -> HttpOptions {
+    HttpOptions {
+        headers: matches
+            .get_many::<(String, String)>("header")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        bearer_token: matches.get_one::<String>("bearer").cloned(),
+        timeout: matches
+            .get_one::<u64>("timeout")
+            .map(|&seconds| Duration::from_secs(seconds)),
+        max_redirects: *matches.get_one::<u32>("max-redirects").unwrap(),
+    }
+}
+
+/// Parses a `--header` value of the form `Name: value`.
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected 'Name: value', got '{}'", header)),
+    }
+}
+
+/// Whether stdout and stderr get colored output, from `--color` and the environment.
+struct Colors {
+    stdout: bool,
+    stderr: bool,
+}
+
+impl Colors {
+    /// Resolves `--color`: `auto` colors a stream only if it is a terminal
+    /// and `NO_COLOR` is unset or empty.
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let colors = match matches.get_one::<String>("color").unwrap().as_str() {
+            "always" => Colors {
+                stdout: true,
+                stderr: true,
+            },
+            "never" => Colors {
+                stdout: false,
+                stderr: false,
+            },
+            _ => Colors {
+                stdout: !no_color
+                    && !matches.contains_id("output")
+                    && !matches.get_flag("clipboard-out")
+                    && !matches.get_flag("in-place")
+                    && io::stdout().is_terminal(),
+                stderr: !no_color && io::stderr().is_terminal(),
+            },
+        };
+
+        // The decision is made here per stream, not by `colored`'s own detection
+        colored::control::set_override(true);
+        colors
+    }
+
+    /// Formats an error message for stderr.
+    fn error(&self, error: impl fmt::Display) -> String {
+        let message = error.to_string();
+        if self.stderr {
+            message.red().to_string()
+        } else {
+            message
+        }
+    }
+
+    /// Formats a `--with-filename` prefix for stdout.
+    fn filename(&self, name: &str) -> String {
+        if self.stdout {
+            name.magenta().to_string()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Formats a `--dual` section header for stdout.
+    fn section_header(&self, title: &str) -> String {
+        let header = format!("── {} ──", title);
+        if self.stdout {
+            header.cyan().bold().to_string()
+        } else {
+            header
+        }
+    }
+}
+
+/// Writes the final rendered output, optionally prefixed with line numbers
+/// and, on every line before those, a file name (`name:line`, as grep does).
+fn print_output(
+    out: &mut impl Write,
+    output: &str,
+    line_numbers: bool,
+    filename: Option<&str>,
+) -> io::Result<()> {
+    let output = if line_numbers {
+        number_lines(output)
+    } else {
+        output.to_string()
+    };
+    match filename {
+        Some(name) => output
+            .lines()
+            .try_for_each(|line| writeln!(out, "{}:{}", name, line)),
+        None => writeln!(out, "{}", output),
+    }
+}
+
+/// Skips the first `offset` items and keeps at most `limit` of the rest.
+fn page<T>(items: Vec<T>, offset: usize, limit: Option<usize>) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Completes the output after everything has been written and returns
+/// `status`, or reports a write error; on error an `--output` file is left
+/// untouched.
+fn finish_output(out: Output, written: io::Result<()>, status: ExitCode) -> ExitCode {
+    match written.and_then(|_| out.finish()) {
+        Ok(()) => status,
+        Err(e) => {
+            eprintln!("Error writing output: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// The query and post-query stages selected on the command line.
+struct Pipeline {
+    /// `--select`: records the predicate does not hold for are dropped
+    /// before the query runs.
+    select: Option<Predicate>,
+    query: Option<Query>,
+    no_results: NoResults,
+    projection: Option<Projection>,
+    select_types: Option<String>,
+    renames: Option<HashMap<String, String>>,
+    coerce_numbers: bool,
+    coerce_booleans: bool,
+    walk_ops: Vec<WalkOp>,
+    /// `Some(keep_null)` when `--remove-empty` is given.
+    remove_empty: Option<bool>,
+    transforms: Vec<Transform>,
+    fold_threshold: Option<usize>,
+    parse: ParseOptions,
+    /// `--paths`: every match is printed with its location.
+    with_paths: bool,
+    /// `--index-base`: what the first array index in a printed location is.
+    index_base: usize,
+    /// `Some(key)` when `--unique` is given, with the `PATH` key if any.
+    unique: Option<Option<CompiledQuery>>,
+    sort_keys: Vec<SortKey>,
+    /// `--sort-at`: the JSONPath of the arrays to sort inside each result.
+    sort_target: Option<String>,
+    sample: Option<usize>,
+    /// `Some(seed)` when `--shuffle` is given, with the `SEED` if any.
+    shuffle: Option<Option<u64>>,
+    reverse: bool,
+    aggregate: Option<Aggregate>,
+}
+
+impl Pipeline {
+    /// Collects and validates the pipeline options from the parsed arguments.
+    fn from_matches(matches: &ArgMatches) -> Result<Self, JqrError> {
+        let walk_ops = matches
+            .get_many::<String>("walk")
+            .unwrap_or_default()
+            .map(|op| op.parse())
+            .collect::<Result<Vec<WalkOp>, JqrError>>()?;
+
+        let transforms = match matches.get_one::<String>("apply") {
+            Some(spec) => parse_transforms(spec)?,
+            None => Vec::new(),
+        };
+
+        // A .jq query file holds a jq filter, which keeps its comments and
+        // line breaks; any other is a JSONPath query
+        let mut jq_file = None;
+        let query = match matches.get_one::<String>("query-file") {
+            Some(path) => {
+                let text = fs::read_to_string(path).map_err(|e| {
+                    JqrError::InvalidArgument(format!("Error reading query file: {}", e))
+                })?;
+                if Path::new(path).extension().is_some_and(|ext| ext == "jq") {
+                    jq_file = Some(text);
+                    None
+                } else {
+                    Some(normalize_query_text(&text)?)
+                }
+            }
+            None if ["jq", "jmespath", "pointer", "jsonata"]
+                .iter()
+                .any(|id| matches.contains_id(id)) =>
+            {
+                None
+            }
+            None if no_input_file(matches).is_some()
+                && matches.contains_id("file")
+                && matches.contains_id("query") =>
+            {
+                return Err(JqrError::InvalidArgument(format!(
+                    "{} reads no input file, but a file was given",
+                    no_input_file(matches).unwrap()
+                )))
+            }
+            // Without an input file, a single positional argument is the query
+            None if no_input_file(matches).is_some() => matches
+                .get_one::<String>("query")
+                .or(matches.get_one::<String>("file"))
+                .cloned(),
+            // With --glob, the query comes first and any positional files after it
+            None if matches.contains_id("glob") => matches.get_one::<String>("file").cloned(),
+            None => matches.get_one::<String>("query").cloned(),
+        };
+
+        let queries: Vec<&String> = matches
+            .get_many::<String>("queries")
+            .unwrap_or_default()
+            .collect();
+        let labeled = !matches.get_flag("profile-paths")
+            && queries.iter().any(|q| split_query_label(q).is_some());
+        let query = match queries.as_slice() {
+            [] => query,
+            _ if labeled => None,
+            [single] => Some(single.to_string()),
+            _ if matches.get_flag("profile-paths") => None,
+            _ => {
+                return Err(JqrError::InvalidArgument(
+                    "Multiple --query options need a name=QUERY label each, or --profile-paths"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let dialect = match matches
+            .get_one::<String>("jsonpath-dialect")
+            .map(String::as_str)
+        {
+            Some("rfc9535") => JsonPathDialect::Rfc9535,
+            _ => JsonPathDialect::Legacy,
+        };
+        let variables = query_variables(matches)?;
+        let compile = |q: String| -> Result<Query, JqrError> {
+            let q = substitute_query_variables(&q, &variables);
+            if !matches.get_flag("auto-query") {
+                return Ok(Query::JsonPath(CompiledQuery::with_dialect(
+                    &expand_shorthand(q),
+                    dialect,
+                )?));
+            }
+            Ok(match detect_query_syntax(&q)? {
+                QuerySyntax::JsonPath => Query::JsonPath(CompiledQuery::with_dialect(&q, dialect)?),
+                QuerySyntax::Shorthand => Query::JsonPath(CompiledQuery::with_dialect(
+                    &shorthand_to_jsonpath(&q),
+                    dialect,
+                )?),
+                QuerySyntax::JsonPointer => Query::Pointer(JsonPointer::new(q.trim_start())?),
+            })
+        };
+        let query = if labeled {
+            let mut labeled_queries: Vec<(String, Query)> = Vec::new();
+            for q in queries {
+                let (label, q) = split_query_label(q).ok_or_else(|| {
+                    JqrError::InvalidArgument(format!(
+                        "--query '{}' needs a name=QUERY label, as the other --query options have",
+                        q
+                    ))
+                })?;
+                if labeled_queries.iter().any(|(seen, _)| seen == label) {
+                    return Err(JqrError::InvalidArgument(format!(
+                        "The --query label '{}' is used twice",
+                        label
+                    )));
+                }
+                labeled_queries.push((label.to_string(), compile(q.to_string())?));
+            }
+            Some(Query::Labeled(labeled_queries))
+        } else {
+            query.map(compile).transpose()?
+        };
+        let query = if let Some(filter) = matches.get_one::<String>("jq").or(jq_file.as_ref()) {
+            Some(Query::Jq(JqFilter::new(&substitute_query_variables(
+                filter, &variables,
+            ))?))
+        } else if let Some(expression) = matches.get_one::<String>("jmespath") {
+            Some(Query::JmesPath(JmesPath::new(expression)?))
+        } else if let Some(pointer) = matches.get_one::<String>("pointer") {
+            Some(Query::Pointer(JsonPointer::new(pointer)?))
+        } else if let Some(expression) = matches.get_one::<String>("jsonata") {
+            Some(jsonata_query(&substitute_query_variables(
+                expression, &variables,
+            ))?)
+        } else {
+            query
+        };
+
+        let with_paths = matches.get_flag("paths");
+        if with_paths && !matches!(query, Some(Query::JsonPath(_))) {
+            return Err(JqrError::InvalidArgument(
+                "--paths requires a single JSONPath query".to_string(),
+            ));
+        }
+
+        let no_results = if let Some(default) = matches.get_one::<String>("default") {
+            let value = parse_json(default).map_err(|e| {
+                JqrError::InvalidArgument(format!("Invalid --default value: {}", e))
+            })?;
+            NoResults::Default(value)
+        } else if matches.get_flag("no-results-empty") {
+            NoResults::Empty
+        } else if matches.get_flag("no-results-null") {
+            NoResults::Null
+        } else {
+            NoResults::Report
+        };
+
+        Ok(Pipeline {
+            select: matches
+                .get_one::<String>("select")
+                .map(|expression| {
+                    Predicate::new(&substitute_query_variables(expression, &variables))
+                })
+                .transpose()?,
+            query,
+            no_results,
+            projection: matches
+                .get_one::<String>("map")
+                .map(|template| Projection::new(&substitute_query_variables(template, &variables)))
+                .transpose()?,
+            select_types: matches.get_one::<String>("select-type").cloned(),
+            renames: matches
+                .get_one::<String>("rename")
+                .map(|spec| parse_rename_mapping(spec))
+                .transpose()?,
+            coerce_numbers: matches.get_flag("coerce-numbers"),
+            coerce_booleans: matches.get_flag("coerce-booleans"),
+            walk_ops,
+            remove_empty: matches
+                .get_flag("remove-empty")
+                .then(|| matches.get_flag("keep-null")),
+            transforms,
+            fold_threshold: matches.get_one::<usize>("fold-threshold").copied(),
+            parse: parse_options(matches),
+            with_paths,
+            index_base: if matches.get_one::<String>("index-base").unwrap() == "1" {
+                1
+            } else {
+                0
+            },
+            unique: if matches.contains_id("unique") {
+                Some(
+                    matches
+                        .get_one::<String>("unique")
+                        .map(|path| CompiledQuery::new(&expand_shorthand(path.clone())))
+                        .transpose()?,
+                )
+            } else {
+                None
+            },
+            sort_keys: match matches.get_one::<String>("sort-by") {
+                Some(spec) => parse_sort_keys(spec)?,
+                None => Vec::new(),
+            },
+            sort_target: matches
+                .get_one::<String>("sort-at")
+                .cloned()
+                .map(expand_shorthand),
+            sample: matches.get_one::<usize>("sample").copied(),
+            shuffle: matches
+                .contains_id("shuffle")
+                .then(|| matches.get_one::<u64>("shuffle").copied()),
+            reverse: matches.get_flag("reverse"),
+            aggregate: matches
+                .get_one::<String>("agg")
+                .map(|op| op.parse())
+                .transpose()?,
+        })
+    }
+
+    /// Returns `true` if the pipeline passes documents through unchanged, so
+    /// they can be reformatted without being parsed into memory.
+    fn is_passthrough(&self) -> bool {
+        self.select.is_none()
+            && self.query.is_none()
+            && self.projection.is_none()
+            && self.select_types.is_none()
+            && self.renames.is_none()
+            && !self.coerce_numbers
+            && !self.coerce_booleans
+            && self.walk_ops.is_empty()
+            && self.remove_empty.is_none()
+            && self.transforms.is_empty()
+            && self.fold_threshold.is_none()
+            && self.unique.is_none()
+            && self.sort_keys.is_empty()
+            && self.sample.is_none()
+            && self.shuffle.is_none()
+            && !self.reverse
+            && self.aggregate.is_none()
+            && self.parse == ParseOptions::default()
+    }
+
+    /// Parses the input, runs the optional query and applies post-query stages.
+    ///
+    /// Returns no results when the query matched nothing and the no-results
+    /// policy is to print nothing.
+    fn evaluate(&self, content: &str) -> Result<Vec<Value>, JqrError> {
+        self.evaluate_value(parse_json_with(content, &self.parse)?)
+    }
+
+    /// Runs the pipeline on every document, collecting all the results.
+    fn evaluate_all(
+        &self,
+        documents: impl IntoIterator<Item = Value>,
+    ) -> Result<Vec<Value>, JqrError> {
+        let mut results = Vec::new();
+        for document in documents {
+            results.extend(self.evaluate_value(document)?);
+        }
+        Ok(results)
+    }
+
+    /// Runs the optional query and post-query stages on an already parsed
+    /// document. Only a jq filter can produce more than one result.
+    fn evaluate_value(&self, json: Value) -> Result<Vec<Value>, JqrError> {
+        if self
+            .select
+            .as_ref()
+            .is_some_and(|select| !select.matches(&json))
+        {
+            return Ok(Vec::new());
+        }
+        if let (true, Some(Query::JsonPath(query))) = (self.with_paths, &self.query) {
+            let found = query.find_with_paths(&json);
+            if found.is_empty() {
+                return self.no_results.fallback().into_iter().map(Ok).collect();
+            }
+            return found
+                .into_iter()
+                .map(|(path, value)| {
+                    let mut located = Map::new();
+                    let path = path.map(|path| rebase_path_indexes(&path, self.index_base));
+                    located.insert("path".to_string(), path.map_or(Value::Null, Value::String));
+                    located.insert("value".to_string(), self.finish(value)?);
+                    Ok(Value::Object(located))
+                })
+                .collect();
+        }
+        let mut found = match &self.query {
+            Some(query) => query.find(&json)?,
+            None => vec![json],
+        };
+        // The results of a jq filter are aggregated together, while a single
+        // array result is aggregated over its elements
+        if let Some(op) = self.aggregate {
+            let results = match found.len() {
+                1 => self.finish(found.pop().unwrap())?,
+                _ => Value::Array(
+                    found
+                        .into_iter()
+                        .map(|result| self.finish(result))
+                        .collect::<Result<_, _>>()?,
+                ),
+            };
+            return Ok(vec![aggregate(&results, op)?]);
+        }
+        let found = if found.is_empty() {
+            self.no_results.fallback().into_iter().collect()
+        } else {
+            found
+        };
+        found
+            .into_iter()
+            .map(|result| self.finish(result))
+            .collect()
+    }
+
+    /// Applies the post-query stages to one result.
+    fn finish(&self, result: Value) -> Result<Value, JqrError> {
+        let result = match &self.projection {
+            Some(projection) => projection.map(&result),
+            None => result,
+        };
+        let result = match &self.select_types {
+            Some(types) => select_type(result, types)?,
+            None => result,
+        };
+
+        let mut result = result;
+        if let Some(mapping) = &self.renames {
+            rename_keys(&mut result, mapping)?;
+        }
+        if self.coerce_numbers {
+            coerce_numeric_strings(&mut result);
+        }
+        if self.coerce_booleans {
+            coerce_boolean_strings(&mut result);
+        }
+
+        let result = self
+            .walk_ops
+            .iter()
+            .fold(result, |value, &op| walk(value, op));
+
+        let result = match self.remove_empty {
+            Some(true) => prune_empty_keep_null(result),
+            Some(false) => prune_empty(result),
+            None => result,
+        };
+
+        let mut result = apply_transforms(result, &self.transforms)?;
+
+        match &self.unique {
+            Some(Some(key)) => unique_by(&mut result, key)?,
+            Some(None) => unique(&mut result)?,
+            None => {}
+        }
+        if !self.sort_keys.is_empty() {
+            match &self.sort_target {
+                Some(target) => sort_at(&mut result, target, &self.sort_keys)?,
+                None => sort_by(&mut result, &self.sort_keys)?,
+            }
+        }
+        if let Some(n) = self.sample {
+            sample(&mut result, n, None)?;
+        }
+        if let Some(seed) = self.shuffle {
+            shuffle(&mut result, seed)?;
+        }
+        if self.reverse {
+            reverse(&mut result)?;
+        }
+        if let Some(threshold) = self.fold_threshold {
+            result = fold_large(result, threshold);
+        }
+
+        Ok(result)
+    }
+}
+
+/// What to output when a query matches nothing.
+enum NoResults {
+    /// Print nothing, and report `No results found` on stderr once if the
+    /// whole run has no results (the default).
+    Report,
+    /// Print `null` (`--no-results-null`).
+    Null,
+    /// Print nothing and exit with status 1 (`--no-results-empty`).
+    Empty,
+    /// Print the given JSON value (`--default`).
+    Default(Value),
+}
+
+impl NoResults {
+    /// Returns the value to print for a query without matches, or `None` if
+    /// nothing should be printed.
+    fn fallback(&self) -> Option<Value> {
+        match self {
+            NoResults::Report | NoResults::Empty => None,
+            NoResults::Null => Some(Value::Null),
+            NoResults::Default(value) => Some(value.clone()),
+        }
+    }
+}
+
+/// A query after syntax detection.
+enum Query {
+    /// A JSONPath expression (shorthand paths are converted to JSONPath),
+    /// compiled once and applied to every input document.
+    JsonPath(CompiledQuery),
+    /// An RFC 6901 JSON Pointer (`--pointer`, or `/...` with `--auto-query`).
+    Pointer(JsonPointer),
+    /// A filter in the jq language subset (`--jq`), which can produce any
+    /// number of results.
+    Jq(JqFilter),
+    /// A JMESPath expression (`--jmespath`).
+    JmesPath(JmesPath),
+    /// A JSONata expression (`--jsonata`), whose undefined result is no
+    /// result.
+    #[cfg(feature = "jsonata")]
+    Jsonata(Jsonata),
+    /// Labeled queries (`--query name=QUERY`, repeated), whose results are
+    /// gathered into one object.
+    Labeled(Vec<(String, Query)>),
+}
+
+impl Query {
+    /// Runs the query on a document. Only a jq filter can produce more than
+    /// one result.
+    fn find(&self, json: &Value) -> Result<Vec<Value>, JqrError> {
+        Ok(match self {
+            Query::JsonPath(q) => q.apply(json).into_iter().collect(),
+            Query::Pointer(p) => p.resolve(json).cloned().into_iter().collect(),
+            Query::Jq(filter) => filter.apply(json)?,
+            // JMESPath evaluates a path to nothing as null
+            Query::JmesPath(expression) => match expression.apply(json)? {
+                Value::Null => Vec::new(),
+                result => vec![result],
+            },
+            #[cfg(feature = "jsonata")]
+            Query::Jsonata(expression) => expression.apply(json)?.into_iter().collect(),
+            // A label without matches is null, so the object keeps its shape
+            Query::Labeled(queries) => {
+                let mut object = Map::new();
+                for (label, query) in queries {
+                    let mut found = query.find(json)?;
+                    let value = match found.len() {
+                        0 => Value::Null,
+                        1 => found.pop().unwrap(),
+                        _ => Value::Array(found),
+                    };
+                    object.insert(label.clone(), value);
+                }
+                vec![Value::Object(object)]
+            }
+        })
+    }
+}
+
+/// The variables set with `--arg` (strings) and `--argjson` (JSON values).
+fn query_variables(matches: &ArgMatches) -> Result<Vec<(String, Value)>, JqrError> {
+    let mut variables: Vec<(String, Value)> = Vec::new();
+    for id in ["arg", "argjson"] {
+        let values: Vec<&String> = matches.get_many::<String>(id).unwrap_or_default().collect();
+        for pair in values.chunks(2) {
+            let (name, value) = (pair[0], pair[1]);
+            let valid = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if !valid {
+                return Err(JqrError::InvalidArgument(format!(
+                    "Invalid --{} name '{}': use letters, digits and '_'",
+                    id, name
+                )));
+            }
+            if variables.iter().any(|(seen, _)| seen == name) {
+                return Err(JqrError::InvalidArgument(format!(
+                    "The variable ${} is set twice",
+                    name
+                )));
+            }
+            let value = if id == "argjson" {
+                parse_json(value).map_err(|e| {
+                    JqrError::InvalidArgument(format!(
+                        "Invalid --argjson value for {}: {}",
+                        name, e
+                    ))
+                })?
+            } else {
+                Value::String(value.clone())
+            };
+            variables.push((name.clone(), value));
+        }
+    }
+    Ok(variables)
+}
+
+/// Turns a shorthand path (`.user.name`, `users[0].email`) into JSONPath.
+/// Other queries are left to be parsed, or to fail, as JSONPath.
+fn expand_shorthand(query: String) -> String {
+    match detect_query_syntax(&query) {
+        Ok(QuerySyntax::Shorthand) => shorthand_to_jsonpath(&query),
+        _ => query,
+    }
+}
+
+#[cfg(feature = "jsonata")]
+fn jsonata_query(expression: &str) -> Result<Query, JqrError> {
+    Ok(Query::Jsonata(Jsonata::new(expression)?))
+}
+
+#[cfg(not(feature = "jsonata"))]
+fn jsonata_query(_expression: &str) -> Result<Query, JqrError> {
+    Err(JqrError::InvalidArgument(
+        "jqr was built without the jsonata feature".to_string(),
+    ))
 }
@@ -0,0 +1,205 @@
+//! Where results are written: stdout, a file given with `--output`, the
+//! input file itself with `--in-place`, or the clipboard with `--clipboard-out`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::clipboard;
+
+/// The destination for results.
+pub enum Output {
+    Stdout(io::Stdout),
+    File(AtomicFile),
+    /// Collected and put on the clipboard when finished.
+    Clipboard(Vec<u8>),
+}
+
+impl Output {
+    /// Writes to the file at `path` if given, otherwise to stdout.
+    pub fn new(path: Option<&str>) -> Self {
+        match path {
+            Some(path) => Output::File(AtomicFile::new(path)),
+            None => Output::Stdout(io::stdout()),
+        }
+    }
+
+    /// Rewrites the file at `path`, which may still be read while the output
+    /// is being written. With a `backup_suffix`, the original is kept next to
+    /// it under that suffix; with `numbered`, an existing backup is kept too
+    /// and the new one numbered after it.
+    pub fn in_place(path: &str, backup_suffix: Option<&str>, numbered: bool) -> Self {
+        let file = AtomicFile::new(path);
+        Output::File(match backup_suffix {
+            Some(suffix) => file.backup(format!("{}{}", path, suffix), numbered),
+            None => file,
+        })
+    }
+
+    /// Collects the output for the system clipboard.
+    pub fn clipboard() -> Self {
+        Output::Clipboard(Vec::new())
+    }
+
+    /// Completes the output; for a file, this is what replaces the destination.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Stdout(mut stdout) => stdout.flush(),
+            Output::File(file) => file.commit(),
+            Output::Clipboard(buffer) => {
+                let text = String::from_utf8(buffer).map_err(io::Error::other)?;
+                clipboard::set_text(text)
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.write(buf),
+            Output::Clipboard(buffer) => buffer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.flush(),
+            Output::Clipboard(_) => Ok(()),
+        }
+    }
+}
+
+/// A file that is written under a temporary name in the same directory and
+/// renamed over the destination by [`commit`](AtomicFile::commit), so the
+/// destination is never left truncated or half-written.
+///
+/// The temporary file is created on the first write and removed again if the
+/// `AtomicFile` is dropped without being committed.
+pub struct AtomicFile {
+    path: PathBuf,
+    backup: Option<PathBuf>,
+    numbered_backup: bool,
+    temp: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl AtomicFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AtomicFile {
+            path: path.into(),
+            backup: None,
+            numbered_backup: false,
+            temp: None,
+        }
+    }
+
+    /// Copies the file being replaced to `backup_path` before committing.
+    /// With `numbered`, an existing backup is not replaced: the copy goes to
+    /// the first free `backup_path.1`, `backup_path.2` and so on.
+    pub fn backup(mut self, backup_path: impl Into<PathBuf>, numbered: bool) -> Self {
+        self.backup = Some(backup_path.into());
+        self.numbered_backup = numbered;
+        self
+    }
+
+    /// The temporary file, created on first use.
+    fn writer(&mut self) -> io::Result<&mut BufWriter<File>> {
+        if self.temp.is_none() {
+            let temp_path = temp_path(&self.path);
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)?;
+            self.temp = Some((temp_path, BufWriter::new(file)));
+        }
+        Ok(&mut self.temp.as_mut().unwrap().1)
+    }
+
+    /// Flushes the written content to disk and moves it into place, keeping
+    /// the permissions of the file it replaces.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.writer()?;
+        let (temp_path, writer) = self.temp.take().unwrap();
+
+        let result = writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|file| {
+                file.sync_all()?;
+                if let Ok(metadata) = fs::metadata(&self.path) {
+                    file.set_permissions(metadata.permissions())?;
+                }
+                if let Some(backup_path) = &self.backup {
+                    write_backup(&self.path, backup_path, self.numbered_backup)?;
+                }
+                fs::rename(&temp_path, &self.path)
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.temp {
+            Some((_, writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if let Some((temp_path, writer)) = self.temp.take() {
+            drop(writer);
+            let _ = fs::remove_file(temp_path);
+        }
+    }
+}
+
+/// Copies `path` to `backup_path`, or with `numbered` to the first numbered
+/// name after it that is free, and flushes the copy to disk so the backup is
+/// safe before the original is replaced.
+fn write_backup(path: &Path, backup_path: &Path, numbered: bool) -> io::Result<()> {
+    let mut original = File::open(path)?;
+    let mut options = OpenOptions::new();
+    options.write(true);
+    let mut backup = if numbered {
+        options.create_new(true);
+        let mut candidate = backup_path.to_path_buf();
+        let mut n = 0;
+        loop {
+            match options.open(&candidate) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1;
+                    let mut name = backup_path.as_os_str().to_os_string();
+                    name.push(format!(".{}", n));
+                    candidate = PathBuf::from(name);
+                }
+                opened => break opened?,
+            }
+        }
+    } else {
+        options.create(true).truncate(true).open(backup_path)?
+    };
+    io::copy(&mut original, &mut backup)?;
+    backup.set_permissions(original.metadata()?.permissions())?;
+    backup.sync_all()
+}
+
+/// A hidden name next to `path`, e.g. `dir/.out.json.1234.tmp`.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map_or_else(|| "output".into(), |name| name.to_string_lossy());
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
@@ -0,0 +1,2454 @@
+// tests/cli_tests.rs
+
+#[cfg(test)]
+mod tests {
+    use assert_cmd::Command;
+    use predicates::prelude::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn jqr() -> Command {
+        Command::cargo_bin("jqr").unwrap()
+    }
+
+    /// A fresh, empty scratch directory for tests that write files.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jqr-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_default_on_missing_value() {
+        jqr()
+            .args(["data/user.json", "$.user.phone", "--default", "\"n/a\""])
+            .assert()
+            .success()
+            .stdout("\"n/a\"\n");
+
+        jqr()
+            .args(["data/user.json", "$.user.age", "--default", "0"])
+            .assert()
+            .success()
+            .stdout("30\n");
+    }
+
+    #[test]
+    fn test_no_results_empty_exit_status() {
+        jqr()
+            .args(["data/user.json", "$.user.phone", "--no-results-empty"])
+            .assert()
+            .code(1)
+            .stdout("");
+    }
+
+    #[test]
+    fn test_equal_exit_status() {
+        jqr()
+            .args(["data/user.json", "$.user.name", "--equal", "\"Alice\""])
+            .assert()
+            .success();
+
+        jqr()
+            .args(["data/user.json", "$.user.name", "--equal", "\"Bob\""])
+            .assert()
+            .code(1)
+            .stderr(predicate::str::contains(
+                "expected \"Bob\", found \"Alice\"",
+            ));
+
+        // Several results are not compared by the first alone
+        jqr()
+            .args([
+                "data/users.json",
+                "--jq",
+                ".users[].name",
+                "--equal",
+                "\"Alice\"",
+            ])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "--equal needs a single result, but the query gave 3",
+            ));
+    }
+
+    #[test]
+    fn test_json_seq_query_each_record() {
+        jqr()
+            .args(["-q", "$.id", "--from-json-seq", "--to-json-seq"])
+            .write_stdin("\u{1e}{\"id\": 1}\n\u{1e}{\"id\": 2}\n")
+            .assert()
+            .success()
+            .stdout("\u{1e}1\n\u{1e}2\n");
+    }
+
+    #[test]
+    fn test_auto_query_routes_by_syntax() {
+        for query in [
+            "$.users[1].name",
+            "/users/1/name",
+            ".users[1].name",
+            "users[1].name",
+        ] {
+            jqr()
+                .args(["data/users.json", query, "--auto-query"])
+                .assert()
+                .success()
+                .stdout("\"Bob\"\n");
+        }
+
+        jqr()
+            .args(["data/users.json", "#users", "--auto-query"])
+            .assert()
+            .stderr(predicate::str::contains("Cannot detect the syntax"));
+    }
+
+    #[test]
+    fn test_dir_maps_file_names_to_results() {
+        jqr()
+            .args(["--dir", "data/configs", "-q", "$.version"])
+            .assert()
+            .success()
+            .stdout("{\n  \"api.json\": \"1.2.0\",\n  \"web.json\": \"2.0.1\"\n}\n")
+            .stderr(predicate::str::contains("Skipping broken.json"));
+
+        jqr()
+            .args(["--dir", "data/configs", "-q", "$.version", "--recursive"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "\"nested/worker.json\": \"0.9.3\"",
+            ));
+    }
+
+    #[test]
+    fn test_max_depth_guard_reports_clean_error() {
+        let deep = format!("{}{}", "{\"a\":".repeat(100_000), "}".repeat(100_000));
+
+        jqr()
+            .write_stdin(deep)
+            .args(["-q", "$.a"])
+            .assert()
+            .stderr(predicate::str::contains(
+                "Maximum nesting depth of 128 exceeded",
+            ));
+
+        jqr()
+            .write_stdin("[[[1]]]")
+            .args(["-q", "$[0][0][0]", "--max-depth-guard", "2"])
+            .assert()
+            .stderr(predicate::str::contains("line 1, column 3"));
+    }
+
+    #[test]
+    fn test_plain_formatting_streams_input() {
+        jqr()
+            .args(["--indent", "4", "--preserve-order"])
+            .write_stdin("{\"b\": [1], \"a\": null}")
+            .assert()
+            .success()
+            .stdout("{\n    \"b\": [\n        1\n    ],\n    \"a\": null\n}\n");
+
+        jqr()
+            .write_stdin("{\"a\": [1,}")
+            .assert()
+            .stderr(predicate::str::contains("Invalid JSON"));
+
+        // By default keys are sorted one element of a top-level array at a
+        // time, so the records before a malformed one are already written
+        jqr()
+            .write_stdin("[{\"b\": 1, \"a\": [2]}, {\"d\": 3, \"c\": 4}]")
+            .assert()
+            .success()
+            .stdout("[\n  {\n    \"a\": [\n      2\n    ],\n    \"b\": 1\n  },\n  {\n    \"c\": 4,\n    \"d\": 3\n  }\n]\n");
+
+        jqr()
+            .write_stdin("[{\"b\": 1, \"a\": 2}, {\"a\": }")
+            .assert()
+            .code(2)
+            .stdout(predicate::str::starts_with(
+                "[\n  {\n    \"a\": 2,\n    \"b\": 1\n  }",
+            ))
+            .stderr(predicate::str::contains("Invalid JSON"));
+    }
+
+    #[test]
+    fn test_preserve_order() {
+        let input = "{\"name\": \"api\", \"port\": 80, \"env\": {\"z\": 1, \"a\": 2}}";
+
+        jqr()
+            .arg("--compact")
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"env\":{\"a\":2,\"z\":1},\"name\":\"api\",\"port\":80}\n");
+
+        jqr()
+            .args(["--compact", "--preserve-order", "-q", "$.env"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"z\":1,\"a\":2}\n");
+
+        jqr()
+            .args(["--to-properties", "--preserve-order"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("name=api\nport=80\nenv.z=1\nenv.a=2\n");
+
+        jqr()
+            .args(["--to-properties"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("env.a=2\nenv.z=1\nname=api\nport=80\n");
+
+        // --sort-keys takes precedence
+        jqr()
+            .args(["--preserve-order", "--sort-keys", "-c"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"env\":{\"a\":2,\"z\":1},\"name\":\"api\",\"port\":80}\n");
+    }
+
+    #[test]
+    fn test_compact_short_flag() {
+        jqr()
+            .args(["-c", "-q", "$.users[0]"])
+            .write_stdin("{\"users\": [{\"name\": \"Alice\", \"age\": 30}]}")
+            .assert()
+            .success()
+            .stdout("{\"age\":30,\"name\":\"Alice\"}\n");
+    }
+
+    #[test]
+    fn test_raw_output() {
+        let input = "{\"name\": \"Alice \\\"Al\\\"\", \"tags\": [\"a\", \"b c\"], \"age\": 30}";
+
+        jqr()
+            .args(["-r", "-q", "$.name"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("Alice \"Al\"\n");
+
+        jqr()
+            .args(["--raw-output", "-q", "$.tags"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("a\nb c\n");
+
+        jqr()
+            .args(["-r", "-q", "$.age"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("30\n");
+    }
+
+    #[test]
+    fn test_sort_keys_short_flag() {
+        let input = "{\"b\": {\"d\": 1, \"c\": 2}, \"a\": 3}";
+        jqr()
+            .args(["--preserve-order", "--to-yaml"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("b:\n  d: 1\n  c: 2\na: 3\n");
+
+        // -S takes precedence over --preserve-order
+        jqr()
+            .args(["-S", "--preserve-order", "--to-yaml"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("a: 3\nb:\n  c: 2\n  d: 1\n");
+        jqr()
+            .args(["-S", "--preserve-order", "-c"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"a\":3,\"b\":{\"c\":2,\"d\":1}}\n");
+    }
+
+    #[test]
+    fn test_color_modes() {
+        let red = "\u{1b}[31m";
+
+        // Piped stderr is never colored automatically
+        jqr().write_stdin("{\"a\": [1,}").assert().stderr(
+            predicate::str::contains("Invalid JSON").and(predicate::str::contains(red).not()),
+        );
+
+        jqr()
+            .args(["--color", "always"])
+            .env("NO_COLOR", "1")
+            .write_stdin("{\"a\": [1,}")
+            .assert()
+            .stderr(predicate::str::contains(red));
+
+        jqr()
+            .args(["--dual", "--color", "never"])
+            .write_stdin("{\"a\": 1}")
+            .assert()
+            .success()
+            .stdout("── JSON ──\n{\n  \"a\": 1\n}\n── YAML ──\na: 1\n");
+    }
+
+    #[test]
+    fn test_highlighting() {
+        let output = jqr()
+            .args(["--color", "always", "-c"])
+            .write_stdin("{\"a\": [\"x\\\"\", -1.5e3, true, null]}")
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "{\u{1b}[1;34m\"a\"\u{1b}[0m:[\u{1b}[32m\"x\\\"\"\u{1b}[0m,\u{1b}[36m-1.5e+3\u{1b}[0m,\
+             \u{1b}[33mtrue\u{1b}[0m,\u{1b}[90mnull\u{1b}[0m]}\n"
+        );
+
+        jqr()
+            .args(["-c"])
+            .write_stdin("{\"a\": true}")
+            .assert()
+            .stdout("{\"a\":true}\n");
+    }
+
+    #[test]
+    fn test_output_file() {
+        let dir = scratch_dir("output");
+        let target = dir.join("out.json");
+
+        jqr()
+            .args(["-c", "-o", target.to_str().unwrap()])
+            .write_stdin("{\"b\": 1, \"a\": [true]}")
+            .assert()
+            .success()
+            .stdout("");
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "{\"a\":[true],\"b\":1}\n"
+        );
+
+        // A failed run leaves the previous output in place and no temporary file behind
+        jqr()
+            .args(["-o", target.to_str().unwrap()])
+            .write_stdin("{\"a\": [1,}")
+            .assert()
+            .stderr(predicate::str::contains("Invalid JSON"));
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "{\"a\":[true],\"b\":1}\n"
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_in_place() {
+        let dir = scratch_dir("in-place");
+        let target = dir.join("config.json");
+        let original = "{\"port\": 80, \"host\": \"a\"}";
+        fs::write(&target, original).unwrap();
+
+        jqr()
+            .args([target.to_str().unwrap(), "-i", "--backup", "--compact"])
+            .assert()
+            .success()
+            .stdout("");
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "{\"host\":\"a\",\"port\":80}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("config.json.bak")).unwrap(),
+            original
+        );
+
+        jqr()
+            .args([
+                target.to_str().unwrap(),
+                "$.port",
+                "--in-place",
+                "--backup=.orig",
+            ])
+            .assert()
+            .success();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "80\n");
+        assert!(dir.join("config.json.orig").exists());
+
+        // A query without matches leaves the file alone
+        jqr()
+            .args([target.to_str().unwrap(), "$.missing", "-i"])
+            .assert()
+            .stderr(predicate::str::contains("was not modified"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "80\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        jqr().args(["-i"]).write_stdin("{}").assert().failure();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let input = "{\"enabled\": true, \"disabled\": false, \"none\": null}";
+
+        for (query, code) in [
+            ("$.enabled", 0),
+            ("$.disabled", 1),
+            ("$.none", 1),
+            ("$.missing", 1),
+        ] {
+            jqr()
+                .args(["-e", "-q", query])
+                .write_stdin(input)
+                .assert()
+                .code(code);
+        }
+
+        // Without -e only errors fail
+        jqr()
+            .args(["-q", "$.none"])
+            .write_stdin(input)
+            .assert()
+            .success();
+        jqr().write_stdin("{\"a\": [1,}").assert().code(2);
+        jqr().args(["-q", "$["]).write_stdin(input).assert().code(2);
+    }
+
+    #[test]
+    fn test_ascii_output() {
+        for args in [vec!["-a", "-c"], vec!["--ascii-output", "-c", "-q", "$"]] {
+            jqr()
+                .args(args)
+                .write_stdin("{\"café\": \"naïve 😀\"}")
+                .assert()
+                .success()
+                .stdout("{\"caf\\u00e9\":\"na\\u00efve \\ud83d\\ude00\"}\n");
+        }
+    }
+
+    #[test]
+    fn test_width() {
+        jqr()
+            .args(["--width", "80", "--preserve-order"])
+            .write_stdin("{\"ids\": [1, 2, 3], \"user\": {\"name\": \"Alice\"}}")
+            .assert()
+            .success()
+            .stdout("{\n  \"ids\": [1, 2, 3],\n  \"user\": {\"name\": \"Alice\"}\n}\n");
+
+        jqr()
+            .args(["--width", "80", "-c"])
+            .write_stdin("{}")
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let logs = "{\"level\": \"info\", \"msg\": \"start\"}\n\n{\"level\": \"error\", \"msg\": \"boom\"}\n";
+
+        // Detected automatically
+        jqr()
+            .args(["-r", "-q", "$.msg"])
+            .write_stdin(logs)
+            .assert()
+            .success()
+            .stdout("start\nboom\n");
+
+        jqr()
+            .args(["--ndjson", "-c"])
+            .write_stdin("[1]\n")
+            .assert()
+            .success()
+            .stdout("[1]\n");
+
+        // Records without a match are not reported one by one; a run
+        // without any result is reported once
+        jqr()
+            .args(["-c", "-q", "$.level"])
+            .write_stdin("{\"level\": 1}\n{\"msg\": 2}\n{\"msg\": 3}\n")
+            .assert()
+            .success()
+            .stdout("1\n")
+            .stderr("");
+
+        // Read line by line with --ndjson, and whole when detected
+        for ndjson in [true, false] {
+            jqr()
+                .args(["-c", "-q", "$.level"])
+                .args(ndjson.then_some("--ndjson"))
+                .write_stdin("{\"msg\": 1}\n{\"msg\": 2}\n")
+                .assert()
+                .success()
+                .stdout("")
+                .stderr("No results found\n");
+        }
+
+        jqr()
+            .args(["--jsonl"])
+            .write_stdin("{\"a\": 1}\n{\"a\": }\n")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("at line 2 column 7"));
+    }
+
+    #[test]
+    fn test_ndjson_streams_records() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::process::{Command, Stdio};
+
+        let mut child = Command::new(assert_cmd::cargo::cargo_bin("jqr"))
+            .args(["--ndjson", "-c", "-q", "$.n"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+        // Each record is printed while stdin is still open
+        let mut line = String::new();
+        for n in 1..=2 {
+            writeln!(stdin, "{{\"n\": {}}}", n).unwrap();
+            line.clear();
+            stdout.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("{}\n", n));
+        }
+        drop(stdin);
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn test_collect_errors() {
+        let dir = scratch_dir("collect-errors");
+        let events = dir.join("events.ndjson");
+        let broken = dir.join("broken.json");
+        fs::write(&events, "{\"n\": 1}\n{\"n\": }\n{\"n\": 3}\nnope\n").unwrap();
+        fs::write(&broken, "{\"n\":").unwrap();
+
+        // Good records are printed, the bad lines listed at the end
+        jqr()
+            .args(["--collect-errors", "-c", "-q", "$.n"])
+            .arg(&events)
+            .assert()
+            .code(2)
+            .stdout("1\n3\n")
+            .stderr(predicate::str::starts_with("2 errors:\n"))
+            .stderr(predicate::str::contains(
+                "events.ndjson: Invalid JSON: expected value at line 2",
+            ))
+            .stderr(predicate::str::contains(
+                "events.ndjson: Invalid JSON: expected ident at line 4",
+            ));
+
+        jqr()
+            .args(["--collect-errors", "-c", "-q", "$.n"])
+            .write_stdin(fs::read(&events).unwrap())
+            .assert()
+            .code(2)
+            .stdout("1\n3\n")
+            .stderr(predicate::str::contains("(standard input): Invalid JSON"));
+
+        jqr()
+            .args(["--collect-errors", "-c", "-q", "$.users[0].id"])
+            .arg(&broken)
+            .arg("data/users.json")
+            .assert()
+            .code(2)
+            .stdout("1\n")
+            .stderr(predicate::str::starts_with("1 error:\n"))
+            .stderr(predicate::str::contains("broken.json: Invalid JSON"));
+
+        // Without it, the first bad line fails the input
+        jqr()
+            .args(["-c", "-q", "$.n"])
+            .arg(&events)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("at line 2 column 7"))
+            .stderr(predicate::str::contains("line 4").not());
+    }
+
+    #[test]
+    fn test_slurp() {
+        jqr()
+            .args(["-s", "-c", "-q", "$[*].id"])
+            .write_stdin("{\"id\": 1}\n{\"id\": 2}{\"id\": 3} 4")
+            .assert()
+            .success()
+            .stdout("[1,2,3]\n");
+
+        jqr()
+            .args(["--slurp", "--from-json-seq", "-c"])
+            .write_stdin("\u{1e}1\n\u{1e}\"two\"\n")
+            .assert()
+            .success()
+            .stdout("[1,\"two\"]\n");
+
+        jqr()
+            .args(["-s", "-c"])
+            .write_stdin("")
+            .assert()
+            .success()
+            .stdout("[]\n");
+    }
+
+    #[test]
+    fn test_null_input() {
+        jqr().arg("-n").assert().success().stdout("null\n");
+
+        jqr()
+            .args(["--null-input", "$.missing", "--default", "{\"a\": 1}", "-c"])
+            .assert()
+            .success()
+            .stdout("{\"a\":1}\n");
+
+        jqr()
+            .args(["-n", "data/user.json", "$.user"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("--null-input reads no input"));
+    }
+
+    #[test]
+    fn test_raw_input() {
+        jqr()
+            .args(["-R", "-c"])
+            .write_stdin("GET /index \"ok\"\r\n{\"not\": \"parsed\"}\n")
+            .assert()
+            .success()
+            .stdout("\"GET /index \\\"ok\\\"\"\n\"{\\\"not\\\": \\\"parsed\\\"}\"\n");
+
+        jqr()
+            .args(["--raw-input", "--slurp"])
+            .write_stdin("one\ntwo\n")
+            .assert()
+            .success()
+            .stdout("\"one\\ntwo\\n\"\n");
+    }
+
+    #[test]
+    fn test_multiple_files() {
+        let dir = scratch_dir("multiple_files");
+        let first = dir.join("first.json");
+        let second = dir.join("second.json");
+        fs::write(&first, r#"{"name": "a"}"#).unwrap();
+        fs::write(&second, r#"{"name": "b"}"#).unwrap();
+        let first = first.to_str().unwrap();
+        let second = second.to_str().unwrap();
+
+        jqr()
+            .args(["-q", "$.name", first, second])
+            .assert()
+            .success()
+            .stdout("\"a\"\n\"b\"\n");
+
+        jqr()
+            .args([first, "$", second, "-c", "--with-filename"])
+            .assert()
+            .success()
+            .stdout(format!(
+                "{}:{{\"name\":\"a\"}}\n{}:{{\"name\":\"b\"}}\n",
+                first, second
+            ));
+
+        // A missing file is reported and the others are still queried
+        jqr()
+            .args(["-H", "-q", "$.name", first, "missing.json"])
+            .assert()
+            .code(2)
+            .stdout(format!("{}:\"a\"\n", first))
+            .stderr(predicate::str::contains("missing.json: "));
+
+        jqr()
+            .args(["-q", "$", first, second, "--in-place"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "--in-place takes a single input file",
+            ));
+    }
+
+    #[test]
+    fn test_glob_input() {
+        jqr()
+            .args(["--glob", "data/configs/**/*.json", "$.version", "-H"])
+            .assert()
+            .code(2)
+            .stdout(
+                "data/configs/api.json:\"1.2.0\"\n\
+                 data/configs/nested/worker.json:\"0.9.3\"\n\
+                 data/configs/web.json:\"2.0.1\"\n",
+            )
+            .stderr(predicate::str::contains("data/configs/broken.json: "));
+
+        jqr()
+            .args(["--glob", "data/nowhere/*.json", "$"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("No JSON files match"));
+    }
+
+    #[test]
+    fn test_compressed_input() {
+        for path in [
+            "data/users.json.gz",
+            "data/users.json.zst",
+            "data/users.json.bz2",
+        ] {
+            jqr()
+                .args([path, "$.users[0].name"])
+                .assert()
+                .success()
+                .stdout("\"Alice\"\n");
+        }
+
+        // Detected by content, so piped input is decompressed as well
+        jqr()
+            .args(["-q", "$.users[1].name"])
+            .write_stdin(fs::read("data/users.json.gz").unwrap())
+            .assert()
+            .success()
+            .stdout("\"Bob\"\n");
+    }
+
+    #[test]
+    fn test_compressed_ndjson() {
+        jqr()
+            .args(["data/events.ndjson.gz", "-r", "-q", "$.msg"])
+            .assert()
+            .success()
+            .stdout("start\nboom\ndone\n");
+
+        jqr()
+            .args(["--ndjson", "-c", "-q", "$.level"])
+            .write_stdin(fs::read("data/events.ndjson.gz").unwrap())
+            .assert()
+            .success()
+            .stdout("\"info\"\n\"error\"\n\"info\"\n");
+
+        // A corrupt stream is a read error, not a JSON one
+        let dir = scratch_dir("compressed-ndjson");
+        let truncated = dir.join("events.ndjson.gz");
+        fs::write(
+            &truncated,
+            &fs::read("data/events.ndjson.gz").unwrap()[..40],
+        )
+        .unwrap();
+        jqr()
+            .arg(&truncated)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::starts_with("Error reading input:"));
+    }
+
+    #[cfg(not(feature = "http"))]
+    #[test]
+    fn test_url_input_requires_http_feature() {
+        jqr()
+            .args(["http://127.0.0.1:9/users.json", "$"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("built without the http feature"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_url_input() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        // Answers one request with the Authorization header it received
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/users", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut auth = String::new();
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(": ") {
+                    if name.eq_ignore_ascii_case("authorization") {
+                        auth = value.to_string();
+                    }
+                }
+            }
+            let body = format!(r#"{{"auth": "{}"}}"#, auth);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        jqr()
+            .args([url.as_str(), "$.auth", "--bearer", "secret"])
+            .assert()
+            .success()
+            .stdout("\"Bearer secret\"\n");
+        server.join().unwrap();
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    #[test]
+    fn test_clipboard_requires_clipboard_feature() {
+        jqr()
+            .args(["--clipboard-in", "$.user"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "built without the clipboard feature",
+            ));
+
+        jqr()
+            .args(["data/user.json", "--clipboard-out"])
+            .assert()
+            .code(2)
+            .stdout("")
+            .stderr(predicate::str::contains(
+                "built without the clipboard feature",
+            ));
+    }
+
+    #[test]
+    fn test_json_seq_detected_and_written_with_seq() {
+        jqr()
+            .args(["-q", "$.id", "--seq"])
+            .write_stdin("\u{1e}{\"id\": 1}\n\u{1e}{\"id\": 2}\n")
+            .assert()
+            .success()
+            .stdout("\u{1e}1\n\u{1e}2\n");
+    }
+
+    #[test]
+    fn test_toml_conversion() {
+        jqr()
+            .args(["--from-toml", "-q", "$.db", "--to-toml"])
+            .write_stdin("title = \"app\"\n\n[db]\nport = 5432\nhost = \"localhost\"\n")
+            .assert()
+            .success()
+            .stdout("host = \"localhost\"\nport = 5432\n");
+
+        jqr()
+            .args(["--to-toml"])
+            .write_stdin(r#"{"db": {"password": null}}"#)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "TOML has no null value (at $.db.password)",
+            ));
+    }
+
+    #[test]
+    fn test_xml_conversion() {
+        let xml = r#"<catalog><book id="1"><title>Dune</title></book></catalog>"#;
+
+        jqr()
+            .args([
+                "--from-xml",
+                "-q",
+                "$.catalog.book[0].title",
+                "--xml-array",
+                "book",
+            ])
+            .write_stdin(xml)
+            .assert()
+            .success()
+            .stdout("\"Dune\"\n");
+
+        jqr()
+            .args(["--from-xml", "--to-xml", "--xml-attribute-prefix", "_"])
+            .write_stdin(xml)
+            .assert()
+            .success()
+            .stdout(
+                "<catalog>\n  <book id=\"1\">\n    <title>Dune</title>\n  </book>\n</catalog>\n",
+            );
+
+        jqr()
+            .args(["--from-xml"])
+            .write_stdin("<catalog><book></catalog>")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid XML"));
+    }
+
+    #[test]
+    fn test_csv_input() {
+        let csv = "name,age,admin\nAlice,30,true\nBob,,false\n";
+
+        jqr()
+            .args([
+                "--from-csv",
+                "--infer-types",
+                "-q",
+                "$[?(@.admin == true)]",
+                "-c",
+            ])
+            .write_stdin(csv)
+            .assert()
+            .success()
+            .stdout("{\"admin\":true,\"age\":30,\"name\":\"Alice\"}\n");
+
+        jqr()
+            .args(["--from-tsv", "-q", "$[1]", "-c", "--preserve-order"])
+            .write_stdin("name\tage\nAlice\t30\nBob\t\n")
+            .assert()
+            .success()
+            .stdout("{\"name\":\"Bob\",\"age\":\"\"}\n");
+    }
+
+    #[test]
+    fn test_csv_output() {
+        let rows = r#"[{"name": "Alice", "address": {"city": "Oslo"}}, {"name": "Bob", "address": {"city": null}}]"#;
+
+        jqr()
+            .args(["--to-csv", "--preserve-order"])
+            .write_stdin(rows)
+            .assert()
+            .success()
+            .stdout("name,address.city\nAlice,Oslo\nBob,\n");
+
+        jqr()
+            .args(["--to-tsv", "--column", "address.city", "--column", "name"])
+            .write_stdin(rows)
+            .assert()
+            .success()
+            .stdout("address.city\tname\nOslo\tAlice\n\tBob\n");
+
+        jqr()
+            .args(["--to-csv"])
+            .write_stdin("[1, 2]")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("CSV output requires"));
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let encoded = jqr()
+            .args(["--to-msgpack", "--preserve-order"])
+            .write_stdin(r#"{"id": 1, "tags": ["a"]}"#)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        assert_eq!(encoded, b"\x82\xa2id\x01\xa4tags\x91\xa1a");
+
+        // Two concatenated values, the second with binary data
+        let mut input = encoded;
+        input.extend_from_slice(b"\x81\xa2id\xc4\x02\x00\xff");
+        jqr()
+            .args(["--from-msgpack", "-c"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"id\":1,\"tags\":[\"a\"]}\n{\"id\":\"AP8=\"}\n");
+
+        jqr()
+            .args(["--from-msgpack"])
+            .write_stdin(&b"\x82\xa2id"[..])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid MessagePack"));
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let encoded = jqr()
+            .args(["--to-cbor", "--preserve-order"])
+            .write_stdin(r#"{"id": 1, "tags": ["a"]}"#)
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        assert_eq!(encoded, b"\xa2\x62id\x01\x64tags\x81\x61a");
+
+        // A COSE-style tagged item wrapping a byte string
+        let mut input = encoded;
+        input.extend_from_slice(b"\xd8\x12\x42\x00\xff");
+        jqr()
+            .args(["--from-cbor", "-c"])
+            .write_stdin(input)
+            .assert()
+            .success()
+            .stdout("{\"id\":1,\"tags\":[\"a\"]}\n\"AP8=\"\n");
+
+        jqr()
+            .args(["--from-cbor"])
+            .write_stdin(&b"\xa2\x62id"[..])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid CBOR"));
+    }
+
+    #[test]
+    fn test_bson_input() {
+        // Two documents as mongodump writes them, the second with a date
+        let mut dump = b"\x1d\x00\x00\x00\
+            \x07_id\x00\x65\xa1\xb2\xc3\xd4\xe5\xf6\x07\x18\x29\x3a\x4b\
+            \x10n\x00\x01\x00\x00\x00\x00"
+            .to_vec();
+        dump.extend_from_slice(b"\x11\x00\x00\x00\x09at\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+
+        jqr()
+            .args(["--from-bson", "-c"])
+            .write_stdin(dump)
+            .assert()
+            .success()
+            .stdout(
+                "{\"_id\":{\"$oid\":\"65a1b2c3d4e5f60718293a4b\"},\"n\":1}\n\
+                 {\"at\":{\"$date\":\"1970-01-01T00:00:00Z\"}}\n",
+            );
+
+        jqr()
+            .args(["--from-bson"])
+            .write_stdin(&b"\x1d\x00\x00\x00\x07"[..])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid BSON"));
+    }
+
+    #[test]
+    #[cfg(feature = "parquet")]
+    fn test_parquet_and_arrow_input() {
+        jqr()
+            .args([
+                "data/users.parquet",
+                "--from-parquet",
+                "-c",
+                "--preserve-order",
+            ])
+            .assert()
+            .success()
+            .stdout(concat!(
+                r#"[{"id":1,"name":"Alice","email":"alice@example.com"},"#,
+                r#"{"id":2,"name":"Bob","email":null},"#,
+                r#"{"id":3,"name":"Charlie","email":"charlie@example.com"}]"#,
+                "\n"
+            ));
+
+        jqr()
+            .args([
+                "-q",
+                "$.name",
+                "data/users.arrow",
+                "--from-parquet",
+                "--ndjson",
+            ])
+            .assert()
+            .success()
+            .stdout("\"Alice\"\n\"Bob\"\n\"Charlie\"\n");
+
+        jqr()
+            .args(["data/users.json", "--from-parquet"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("not a Parquet file"));
+    }
+
+    #[test]
+    fn test_avro_input() {
+        jqr()
+            .args(["-q", "$.email", "data/users.avro", "--from-avro", "-c"])
+            .assert()
+            .success()
+            .stdout("\"alice@example.com\"\nnull\n\"charlie@example.com\"\n");
+
+        let dir = scratch_dir("avro");
+        let schema = dir.join("user.avsc");
+        fs::write(
+            &schema,
+            r#"{"type": "record", "name": "User", "fields": [{"name": "id", "type": "long"}]}"#,
+        )
+        .unwrap();
+        jqr()
+            .args([
+                "--from-avro",
+                "--avro-schema",
+                schema.to_str().unwrap(),
+                "-c",
+            ])
+            .write_stdin(&b"\x02\x04"[..])
+            .assert()
+            .success()
+            .stdout("{\"id\":1}\n{\"id\":2}\n");
+
+        jqr()
+            .args(["data/users.json", "--from-avro"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid Avro"));
+    }
+
+    #[test]
+    fn test_protobuf_input() {
+        let proto = [
+            "data/order.bin",
+            "--from-proto",
+            "--descriptor",
+            "data/order.pb",
+            "--message-type",
+        ];
+
+        jqr()
+            .args(proto)
+            .args(["shop.Order", "-c", "--preserve-order"])
+            .assert()
+            .success()
+            .stdout(concat!(
+                r#"{"orderId":"1001","customer":"Alice","#,
+                r#""items":[{"sku":"A-1","quantity":2},{"sku":"B-7","quantity":1}]}"#,
+                "\n"
+            ));
+
+        jqr()
+            .args(proto)
+            .arg("shop.Invoice")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "The descriptor set has no message type 'shop.Invoice'",
+            ));
+    }
+
+    #[test]
+    fn test_query_yaml_input() {
+        let manifest = "spec:\n  containers:\n    - name: web\n      image: nginx:1.27\n    - name: cache\n      image: redis:7\n";
+
+        jqr()
+            .args(["--yaml", "-q", "$.spec.containers[*].image", "-c"])
+            .write_stdin(manifest)
+            .assert()
+            .success()
+            .stdout("[\"nginx:1.27\",\"redis:7\"]\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$.spec.containers[0]", "--to-yaml"])
+            .write_stdin(manifest)
+            .assert()
+            .success()
+            .stdout("image: nginx:1.27\nname: web\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$.spec"])
+            .write_stdin("spec: [nginx")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid YAML"));
+    }
+
+    #[test]
+    fn test_multi_document_yaml() {
+        let manifests = "---\nkind: Service\n---\nkind: Deployment\n";
+
+        jqr()
+            .args(["--yaml", "-q", "$.kind"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("\"Service\"\n\"Deployment\"\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$[*].kind", "--slurp", "-c"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("[\"Service\",\"Deployment\"]\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$.kind", "--document", "1"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("\"Deployment\"\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$.kind", "--document", "2"])
+            .write_stdin(manifests)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("the input has 2 YAML documents"));
+
+        jqr()
+            .args(["--to-json", "-c"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("[{\"kind\":\"Service\"},{\"kind\":\"Deployment\"}]\n");
+    }
+
+    #[test]
+    fn test_yaml_aliases_and_merge_keys() {
+        let config = "base: &base {retries: 3}\nprod:\n  <<: *base\n  host: db\n";
+
+        jqr()
+            .args(["--yaml", "-q", "$.prod", "-c"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"host\":\"db\",\"retries\":3}\n");
+
+        jqr()
+            .args(["--yaml", "-q", "$.prod", "-c", "--keep-merge-keys"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"<<\":{\"retries\":3},\"host\":\"db\"}\n");
+
+        jqr()
+            .args(["--to-json", "-c", "--keep-aliases"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"base\":{\"retries\":3},\"prod\":{\"<<\":\"*base\",\"host\":\"db\"}}\n");
+
+        let bomb = "a: &a [x, x, x, x, x, x, x, x, x, x]\n\
+                    b: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a, *a]\n\
+                    c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b, *b]\n";
+
+        jqr()
+            .args(["--yaml", "-q", "$.c[0][0][0]", "--alias-limit", "1000"])
+            .write_stdin(bomb)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "aliases expand to more than 1000 nodes (at line 3 column 40)",
+            ));
+    }
+
+    #[test]
+    fn test_yaml_out() {
+        let manifests =
+            "kind: Service\nspec:\n  ports: [{port: 80, name: http}]\n---\nkind: Deployment\n";
+
+        jqr()
+            .args(["--yaml-out"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("kind: Service\nspec:\n  ports:\n  - name: http\n    port: 80\n---\nkind: Deployment\n");
+
+        jqr()
+            .args([
+                "--yaml-out",
+                "--indent",
+                "4",
+                "--preserve-order",
+                "--document",
+                "0",
+            ])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("kind: Service\nspec:\n    ports:\n    -   port: 80\n        name: http\n");
+
+        jqr()
+            .args(["--yaml-out", "-q", "$.kind"])
+            .write_stdin(manifests)
+            .assert()
+            .success()
+            .stdout("Service\n---\nDeployment\n");
+    }
+
+    #[test]
+    fn test_json5_input() {
+        let config = "{\n  // listen on all interfaces\n  host: '0.0.0.0',\n  port: 0x1F90,\n  tags: ['a', 'b',],\n}\n";
+
+        jqr()
+            .args(["--json5", "-c"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"host\":\"0.0.0.0\",\"port\":8080,\"tags\":[\"a\",\"b\"]}\n");
+
+        // A .json5 file is read as JSON5 without the flag
+        let dir = scratch_dir("json5");
+        let path = dir.join("server.json5");
+        fs::write(&path, config).unwrap();
+
+        jqr()
+            .args([path.to_str().unwrap(), "$.port"])
+            .assert()
+            .success()
+            .stdout("8080\n");
+
+        jqr()
+            .args(["--json5"])
+            .write_stdin("{ratio: NaN}")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Invalid JSON5: JSON has no NaN value",
+            ));
+    }
+
+    #[test]
+    fn test_jsonc_input() {
+        let settings = "{\n  // editor\n  \"editor.tabSize\": 2, /* was 4 */\n  \"files.exclude\": [\"dist\",],\n}\n";
+
+        jqr()
+            .args(["--jsonc", "-q", "$['editor.tabSize']"])
+            .write_stdin(settings)
+            .assert()
+            .success()
+            .stdout("2\n");
+
+        // A .jsonc file is read as JSONC without the flag, also when reformatted
+        let dir = scratch_dir("jsonc");
+        let path = dir.join("settings.jsonc");
+        fs::write(&path, settings).unwrap();
+
+        jqr()
+            .args([path.to_str().unwrap(), "-c"])
+            .assert()
+            .success()
+            .stdout("{\"editor.tabSize\":2,\"files.exclude\":[\"dist\"]}\n");
+
+        jqr()
+            .args(["--jsonc"])
+            .write_stdin("{\n  // unquoted\n  tabSize: 2\n}")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "key must be a string at line 3 column 3",
+            ));
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let settings = "{\n  // Wrap long lines\n  \"editor.wordWrap\": \"on\", // or \"off\"\n  \"editor.tabSize\": 4,\n}\n";
+
+        jqr()
+            .args(["--jsonc", "--keep-comments"])
+            .write_stdin(settings)
+            .assert()
+            .success()
+            .stdout("{\n  \"editor.tabSize\": 4,\n  // Wrap long lines\n  \"editor.wordWrap\": \"on\" // or \"off\"\n}\n");
+
+        // Reformatting a .jsonc file in place keeps its comments
+        let dir = scratch_dir("keep-comments");
+        let path = dir.join("settings.jsonc");
+        fs::write(&path, settings).unwrap();
+
+        jqr()
+            .args([
+                "-i",
+                path.to_str().unwrap(),
+                "--keep-comments",
+                "--preserve-order",
+            ])
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\n  // Wrap long lines\n  \"editor.wordWrap\": \"on\", // or \"off\"\n  \"editor.tabSize\": 4\n}\n"
+        );
+
+        jqr()
+            .args(["--keep-comments"])
+            .write_stdin(settings)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "--keep-comments needs JSONC input",
+            ));
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let config = "{\n  \"port\": 80,\n  \"port\": 8080\n}";
+
+        jqr()
+            .args(["-c"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"port\":8080}\n");
+
+        jqr()
+            .args(["-c", "--duplicate-keys", "first"])
+            .write_stdin(config)
+            .assert()
+            .success()
+            .stdout("{\"port\":80}\n");
+
+        jqr()
+            .args(["-q", "$.port", "--duplicate-keys", "error"])
+            .write_stdin(config)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Duplicate key 'port' at line 3 column 8",
+            ));
+    }
+
+    #[test]
+    fn test_strict_and_lenient_profiles() {
+        jqr()
+            .args(["-c"])
+            .write_stdin("\u{feff}{\"a\": 1}")
+            .assert()
+            .success()
+            .stdout("{\"a\":1}\n");
+
+        jqr()
+            .args(["--strict"])
+            .write_stdin("\u{feff}{\"a\": 1}")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "byte order mark is not allowed at line 1 column 1",
+            ));
+
+        jqr()
+            .args(["--strict"])
+            .write_stdin("{\"a\": 1, \"a\": 2}")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Duplicate key 'a' at line 1 column 12",
+            ));
+
+        jqr()
+            .args(["--lenient", "-c"])
+            .write_stdin("{'ratio': NaN, // unknown\n 'tags': ['a',],}")
+            .assert()
+            .success()
+            .stdout("{\"ratio\":null,\"tags\":[\"a\"]}\n");
+
+        jqr()
+            .args(["--strict", "--lenient"])
+            .write_stdin("{}")
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_input_format_detection() {
+        let dir = scratch_dir("input-format");
+        let yaml = dir.join("service.yml");
+        fs::write(&yaml, "name: api\nport: 8080\n").unwrap();
+
+        jqr()
+            .args([yaml.to_str().unwrap(), "$.port"])
+            .assert()
+            .success()
+            .stdout("8080\n");
+
+        jqr()
+            .args(["-c"])
+            .write_stdin("[package]\nname = \"jqr\"\n")
+            .assert()
+            .success()
+            .stdout("{\"package\":{\"name\":\"jqr\"}}\n");
+
+        jqr()
+            .args(["-c"])
+            .write_stdin(vec![0x81, 0xa2, b'i', b'd', 0x07])
+            .assert()
+            .success()
+            .stdout("{\"id\":7}\n");
+
+        // A YAML file converts to YAML through the pipeline
+        jqr()
+            .args([yaml.to_str().unwrap(), "--to-yaml", "--preserve-order"])
+            .assert()
+            .success()
+            .stdout("name: api\nport: 8080\n");
+
+        // --input-format overrides detection
+        jqr()
+            .args([yaml.to_str().unwrap(), "--input-format", "json"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::starts_with(
+                "Error processing JSON: Invalid JSON",
+            ))
+            .stderr(predicate::str::contains(
+                "The input looks like YAML; read it with --yaml or --input-format yaml",
+            ));
+
+        jqr()
+            .args(["--input-format", "yaml", "--yaml"])
+            .write_stdin("a: 1")
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_jq_filter() {
+        let dir = scratch_dir("jq-filter");
+        let users = dir.join("users.json");
+        fs::write(
+            &users,
+            r#"{"users": [{"name": "Alice", "age": 31}, {"name": "Bob", "age": 25}]}"#,
+        )
+        .unwrap();
+
+        // Each result of the filter is printed as its own document
+        jqr()
+            .args([
+                users.to_str().unwrap(),
+                "--jq",
+                ".users[] | select(.age > 20) | {name}",
+                "-c",
+            ])
+            .assert()
+            .success()
+            .stdout("{\"name\":\"Alice\"}\n{\"name\":\"Bob\"}\n");
+
+        jqr()
+            .args(["--jq", ".[] * 2"])
+            .write_stdin("[1, 2]")
+            .assert()
+            .success()
+            .stdout("2\n4\n");
+
+        jqr()
+            .args(["--jq", ".users |"])
+            .write_stdin("{}")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid jq filter '.users |'"));
+
+        jqr()
+            .args(["--jq", ".a.b"])
+            .write_stdin(r#"{"a": 1}"#)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Cannot index number with \"b\""));
+    }
+
+    #[test]
+    fn test_jmespath() {
+        let instances = r#"{"Reservations": [{"Instances": [
+            {"InstanceId": "i-1", "State": {"Name": "running"}},
+            {"InstanceId": "i-2", "State": {"Name": "stopped"}}
+        ]}]}"#;
+
+        jqr()
+            .args([
+                "--jmespath",
+                "Reservations[].Instances[?State.Name == 'running'].InstanceId[]",
+                "-c",
+            ])
+            .write_stdin(instances)
+            .assert()
+            .success()
+            .stdout("[\"i-1\"]\n");
+
+        jqr()
+            .args(["--jmespath", "length(Reservations[0].Instances)"])
+            .write_stdin(instances)
+            .assert()
+            .success()
+            .stdout("2\n");
+
+        // A null result is no match, so the no-results policies apply
+        jqr()
+            .args(["--jmespath", "Reservations[0].Missing", "--default", "0"])
+            .write_stdin(instances)
+            .assert()
+            .success()
+            .stdout("0\n");
+
+        jqr()
+            .args(["--jmespath", "Reservations[?"])
+            .write_stdin(instances)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid JMESPath expression"));
+    }
+
+    #[test]
+    fn test_pointer() {
+        jqr()
+            .args(["data/users.json", "--pointer", "/users/1/name"])
+            .assert()
+            .success()
+            .stdout("\"Bob\"\n");
+
+        // A missing value goes through the no-results policies
+        jqr()
+            .args(["data/users.json", "--pointer", "/users/5/name"])
+            .assert()
+            .success()
+            .stdout("")
+            .stderr(predicate::str::contains("No results found"));
+
+        jqr()
+            .args([
+                "data/users.json",
+                "--pointer",
+                "/users/5",
+                "--no-results-empty",
+            ])
+            .assert()
+            .code(1)
+            .stdout("");
+
+        jqr()
+            .args(["data/users.json", "--pointer", "users/0"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid JSON Pointer 'users/0'"));
+    }
+
+    #[cfg(feature = "jsonata")]
+    #[test]
+    fn test_jsonata() {
+        jqr()
+            .args(["data/users.json", "--jsonata", "$count(users)"])
+            .assert()
+            .success()
+            .stdout("3\n");
+
+        jqr()
+            .args([
+                "data/users.json",
+                "--jsonata",
+                "users[id > 1].name",
+                "--compact",
+            ])
+            .assert()
+            .success()
+            .stdout("[\"Bob\",\"Charlie\"]\n");
+
+        // An undefined result goes through the no-results policies
+        jqr()
+            .args([
+                "data/users.json",
+                "--jsonata",
+                "users.phone",
+                "--no-results-empty",
+            ])
+            .assert()
+            .code(1)
+            .stdout("");
+
+        jqr()
+            .args(["data/users.json", "--jsonata", "$sum(users"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid JSONata expression"));
+    }
+
+    #[cfg(not(feature = "jsonata"))]
+    #[test]
+    fn test_jsonata_requires_jsonata_feature() {
+        jqr()
+            .args(["data/users.json", "--jsonata", "$count(users)"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "built without the jsonata feature",
+            ));
+    }
+
+    #[test]
+    fn test_jsonpath_dialect() {
+        jqr()
+            .args([
+                "data/users.json",
+                "--jsonpath-dialect",
+                "rfc9535",
+                "$.users[?@.id > 1 && length(@.name) == 3].name",
+            ])
+            .assert()
+            .success()
+            .stdout("\"Bob\"\n");
+
+        jqr()
+            .args([
+                "data/users.json",
+                "--jsonpath-dialect",
+                "rfc9535",
+                "$.users[01]",
+            ])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("invalid integer at column 9"));
+
+        jqr()
+            .args([
+                "data/users.json",
+                "--jsonpath-dialect",
+                "legacy",
+                "$.users[?(@.id == 1)].name",
+            ])
+            .assert()
+            .success()
+            .stdout("\"Alice\"\n");
+    }
+
+    #[test]
+    fn test_shorthand_query_without_dollar() {
+        for query in [".users[1].name", "users[1].name", "$.users[1].name"] {
+            jqr()
+                .args(["data/users.json", query])
+                .assert()
+                .success()
+                .stdout("\"Bob\"\n");
+        }
+
+        jqr()
+            .args(["data/users.json", "."])
+            .arg("--compact")
+            .assert()
+            .success()
+            .stdout(predicate::str::starts_with("{\"users\":"));
+
+        jqr()
+            .args(["data/users.json", "--query", "users[0].email"])
+            .assert()
+            .success()
+            .stdout("\"alice@example.com\"\n");
+    }
+
+    #[test]
+    fn test_labeled_queries() {
+        jqr()
+            .args([
+                "data/users.json",
+                "--query",
+                "id=$.users[0].id",
+                "--query",
+                "emails=$..email",
+                "--query",
+                "phone=users[0].phone",
+                "--compact",
+                "--preserve-order",
+            ])
+            .assert()
+            .success()
+            .stdout(
+                "{\"id\":1,\"emails\":[\"alice@example.com\",\"bob@example.com\",\"charlie@example.com\"],\"phone\":null}\n",
+            );
+
+        jqr()
+            .args([
+                "data/users.json",
+                "-q",
+                "id=$.users[0].id",
+                "-q",
+                "$..email",
+            ])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("needs a name=QUERY label"));
+
+        jqr()
+            .args([
+                "data/users.json",
+                "-q",
+                "a=$.users[0]",
+                "-q",
+                "a=$.users[1]",
+            ])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("label 'a' is used twice"));
+    }
+
+    #[test]
+    fn test_query_file() {
+        let dir = scratch_dir("query-file");
+        let jsonpath = dir.join("second.jsonpath");
+        fs::write(&jsonpath, "# The second user\n$.users\n  [1]\n  .name\n").unwrap();
+        let filter = dir.join("later.jq");
+        fs::write(
+            &filter,
+            "# Users after the first\n.users[]\n  | select(.id > 1) # not Alice\n  | .name\n",
+        )
+        .unwrap();
+
+        jqr()
+            .arg("data/users.json")
+            .arg("--query-file")
+            .arg(&jsonpath)
+            .assert()
+            .success()
+            .stdout("\"Bob\"\n");
+
+        jqr()
+            .arg("data/users.json")
+            .arg("--query-file")
+            .arg(&filter)
+            .assert()
+            .success()
+            .stdout("\"Bob\"\n\"Charlie\"\n");
+    }
+
+    #[test]
+    fn test_query_variables() {
+        jqr()
+            .args(["data/users.json", "--arg", "who", "Bob"])
+            .arg("$.users[?(@.name == $who)].email")
+            .assert()
+            .success()
+            .stdout("\"bob@example.com\"\n");
+
+        jqr()
+            .args(["data/users.json", "--argjson", "min", "2", "--compact"])
+            .arg("$.users[?(@.id >= $min)].name")
+            .assert()
+            .success()
+            .stdout("[\"Bob\",\"Charlie\"]\n");
+
+        jqr()
+            .args(["data/users.json", "--argjson", "id", "3", "--jq"])
+            .arg(".users[] | select(.id == $id) | .name")
+            .assert()
+            .success()
+            .stdout("\"Charlie\"\n");
+
+        jqr()
+            .args(["data/users.json", "--argjson", "min", "{", "$"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid --argjson value for min"));
+    }
+
+    #[test]
+    fn test_stream_count_matches_plain_query() {
+        // A count means the same with and without --stream-count, which
+        // --paths shows as one line per match
+        let cases = [
+            ("[1,2,3]", "$[*]"),
+            ("[{\"a\":[1,2]},{\"a\":[3]}]", "$[0].a[*]"),
+            ("[{\"a\":[1,2]},{\"a\":[3]}]", "$.*.a[*]"),
+            ("{\"users\":[1,2]}", "$.users[*]"),
+        ];
+        for (input, query) in cases {
+            let counted = jqr()
+                .args(["--stream-count", "-q", query])
+                .write_stdin(input)
+                .output()
+                .unwrap();
+            let found = jqr()
+                .args(["--paths", "-c", "-q", query])
+                .write_stdin(input)
+                .output()
+                .unwrap();
+            assert!(counted.status.success(), "{}", query);
+            assert_eq!(
+                String::from_utf8_lossy(&counted.stdout).trim(),
+                String::from_utf8_lossy(&found.stdout)
+                    .lines()
+                    .count()
+                    .to_string(),
+                "{}",
+                query
+            );
+        }
+
+        // A query that does not pick elements of a root array is refused
+        jqr()
+            .args(["--stream-count", "-q", "$.a"])
+            .write_stdin("[{\"a\":1}]")
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("starting with $[*], $.* or $[N]"));
+    }
+
+    #[test]
+    fn test_paths() {
+        jqr()
+            .args(["data/users.json", "$.users[1:]..name", "--paths", "--compact"])
+            .assert()
+            .success()
+            .stdout(
+                "{\"path\":\"$.users[1].name\",\"value\":\"Bob\"}\n{\"path\":\"$.users[2].name\",\"value\":\"Charlie\"}\n",
+            );
+
+        jqr()
+            .args([
+                "data/users.json",
+                "$.users[?@.id == 1].id",
+                "--paths",
+                "--compact",
+            ])
+            .args(["--jsonpath-dialect", "rfc9535"])
+            .assert()
+            .success()
+            .stdout("{\"path\":\"$.users[0].id\",\"value\":1}\n");
+
+        // Printed paths count from 1, the query itself still from 0
+        jqr()
+            .args(["data/users.json", "$.users[1].name", "--paths", "--compact"])
+            .args(["--index-base", "1"])
+            .assert()
+            .success()
+            .stdout("{\"path\":\"$.users[2].name\",\"value\":\"Bob\"}\n");
+
+        jqr()
+            .args(["data/users.json", "$.users", "--index-base", "1"])
+            .assert()
+            .code(2);
+
+        jqr()
+            .args(["data/users.json", "--jq", ".users", "--paths"])
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_agg() {
+        let cases = [
+            ("sum", "6"),
+            ("min", "1"),
+            ("max", "3"),
+            ("avg", "2"),
+            ("count", "3"),
+        ];
+        for (op, expected) in cases {
+            jqr()
+                .args(["data/users.json", "$.users[*].id", "--agg", op])
+                .assert()
+                .success()
+                .stdout(format!("{}\n", expected));
+        }
+
+        jqr()
+            .args(["data/users.json", "--jq", ".users[].id", "--agg", "sum"])
+            .assert()
+            .success()
+            .stdout("6\n");
+
+        jqr()
+            .args(["data/users.json", "$.missing[*]", "--agg", "count"])
+            .assert()
+            .success()
+            .stdout("0\n");
+
+        jqr()
+            .args(["data/users.json", "$.users[*].name", "--agg", "sum"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Cannot aggregate a value of type string",
+            ));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let items = r#"{"items": [
+            {"name": "apple", "category": "fruit"},
+            {"name": "leek", "category": "vegetable"},
+            {"name": "pear", "category": "fruit"},
+            {"name": "salt"}
+        ]}"#;
+
+        jqr()
+            .args(["group-by", "$.items[*]", "--key", "$.category", "--compact"])
+            .write_stdin(items)
+            .assert()
+            .success()
+            .stdout(concat!(
+                "{\"fruit\":[{\"category\":\"fruit\",\"name\":\"apple\"},",
+                "{\"category\":\"fruit\",\"name\":\"pear\"}],",
+                "\"null\":[{\"name\":\"salt\"}],",
+                "\"vegetable\":[{\"category\":\"vegetable\",\"name\":\"leek\"}]}\n"
+            ));
+
+        jqr()
+            .args(["group-by", "items[*]", "--key", "category", "--count", "-c"])
+            .write_stdin(items)
+            .assert()
+            .success()
+            .stdout("{\"fruit\":2,\"null\":1,\"vegetable\":1}\n");
+
+        jqr()
+            .args([
+                "group-by",
+                "$.users[*]",
+                "data/users.json",
+                "--key",
+                "$.id",
+                "-c",
+            ])
+            .args(["--count", "--preserve-order"])
+            .assert()
+            .success()
+            .stdout("{\"1\":1,\"2\":1,\"3\":1}\n");
+
+        jqr()
+            .args(["group-by", "$.users[*]", "data/users.json"])
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        jqr()
+            .args([
+                "data/users.json",
+                "$.users[*].name",
+                "--sort-by",
+                "$:desc",
+                "-c",
+            ])
+            .assert()
+            .success()
+            .stdout("[\"Charlie\",\"Bob\",\"Alice\"]\n");
+
+        jqr()
+            .args(["--sort-by", "age:desc,name", "-c"])
+            .write_stdin(r#"[{"name": "b", "age": 1}, {"name": "a"}, {"name": "c", "age": 1}]"#)
+            .assert()
+            .success()
+            .stdout("[{\"age\":1,\"name\":\"b\"},{\"age\":1,\"name\":\"c\"},{\"name\":\"a\"}]\n");
+
+        jqr()
+            .args(["--sort-at", "$.team.members", "--sort-by", "$.name", "-c"])
+            .write_stdin(r#"{"team": {"members": [{"name": "b"}, {"name": "a"}], "lead": "b"}}"#)
+            .assert()
+            .success()
+            .stdout(
+                "{\"team\":{\"lead\":\"b\",\"members\":[{\"name\":\"a\"},{\"name\":\"b\"}]}}\n",
+            );
+
+        jqr()
+            .args(["data/users.json", "--sort-by", "$.id"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Sorting requires an array"));
+    }
+
+    #[test]
+    fn test_unique() {
+        jqr()
+            .args(["--unique", "-c"])
+            .write_stdin(r#"[{"a": 1, "b": [2]}, 1, {"b": [2], "a": 1}, "1", 1]"#)
+            .assert()
+            .success()
+            .stdout("[{\"a\":1,\"b\":[2]},1,\"1\"]\n");
+
+        jqr()
+            .args(["--unique=$.email", "--sort-by", "$.name", "-c"])
+            .write_stdin(r#"[{"name": "b", "email": "x"}, {"name": "a", "email": "y"}, {"name": "c", "email": "x"}]"#)
+            .assert()
+            .success()
+            .stdout("[{\"email\":\"y\",\"name\":\"a\"},{\"email\":\"x\",\"name\":\"b\"}]\n");
+
+        jqr()
+            .args(["data/users.json", "--unique"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Removing duplicates requires an array",
+            ));
+    }
+
+    #[test]
+    fn test_flatten_unflatten() {
+        let nested = r#"{"server": {"ports": [80, 443], "tls": {}}, "x.y": null}"#;
+        let flat =
+            "{\"server.ports[0]\":80,\"server.ports[1]\":443,\"server.tls\":{},\"x\\\\.y\":null}\n";
+
+        jqr()
+            .args(["flatten", "-c", "--preserve-order"])
+            .write_stdin(nested)
+            .assert()
+            .success()
+            .stdout(flat);
+
+        jqr()
+            .args(["unflatten", "-c", "--preserve-order"])
+            .write_stdin(flat)
+            .assert()
+            .success()
+            .stdout("{\"server\":{\"ports\":[80,443],\"tls\":{}},\"x.y\":null}\n");
+
+        jqr()
+            .arg("unflatten")
+            .write_stdin(r#"{"a[0]": 1, "a.b": 2}"#)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "The flat keys 'a[0]' and 'a.b' conflict",
+            ));
+
+        jqr()
+            .args(["flatten", "data/users.json", "-c"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("\"users[2].name\":\"Charlie\""));
+    }
+
+    #[test]
+    fn test_select() {
+        let log = concat!(
+            "{\"level\":\"error\",\"code\":500,\"msg\":\"down\"}\n",
+            "{\"level\":\"info\",\"msg\":\"up\"}\n",
+            "{\"level\":\"error\",\"code\":404,\"msg\":\"missing\"}\n",
+        );
+
+        jqr()
+            .args(["--select", "$.level == \"error\"", "-c", "--preserve-order"])
+            .write_stdin(log)
+            .assert()
+            .success()
+            .stdout(concat!(
+                "{\"level\":\"error\",\"code\":500,\"msg\":\"down\"}\n",
+                "{\"level\":\"error\",\"code\":404,\"msg\":\"missing\"}\n",
+            ));
+
+        jqr()
+            .args([
+                "--select",
+                "$.level == $level && @.code < 500",
+                "-q",
+                "$.msg",
+            ])
+            .args(["--arg", "level", "error", "-r"])
+            .write_stdin(log)
+            .assert()
+            .success()
+            .stdout("missing\n");
+
+        jqr()
+            .args(["--select", "$.trace", "-e"])
+            .write_stdin(log)
+            .assert()
+            .code(1)
+            .stdout("");
+
+        jqr()
+            .args(["--select", "$.level = 'error'"])
+            .write_stdin(log)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid predicate"));
+    }
+
+    #[test]
+    fn test_map() {
+        jqr()
+            .args(["data/users.json", "$.users[:2]", "-c", "--preserve-order"])
+            .args(["--map", "{id, contact: {email: @.email}, kind: 'user'}"])
+            .assert()
+            .success()
+            .stdout(concat!(
+                "[{\"id\":1,\"contact\":{\"email\":\"alice@example.com\"},\"kind\":\"user\"},",
+                "{\"id\":2,\"contact\":{\"email\":\"bob@example.com\"},\"kind\":\"user\"}]\n"
+            ));
+
+        jqr()
+            .args([
+                "data/users.json",
+                "$.users[2]",
+                "--map",
+                "[$.name, $.missing]",
+                "-c",
+            ])
+            .assert()
+            .success()
+            .stdout("[\"Charlie\",null]\n");
+
+        jqr()
+            .args(["data/users.json", "$.users", "--map", "{id: $.id"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid map template"));
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        jqr()
+            .args([
+                "data/users.json",
+                "$.users[*].name",
+                "--offset",
+                "1",
+                "--limit",
+                "1",
+                "-c",
+            ])
+            .assert()
+            .success()
+            .stdout("[\"Bob\"]\n");
+
+        jqr()
+            .args([
+                "data/users.json",
+                "$.users[*].id",
+                "--sort-by",
+                "$:desc",
+                "--limit",
+                "2",
+                "-c",
+            ])
+            .assert()
+            .success()
+            .stdout("[3,2]\n");
+
+        jqr()
+            .args(["--ndjson", "--offset", "2", "-c"])
+            .write_stdin("{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n")
+            .assert()
+            .success()
+            .stdout("{\"n\":3}\n");
+
+        jqr()
+            .args(["data/users.json", "$.users", "--offset", "9", "-c"])
+            .assert()
+            .success()
+            .stdout("[]\n");
+
+        jqr()
+            .args(["data/users.json", "--limit", "-1"])
+            .assert()
+            .code(2);
+    }
+
+    #[test]
+    fn test_reverse_shuffle_sample() {
+        jqr()
+            .args(["data/users.json", "$.users[*].id", "--reverse", "-c"])
+            .assert()
+            .success()
+            .stdout("[3,2,1]\n");
+
+        let numbers = "[1,2,3,4,5,6,7,8,9,10,11,12]";
+        let shuffled = |seed: &str| {
+            let output = jqr()
+                .args([format!("--shuffle={}", seed).as_str(), "-c"])
+                .write_stdin(numbers)
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            let shuffled: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+            shuffled
+        };
+        let first = shuffled("7");
+        assert_eq!(first, shuffled("7"), "a seed repeats the order");
+        let mut sorted: Vec<u64> = first
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_u64().unwrap())
+            .collect();
+        sorted.sort();
+        assert_eq!(sorted, (1..=12).collect::<Vec<u64>>());
+
+        let output = jqr()
+            .args(["--sample", "4", "-c"])
+            .write_stdin(numbers)
+            .output()
+            .unwrap();
+        let sampled: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let sampled: Vec<u64> = sampled
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_u64().unwrap())
+            .collect();
+        assert_eq!(sampled.len(), 4);
+        assert!(sampled.windows(2).all(|pair| pair[0] < pair[1]));
+
+        jqr()
+            .args(["data/users.json", "--shuffle"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Shuffling requires an array"));
+    }
+
+    #[test]
+    fn test_pivot() {
+        jqr()
+            .args(["pivot", "-c", "--preserve-order"])
+            .write_stdin(r#"[{"a": 1, "b": 2}, {"b": 4, "c": 5}]"#)
+            .assert()
+            .success()
+            .stdout("{\"a\":[1,null],\"b\":[2,4],\"c\":[null,5]}\n");
+
+        jqr()
+            .args(["pivot", "-c", "--preserve-order"])
+            .write_stdin(r#"{"a": [1, 3], "b": [2, 4]}"#)
+            .assert()
+            .success()
+            .stdout("[{\"a\":1,\"b\":2},{\"a\":3,\"b\":4}]\n");
+
+        jqr()
+            .arg("pivot")
+            .write_stdin(r#"{"ids": [1, 2], "team": "core"}"#)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Pivoting an object requires arrays, but the member 'team' is of type string",
+            ));
+    }
+
+    #[test]
+    fn test_set() {
+        let dir = scratch_dir("set");
+        let config = dir.join("config.json");
+        fs::write(
+            &config,
+            r#"{"server": {"port": 80}, "hosts": [{"up": false}]}"#,
+        )
+        .unwrap();
+        let config = config.to_str().unwrap();
+
+        jqr()
+            .args(["set", config, "$.server.port", "8080", "-c"])
+            .assert()
+            .success()
+            .stdout("{\"hosts\":[{\"up\":false}],\"server\":{\"port\":8080}}\n");
+
+        jqr()
+            .args(["set", config, "$.hosts[*].up", "true", "--string", "-c"])
+            .assert()
+            .success()
+            .stdout("{\"hosts\":[{\"up\":\"true\"}],\"server\":{\"port\":80}}\n");
+
+        jqr()
+            .args(["set", config, "$.server.name", "{web", "--json"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("Invalid JSON value '{web'"));
+
+        jqr()
+            .args(["set", config, "$.log.level", "debug"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(
+                "Cannot set '$.log.level': $ has no member 'log'",
+            ));
+
+        jqr()
+            .args([
+                "set",
+                config,
+                "$.log.level",
+                "debug",
+                "--create-missing",
+                "--in-place",
+            ])
+            .assert()
+            .success()
+            .stdout("");
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(config).unwrap()).unwrap();
+        assert_eq!(written["log"]["level"], "debug");
+        assert_eq!(written["server"]["port"], 80);
+
+        // An edit in place keeps the keys in the order the file has them
+        fs::write(config, r#"{"z": 1, "a": {"y": 2, "b": 3}}"#).unwrap();
+        jqr()
+            .args(["set", config, "$.a.b", "9", "-i", "-c"])
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read_to_string(config).unwrap(),
+            "{\"z\":1,\"a\":{\"y\":2,\"b\":9}}\n"
+        );
+        jqr()
+            .args(["delete", config, "$.a.y", "-i", "-c"])
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read_to_string(config).unwrap(),
+            "{\"z\":1,\"a\":{\"b\":9}}\n"
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        jqr()
+            .args(["delete", "data/users.json", "$.users[*].email", "-c"])
+            .assert()
+            .success()
+            .stdout(
+                "{\"users\":[{\"id\":1,\"name\":\"Alice\"},{\"id\":2,\"name\":\"Bob\"},{\"id\":3,\"name\":\"Charlie\"}]}\n",
+            );
+
+        jqr()
+            .args(["delete", "data/users.json", "$.users[?(@.id != 2)]", "-c"])
+            .assert()
+            .success()
+            .stdout("{\"users\":[{\"email\":\"bob@example.com\",\"id\":2,\"name\":\"Bob\"}]}\n");
+
+        jqr()
+            .args(["delete", "data/users.json", "$"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("it matches the whole document"));
+
+        let dir = scratch_dir("delete");
+        let payload = dir.join("payload.json");
+        fs::write(
+            &payload,
+            r#"{"token": "t", "data": {"token": "u", "id": 1}}"#,
+        )
+        .unwrap();
+        let payload = payload.to_str().unwrap();
+        jqr()
+            .args(["delete", payload, "$..token", "-i", "-c"])
+            .assert()
+            .success()
+            .stdout("");
+        assert_eq!(
+            fs::read_to_string(payload).unwrap(),
+            "{\"data\":{\"id\":1}}\n"
+        );
+    }
+
+    #[test]
+    fn test_numbered_backup() {
+        let dir = scratch_dir("numbered-backup");
+        let target = dir.join("config.json");
+        fs::write(&target, "{\"n\":0}\n").unwrap();
+        let target = target.to_str().unwrap();
+        let backup = |suffix: &str| fs::read_to_string(format!("{}{}", target, suffix)).unwrap();
+
+        for n in 1..=3 {
+            jqr()
+                .args(["set", target, "$.n", &n.to_string(), "-i", "-c"])
+                .args(["--backup", "--numbered-backup"])
+                .assert()
+                .success();
+        }
+        assert_eq!(backup(".bak"), "{\"n\":0}\n");
+        assert_eq!(backup(".bak.1"), "{\"n\":1}\n");
+        assert_eq!(backup(".bak.2"), "{\"n\":2}\n");
+
+        // The main command numbers its backups the same way, and a plain
+        // --backup replaces the first one
+        jqr()
+            .args([target, "-i", "-c", "--backup", "--numbered-backup"])
+            .assert()
+            .success();
+        assert_eq!(backup(".bak.3"), "{\"n\":3}\n");
+        jqr()
+            .args(["delete", target, "$.n", "-i", "-c", "--backup"])
+            .assert()
+            .success();
+        assert_eq!(backup(".bak"), "{\"n\":3}\n");
+        assert_eq!(fs::read_to_string(target).unwrap(), "{}\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 5);
+
+        jqr()
+            .args([target, "-i", "--numbered-backup"])
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains("--backup"));
+    }
+}